@@ -0,0 +1,240 @@
+//! # burster-py
+//!
+//! Python bindings for the burster rate limiters, sharing the exact same
+//! implementation with Rust services.
+//!
+//! All limiter objects are safe to share between Python threads. The
+//! limiter state is guarded by a mutex that is only held for the duration
+//! of a single consume, and never while calling back into Python.
+//! Invalid limiter parameters, e.g. a zero capacity, raise a `ValueError`.
+//!
+//! ```python
+//! import burster
+//!
+//! bucket = burster.TokenBucket(100, 10)
+//! if bucket.try_consume():
+//!     ...
+//! ```
+
+use std::{
+    sync::{Mutex, MutexGuard, PoisonError},
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+
+use burster_rs::{ConfigError, Limiter, RemainingBudget};
+use pyo3::{exceptions::PyValueError, prelude::*};
+
+/// System clock, same as used by the `std` utility functions of burster
+fn system_time() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("Time went backwards")
+}
+
+type Clock = fn() -> Duration;
+
+/// Lock a limiter, a panic while holding the lock can't leave it inconsistent
+fn lock<L>(limiter: &Mutex<L>) -> MutexGuard<'_, L> {
+    limiter.lock().unwrap_or_else(PoisonError::into_inner)
+}
+
+/// Invalid limiter parameters are raised as `ValueError`
+fn config_error(error: ConfigError) -> PyErr {
+    PyValueError::new_err(error.to_string())
+}
+
+/// Remaining budget, evaluated by consuming zero tokens
+fn remaining(limiter: &mut impl RemainingBudget) -> u64 {
+    limiter.try_consume_remaining(0).unwrap_or(0)
+}
+
+/// Token bucket -type rate limiter
+#[pyclass(frozen, module = "burster")]
+struct TokenBucket {
+    inner: Mutex<burster_rs::TokenBucket<Clock>>,
+}
+
+impl TokenBucket {
+    /// Validating constructor, the errors are raised as `ValueError`
+    fn build(rate_per_s: u64, capacity: u64) -> Result<Self, ConfigError> {
+        let limiter = burster_rs::TokenBucket::try_new_with_time_provider(
+            rate_per_s,
+            capacity,
+            system_time as Clock,
+        )?;
+        Ok(Self {
+            inner: Mutex::new(limiter),
+        })
+    }
+}
+
+#[pymethods]
+impl TokenBucket {
+    #[new]
+    fn new(rate_per_s: u64, capacity: u64) -> PyResult<Self> {
+        Self::build(rate_per_s, capacity).map_err(config_error)
+    }
+
+    /// Try to consume tokens, returns `True` if the tokens were consumed
+    #[pyo3(signature = (tokens = 1))]
+    fn try_consume(&self, tokens: u64) -> bool {
+        lock(&self.inner).try_consume(tokens).is_ok()
+    }
+
+    /// How many tokens could currently be consumed
+    fn remaining(&self) -> u64 {
+        remaining(&mut *lock(&self.inner))
+    }
+
+    /// Configured capacity
+    #[getter]
+    fn capacity(&self) -> u64 {
+        lock(&self.inner).capacity()
+    }
+}
+
+/// Fixed window -type rate limiter
+#[pyclass(frozen, module = "burster")]
+struct FixedWindow {
+    inner: Mutex<burster_rs::FixedWindow<Clock>>,
+}
+
+impl FixedWindow {
+    /// Validating constructor, the errors are raised as `ValueError`
+    fn build(capacity: u64, window_width_ms: u64) -> Result<Self, ConfigError> {
+        let limiter = burster_rs::FixedWindow::try_new_with_time_provider(
+            capacity,
+            window_width_ms,
+            system_time as Clock,
+        )?;
+        Ok(Self {
+            inner: Mutex::new(limiter),
+        })
+    }
+}
+
+#[pymethods]
+impl FixedWindow {
+    #[new]
+    fn new(capacity: u64, window_width_ms: u64) -> PyResult<Self> {
+        Self::build(capacity, window_width_ms).map_err(config_error)
+    }
+
+    /// Try to consume tokens, returns `True` if the tokens were consumed
+    #[pyo3(signature = (tokens = 1))]
+    fn try_consume(&self, tokens: u64) -> bool {
+        lock(&self.inner).try_consume(tokens).is_ok()
+    }
+
+    /// How many tokens could currently be consumed
+    fn remaining(&self) -> u64 {
+        remaining(&mut *lock(&self.inner))
+    }
+
+    /// Configured capacity
+    #[getter]
+    fn capacity(&self) -> u64 {
+        lock(&self.inner).capacity()
+    }
+}
+
+/// Sliding window counter -type rate limiter
+#[pyclass(frozen, module = "burster")]
+struct SlidingWindowCounter {
+    inner: Mutex<burster_rs::SlidingWindowCounter<Clock>>,
+}
+
+impl SlidingWindowCounter {
+    /// Validating constructor, the errors are raised as `ValueError`
+    fn build(capacity: u64, window_width_ms: u64) -> Result<Self, ConfigError> {
+        let limiter = burster_rs::SlidingWindowCounter::try_new_with_time_provider(
+            capacity,
+            window_width_ms,
+            system_time as Clock,
+        )?;
+        Ok(Self {
+            inner: Mutex::new(limiter),
+        })
+    }
+}
+
+#[pymethods]
+impl SlidingWindowCounter {
+    #[new]
+    fn new(capacity: u64, window_width_ms: u64) -> PyResult<Self> {
+        Self::build(capacity, window_width_ms).map_err(config_error)
+    }
+
+    /// Try to consume tokens, returns `True` if the tokens were consumed
+    #[pyo3(signature = (tokens = 1))]
+    fn try_consume(&self, tokens: u64) -> bool {
+        lock(&self.inner).try_consume(tokens).is_ok()
+    }
+
+    /// How many tokens could currently be consumed
+    fn remaining(&self) -> u64 {
+        remaining(&mut *lock(&self.inner))
+    }
+
+    /// Configured capacity
+    #[getter]
+    fn capacity(&self) -> u64 {
+        lock(&self.inner).capacity()
+    }
+}
+
+/// Python module definition
+#[pymodule]
+fn burster(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<TokenBucket>()?;
+    m.add_class::<FixedWindow>()?;
+    m.add_class::<SlidingWindowCounter>()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use burster_rs::ConfigError;
+
+    use super::{FixedWindow, SlidingWindowCounter, TokenBucket};
+
+    // The Python constructors only add the ValueError mapping, which can't be
+    // exercised without an interpreter, so the limiters are built directly
+
+    #[test]
+    fn verify_limiters() {
+        let bucket = TokenBucket::build(1, 3).unwrap();
+        assert_eq!(bucket.capacity(), 3);
+        assert!(bucket.try_consume(2));
+        assert!(!bucket.try_consume(2));
+        assert_eq!(bucket.remaining(), 1);
+
+        let window = FixedWindow::build(2, 60_000).unwrap();
+        assert!(window.try_consume(1));
+        assert!(window.try_consume(1));
+        assert!(!window.try_consume(1));
+        assert_eq!(window.remaining(), 0);
+
+        let counter = SlidingWindowCounter::build(2, 60_000).unwrap();
+        assert_eq!(counter.capacity(), 2);
+        assert!(counter.try_consume(2));
+        assert_eq!(counter.remaining(), 0);
+    }
+
+    #[test]
+    fn verify_config_errors() {
+        assert_eq!(TokenBucket::build(0, 3).err(), Some(ConfigError::ZeroRate));
+        assert_eq!(
+            TokenBucket::build(1, 0).err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+        assert_eq!(
+            FixedWindow::build(2, 0).err(),
+            Some(ConfigError::ZeroWindow)
+        );
+        assert_eq!(
+            SlidingWindowCounter::build(0, 1000).err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+    }
+}
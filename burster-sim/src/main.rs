@@ -0,0 +1,336 @@
+//! # burster-sim
+//!
+//! Feed synthetic or recorded traffic through a burster limiter and print
+//! acceptance statistics, without writing a Rust program.
+//!
+//! ```text
+//! burster-sim --limiter token-bucket:100:10 --traffic constant:200 --duration 2000
+//! ```
+
+use std::{cell::Cell, env, fs, process::ExitCode, time::Duration};
+
+use burster::{Limiter, Stats};
+
+const USAGE: &str = "\
+Usage: burster-sim --limiter <SPEC> --traffic <SPEC> [--duration <MS>] [--resolution <MS>]
+
+Limiters:
+  token-bucket:<RATE_PER_S>:<CAPACITY>
+  fixed-window:<CAPACITY>:<WINDOW_MS>
+  sliding-window-counter:<CAPACITY>:<WINDOW_MS>
+  sliding-window-log:<CAPACITY>            (1000 ms window)
+
+Traffic:
+  constant:<REQUESTS_PER_S>                single token requests at a constant rate
+  burst:<SIZE>:<PERIOD_MS>                 SIZE requests at the start of every period
+  trace:<PATH>                             one request per line: <TIME_MS> [TOKENS]
+
+Options:
+  --duration <MS>    simulated time for synthetic traffic (default 1000)
+  --resolution <MS>  timeline bucket width (default 100)";
+
+/// Limiter configuration parsed from the command line
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum LimiterSpec {
+    TokenBucket { rate_per_s: u64, capacity: u64 },
+    FixedWindow { capacity: u64, window_ms: u64 },
+    SlidingWindowCounter { capacity: u64, window_ms: u64 },
+    SlidingWindowLog { capacity: u64 },
+}
+
+/// Traffic description parsed from the command line
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum TrafficSpec {
+    Constant { rate_per_s: u64 },
+    Burst { size: u64, period_ms: u64 },
+    Trace { path: String },
+}
+
+/// Single simulated request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Request {
+    time: Duration,
+    tokens: u64,
+}
+
+struct Options {
+    limiter: LimiterSpec,
+    traffic: TrafficSpec,
+    duration: Duration,
+    resolution: Duration,
+}
+
+fn main() -> ExitCode {
+    match parse_args(env::args().skip(1)).and_then(|options| run(&options)) {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {e}\n\n{USAGE}");
+            ExitCode::FAILURE
+        }
+    }
+}
+
+fn run(options: &Options) -> Result<(), String> {
+    let requests = generate(&options.traffic, options.duration)?;
+    let timeline = simulate(options.limiter, &requests, options.resolution);
+
+    println!(
+        "{:>10} {:>10} {:>10} {:>10}",
+        "time_ms", "accepted", "rejected", "tokens"
+    );
+    let mut total = Stats::default();
+    for (i, stats) in timeline.iter().enumerate() {
+        println!(
+            "{:>10} {:>10} {:>10} {:>10}",
+            i as u128 * options.resolution.as_millis(),
+            stats.accepted,
+            stats.rejected,
+            stats.tokens_granted
+        );
+        total.accepted += stats.accepted;
+        total.rejected += stats.rejected;
+        total.tokens_granted += stats.tokens_granted;
+    }
+
+    let requests_total = total.accepted + total.rejected;
+    let ratio = if requests_total == 0 {
+        0.0
+    } else {
+        100.0 * total.accepted as f64 / requests_total as f64
+    };
+    println!(
+        "\ntotal: {} accepted, {} rejected ({ratio:.1}% accepted), {} tokens granted",
+        total.accepted, total.rejected, total.tokens_granted
+    );
+    Ok(())
+}
+
+/// Run the requests through the limiter, collecting statistics per timeline bucket
+fn simulate(spec: LimiterSpec, requests: &[Request], resolution: Duration) -> Vec<Stats> {
+    let now = Cell::new(Duration::ZERO);
+    let clock = || now.get();
+    let mut limiter: Box<dyn Limiter + '_> = match spec {
+        LimiterSpec::TokenBucket {
+            rate_per_s,
+            capacity,
+        } => Box::new(burster::TokenBucket::new_with_time_provider(
+            rate_per_s, capacity, clock,
+        )),
+        LimiterSpec::FixedWindow {
+            capacity,
+            window_ms,
+        } => Box::new(burster::FixedWindow::new_with_time_provider(
+            capacity, window_ms, clock,
+        )),
+        LimiterSpec::SlidingWindowCounter {
+            capacity,
+            window_ms,
+        } => Box::new(burster::SlidingWindowCounter::new_with_time_provider(
+            capacity, window_ms, clock,
+        )),
+        LimiterSpec::SlidingWindowLog { capacity } => {
+            Box::new(burster::SlidingWindowLog::<_, 1000>::new_with_time_provider(capacity, clock))
+        }
+    };
+
+    let resolution_ns = resolution.as_nanos().max(1);
+    let mut timeline = Vec::new();
+    for request in requests {
+        now.set(request.time);
+        let bucket = (request.time.as_nanos() / resolution_ns) as usize;
+        if timeline.len() <= bucket {
+            timeline.resize(bucket + 1, Stats::default());
+        }
+        timeline[bucket].record(request.tokens, &limiter.try_consume(request.tokens));
+    }
+    timeline
+}
+
+/// Expand a traffic description into a time ordered list of requests
+fn generate(spec: &TrafficSpec, duration: Duration) -> Result<Vec<Request>, String> {
+    let duration_ns = duration.as_nanos() as u64;
+    let requests = match spec {
+        TrafficSpec::Constant { rate_per_s } => {
+            if *rate_per_s == 0 {
+                return Ok(Vec::new());
+            }
+            let interval_ns = 1_000_000_000 / rate_per_s;
+            (0..duration_ns / interval_ns.max(1))
+                .map(|i| Request {
+                    time: Duration::from_nanos(i * interval_ns),
+                    tokens: 1,
+                })
+                .collect()
+        }
+        TrafficSpec::Burst { size, period_ms } => {
+            let period_ns = period_ms.saturating_mul(1_000_000).max(1);
+            (0..duration_ns.div_ceil(period_ns))
+                .flat_map(|i| {
+                    (0..*size).map(move |_| Request {
+                        time: Duration::from_nanos(i * period_ns),
+                        tokens: 1,
+                    })
+                })
+                .collect()
+        }
+        TrafficSpec::Trace { path } => {
+            let trace = fs::read_to_string(path).map_err(|e| format!("{path}: {e}"))?;
+            let mut requests = parse_trace(&trace)?;
+            requests.sort_by_key(|r| r.time);
+            requests
+        }
+    };
+    Ok(requests)
+}
+
+fn parse_trace(trace: &str) -> Result<Vec<Request>, String> {
+    trace
+        .lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty() && !line.trim_start().starts_with('#'))
+        .map(|(i, line)| {
+            let mut fields = line.split_whitespace();
+            let time_ms = parse_u64(fields.next().unwrap_or_default())
+                .map_err(|e| format!("trace line {}: {e}", i + 1))?;
+            let tokens = fields
+                .next()
+                .map(parse_u64)
+                .transpose()
+                .map_err(|e| format!("trace line {}: {e}", i + 1))?
+                .unwrap_or(1);
+            Ok(Request {
+                time: Duration::from_millis(time_ms),
+                tokens,
+            })
+        })
+        .collect()
+}
+
+fn parse_args(mut args: impl Iterator<Item = String>) -> Result<Options, String> {
+    let mut limiter = None;
+    let mut traffic = None;
+    let mut duration = Duration::from_millis(1000);
+    let mut resolution = Duration::from_millis(100);
+
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("missing value for {arg}"));
+        match arg.as_str() {
+            "--limiter" => limiter = Some(parse_limiter(&value()?)?),
+            "--traffic" => traffic = Some(parse_traffic(&value()?)?),
+            "--duration" => duration = Duration::from_millis(parse_u64(&value()?)?),
+            "--resolution" => resolution = Duration::from_millis(parse_u64(&value()?)?.max(1)),
+            "-h" | "--help" => return Err("help requested".into()),
+            _ => return Err(format!("unknown argument {arg}")),
+        }
+    }
+
+    Ok(Options {
+        limiter: limiter.ok_or("--limiter is required")?,
+        traffic: traffic.ok_or("--traffic is required")?,
+        duration,
+        resolution,
+    })
+}
+
+fn parse_limiter(spec: &str) -> Result<LimiterSpec, String> {
+    let fields: Vec<&str> = spec.split(':').collect();
+    let limiter = match fields.as_slice() {
+        ["token-bucket", rate, capacity] => LimiterSpec::TokenBucket {
+            rate_per_s: parse_u64(rate)?,
+            capacity: parse_u64(capacity)?,
+        },
+        ["fixed-window", capacity, window] => LimiterSpec::FixedWindow {
+            capacity: parse_u64(capacity)?,
+            window_ms: parse_u64(window)?,
+        },
+        ["sliding-window-counter", capacity, window] => LimiterSpec::SlidingWindowCounter {
+            capacity: parse_u64(capacity)?,
+            window_ms: parse_u64(window)?,
+        },
+        ["sliding-window-log", capacity] => LimiterSpec::SlidingWindowLog {
+            capacity: parse_u64(capacity)?,
+        },
+        _ => return Err(format!("invalid limiter {spec}")),
+    };
+
+    let valid = match limiter {
+        LimiterSpec::TokenBucket { rate_per_s, .. } => rate_per_s != 0,
+        LimiterSpec::FixedWindow { window_ms, .. }
+        | LimiterSpec::SlidingWindowCounter { window_ms, .. } => window_ms != 0,
+        LimiterSpec::SlidingWindowLog { .. } => true,
+    };
+    valid.then_some(limiter).ok_or(format!(
+        "invalid limiter {spec}: rates and widths must be nonzero"
+    ))
+}
+
+fn parse_traffic(spec: &str) -> Result<TrafficSpec, String> {
+    match spec.split_once(':') {
+        Some(("constant", rate)) => Ok(TrafficSpec::Constant {
+            rate_per_s: parse_u64(rate)?,
+        }),
+        Some(("burst", rest)) => {
+            let (size, period) = rest
+                .split_once(':')
+                .ok_or(format!("invalid traffic {spec}"))?;
+            Ok(TrafficSpec::Burst {
+                size: parse_u64(size)?,
+                period_ms: parse_u64(period)?,
+            })
+        }
+        Some(("trace", path)) => Ok(TrafficSpec::Trace { path: path.into() }),
+        _ => Err(format!("invalid traffic {spec}")),
+    }
+}
+
+fn parse_u64(s: &str) -> Result<u64, String> {
+    s.parse().map_err(|_| format!("invalid number {s:?}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use super::{generate, parse_limiter, parse_trace, simulate, LimiterSpec, TrafficSpec};
+
+    #[test]
+    fn verify_parsing() {
+        assert_eq!(
+            parse_limiter("token-bucket:100:10"),
+            Ok(LimiterSpec::TokenBucket {
+                rate_per_s: 100,
+                capacity: 10
+            })
+        );
+        assert!(parse_limiter("token-bucket:0:10").is_err());
+        assert!(parse_limiter("leaky-bucket:1").is_err());
+
+        let trace = parse_trace("# time tokens\n0\n5 3\n").unwrap();
+        assert_eq!(trace.len(), 2);
+        assert_eq!(trace[1].tokens, 3);
+        assert!(parse_trace("x").is_err());
+    }
+
+    #[test]
+    fn verify_simulation() {
+        let requests = generate(
+            &TrafficSpec::Burst {
+                size: 20,
+                period_ms: 500,
+            },
+            Duration::from_millis(1000),
+        )
+        .unwrap();
+        assert_eq!(requests.len(), 40);
+
+        let spec = LimiterSpec::FixedWindow {
+            capacity: 15,
+            window_ms: 1000,
+        };
+        let timeline = simulate(spec, &requests, Duration::from_millis(500));
+        assert_eq!(timeline.len(), 2);
+        assert_eq!(timeline[0].accepted, 15);
+        assert_eq!(timeline[0].rejected, 5);
+        assert_eq!(timeline[1].accepted, 0);
+    }
+}
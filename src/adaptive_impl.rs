@@ -0,0 +1,340 @@
+//! Adaptive AIMD -type limiter
+
+use core::time::Duration;
+
+use crate::{
+    Clock, ConfigError, Diagnostics, Inspect, Limiter, LimiterKind, LimiterResult, Reset,
+    RetryHint, TokenBucketCore, TokenBucketState,
+};
+
+/// Tuning parameters of an [`AdaptiveLimiter`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AimdConfig {
+    /// Rate is never decreased below this
+    pub min_rate_per_s: u64,
+    /// Rate is never increased above this
+    pub max_rate_per_s: u64,
+    /// How much the rate is increased at a time
+    pub increase_per_s: u64,
+    /// How often the rate is increased while consumes succeed
+    pub increase_interval: Duration,
+}
+
+/// Adaptive rate limiter with additive increase and multiplicative decrease
+///
+/// A token bucket whose rate follows the capacity of the downstream
+/// system, like the client-side adaptive throttling of the SRE literature.
+/// While consumes succeed, the rate is increased additively once per
+/// interval. When the application reports downstream pressure with
+/// [`AdaptiveLimiter::report_overload`], the rate is halved. The rate always
+/// stays within the bounds of the [`AimdConfig`].
+///
+/// # Generic arguments
+/// * `T` - time provider
+#[derive(Debug, Clone)]
+pub struct AdaptiveLimiter<T>
+where
+    T: Clock,
+{
+    core: TokenBucketCore,
+    config: AimdConfig,
+    initial_rate_per_s: u64,
+    last_change: Duration,
+    time_provider: T,
+}
+
+impl<T> AdaptiveLimiter<T>
+where
+    T: Clock,
+{
+    /// Initialize a new adaptive limiter
+    ///
+    /// The initial rate is clamped to the configured bounds.
+    ///
+    /// # Arguments
+    /// * `initial_rate_per_s` - rate to start from
+    /// * `capacity` - bucket capacity to dictate the burstiness of this limiter
+    /// * `config` - rate adaptation parameters
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    pub fn new_with_time_provider(
+        initial_rate_per_s: u64,
+        capacity: u64,
+        config: AimdConfig,
+        time_provider: T,
+    ) -> Self {
+        let time_now = time_provider.now();
+        let initial_rate_per_s = initial_rate_per_s.clamp(
+            config.min_rate_per_s,
+            config.max_rate_per_s.max(config.min_rate_per_s),
+        );
+        Self {
+            core: TokenBucketCore::new(initial_rate_per_s, capacity, time_now),
+            config,
+            initial_rate_per_s,
+            last_change: time_now,
+            time_provider,
+        }
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting configurations
+    /// that would never admit anything or could stop refilling for good
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroCapacity`] if `capacity` is zero, [`ConfigError::ZeroRate`]
+    /// if the minimum rate of the `config` is zero
+    pub fn try_new_with_time_provider(
+        initial_rate_per_s: u64,
+        capacity: u64,
+        config: AimdConfig,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        if config.min_rate_per_s == 0 {
+            // Halving down to zero would stop refilling, and without admitted
+            // consumes the rate would never increase again
+            return Err(ConfigError::ZeroRate);
+        }
+        if capacity == 0 {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        Ok(Self::new_with_time_provider(
+            initial_rate_per_s,
+            capacity,
+            config,
+            time_provider,
+        ))
+    }
+
+    /// Report downstream pressure, e.g. a throttling response or a timeout
+    ///
+    /// Halves the rate, and the next increase happens only after a full interval.
+    pub fn report_overload(&mut self) {
+        let now = self.time_provider.now();
+        let rate = (self.core.rate_per_s() / 2).max(self.config.min_rate_per_s);
+        self.core.set_rate_per_s_at(now, rate);
+        self.last_change = now;
+    }
+
+    /// Current rate in tokens per second
+    pub fn rate_per_s(&self) -> u64 {
+        self.core.rate_per_s()
+    }
+
+    /// Current state of the bucket, including the adapted rate, e.g. for
+    /// dashboards and debug logs
+    pub fn state(&self) -> TokenBucketState {
+        let now = self.time_provider.now();
+        self.core.state_at(now)
+    }
+
+    /// Get the rate adaptation parameters
+    pub fn config(&self) -> &AimdConfig {
+        &self.config
+    }
+
+    /// Get a reference to the underlying token bucket state machine
+    pub fn core(&self) -> &TokenBucketCore {
+        &self.core
+    }
+
+    /// Increase the rate if an interval has elapsed since the last change
+    fn increase(&mut self, now: Duration) {
+        if now.saturating_sub(self.last_change) < self.config.increase_interval {
+            return;
+        }
+        let rate = self
+            .core
+            .rate_per_s()
+            .saturating_add(self.config.increase_per_s)
+            .min(self.config.max_rate_per_s.max(self.config.min_rate_per_s));
+        self.core.set_rate_per_s_at(now, rate);
+        self.last_change = now;
+    }
+}
+
+impl<T> Limiter for AdaptiveLimiter<T>
+where
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens)?;
+        self.increase(now);
+        Ok(())
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::Adaptive
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.time_provider.now();
+        self.core.self_check_at(now)
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.core.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.time_provider.now();
+        Some(self.core.idle_for_at(now))
+    }
+
+    fn reset(&mut self) {
+        let now = self.time_provider.now();
+        self.core.reset_at(now);
+        self.core.set_rate_per_s_at(now, self.initial_rate_per_s);
+        self.last_change = now;
+    }
+}
+
+impl<T> Reset for AdaptiveLimiter<T> where T: Clock {}
+
+impl<T> RetryHint for AdaptiveLimiter<T>
+where
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.time_provider.now();
+        self.core.time_until_available_at(now, tokens)
+    }
+}
+
+impl<T> Inspect for AdaptiveLimiter<T>
+where
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.tokens_available_at(now)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{mock_assets::MockClock, ConfigError, Inspect, Limiter};
+
+    use super::{AdaptiveLimiter, AimdConfig};
+
+    const CONFIG: AimdConfig = AimdConfig {
+        min_rate_per_s: 100,
+        max_rate_per_s: 1000,
+        increase_per_s: 100,
+        increase_interval: Duration::from_millis(10),
+    };
+
+    #[test]
+    fn verify_try_new() {
+        let clock = MockClock::new();
+        let config = AimdConfig {
+            min_rate_per_s: 0,
+            ..CONFIG
+        };
+        assert_eq!(
+            AdaptiveLimiter::try_new_with_time_provider(400, 100, config, || clock.step(0)).err(),
+            Some(ConfigError::ZeroRate)
+        );
+        assert_eq!(
+            AdaptiveLimiter::try_new_with_time_provider(400, 0, CONFIG, || clock.step(0)).err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+        let mut a = AdaptiveLimiter::try_new_with_time_provider(400, 100, CONFIG, || clock.step(0))
+            .unwrap();
+        assert!(a.try_consume(100).is_ok());
+    }
+
+    #[test]
+    fn verify_state() {
+        let clock = MockClock::new();
+        let mut a = AdaptiveLimiter::new_with_time_provider(400, 100, CONFIG, || clock.step(0));
+        assert!(a.try_consume(10).is_ok());
+        a.report_overload();
+
+        let state = a.state();
+        assert_eq!(state.tokens, 90);
+        assert_eq!(state.capacity, 100);
+        assert_eq!(state.rate_per_s, 200);
+    }
+
+    #[test]
+    fn verify_aimd() {
+        let clock = MockClock::new();
+        let mut a = AdaptiveLimiter::new_with_time_provider(400, 100, CONFIG, || clock.step(0));
+
+        // Not increased before a full interval
+        assert!(a.try_consume_one().is_ok());
+        assert_eq!(a.rate_per_s(), 400);
+        for _ in 0..10 {
+            clock.step(10_000);
+            assert!(a.try_consume_one().is_ok());
+        }
+        // Capped to the maximum
+        assert_eq!(a.rate_per_s(), 1000);
+
+        // Halved on overload, never below the minimum
+        a.report_overload();
+        assert_eq!(a.rate_per_s(), 500);
+        for _ in 0..5 {
+            a.report_overload();
+        }
+        assert_eq!(a.rate_per_s(), 100);
+
+        // Increased again after a full interval of successes
+        clock.step(5_000);
+        assert!(a.try_consume_one().is_ok());
+        assert_eq!(a.rate_per_s(), 100);
+        clock.step(5_000);
+        assert!(a.try_consume_one().is_ok());
+        assert_eq!(a.rate_per_s(), 200);
+
+        // Rejected consumes don't increase the rate
+        assert!(a.try_consume(1000).is_err());
+        clock.step(10_000);
+        assert!(a.try_consume(1000).is_err());
+        assert_eq!(a.rate_per_s(), 200);
+
+        a.reset();
+        assert_eq!(a.rate_per_s(), 400);
+    }
+
+    #[test]
+    fn verify_floor_under_repeated_overload() {
+        let clock = MockClock::new();
+        // Halving from the initial rate never lands on the minimum exactly
+        let config = AimdConfig {
+            min_rate_per_s: 3,
+            ..CONFIG
+        };
+        let mut a = AdaptiveLimiter::new_with_time_provider(1000, 10, config, || clock.step(0));
+        for _ in 0..100 {
+            a.report_overload();
+            assert!(a.rate_per_s() >= 3);
+        }
+        assert_eq!(a.rate_per_s(), 3);
+        assert_eq!(a.state().capacity, 10);
+
+        // The bucket keeps refilling at the floor rate
+        assert!(a.try_consume(10).is_ok());
+        clock.step(1_000_000);
+        assert_eq!(a.tokens_available(), 3);
+
+        // Halving one token per second would stop refilling altogether
+        let config = AimdConfig {
+            min_rate_per_s: 1,
+            ..CONFIG
+        };
+        let mut a = AdaptiveLimiter::new_with_time_provider(1, 1, config, || clock.step(0));
+        assert!(a.try_consume_one().is_ok());
+        for _ in 0..10 {
+            a.report_overload();
+        }
+        assert_eq!(a.rate_per_s(), 1);
+        clock.step(1_000_000);
+        assert!(a.try_consume_one().is_ok());
+    }
+}
@@ -0,0 +1,244 @@
+//! Combined rate and concurrency admission control
+
+use core::{
+    fmt,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use crate::Limiter;
+
+/// Bound on the number of simultaneously in-flight operations
+///
+/// Slots are acquired as [`InFlightGuard`]s that release the slot when
+/// dropped. Only a shared reference is needed, so a single bound can be
+/// shared between threads, e.g. as a `static`.
+///
+/// Where a guard can't be held, e.g. when the operation completes in a
+/// callback or an interrupt handler, slots can also be acquired as
+/// [`InFlightPermit`]s that are returned explicitly with [`InFlight::release`].
+#[derive(Debug)]
+pub struct InFlight {
+    max: usize,
+    current: AtomicUsize,
+}
+
+impl InFlight {
+    /// Create a new bound
+    ///
+    /// # Arguments
+    /// * `max` - maximum number of simultaneously in-flight operations
+    pub const fn new(max: usize) -> Self {
+        Self {
+            max,
+            current: AtomicUsize::new(0),
+        }
+    }
+
+    /// Try to acquire a slot
+    ///
+    /// # Returns
+    /// * `Some(guard)` - slot acquired, released when the guard is dropped
+    /// * `None` - all slots are in use
+    pub fn try_acquire(&self) -> Option<InFlightGuard<'_>> {
+        self.current
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |current| {
+                (current < self.max).then_some(current + 1)
+            })
+            .ok()
+            .map(|_| InFlightGuard { in_flight: self })
+    }
+
+    /// Try to acquire a slot that is released explicitly
+    ///
+    /// # Returns
+    /// * `Some(permit)` - slot acquired, return it with [`InFlight::release`]
+    /// * `None` - all slots are in use
+    pub fn try_acquire_permit(&self) -> Option<InFlightPermit> {
+        self.try_acquire().map(InFlightGuard::into_permit)
+    }
+
+    /// Return a slot acquired with [`InFlight::try_acquire_permit`]
+    ///
+    /// The permit must have been acquired from this bound.
+    pub fn release(&self, permit: InFlightPermit) {
+        let InFlightPermit { _private: () } = permit;
+        self.current.fetch_sub(1, Ordering::Release);
+    }
+
+    /// Number of currently acquired slots
+    pub fn in_flight(&self) -> usize {
+        self.current.load(Ordering::Relaxed)
+    }
+
+    /// Configured maximum number of in-flight operations
+    pub fn max(&self) -> usize {
+        self.max
+    }
+}
+
+/// Slot of an [`InFlight`] bound, released on drop
+#[derive(Debug)]
+#[must_use = "the slot is released immediately if the guard is not held"]
+pub struct InFlightGuard<'a> {
+    in_flight: &'a InFlight,
+}
+
+impl InFlightGuard<'_> {
+    /// Keep the slot acquired after the guard is gone
+    ///
+    /// The slot must be returned with [`InFlight::release`].
+    pub fn into_permit(self) -> InFlightPermit {
+        core::mem::forget(self);
+        InFlightPermit { _private: () }
+    }
+}
+
+impl Drop for InFlightGuard<'_> {
+    fn drop(&mut self) {
+        self.in_flight.current.fetch_sub(1, Ordering::Release);
+    }
+}
+
+/// Slot of an [`InFlight`] bound, returned with [`InFlight::release`]
+///
+/// Unlike [`InFlightGuard`], a permit doesn't borrow the bound, so it can be
+/// stored anywhere until the operation completes. Dropping a permit without
+/// returning it leaks the slot.
+#[derive(Debug, PartialEq, Eq)]
+#[must_use = "the slot is leaked if the permit is not released"]
+pub struct InFlightPermit {
+    _private: (),
+}
+
+/// Error type indicating why an [`AdmissionController`] rejected a request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum AdmissionError {
+    /// The rate limit was exceeded
+    Rate,
+    /// Too many requests are already in flight
+    Concurrency,
+}
+
+impl fmt::Display for AdmissionError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            AdmissionError::Rate => write!(f, "Rate limit exceeded"),
+            AdmissionError::Concurrency => write!(f, "Too many requests in flight"),
+        }
+    }
+}
+
+// core::error::Error trait stabilised at release 1.81
+#[rustversion::since(1.81)]
+impl core::error::Error for AdmissionError {}
+
+/// Admission controller enforcing both a rate limit and a concurrency limit
+///
+/// A request is admitted only if a slot of the [`InFlight`] bound is free
+/// *and* the rate limiter admits it. Requests rejected for concurrency don't
+/// consume any tokens from the rate limiter, and requests rejected for rate
+/// don't hold on to a slot.
+pub struct AdmissionController<'a, L>
+where
+    L: Limiter,
+{
+    limiter: L,
+    in_flight: &'a InFlight,
+}
+
+impl<'a, L> AdmissionController<'a, L>
+where
+    L: Limiter,
+{
+    /// Combine a rate limiter and a concurrency bound
+    ///
+    /// # Arguments
+    /// * `limiter` - rate limiter
+    /// * `in_flight` - concurrency bound
+    pub fn new(limiter: L, in_flight: &'a InFlight) -> Self {
+        Self { limiter, in_flight }
+    }
+
+    /// Try to admit a request
+    ///
+    /// # Arguments
+    /// * `tokens` - how many tokens to consume from the rate limiter
+    ///
+    /// # Returns
+    /// * `Ok(guard)` - request admitted, its concurrency slot is released when the guard is dropped
+    /// * `Err(AdmissionError)` - request rejected
+    pub fn try_admit(&mut self, tokens: u64) -> Result<InFlightGuard<'a>, AdmissionError> {
+        let guard = self
+            .in_flight
+            .try_acquire()
+            .ok_or(AdmissionError::Concurrency)?;
+        self.limiter
+            .try_consume(tokens)
+            .map_err(|_| AdmissionError::Rate)?;
+        Ok(guard)
+    }
+
+    /// Get a reference to the concurrency bound
+    pub fn in_flight(&self) -> &'a InFlight {
+        self.in_flight
+    }
+
+    /// Get a reference to the rate limiter
+    pub fn limiter(&self) -> &L {
+        &self.limiter
+    }
+
+    /// Get a mutable reference to the rate limiter
+    pub fn limiter_mut(&mut self) -> &mut L {
+        &mut self.limiter
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CreditBucket;
+
+    use super::{AdmissionController, AdmissionError, InFlight};
+
+    #[test]
+    fn verify_permits() {
+        let in_flight = InFlight::new(2);
+        let permit = in_flight.try_acquire_permit().unwrap();
+        let guard = in_flight.try_acquire().unwrap();
+        assert!(in_flight.try_acquire_permit().is_none());
+
+        // Converted guard keeps the slot
+        let converted = guard.into_permit();
+        assert_eq!(in_flight.in_flight(), 2);
+
+        in_flight.release(permit);
+        assert_eq!(in_flight.in_flight(), 1);
+        in_flight.release(converted);
+        assert_eq!(in_flight.in_flight(), 0);
+    }
+
+    #[test]
+    fn verify_admission() {
+        let in_flight = InFlight::new(2);
+        let mut a = AdmissionController::new(CreditBucket::new(3, 3), &in_flight);
+
+        let first = a.try_admit(1).unwrap();
+        let second = a.try_admit(1).unwrap();
+        assert_eq!(in_flight.in_flight(), 2);
+
+        // No slots left, no tokens are consumed
+        assert_eq!(a.try_admit(1).err(), Some(AdmissionError::Concurrency));
+        assert_eq!(a.limiter().tokens(), 1);
+
+        drop(first);
+        // Rate limited, slot is released again
+        assert_eq!(a.try_admit(2).err(), Some(AdmissionError::Rate));
+        assert_eq!(in_flight.in_flight(), 1);
+
+        let third = a.try_admit(1).unwrap();
+        drop((second, third));
+        assert_eq!(in_flight.in_flight(), 0);
+    }
+}
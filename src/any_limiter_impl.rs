@@ -0,0 +1,226 @@
+//! Enum-dispatched limiter for runtime selection without a heap
+
+use core::time::Duration;
+
+#[cfg(feature = "fixed-window")]
+use crate::FixedWindow;
+#[cfg(feature = "sliding-window")]
+use crate::SlidingWindowCounter;
+#[cfg(feature = "token-bucket")]
+use crate::TokenBucket;
+use crate::{
+    CantConsume, Clock, Diagnostics, Inspect, Limiter, LimiterConfig, LimiterKind, LimiterResult,
+    Refund, RemainingBudget, Reset, RetryHint,
+};
+
+/// Limiter of any algorithm picked at runtime
+///
+/// Like [`crate::BoxedLimiter`], but stored inline, so the algorithm can be
+/// selected e.g. from a configuration byte on targets without an allocator.
+/// The size is that of the largest enabled variant.
+///
+/// [`crate::SlidingWindowLog`] is left out, as its buffer would grow every
+/// variant to its size.
+///
+/// # Generic arguments
+/// * `T` - time provider
+#[derive(Debug, Clone)]
+#[non_exhaustive]
+pub enum AnyLimiter<T>
+where
+    T: Clock,
+{
+    /// [`TokenBucket`]
+    #[cfg(feature = "token-bucket")]
+    TokenBucket(TokenBucket<T>),
+    /// [`FixedWindow`]
+    #[cfg(feature = "fixed-window")]
+    FixedWindow(FixedWindow<T>),
+    /// [`SlidingWindowCounter`]
+    #[cfg(feature = "sliding-window")]
+    SlidingWindowCounter(SlidingWindowCounter<T>),
+}
+
+/// Evaluate an expression with the limiter of any variant
+macro_rules! dispatch {
+    ($any:expr, $limiter:ident => $e:expr) => {
+        match $any {
+            #[cfg(feature = "token-bucket")]
+            AnyLimiter::TokenBucket($limiter) => $e,
+            #[cfg(feature = "fixed-window")]
+            AnyLimiter::FixedWindow($limiter) => $e,
+            #[cfg(feature = "sliding-window")]
+            AnyLimiter::SlidingWindowCounter($limiter) => $e,
+        }
+    };
+}
+
+impl<T> AnyLimiter<T>
+where
+    T: Clock,
+{
+    /// Build a limiter of the algorithm picked at runtime utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `config` - algorithm and its parameters
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// # Returns
+    /// `None` for algorithms without a variant, i.e. [`LimiterConfig::SlidingWindowLog`]
+    pub fn from_config_with_time_provider(config: LimiterConfig, time_provider: T) -> Option<Self> {
+        match config {
+            #[cfg(feature = "token-bucket")]
+            LimiterConfig::TokenBucket {
+                rate_per_s,
+                capacity,
+            } => Some(Self::TokenBucket(TokenBucket::new_with_time_provider(
+                rate_per_s,
+                capacity,
+                time_provider,
+            ))),
+            #[cfg(feature = "fixed-window")]
+            LimiterConfig::FixedWindow {
+                capacity,
+                window_width_ms,
+            } => Some(Self::FixedWindow(FixedWindow::new_with_time_provider(
+                capacity,
+                window_width_ms,
+                time_provider,
+            ))),
+            #[cfg(feature = "sliding-window")]
+            LimiterConfig::SlidingWindowCounter {
+                capacity,
+                window_width_ms,
+            } => Some(Self::SlidingWindowCounter(
+                SlidingWindowCounter::new_with_time_provider(
+                    capacity,
+                    window_width_ms,
+                    time_provider,
+                ),
+            )),
+            #[cfg(all(feature = "alloc", feature = "sliding-window"))]
+            LimiterConfig::SlidingWindowLog { .. } => None,
+        }
+    }
+}
+
+impl<T> Limiter for AnyLimiter<T>
+where
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        dispatch!(self, l => l.try_consume(tokens))
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        dispatch!(self, l => l.try_consume_batch(costs))
+    }
+
+    fn kind(&self) -> LimiterKind {
+        dispatch!(self, l => l.kind())
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        dispatch!(self, l => l.name())
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        dispatch!(self, l => l.self_check())
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        dispatch!(self, l => l.last_consume_at())
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        dispatch!(self, l => l.idle_for())
+    }
+
+    fn reset(&mut self) {
+        dispatch!(self, l => l.reset())
+    }
+}
+
+impl<T> Refund for AnyLimiter<T>
+where
+    T: Clock,
+{
+    fn refund(&mut self, tokens: u64) {
+        dispatch!(self, l => l.refund(tokens))
+    }
+}
+
+impl<T> Reset for AnyLimiter<T> where T: Clock {}
+
+impl<T> RetryHint for AnyLimiter<T>
+where
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        dispatch!(self, l => l.time_until_available(tokens))
+    }
+}
+
+impl<T> Inspect for AnyLimiter<T>
+where
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        dispatch!(self, l => l.tokens_available())
+    }
+}
+
+impl<T> RemainingBudget for AnyLimiter<T>
+where
+    T: Clock,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        dispatch!(self, l => l.try_consume_remaining(tokens))
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "alloc",
+    feature = "token-bucket",
+    feature = "sliding-window"
+))]
+mod tests {
+    use crate::{mock_assets::MockClock, Inspect, Limiter, LimiterConfig, LimiterKind};
+
+    use super::AnyLimiter;
+
+    #[test]
+    fn verify_any_limiter() {
+        let clock = MockClock::new();
+        // E.g. selected by a configuration byte
+        let configs = [
+            LimiterConfig::TokenBucket {
+                rate_per_s: 1000,
+                capacity: 3,
+            },
+            LimiterConfig::SlidingWindowCounter {
+                capacity: 3,
+                window_width_ms: 10,
+            },
+        ];
+        for (config, kind) in configs
+            .into_iter()
+            .zip([LimiterKind::TokenBucket, LimiterKind::SlidingWindowCounter])
+        {
+            let mut l =
+                AnyLimiter::from_config_with_time_provider(config, || clock.step(0)).unwrap();
+            assert_eq!(l.kind(), kind);
+            assert!(l.try_consume(3).is_ok());
+            assert!(l.try_consume_one().is_err());
+            assert_eq!(l.tokens_available(), 0);
+        }
+
+        let log = LimiterConfig::SlidingWindowLog {
+            capacity: 3,
+            window_width_ms: 10,
+        };
+        assert!(AnyLimiter::from_config_with_time_provider(log, || clock.step(0)).is_none());
+    }
+}
@@ -0,0 +1,76 @@
+//! Async consumes with pluggable sleep functions
+
+use core::{future::Future, time::Duration};
+
+use crate::{CantConsume, LimiterResult, RateLimited, RetryHint};
+
+/// Async consumes for any [`RetryHint`] limiter
+///
+/// The waiting is delegated to the async sleep function of the used runtime,
+/// e.g. `tokio::time::sleep`, `async_std::task::sleep` or
+/// `embassy_time::Timer::after`, so no particular executor is required. The
+/// sleep durations come from [`RetryHint::time_until_available`].
+pub trait AsyncLimiter: RetryHint {
+    /// Consume tokens, waiting until they are available
+    ///
+    /// # Arguments
+    /// * `tokens` - how many tokens to consume
+    /// * `sleep` - async sleep function of the used runtime
+    ///
+    /// # Returns
+    /// * `Ok(())` - tokens consumed
+    /// * `Err(CantConsume)` - the tokens can't become available by waiting, e.g. they exceed the capacity
+    fn acquire<S, F>(&mut self, tokens: u64, mut sleep: S) -> impl Future<Output = LimiterResult>
+    where
+        S: FnMut(Duration) -> F,
+        F: Future<Output = ()>,
+    {
+        async move {
+            loop {
+                match self.try_consume_with_retry(tokens) {
+                    Ok(()) => return Ok(()),
+                    Err(RateLimited { retry_after: None }) => return Err(CantConsume),
+                    Err(RateLimited {
+                        retry_after: Some(wait),
+                    }) => sleep(wait).await,
+                }
+            }
+        }
+    }
+}
+
+impl<L> AsyncLimiter for L where L: RetryHint + ?Sized {}
+
+#[cfg(all(test, feature = "token-bucket"))]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{
+        mock_assets::{block_on, MockClock},
+        Limiter, TokenBucket,
+    };
+
+    use super::AsyncLimiter;
+
+    #[test]
+    fn verify_acquire() {
+        let clock = MockClock::new();
+        let mut b = TokenBucket::new_with_time_provider(1000, 2, || clock.step(0));
+        // Sleeping only advances the clock
+        let sleeps = core::cell::Cell::new(0);
+        let sleep = |d: Duration| {
+            sleeps.set(sleeps.get() + 1);
+            clock.step(d.as_micros() as u64);
+            async {}
+        };
+
+        assert!(block_on(b.acquire(2, sleep)).is_ok());
+        assert_eq!(sleeps.get(), 0);
+        assert!(block_on(b.acquire(2, sleep)).is_ok());
+        assert!(sleeps.get() > 0);
+        assert!(b.try_consume_one().is_err());
+
+        // Can never succeed
+        assert!(block_on(b.acquire(3, sleep)).is_err());
+    }
+}
@@ -0,0 +1,419 @@
+//! Bandwidth limiting for async readers and writers with pluggable sleep functions
+
+use std::{
+    future::Future,
+    io,
+    pin::Pin,
+    task::{ready, Context, Poll},
+    time::Duration,
+};
+
+use pin_project_lite::pin_project;
+
+use crate::{
+    io_impl::{never_available, try_acquire_bytes},
+    Inspect, RateLimited, RetryHint,
+};
+
+pin_project! {
+    /// Bookkeeping shared by the async throttles
+    struct Throttle<L, S, F> {
+        limiter: L,
+        sleep: S,
+        #[pin]
+        sleeping: Option<F>,
+        // Bytes already paid for but not yet transferred
+        granted: usize,
+        block_size: usize,
+    }
+}
+
+impl<L, S, F> Throttle<L, S, F>
+where
+    L: Inspect + RetryHint,
+    S: FnMut(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    fn new(limiter: L, block_size: usize, sleep: S) -> Self {
+        Self {
+            limiter,
+            sleep,
+            sleeping: None,
+            granted: 0,
+            block_size: block_size.max(1),
+        }
+    }
+
+    /// Wait until some bytes are paid for
+    ///
+    /// # Returns
+    /// How many bytes may be transferred, at most `len`
+    fn poll_grant(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        len: usize,
+    ) -> Poll<io::Result<usize>> {
+        let mut this = self.project();
+        while *this.granted == 0 {
+            if let Some(sleeping) = this.sleeping.as_mut().as_pin_mut() {
+                ready!(sleeping.poll(cx));
+                this.sleeping.set(None);
+            }
+            match try_acquire_bytes(this.limiter, *this.block_size, len) {
+                Ok(bytes) => *this.granted = bytes,
+                Err(RateLimited {
+                    retry_after: Some(wait),
+                }) => this.sleeping.set(Some((this.sleep)(wait))),
+                Err(RateLimited { retry_after: None }) => {
+                    return Poll::Ready(Err(never_available()))
+                }
+            }
+        }
+        Poll::Ready(Ok(len.min(*this.granted)))
+    }
+
+    /// Record transferred bytes
+    fn spend(self: Pin<&mut Self>, bytes: usize) {
+        let granted = self.project().granted;
+        *granted = granted.saturating_sub(bytes);
+    }
+}
+
+pin_project! {
+    /// Bandwidth limiting wrapper for async readers
+    ///
+    /// The async counterpart of [`crate::ThrottledReader`], implementing `AsyncRead`
+    /// of `tokio` with the `tokio` feature and of `futures` with the `futures`
+    /// feature. The waiting is delegated to the async sleep function of the
+    /// used runtime, e.g. `tokio::time::sleep`.
+    ///
+    /// Tokens paid for bytes that weren't read yet carry over to the next read.
+    ///
+    /// # Generic arguments
+    /// * `R` - wrapped reader
+    /// * `L` - limiter, e.g. a [`crate::TokenBucket`] with a rate in blocks per second
+    /// * `S` - async sleep function
+    pub struct AsyncThrottledReader<R, L, S, F> {
+        #[pin]
+        inner: R,
+        #[pin]
+        throttle: Throttle<L, S, F>,
+    }
+}
+
+impl<R, L, S, F> AsyncThrottledReader<R, L, S, F>
+where
+    L: Inspect + RetryHint,
+    S: FnMut(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    /// Wrap a reader, consuming one token per byte
+    ///
+    /// # Arguments
+    /// * `inner` - reader to wrap
+    /// * `limiter` - limiter shaping the bandwidth
+    /// * `sleep` - async sleep function of the used runtime
+    pub fn new(inner: R, limiter: L, sleep: S) -> Self {
+        Self::with_block_size(inner, limiter, 1, sleep)
+    }
+
+    /// Wrap a reader, consuming one token per block of bytes
+    ///
+    /// # Arguments
+    /// * `inner` - reader to wrap
+    /// * `limiter` - limiter shaping the bandwidth
+    /// * `block_size` - how many bytes a single token covers, at least one
+    /// * `sleep` - async sleep function of the used runtime
+    pub fn with_block_size(inner: R, limiter: L, block_size: usize, sleep: S) -> Self {
+        Self {
+            inner,
+            throttle: Throttle::new(limiter, block_size, sleep),
+        }
+    }
+
+    /// Get a reference to the wrapped reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a reference to the limiter
+    pub fn limiter(&self) -> &L {
+        &self.throttle.limiter
+    }
+
+    /// Unwrap the wrapped reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<R, L, S, F> tokio::io::AsyncRead for AsyncThrottledReader<R, L, S, F>
+where
+    R: tokio::io::AsyncRead,
+    L: Inspect + RetryHint,
+    S: FnMut(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut tokio::io::ReadBuf<'_>,
+    ) -> Poll<io::Result<()>> {
+        let mut this = self.project();
+        if buf.remaining() == 0 {
+            return this.inner.poll_read(cx, buf);
+        }
+        let len = ready!(this.throttle.as_mut().poll_grant(cx, buf.remaining()))?;
+
+        let mut limited = tokio::io::ReadBuf::new(buf.initialize_unfilled_to(len));
+        ready!(this.inner.poll_read(cx, &mut limited))?;
+        let read = limited.filled().len();
+        buf.advance(read);
+        this.throttle.spend(read);
+        Poll::Ready(Ok(()))
+    }
+}
+
+#[cfg(all(feature = "futures", feature = "std"))]
+impl<R, L, S, F> futures_io::AsyncRead for AsyncThrottledReader<R, L, S, F>
+where
+    R: futures_io::AsyncRead,
+    L: Inspect + RetryHint,
+    S: FnMut(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    fn poll_read(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &mut [u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut this = self.project();
+        if buf.is_empty() {
+            return this.inner.poll_read(cx, buf);
+        }
+        let len = ready!(this.throttle.as_mut().poll_grant(cx, buf.len()))?;
+
+        let read = ready!(this.inner.poll_read(cx, &mut buf[..len]))?;
+        this.throttle.spend(read);
+        Poll::Ready(Ok(read))
+    }
+}
+
+pin_project! {
+    /// Bandwidth limiting wrapper for async writers
+    ///
+    /// The async counterpart of [`crate::ThrottledWriter`], implementing `AsyncWrite`
+    /// of `tokio` with the `tokio` feature and of `futures` with the `futures`
+    /// feature. The waiting is delegated to the async sleep function of the
+    /// used runtime, e.g. `tokio::time::sleep`.
+    ///
+    /// Tokens paid for bytes that weren't written yet carry over to the next write.
+    ///
+    /// # Generic arguments
+    /// * `W` - wrapped writer
+    /// * `L` - limiter, e.g. a [`crate::TokenBucket`] with a rate in blocks per second
+    /// * `S` - async sleep function
+    pub struct AsyncThrottledWriter<W, L, S, F> {
+        #[pin]
+        inner: W,
+        #[pin]
+        throttle: Throttle<L, S, F>,
+    }
+}
+
+impl<W, L, S, F> AsyncThrottledWriter<W, L, S, F>
+where
+    L: Inspect + RetryHint,
+    S: FnMut(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    /// Wrap a writer, consuming one token per byte
+    ///
+    /// # Arguments
+    /// * `inner` - writer to wrap
+    /// * `limiter` - limiter shaping the bandwidth
+    /// * `sleep` - async sleep function of the used runtime
+    pub fn new(inner: W, limiter: L, sleep: S) -> Self {
+        Self::with_block_size(inner, limiter, 1, sleep)
+    }
+
+    /// Wrap a writer, consuming one token per block of bytes
+    ///
+    /// # Arguments
+    /// * `inner` - writer to wrap
+    /// * `limiter` - limiter shaping the bandwidth
+    /// * `block_size` - how many bytes a single token covers, at least one
+    /// * `sleep` - async sleep function of the used runtime
+    pub fn with_block_size(inner: W, limiter: L, block_size: usize, sleep: S) -> Self {
+        Self {
+            inner,
+            throttle: Throttle::new(limiter, block_size, sleep),
+        }
+    }
+
+    /// Get a reference to the wrapped writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a reference to the limiter
+    pub fn limiter(&self) -> &L {
+        &self.throttle.limiter
+    }
+
+    /// Unwrap the wrapped writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+#[cfg(feature = "tokio")]
+impl<W, L, S, F> tokio::io::AsyncWrite for AsyncThrottledWriter<W, L, S, F>
+where
+    W: tokio::io::AsyncWrite,
+    L: Inspect + RetryHint,
+    S: FnMut(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut this = self.project();
+        if buf.is_empty() {
+            return this.inner.poll_write(cx, buf);
+        }
+        let len = ready!(this.throttle.as_mut().poll_grant(cx, buf.len()))?;
+
+        let written = ready!(this.inner.poll_write(cx, &buf[..len]))?;
+        this.throttle.spend(written);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_shutdown(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_shutdown(cx)
+    }
+}
+
+#[cfg(all(feature = "futures", feature = "std"))]
+impl<W, L, S, F> futures_io::AsyncWrite for AsyncThrottledWriter<W, L, S, F>
+where
+    W: futures_io::AsyncWrite,
+    L: Inspect + RetryHint,
+    S: FnMut(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    fn poll_write(
+        self: Pin<&mut Self>,
+        cx: &mut Context<'_>,
+        buf: &[u8],
+    ) -> Poll<io::Result<usize>> {
+        let mut this = self.project();
+        if buf.is_empty() {
+            return this.inner.poll_write(cx, buf);
+        }
+        let len = ready!(this.throttle.as_mut().poll_grant(cx, buf.len()))?;
+
+        let written = ready!(this.inner.poll_write(cx, &buf[..len]))?;
+        this.throttle.spend(written);
+        Poll::Ready(Ok(written))
+    }
+
+    fn poll_flush(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_flush(cx)
+    }
+
+    fn poll_close(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<io::Result<()>> {
+        self.project().inner.poll_close(cx)
+    }
+}
+
+#[cfg(all(test, feature = "token-bucket"))]
+mod tests {
+    use std::time::Duration;
+
+    use crate::TokenBucket;
+
+    use super::{AsyncThrottledReader, AsyncThrottledWriter};
+
+    #[cfg(feature = "tokio")]
+    #[tokio::test(start_paused = true)]
+    async fn verify_tokio_throttle() {
+        use std::pin::pin;
+
+        use tokio::{
+            io::{AsyncReadExt, AsyncWriteExt},
+            time::Instant,
+        };
+
+        let start = Instant::now();
+        // One token per millisecond, burst of four
+        let b = TokenBucket::new_with_time_provider(1000, 4, move || start.elapsed());
+        let data = [7u8; 10];
+        let mut r = pin!(AsyncThrottledReader::new(&data[..], b, tokio::time::sleep));
+
+        let mut buf = [0u8; 10];
+        assert_eq!(r.read(&mut buf).await.unwrap(), 4);
+        let mut rest = Vec::new();
+        assert_eq!(r.read_to_end(&mut rest).await.unwrap(), 6);
+        assert_eq!(rest, [7u8; 6]);
+        // Six bytes had to be waited for
+        assert!(start.elapsed() >= Duration::from_millis(6));
+
+        // Two blocks of two bytes at once
+        let b = TokenBucket::new_with_time_provider(1000, 2, move || start.elapsed());
+        let mut w = pin!(AsyncThrottledWriter::with_block_size(
+            Vec::new(),
+            b,
+            2,
+            tokio::time::sleep
+        ));
+        let before = Instant::now();
+        w.write_all(&[1, 2, 3, 4, 5, 6]).await.unwrap();
+        assert_eq!(AsyncThrottledWriter::get_ref(&w), &[1, 2, 3, 4, 5, 6]);
+        // The last block had to be waited for
+        assert!(before.elapsed() >= Duration::from_millis(1));
+    }
+
+    #[cfg(all(feature = "futures", feature = "std"))]
+    #[test]
+    fn verify_futures_throttle() {
+        use core::future::poll_fn;
+        use std::pin::pin;
+
+        use futures_io::{AsyncRead, AsyncWrite};
+
+        use crate::mock_assets::{block_on, MockClock};
+
+        let clock = MockClock::new();
+        let b = TokenBucket::new_with_time_provider(1000, 2, || clock.step(0));
+        // Sleeping only advances the clock
+        let sleep = |d: Duration| {
+            clock.step(d.as_micros() as u64);
+            async {}
+        };
+
+        let data = [7u8; 5];
+        let mut r = pin!(AsyncThrottledReader::new(&data[..], b, sleep));
+        let mut buf = [0u8; 5];
+        let mut total = 0;
+        while total < data.len() {
+            let read = block_on(poll_fn(|cx| r.as_mut().poll_read(cx, &mut buf[total..]))).unwrap();
+            // Never more than the burst at once
+            assert!(read <= 2);
+            total += read;
+        }
+        assert_eq!(clock.step(0), Duration::from_millis(3));
+
+        let b = TokenBucket::new_with_time_provider(1000, 2, || clock.step(0));
+        let mut w = pin!(AsyncThrottledWriter::new(Vec::new(), b, sleep));
+        let written = block_on(poll_fn(|cx| w.as_mut().poll_write(cx, &[1, 2, 3])));
+        assert_eq!(written.unwrap(), 2);
+    }
+}
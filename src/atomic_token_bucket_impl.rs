@@ -0,0 +1,633 @@
+//! Atomic token bucket -type limiter with externally stored state
+
+use core::{
+    ops::Deref,
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+#[cfg(feature = "std")]
+use crate::StdClock;
+#[cfg(all(feature = "shared-memory", unix))]
+use crate::SystemClock;
+use crate::{
+    CantConsume, Clock, ConfigError, Diagnostics, Inspect, Limiter, LimiterKind, LimiterResult,
+    RemainingBudget, Reset, RetryHint,
+};
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// [`AtomicTokenBucket`] using the system clock, as returned by [`token_bucket_sync`]
+#[cfg(feature = "std")]
+pub type StdAtomicTokenBucket = AtomicTokenBucket<StdClock, Box<AtomicTokenBucketState>>;
+
+/// Build a token bucket limiter that can be shared between threads
+///
+/// The returned limiter is `Send + Sync` and can be consumed through a shared
+/// reference with [`AtomicTokenBucket::try_consume_shared`], e.g. after
+/// wrapping it in an `Arc`.
+///
+/// # Arguments
+/// * `rate_per_sec` - how many consumes should be allowed per second on average
+/// * `capacity` - bucket capacity to dictate the burstiness of this limiter
+#[cfg(feature = "std")]
+pub fn token_bucket_sync(rate_per_s: u64, capacity: u64) -> StdAtomicTokenBucket {
+    AtomicTokenBucket::new_with_time_provider(
+        rate_per_s,
+        capacity,
+        Box::new(AtomicTokenBucketState::new()),
+        StdClock,
+    )
+}
+
+/// Build a token bucket limiter whose state lives in a named POSIX shared memory region
+///
+/// Every process opening the region with the same `name` shares a single bucket.
+/// The region is created if it doesn't exist yet.
+///
+/// # Arguments
+/// * `name` - name of the shared memory object, e.g. `"/my-app-limiter"`
+/// * `rate_per_sec` - how many consumes should be allowed per second on average
+/// * `capacity` - bucket capacity to dictate the burstiness of this limiter
+#[cfg(all(feature = "shared-memory", unix))]
+pub fn shared_token_bucket(
+    name: &str,
+    rate_per_s: u64,
+    capacity: u64,
+) -> std::io::Result<AtomicTokenBucket<SystemClock, SharedMemory>> {
+    let region = SharedMemory::open(name)?;
+    // Processes only share the epoch of the wall clock
+    Ok(AtomicTokenBucket::new_with_time_provider(
+        rate_per_s,
+        capacity,
+        region,
+        SystemClock,
+    ))
+}
+
+/// Lock-free state of an [`AtomicTokenBucket`]
+///
+/// The whole bucket is represented by a single atomic timestamp, the
+/// *theoretical arrival time* of the next conforming consume (see GCRA).
+/// This makes the state trivially placeable in memory shared between
+/// threads or processes. A zeroed state represents a full bucket.
+#[derive(Debug)]
+#[repr(C)]
+pub struct AtomicTokenBucketState {
+    tat_ns: AtomicU64,
+}
+
+impl AtomicTokenBucketState {
+    /// Create a new state representing a full bucket
+    pub const fn new() -> Self {
+        Self {
+            tat_ns: AtomicU64::new(0),
+        }
+    }
+}
+
+impl Default for AtomicTokenBucketState {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Atomic token bucket -type rate limiter
+///
+/// Behaves like [`crate::TokenBucket`], but keeps its state in an
+/// [`AtomicTokenBucketState`] accessed through a handle, so that several
+/// limiters (possibly in different processes) can enforce one combined
+/// limit. All limiters sharing a state must use the same configuration
+/// and a time provider with a common epoch.
+///
+/// # Generic arguments
+/// * `T` - time provider
+/// * `S` - handle to the state, e.g. `&'static AtomicTokenBucketState` or `SharedMemory`
+#[derive(Debug)]
+pub struct AtomicTokenBucket<T, S>
+where
+    T: Clock,
+    S: Deref<Target = AtomicTokenBucketState>,
+{
+    rate_per_s: u64,
+    capacity: u64,
+    /// Time it takes to refill the whole bucket
+    tolerance_ns: u64,
+    state: S,
+    time_provider: T,
+    name: Option<&'static str>,
+}
+
+impl<T, S> AtomicTokenBucket<T, S>
+where
+    T: Clock,
+    S: Deref<Target = AtomicTokenBucketState>,
+{
+    /// Initialize a new atomic token bucket utilizing the given timer and state
+    ///
+    /// # Arguments
+    /// * `rate_per_sec` - how many consumes should be allowed per second on average
+    /// * `capacity` - bucket capacity to dictate the burstiness of this limiter
+    /// * `state` - handle to the shared bucket state
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// The state is kept with nanosecond resolution, so every consume takes
+    /// at least a nanosecond of budget and rates above 10^9 tokens per second
+    /// only hold for multi-token consumes.
+    ///
+    /// # Panics
+    /// On a division by zero if `rate_per_s` is zero, use
+    /// [`Self::try_new_with_time_provider`] to validate the configuration.
+    pub fn new_with_time_provider(
+        rate_per_s: u64,
+        capacity: u64,
+        state: S,
+        time_provider: T,
+    ) -> Self {
+        let tolerance_ns = duration_of(capacity, rate_per_s);
+        Self {
+            rate_per_s,
+            capacity,
+            tolerance_ns: tolerance_ns.min(u64::MAX as u128) as u64,
+            state,
+            time_provider,
+            name: None,
+        }
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting configurations
+    /// that would never admit anything or never refill
+    ///
+    /// # Returns
+    /// * [`ConfigError::ZeroRate`] or [`ConfigError::ZeroCapacity`] for zero arguments
+    /// * [`ConfigError::RateOverflow`] if `rate_per_s` exceeds one token per nanosecond
+    /// * [`ConfigError::CapacityOverflow`] if refilling `capacity` tokens takes longer
+    ///   than the nanosecond state can represent
+    pub fn try_new_with_time_provider(
+        rate_per_s: u64,
+        capacity: u64,
+        state: S,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        if rate_per_s == 0 {
+            return Err(ConfigError::ZeroRate);
+        }
+        if capacity == 0 {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        if rate_per_s as u128 > NANOS_PER_SEC {
+            return Err(ConfigError::RateOverflow);
+        }
+        if duration_of(capacity, rate_per_s) > u64::MAX as u128 {
+            return Err(ConfigError::CapacityOverflow);
+        }
+        Ok(Self::new_with_time_provider(
+            rate_per_s,
+            capacity,
+            state,
+            time_provider,
+        ))
+    }
+
+    /// Assign a name to this limiter, see [`Limiter::name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Configured average rate in tokens per second
+    pub fn rate_per_s(&self) -> u64 {
+        self.rate_per_s
+    }
+
+    /// Try to consume tokens through a shared reference
+    ///
+    /// Same as [`Limiter::try_consume`], but doesn't require exclusive access.
+    pub fn try_consume_shared(&self, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        self.try_consume_at(now, tokens).map(|_| ())
+    }
+
+    /// Consume at the given time instead of reading the time provider,
+    /// returning the remaining budget
+    fn try_consume_at(&self, now: Duration, tokens: u64) -> Result<u64, CantConsume> {
+        let now = now.as_nanos().min(u64::MAX as u128) as u64;
+        let cost = self.cost_ns(tokens);
+
+        let mut tat = self.state.tat_ns.load(Ordering::Acquire);
+        loop {
+            let new_tat = tat.max(now).saturating_add(cost);
+            if new_tat - now > self.tolerance_ns {
+                return Err(CantConsume);
+            }
+
+            match self.state.tat_ns.compare_exchange_weak(
+                tat,
+                new_tat,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => {
+                    let headroom = self.tolerance_ns - (new_tat - now);
+                    return Ok(self.tokens_in(headroom));
+                }
+                Err(actual) => tat = actual,
+            }
+        }
+    }
+
+    /// Time it takes to refill `tokens`, saturated to the state's range
+    fn cost_ns(&self, tokens: u64) -> u64 {
+        duration_of(tokens, self.rate_per_s).min(u64::MAX as u128) as u64
+    }
+
+    /// Whole tokens refilled in `ns` nanoseconds
+    fn tokens_in(&self, ns: u64) -> u64 {
+        (ns as u128 * self.rate_per_s as u128 / NANOS_PER_SEC) as u64
+    }
+}
+
+/// Nanoseconds it takes to refill `tokens` at `rate_per_s`, rounded up so
+/// that no consume is charged less than it's worth
+fn duration_of(tokens: u64, rate_per_s: u64) -> u128 {
+    (tokens as u128 * NANOS_PER_SEC).div_ceil(rate_per_s as u128)
+}
+
+impl<T, S> Limiter for AtomicTokenBucket<T, S>
+where
+    T: Clock,
+    S: Deref<Target = AtomicTokenBucketState>,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        self.try_consume_shared(tokens)
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        let now = self.time_provider.now();
+        costs
+            .iter()
+            .take_while(|&&tokens| self.try_consume_at(now, tokens).is_ok())
+            .count()
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::AtomicTokenBucket
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        // The state may be shared with other processes, so clock behavior
+        // can't be tracked locally
+        Diagnostics {
+            config_valid: self.rate_per_s != 0 && self.capacity != 0,
+            ..Diagnostics::HEALTHY
+        }
+    }
+
+    fn reset(&mut self) {
+        // Shared with all limiters using the same state
+        self.state.tat_ns.store(0, Ordering::Release);
+    }
+}
+
+impl<T, S> Reset for AtomicTokenBucket<T, S>
+where
+    T: Clock,
+    S: Deref<Target = AtomicTokenBucketState>,
+{
+}
+
+impl<T, S> RetryHint for AtomicTokenBucket<T, S>
+where
+    T: Clock,
+    S: Deref<Target = AtomicTokenBucketState>,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let cost = self.cost_ns(tokens);
+        if cost > self.tolerance_ns {
+            return None;
+        }
+
+        let now = self.time_provider.now();
+        let now = now.as_nanos().min(u64::MAX as u128) as u64;
+        let tat = self.state.tat_ns.load(Ordering::Acquire);
+        let earliest = tat.saturating_add(cost).saturating_sub(self.tolerance_ns);
+        Some(Duration::from_nanos(earliest.saturating_sub(now)))
+    }
+}
+
+impl<T, S> Inspect for AtomicTokenBucket<T, S>
+where
+    T: Clock,
+    S: Deref<Target = AtomicTokenBucketState>,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        let now = now.as_nanos().min(u64::MAX as u128) as u64;
+        let tat = self.state.tat_ns.load(Ordering::Acquire);
+        let headroom = self.tolerance_ns.saturating_sub(tat.max(now) - now);
+        self.tokens_in(headroom)
+    }
+}
+
+impl<T, S> RemainingBudget for AtomicTokenBucket<T, S>
+where
+    T: Clock,
+    S: Deref<Target = AtomicTokenBucketState>,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        let now = self.time_provider.now();
+        self.try_consume_at(now, tokens)
+    }
+}
+
+/// Handle to an [`AtomicTokenBucketState`] placed in a named POSIX shared memory region
+///
+/// The mapping is released on drop, but the underlying shared memory object
+/// persists until [`SharedMemory::unlink`] is called.
+#[cfg(all(feature = "shared-memory", unix))]
+#[derive(Debug)]
+pub struct SharedMemory {
+    ptr: core::ptr::NonNull<AtomicTokenBucketState>,
+}
+
+#[cfg(all(feature = "shared-memory", unix))]
+impl SharedMemory {
+    /// Open or create the named shared memory region and map it
+    ///
+    /// # Arguments
+    /// * `name` - name of the shared memory object, e.g. `"/my-app-limiter"`
+    pub fn open(name: &str) -> std::io::Result<Self> {
+        let name = std::ffi::CString::new(name)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        let size = core::mem::size_of::<AtomicTokenBucketState>();
+
+        // SAFETY: FFI calls with a valid C string, the file descriptor is closed
+        // on every path and the mapping is checked before use. A freshly created
+        // object is zero-filled by ftruncate, which is a valid full bucket state.
+        unsafe {
+            let fd = libc::shm_open(name.as_ptr(), libc::O_CREAT | libc::O_RDWR, 0o600);
+            if fd < 0 {
+                return Err(std::io::Error::last_os_error());
+            }
+
+            if libc::ftruncate(fd, size as libc::off_t) != 0 {
+                let err = std::io::Error::last_os_error();
+                libc::close(fd);
+                return Err(err);
+            }
+
+            let ptr = libc::mmap(
+                core::ptr::null_mut(),
+                size,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd,
+                0,
+            );
+            let err = std::io::Error::last_os_error();
+            libc::close(fd);
+
+            if ptr == libc::MAP_FAILED {
+                return Err(err);
+            }
+
+            Ok(Self {
+                ptr: core::ptr::NonNull::new_unchecked(ptr.cast()),
+            })
+        }
+    }
+
+    /// Remove the named shared memory object
+    ///
+    /// Existing mappings stay valid, but subsequent [`SharedMemory::open`] calls
+    /// will create a fresh region.
+    pub fn unlink(name: &str) -> std::io::Result<()> {
+        let name = std::ffi::CString::new(name)
+            .map_err(|e| std::io::Error::new(std::io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: FFI call with a valid C string
+        if unsafe { libc::shm_unlink(name.as_ptr()) } != 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+#[cfg(all(feature = "shared-memory", unix))]
+impl Deref for SharedMemory {
+    type Target = AtomicTokenBucketState;
+
+    fn deref(&self) -> &Self::Target {
+        // SAFETY: the pointer refers to a live mapping of the correct size
+        // that is only accessed through atomics
+        unsafe { self.ptr.as_ref() }
+    }
+}
+
+#[cfg(all(feature = "shared-memory", unix))]
+impl Drop for SharedMemory {
+    fn drop(&mut self) {
+        // SAFETY: the pointer refers to a live mapping created in `open`
+        unsafe {
+            libc::munmap(
+                self.ptr.as_ptr().cast(),
+                core::mem::size_of::<AtomicTokenBucketState>(),
+            );
+        }
+    }
+}
+
+// SAFETY: the mapped state is only accessed through atomics
+#[cfg(all(feature = "shared-memory", unix))]
+unsafe impl Send for SharedMemory {}
+#[cfg(all(feature = "shared-memory", unix))]
+unsafe impl Sync for SharedMemory {}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{mock_assets::MockClock, ConfigError, Inspect, Limiter, RetryHint};
+
+    use super::{AtomicTokenBucket, AtomicTokenBucketState};
+
+    #[test]
+    fn verify_rate() {
+        let clock = MockClock::new();
+        let state = AtomicTokenBucketState::new();
+        // Each call steps the clock 100us forward
+        let mut b =
+            AtomicTokenBucket::new_with_time_provider(1000, 100, &state, || clock.step(100));
+
+        // T = 0us, tokens = 100
+        assert!(b.try_consume(100).is_ok());
+        // T = 100us, tokens = 0.1
+        assert!(b.try_consume(1).is_err());
+        // T = 200us..800us, tokens < 1
+        for _ in 0..7 {
+            assert!(b.try_consume(1).is_err());
+        }
+        // T = 900us, tokens = 0.9
+        assert!(b.try_consume(1).is_err());
+        // T = 1000us, tokens = 1
+        assert!(b.try_consume(1).is_ok());
+        // T = 1100us, tokens = 0.1
+        assert!(b.try_consume(1).is_err());
+    }
+
+    #[test]
+    fn verify_shared_state() {
+        let clock = MockClock::new();
+        let state = AtomicTokenBucketState::new();
+        let a = AtomicTokenBucket::new_with_time_provider(1, 10, &state, || clock.step(0));
+        let b = AtomicTokenBucket::new_with_time_provider(1, 10, &state, || clock.step(0));
+
+        // Both limiters draw from the same bucket
+        assert!(a.try_consume_shared(6).is_ok());
+        assert!(b.try_consume_shared(5).is_err());
+        assert!(b.try_consume_shared(4).is_ok());
+        assert!(a.try_consume_shared(1).is_err());
+    }
+
+    #[test]
+    fn verify_retry_hint_fresh_state() {
+        let clock = MockClock::new();
+        let state = AtomicTokenBucketState::new();
+        let b = AtomicTokenBucket::new_with_time_provider(1000, 10, &state, || clock.step(0));
+
+        // Nothing consumed yet, so the state holds no theoretical arrival time
+        assert_eq!(b.time_until_available(1), Some(Duration::ZERO));
+        assert_eq!(b.time_until_available(10), Some(Duration::ZERO));
+        assert_eq!(b.time_until_available(11), None);
+        assert!(b.try_consume_shared(10).is_ok());
+        assert_eq!(b.time_until_available(1), Some(Duration::from_millis(1)));
+    }
+
+    #[test]
+    fn verify_try_new() {
+        let state = AtomicTokenBucketState::new();
+        assert_eq!(
+            AtomicTokenBucket::try_new_with_time_provider(0, 10, &state, || Duration::ZERO).err(),
+            Some(ConfigError::ZeroRate)
+        );
+        assert_eq!(
+            AtomicTokenBucket::try_new_with_time_provider(10, 0, &state, || Duration::ZERO).err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+        assert_eq!(
+            AtomicTokenBucket::try_new_with_time_provider(1_000_000_001, 10, &state, || {
+                Duration::ZERO
+            })
+            .err(),
+            Some(ConfigError::RateOverflow)
+        );
+        assert_eq!(
+            AtomicTokenBucket::try_new_with_time_provider(1, u64::MAX, &state, || Duration::ZERO)
+                .err(),
+            Some(ConfigError::CapacityOverflow)
+        );
+        let b = AtomicTokenBucket::try_new_with_time_provider(10, 1, &state, || Duration::ZERO)
+            .unwrap();
+        assert!(b.try_consume_shared(1).is_ok());
+        assert!(
+            AtomicTokenBucket::try_new_with_time_provider(1_000_000_000, 1, &state, || {
+                Duration::ZERO
+            })
+            .is_ok()
+        );
+    }
+
+    #[test]
+    fn verify_high_rate() {
+        let clock = MockClock::new();
+        let state = AtomicTokenBucketState::new();
+        // 1.666ns per token, truncating the interval to 1ns would refill 1000 tokens per us
+        let mut b =
+            AtomicTokenBucket::new_with_time_provider(600_000_000, 1200, &state, || clock.step(0));
+
+        assert!(b.try_consume(1200).is_ok());
+        assert!(b.try_consume(1).is_err());
+        clock.step(1);
+        assert_eq!(b.tokens_available(), 600);
+        assert!(b.try_consume(600).is_ok());
+        assert!(b.try_consume(1).is_err());
+
+        // Full refill takes exactly 2us
+        clock.step(2);
+        assert_eq!(b.tokens_available(), 1200);
+        assert!(b.try_consume(1201).is_err());
+        assert!(b.try_consume(1200).is_ok());
+    }
+
+    #[test]
+    fn verify_rate_at_resolution() {
+        let clock = MockClock::new();
+        let state = AtomicTokenBucketState::new();
+        let b =
+            AtomicTokenBucket::new_with_time_provider(1_000_000_000, 500, &state, || clock.step(0));
+
+        assert!(b.try_consume_shared(500).is_ok());
+        assert!(b.try_consume_shared(1).is_err());
+        clock.step(1);
+        assert!(b.try_consume_shared(1000).is_err());
+        assert!(b.try_consume_shared(500).is_ok());
+        assert!(b.try_consume_shared(1).is_err());
+    }
+
+    #[test]
+    fn verify_debug() {
+        fn zero() -> Duration {
+            Duration::ZERO
+        }
+
+        let state = AtomicTokenBucketState::new();
+        let b = AtomicTokenBucket::new_with_time_provider(10, 1, &state, zero as fn() -> Duration)
+            .with_name("atomic");
+        let debug = format!("{b:?}");
+        assert!(debug.contains("AtomicTokenBucket"));
+        assert!(debug.contains("atomic"));
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn verify_sync_between_threads() {
+        use std::{sync::Arc, thread};
+
+        let b = Arc::new(super::token_bucket_sync(1, 100));
+        let handles: Vec<_> = (0..4)
+            .map(|_| {
+                let b = b.clone();
+                thread::spawn(move || (0..50).filter(|_| b.try_consume_shared(1).is_ok()).count())
+            })
+            .collect();
+        let admitted: usize = handles.into_iter().map(|h| h.join().unwrap()).sum();
+
+        // Refill during the test is negligible
+        assert!((100..=101).contains(&admitted));
+    }
+
+    #[cfg(all(feature = "shared-memory", unix))]
+    #[test]
+    fn verify_shared_memory() {
+        use super::{shared_token_bucket, SharedMemory};
+
+        let name = "/burster-test-shared-memory";
+        let _ = SharedMemory::unlink(name);
+
+        let a = shared_token_bucket(name, 1, 10).unwrap();
+        let b = shared_token_bucket(name, 1, 10).unwrap();
+        assert!(a.try_consume_shared(10).is_ok());
+        assert!(b.try_consume_shared(1).is_err());
+
+        SharedMemory::unlink(name).unwrap();
+    }
+}
@@ -0,0 +1,278 @@
+//! Utilization based capacity autoscaling
+
+use core::time::Duration;
+
+use crate::{
+    AdjustableCapacity, Clock, Diagnostics, Inspect, Limiter, LimiterKind, LimiterResult, Reset,
+    RetryHint, Stats,
+};
+
+/// Tuning parameters of a [`WithAutoscaling`] controller
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct AutoscaleConfig {
+    /// Capacity is never shrunk below this
+    pub min_capacity: u64,
+    /// Capacity is never grown above this
+    pub max_capacity: u64,
+    /// Length of a single evaluation interval
+    pub interval: Duration,
+    /// How much the capacity is grown or shrunk at a time
+    pub step: u64,
+    /// Grow when the tokens granted during an interval stay below this
+    /// percentage of the capacity...
+    pub grow_below_utilization_pct: u8,
+    /// ...for this many consecutive intervals
+    pub grow_after_intervals: u32,
+    /// Shrink when at least this percentage of consumes during an interval
+    /// were rejected
+    pub shrink_above_rejection_pct: u8,
+}
+
+/// Capacity autoscaling wrapper for any [`AdjustableCapacity`] limiter
+///
+/// Evaluates the traffic of the wrapped limiter over fixed intervals. When
+/// the utilization, i.e. the tokens granted during an interval relative to
+/// the capacity, stays below a threshold for long enough the capacity is
+/// grown. When rejections spike the capacity is shrunk. The capacity always
+/// stays within the bounds of the [`AutoscaleConfig`].
+///
+/// Like [`WithReporting`](crate::WithReporting), intervals are evaluated on
+/// the first consume after they have elapsed and no timer thread is
+/// involved. Intervals without a single consume leave the capacity as is.
+///
+/// # Generic arguments
+/// * `L` - wrapped limiter
+/// * `T` - time provider
+pub struct WithAutoscaling<L, T>
+where
+    L: AdjustableCapacity,
+    T: Clock,
+{
+    inner: L,
+    config: AutoscaleConfig,
+    stats: Stats,
+    quiet_intervals: u32,
+    interval_start: Duration,
+    time_provider: T,
+}
+
+impl<L, T> WithAutoscaling<L, T>
+where
+    L: AdjustableCapacity,
+    T: Clock,
+{
+    /// Wrap a limiter with capacity autoscaling
+    ///
+    /// The current capacity of the limiter is clamped to the configured bounds.
+    ///
+    /// # Arguments
+    /// * `inner` - limiter to wrap
+    /// * `config` - autoscaling parameters
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    pub fn new_with_time_provider(mut inner: L, config: AutoscaleConfig, time_provider: T) -> Self {
+        let time_now = time_provider.now();
+        let capacity = inner.current_capacity().clamp(
+            config.min_capacity,
+            config.max_capacity.max(config.min_capacity),
+        );
+        inner.set_capacity(capacity);
+        Self {
+            inner,
+            config,
+            stats: Stats::default(),
+            quiet_intervals: 0,
+            interval_start: time_now,
+            time_provider,
+        }
+    }
+
+    /// Get the autoscaling parameters
+    pub fn config(&self) -> &AutoscaleConfig {
+        &self.config
+    }
+
+    /// Get a reference to the wrapped limiter
+    pub fn inner(&self) -> &L {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped limiter
+    pub fn inner_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    /// Unwrap the wrapped limiter
+    pub fn into_inner(self) -> L {
+        self.inner
+    }
+
+    /// Evaluate and restart the interval if it has elapsed
+    fn roll_interval(&mut self) {
+        let now = self.time_provider.now();
+        let elapsed = now.saturating_sub(self.interval_start);
+        if elapsed < self.config.interval {
+            return;
+        }
+
+        if self.stats != Stats::default() {
+            self.evaluate();
+        }
+        self.stats = Stats::default();
+
+        // Keep intervals aligned to the start time, even if some were skipped
+        let interval_ns = self.config.interval.as_nanos().max(1);
+        let skipped = elapsed.as_nanos() / interval_ns;
+        let advance = Duration::from_nanos((skipped * interval_ns).min(u64::MAX as u128) as u64);
+        self.interval_start += advance;
+    }
+
+    /// Adjust the capacity according to the statistics of the elapsed interval
+    fn evaluate(&mut self) {
+        let capacity = self.inner.current_capacity();
+        let consumes = self.stats.accepted.saturating_add(self.stats.rejected) as u128;
+        let rejected_pct = self.stats.rejected as u128 * 100 / consumes.max(1);
+        let utilization_pct = self.stats.tokens_granted as u128 * 100 / (capacity as u128).max(1);
+
+        if rejected_pct >= self.config.shrink_above_rejection_pct as u128 {
+            self.quiet_intervals = 0;
+            let shrunk = capacity.saturating_sub(self.config.step);
+            self.inner
+                .set_capacity(shrunk.max(self.config.min_capacity));
+        } else if utilization_pct < self.config.grow_below_utilization_pct as u128 {
+            self.quiet_intervals = self.quiet_intervals.saturating_add(1);
+            if self.quiet_intervals >= self.config.grow_after_intervals {
+                self.quiet_intervals = 0;
+                let grown = capacity.saturating_add(self.config.step);
+                self.inner.set_capacity(grown.min(self.config.max_capacity));
+            }
+        } else {
+            self.quiet_intervals = 0;
+        }
+    }
+}
+
+impl<L, T> Limiter for WithAutoscaling<L, T>
+where
+    L: AdjustableCapacity,
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        self.roll_interval();
+        let result = self.inner.try_consume(tokens);
+        self.stats.record(tokens, &result);
+        result
+    }
+
+    fn kind(&self) -> LimiterKind {
+        self.inner.kind()
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.inner.name()
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        self.inner.self_check()
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.inner.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        self.inner.idle_for()
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+        self.stats = Stats::default();
+        self.quiet_intervals = 0;
+        self.interval_start = self.time_provider.now();
+    }
+}
+
+impl<L, T> Reset for WithAutoscaling<L, T>
+where
+    L: AdjustableCapacity + Reset,
+    T: Clock,
+{
+}
+
+impl<L, T> RetryHint for WithAutoscaling<L, T>
+where
+    L: AdjustableCapacity + RetryHint,
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        self.inner.time_until_available(tokens)
+    }
+}
+
+impl<L, T> Inspect for WithAutoscaling<L, T>
+where
+    L: AdjustableCapacity + Inspect,
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        self.inner.tokens_available()
+    }
+}
+
+#[cfg(all(test, feature = "fixed-window"))]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{mock_assets::MockClock, AdjustableCapacity, FixedWindow, Limiter};
+
+    use super::{AutoscaleConfig, WithAutoscaling};
+
+    const CONFIG: AutoscaleConfig = AutoscaleConfig {
+        min_capacity: 2,
+        max_capacity: 6,
+        interval: Duration::from_millis(10),
+        step: 2,
+        grow_below_utilization_pct: 50,
+        grow_after_intervals: 2,
+        shrink_above_rejection_pct: 50,
+    };
+
+    #[test]
+    fn verify_autoscaling() {
+        let clock = MockClock::new();
+        let w = FixedWindow::new_with_time_provider(10, 10, || clock.step(0));
+        let mut a = WithAutoscaling::new_with_time_provider(w, CONFIG, || clock.step(0));
+        // Clamped to the maximum
+        assert_eq!(a.inner().current_capacity(), 6);
+
+        // Rejections spike, shrink
+        assert!(a.try_consume(6).is_ok());
+        assert!(a.try_consume_one().is_err());
+        assert!(a.try_consume_one().is_err());
+        clock.step(10_000);
+        assert!(a.try_consume_one().is_ok());
+        assert_eq!(a.inner().current_capacity(), 4);
+
+        // Two quiet intervals in a row, grow
+        clock.step(10_000);
+        assert!(a.try_consume_one().is_ok());
+        assert_eq!(a.inner().current_capacity(), 4);
+        clock.step(10_000);
+        assert!(a.try_consume_one().is_ok());
+        assert_eq!(a.inner().current_capacity(), 6);
+
+        // Never shrunk below the minimum
+        for _ in 0..3 {
+            clock.step(10_000);
+            while a.try_consume_one().is_ok() {}
+            for _ in 0..10 {
+                assert!(a.try_consume_one().is_err());
+            }
+        }
+        clock.step(10_000);
+        assert!(a.try_consume_one().is_ok());
+        assert_eq!(a.inner().current_capacity(), 2);
+    }
+}
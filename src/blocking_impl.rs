@@ -0,0 +1,177 @@
+//! Blocking consumes for `std` targets
+
+use std::{
+    thread,
+    time::{Duration, Instant},
+};
+
+use crate::{CantConsume, LimiterResult, RateLimited, RetryHint};
+
+/// Shortest sleep between retries, avoids spinning on rounding errors
+pub(crate) const MIN_SLEEP: Duration = Duration::from_micros(100);
+
+/// Blocking consumes for any [`RetryHint`] limiter
+///
+/// Sleeps the current thread with [`std::thread::sleep`] until the consume
+/// succeeds, using [`RetryHint::time_until_available`] to decide how long to
+/// sleep instead of busy looping around [`crate::Limiter::try_consume`].
+pub trait BlockingConsume: RetryHint {
+    /// Consume tokens, sleeping until they are available
+    ///
+    /// # Arguments
+    /// * `tokens` - how many tokens to consume
+    ///
+    /// # Returns
+    /// * `Ok(())` - tokens consumed
+    /// * `Err(CantConsume)` - the tokens can't become available by waiting, e.g. they exceed the capacity
+    fn consume_blocking(&mut self, tokens: u64) -> LimiterResult {
+        loop {
+            match self.try_consume_with_retry(tokens) {
+                Ok(()) => return Ok(()),
+                Err(RateLimited { retry_after: None }) => return Err(CantConsume),
+                Err(RateLimited {
+                    retry_after: Some(wait),
+                }) => thread::sleep(wait.max(MIN_SLEEP)),
+            }
+        }
+    }
+
+    /// Consume tokens, sleeping until they are available or the deadline passes
+    ///
+    /// Gives up right away if the tokens can't become available before the deadline.
+    ///
+    /// # Arguments
+    /// * `tokens` - how many tokens to consume
+    /// * `deadline` - latest point in time to consume at
+    ///
+    /// # Returns
+    /// * `Ok(())` - tokens consumed
+    /// * `Err(CantConsume)` - the tokens didn't become available in time
+    fn consume_blocking_until(&mut self, tokens: u64, deadline: Instant) -> LimiterResult {
+        loop {
+            let wait = match self.try_consume_with_retry(tokens) {
+                Ok(()) => return Ok(()),
+                Err(RateLimited { retry_after }) => retry_after.ok_or(CantConsume)?,
+            };
+            let wake_up = Instant::now() + wait.max(MIN_SLEEP);
+            if wake_up > deadline {
+                return Err(CantConsume);
+            }
+            thread::sleep(wake_up - Instant::now());
+        }
+    }
+}
+
+impl<L> BlockingConsume for L where L: RetryHint + ?Sized {}
+
+/// Throttling adapter for any [`Iterator`]
+pub trait IteratorRateLimitExt: Iterator + Sized {
+    /// Yield items only as fast as a limiter permits
+    ///
+    /// Each item consumes a single token, sleeping the current thread with
+    /// [`BlockingConsume::consume_blocking`] until it is available. The
+    /// iterator ends early if the limiter can never admit a single token.
+    ///
+    /// # Arguments
+    /// * `limiter` - limiter pacing the items
+    fn rate_limited<L>(self, limiter: L) -> RateLimitedIter<Self, L>
+    where
+        L: RetryHint,
+    {
+        RateLimitedIter {
+            iter: self,
+            limiter,
+        }
+    }
+}
+
+impl<I> IteratorRateLimitExt for I where I: Iterator {}
+
+/// Iterator returned by [`IteratorRateLimitExt::rate_limited`]
+///
+/// # Generic arguments
+/// * `I` - throttled iterator
+/// * `L` - limiter pacing the items
+pub struct RateLimitedIter<I, L> {
+    iter: I,
+    limiter: L,
+}
+
+impl<I, L> RateLimitedIter<I, L> {
+    /// Get a reference to the limiter
+    pub fn limiter(&self) -> &L {
+        &self.limiter
+    }
+
+    /// Get a mutable reference to the limiter
+    pub fn limiter_mut(&mut self) -> &mut L {
+        &mut self.limiter
+    }
+
+    /// Unwrap the throttled iterator
+    pub fn into_inner(self) -> I {
+        self.iter
+    }
+}
+
+impl<I, L> Iterator for RateLimitedIter<I, L>
+where
+    I: Iterator,
+    L: RetryHint,
+{
+    type Item = I::Item;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let item = self.iter.next()?;
+        self.limiter.consume_blocking(1).ok()?;
+        Some(item)
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        self.iter.size_hint()
+    }
+}
+
+#[cfg(all(test, feature = "token-bucket"))]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use crate::{token_bucket, Limiter};
+
+    use super::{BlockingConsume, IteratorRateLimitExt};
+
+    #[test]
+    fn verify_blocking() {
+        let mut b = token_bucket(1000, 2);
+        let start = Instant::now();
+
+        for _ in 0..5 {
+            assert!(b.consume_blocking(1).is_ok());
+        }
+        // Three tokens had to be waited for
+        assert!(start.elapsed() >= Duration::from_millis(3));
+
+        // Can never succeed
+        assert!(b.consume_blocking(3).is_err());
+        assert!(b.try_consume(2).is_err());
+        // Too late
+        assert!(b
+            .consume_blocking_until(2, Instant::now() + Duration::from_micros(100))
+            .is_err());
+        assert!(b
+            .consume_blocking_until(2, Instant::now() + Duration::from_secs(1))
+            .is_ok());
+    }
+
+    #[test]
+    fn verify_rate_limited_iter() {
+        let start = Instant::now();
+        let items: Vec<_> = (0..5).rate_limited(token_bucket(1000, 2)).collect();
+        assert_eq!(items, [0, 1, 2, 3, 4]);
+        // Three items had to wait for a token
+        assert!(start.elapsed() >= Duration::from_millis(3));
+
+        // Can never succeed
+        assert_eq!((0..5).rate_limited(token_bucket(1000, 0)).next(), None);
+    }
+}
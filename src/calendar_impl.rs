@@ -0,0 +1,587 @@
+//! Calendar window -type limiter
+
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+use crate::SystemClock;
+use crate::{
+    diagnostics_impl::ClockMonitor, CantConsume, Clock, ConfigError, Diagnostics, Inspect, Limiter,
+    LimiterCore, LimiterKind, LimiterResult, Refund, RemainingBudget, Request, Reset, Response,
+    RetryHint,
+};
+
+/// [`CalendarWindow`] using the wall clock, as returned by [`calendar_window`]
+#[cfg(feature = "std")]
+pub type StdCalendarWindow = CalendarWindow<SystemClock>;
+
+/// Build a calendar window limiter on the [`SystemClock`]
+///
+/// # Arguments
+/// * `capacity` - how many consumes are allowed during a single calendar unit
+/// * `unit` - calendar unit the windows span
+#[cfg(feature = "std")]
+pub fn calendar_window(capacity: u64, unit: CalendarUnit) -> StdCalendarWindow {
+    CalendarWindow::new_with_time_provider(capacity, unit, SystemClock)
+}
+
+/// Calendar unit spanned by the windows of a [`CalendarWindow`]
+///
+/// Boundaries are in UTC. Leap seconds are not accounted for, as is usual
+/// for Unix time.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum CalendarUnit {
+    /// Windows reset at the start of every minute
+    Minute,
+    /// Windows reset at the start of every hour
+    Hour,
+    /// Windows reset at midnight UTC
+    Day,
+    /// Windows reset at midnight UTC on the first day of every month
+    Month,
+}
+
+impl CalendarUnit {
+    /// Index of the calendar unit containing `now`, counted from the Unix epoch
+    fn period_of(self, now: Duration) -> u64 {
+        let secs = now.as_secs();
+        match self {
+            CalendarUnit::Minute => secs / 60,
+            CalendarUnit::Hour => secs / 3600,
+            CalendarUnit::Day => secs / 86_400,
+            CalendarUnit::Month => {
+                let (year, month) = civil_from_days(secs / 86_400);
+                year * 12 + month - 1
+            }
+        }
+    }
+
+    /// Timestamp at which the calendar unit `period` starts
+    fn period_start(self, period: u64) -> Duration {
+        let secs = match self {
+            CalendarUnit::Minute => period.saturating_mul(60),
+            CalendarUnit::Hour => period.saturating_mul(3600),
+            CalendarUnit::Day => period.saturating_mul(86_400),
+            CalendarUnit::Month => {
+                days_from_civil(period / 12, period % 12 + 1).saturating_mul(86_400)
+            }
+        };
+        Duration::from_secs(secs)
+    }
+}
+
+/// Year and month (1-12) of the given day since the Unix epoch
+///
+/// See <http://howardhinnant.github.io/date_algorithms.html>, the eras start on March 1st.
+fn civil_from_days(days: u64) -> (u64, u64) {
+    let z = days + 719_468;
+    let era = z / 146_097;
+    let doe = z - era * 146_097;
+    let yoe = (doe - doe / 1460 + doe / 36_524 - doe / 146_096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + u64::from(month <= 2);
+    (year, month)
+}
+
+/// Day since the Unix epoch of the first day of the given month (1-12)
+fn days_from_civil(year: u64, month: u64) -> u64 {
+    let year = year - u64::from(month <= 2);
+    let era = year / 400;
+    let yoe = year - era * 400;
+    let doy = (153 * (if month > 2 { month - 3 } else { month + 9 }) + 2) / 5;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    (era * 146_097 + doe).saturating_sub(719_468)
+}
+
+/// Calendar window -type rate limiter
+///
+/// Like a [`crate::FixedWindow`], but the windows are real calendar units
+/// resetting exactly at their boundaries, e.g. 1000 requests per calendar
+/// day starting at midnight UTC. Months have their actual lengths.
+///
+/// The time provider must return the time since the Unix epoch, e.g. the
+/// [`crate::SystemClock`].
+///
+/// The algorithm itself is implemented by [`CalendarWindowCore`], this type
+/// only pairs it with a time provider.
+#[derive(Debug, Clone)]
+pub struct CalendarWindow<T>
+where
+    T: Clock,
+{
+    core: CalendarWindowCore,
+    time_provider: T,
+}
+
+impl<T> CalendarWindow<T>
+where
+    T: Clock,
+{
+    /// Initialize a new calendar window limiter utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single calendar unit
+    /// * `unit` - calendar unit the windows span
+    /// * `time_provider_t` - [`Clock`] or closure returning the current time
+    ///   as [`Duration`] since the Unix epoch
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`calendar_window`]
+    pub fn new_with_time_provider(capacity: u64, unit: CalendarUnit, time_provider: T) -> Self {
+        let time_now = time_provider.now();
+        Self {
+            core: CalendarWindowCore::new(capacity, unit, time_now),
+            time_provider,
+        }
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting a capacity that
+    /// would never admit anything
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroCapacity`] if `capacity` is zero
+    pub fn try_new_with_time_provider(
+        capacity: u64,
+        unit: CalendarUnit,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        let time_now = time_provider.now();
+        Ok(Self {
+            core: CalendarWindowCore::try_new(capacity, unit, time_now)?,
+            time_provider,
+        })
+    }
+
+    /// Assign a name to this limiter, see [`Limiter::name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.core = self.core.with_name(name);
+        self
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.core.capacity()
+    }
+
+    /// Configured calendar unit
+    pub fn unit(&self) -> CalendarUnit {
+        self.core.unit()
+    }
+
+    /// Time left until the current calendar unit ends and the budget is replenished
+    pub fn time_until_reset(&self) -> Duration {
+        let now = self.time_provider.now();
+        self.core.time_until_reset_at(now)
+    }
+
+    /// How far through the current calendar unit we are
+    pub fn window_elapsed(&self) -> Duration {
+        let now = self.time_provider.now();
+        self.core.window_elapsed_at(now)
+    }
+
+    /// Get a reference to the underlying sans-IO state machine
+    pub fn core(&self) -> &CalendarWindowCore {
+        &self.core
+    }
+
+    /// Unwrap the underlying sans-IO state machine, dropping the time provider
+    pub fn into_core(self) -> CalendarWindowCore {
+        self.core
+    }
+
+    /// Take a plain-data snapshot of the limiter state, see [`crate::FixedWindow::snapshot`]
+    pub fn snapshot(&self) -> CalendarWindowSnapshot {
+        self.core.snapshot()
+    }
+
+    /// Resume a limiter from a snapshot taken with [`Self::snapshot`]
+    ///
+    /// The name is not part of the snapshot, see [`Self::with_name`].
+    pub fn restore(snapshot: CalendarWindowSnapshot, time_provider: T) -> Self {
+        Self {
+            core: CalendarWindowCore::restore(snapshot),
+            time_provider,
+        }
+    }
+}
+
+impl<T> Limiter for CalendarWindow<T>
+where
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens).map(|_| ())
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::CalendarWindow
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.core.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.time_provider.now();
+        self.core.self_check_at(now)
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.core.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.time_provider.now();
+        Some(self.core.idle_for_at(now))
+    }
+
+    fn reset(&mut self) {
+        let now = self.time_provider.now();
+        self.core.reset_at(now);
+    }
+}
+
+impl<T> RemainingBudget for CalendarWindow<T>
+where
+    T: Clock,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens)
+    }
+}
+
+impl<T> Reset for CalendarWindow<T> where T: Clock {}
+
+impl<T> RetryHint for CalendarWindow<T>
+where
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.time_provider.now();
+        self.core.time_until_available_at(now, tokens)
+    }
+}
+
+impl<T> Inspect for CalendarWindow<T>
+where
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.tokens_available_at(now)
+    }
+}
+
+impl<T> Refund for CalendarWindow<T>
+where
+    T: Clock,
+{
+    fn refund(&mut self, tokens: u64) {
+        self.core.refund(tokens);
+    }
+}
+
+/// Plain-data snapshot of a [`CalendarWindow`], see [`CalendarWindow::snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CalendarWindowSnapshot {
+    /// Configured capacity
+    pub capacity: u64,
+    /// Calendar unit the windows span
+    pub unit: CalendarUnit,
+    /// Tokens left in the unit `period`
+    pub tokens: u64,
+    /// Index of the latest unit consumed from, counted from the Unix epoch
+    pub period: u64,
+    /// Latest timestamp seen by the limiter
+    pub last_update: Duration,
+}
+
+/// Sans-IO calendar window state machine
+///
+/// Same algorithm as [`CalendarWindow`], but without a time provider: the
+/// current time is passed in with every [`LimiterCore::handle`] call.
+#[derive(Debug, Clone, Copy)]
+pub struct CalendarWindowCore {
+    capacity: u64,
+    unit: CalendarUnit,
+    tokens: u64,
+    period: u64,
+    name: Option<&'static str>,
+    monitor: ClockMonitor,
+}
+
+impl CalendarWindowCore {
+    /// Initialize a new calendar window state machine
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single calendar unit
+    /// * `unit` - calendar unit the windows span
+    /// * `now` - current time as [`Duration`] since the Unix epoch
+    pub fn new(capacity: u64, unit: CalendarUnit, now: Duration) -> Self {
+        Self {
+            capacity,
+            unit,
+            tokens: capacity,
+            period: unit.period_of(now),
+            name: None,
+            monitor: ClockMonitor::new(now),
+        }
+    }
+
+    /// Validating constructor, see [`CalendarWindow::try_new_with_time_provider`]
+    pub fn try_new(capacity: u64, unit: CalendarUnit, now: Duration) -> Result<Self, ConfigError> {
+        if capacity == 0 {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        Ok(Self::new(capacity, unit, now))
+    }
+
+    /// Return to the freshly constructed state at the given time
+    ///
+    /// The configuration, including the name, is kept.
+    pub fn reset_at(&mut self, now: Duration) {
+        self.tokens = self.capacity;
+        self.period = self.unit.period_of(now);
+        self.monitor.reset(now);
+    }
+
+    /// Take a plain-data snapshot, see [`CalendarWindow::snapshot`]
+    pub fn snapshot(&self) -> CalendarWindowSnapshot {
+        CalendarWindowSnapshot {
+            capacity: self.capacity,
+            unit: self.unit,
+            tokens: self.tokens,
+            period: self.period,
+            last_update: self.monitor.latest(),
+        }
+    }
+
+    /// Resume from a snapshot, see [`CalendarWindow::restore`]
+    pub fn restore(snapshot: CalendarWindowSnapshot) -> Self {
+        Self {
+            capacity: snapshot.capacity,
+            unit: snapshot.unit,
+            tokens: snapshot.tokens,
+            period: snapshot.period,
+            name: None,
+            monitor: ClockMonitor::new(snapshot.last_update),
+        }
+    }
+
+    /// Assign a name to this limiter, see [`CalendarWindow::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Configured calendar unit
+    pub fn unit(&self) -> CalendarUnit {
+        self.unit
+    }
+
+    /// Tokens that could be consumed at `now`, see [`Inspect::tokens_available`]
+    pub fn tokens_available_at(&self, now: Duration) -> u64 {
+        if self.unit.period_of(now) > self.period {
+            self.capacity
+        } else {
+            self.tokens
+        }
+    }
+
+    /// Return tokens of an admitted consume to the latest window, see [`Refund::refund`]
+    pub fn refund(&mut self, tokens: u64) {
+        self.tokens = self
+            .monitor
+            .saturating_add(self.tokens, tokens)
+            .min(self.capacity);
+    }
+
+    /// Time left at `now` until the current calendar unit ends and the budget is replenished
+    pub fn time_until_reset_at(&self, now: Duration) -> Duration {
+        let next = self.unit.period_of(now) + 1;
+        self.unit.period_start(next).saturating_sub(now)
+    }
+
+    /// How far through the calendar unit containing `now` we are
+    pub fn window_elapsed_at(&self, now: Duration) -> Duration {
+        let current = self.unit.period_of(now);
+        now.saturating_sub(self.unit.period_start(current))
+    }
+
+    /// Time until `tokens` could be consumed at `now`, see [`RetryHint::time_until_available`]
+    pub fn time_until_available_at(&self, now: Duration, tokens: u64) -> Option<Duration> {
+        if tokens > self.capacity {
+            None
+        } else if self.tokens_available_at(now) >= tokens {
+            Some(Duration::ZERO)
+        } else {
+            Some(self.time_until_reset_at(now))
+        }
+    }
+
+    /// Timestamp of the most recent consume attempt, see [`Limiter::last_consume_at`]
+    pub fn last_consume_at(&self) -> Option<Duration> {
+        self.monitor.last_consume_at()
+    }
+
+    /// Idle time at `now`, see [`Limiter::idle_for`]
+    pub fn idle_for_at(&self, now: Duration) -> Duration {
+        self.monitor.idle_for(now)
+    }
+
+    /// Run the self-check of [`Limiter::self_check`] at the given time
+    pub fn self_check_at(&self, now: Duration) -> Diagnostics {
+        self.monitor.diagnose(now, self.capacity != 0)
+    }
+
+    /// Consume at the given time, returning the remaining budget
+    pub(crate) fn try_consume_at(
+        &mut self,
+        now: Duration,
+        tokens: u64,
+    ) -> Result<u64, CantConsume> {
+        self.monitor.observe(now);
+        let period = self.unit.period_of(now);
+        // A clock stepping back into an earlier unit is flagged by the
+        // monitor, and the current budget is kept instead of replenished
+        if period > self.period {
+            // New calendar unit, replenish tokens
+            self.tokens = self.capacity;
+            self.period = period;
+        }
+
+        self.tokens = self.tokens.checked_sub(tokens).ok_or(CantConsume)?;
+        Ok(self.tokens)
+    }
+}
+
+impl LimiterCore for CalendarWindowCore {
+    fn handle(&mut self, now: Duration, request: Request) -> Response {
+        match request {
+            Request::Consume(tokens) => self.try_consume_at(now, tokens).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{mock_assets::MockClock, ConfigError, Inspect, Limiter, RetryHint};
+
+    use super::{CalendarUnit, CalendarWindow, CalendarWindowCore};
+
+    const DAY_US: u64 = 86_400_000_000;
+
+    #[test]
+    fn verify_try_new() {
+        let clock = MockClock::new();
+        assert_eq!(
+            CalendarWindow::try_new_with_time_provider(0, CalendarUnit::Day, || clock.step(0))
+                .err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+        assert!(CalendarWindowCore::try_new(1, CalendarUnit::Hour, Duration::ZERO).is_ok());
+        let mut w =
+            CalendarWindow::try_new_with_time_provider(1, CalendarUnit::Day, || clock.step(0))
+                .unwrap();
+        assert!(w.try_consume_one().is_ok());
+        assert!(w.try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_snapshot_restore() {
+        let clock = MockClock::new();
+        clock.step(3 * DAY_US);
+        let mut w = CalendarWindow::new_with_time_provider(3, CalendarUnit::Day, || clock.step(0));
+        assert!(w.try_consume(2).is_ok());
+        let snapshot = w.snapshot();
+        assert_eq!(snapshot.unit, CalendarUnit::Day);
+        assert_eq!(snapshot.tokens, 1);
+        assert_eq!(snapshot.period, 3);
+
+        let mut w = CalendarWindow::restore(snapshot, || clock.step(0));
+        assert!(w.try_consume(2).is_err());
+        clock.step(DAY_US);
+        assert!(w.try_consume(3).is_ok());
+    }
+
+    #[test]
+    fn verify_calendar_window() {
+        // 2024-03-01T00:00:00Z, after a leap day
+        assert_eq!(
+            CalendarUnit::Month.period_start(2024 * 12 + 2),
+            Duration::from_secs(1_709_251_200)
+        );
+        assert_eq!(
+            CalendarUnit::Month.period_of(Duration::from_secs(1_709_251_199)),
+            2024 * 12 + 1
+        );
+
+        // The mock clock starts at 1970-01-01T00:00:00Z
+        let clock = MockClock::new();
+        let mut w =
+            CalendarWindow::new_with_time_provider(2, CalendarUnit::Month, || clock.step(0));
+        clock.step(30 * DAY_US + 3_600_000_000);
+        // T = Jan 31st 01:00
+        assert!(w.try_consume(2).is_ok());
+        assert_eq!(w.time_until_reset(), Duration::from_secs(23 * 3600));
+        assert_eq!(
+            w.time_until_available(1),
+            Some(Duration::from_secs(23 * 3600))
+        );
+        clock.step(23 * 3_600_000_000);
+        // T = Feb 1st, 28 days until March
+        assert_eq!(w.tokens_available(), 2);
+        assert!(w.try_consume(2).is_ok());
+        assert_eq!(w.time_until_reset(), Duration::from_secs(28 * 86_400));
+
+        let mut d = CalendarWindow::new_with_time_provider(1, CalendarUnit::Day, || clock.step(0));
+        clock.step(DAY_US - 1);
+        // T = Feb 1st 23:59:59.999999
+        assert!(d.try_consume_one().is_ok());
+        assert!(d.try_consume_one().is_err());
+        clock.step(1);
+        // T = Feb 2nd, midnight
+        assert!(d.try_consume_one().is_ok());
+    }
+
+    #[test]
+    fn verify_reset_timing() {
+        let clock = MockClock::new();
+        let w = CalendarWindow::new_with_time_provider(1, CalendarUnit::Hour, || clock.step(0));
+
+        clock.step(DAY_US + 90 * 60_000_000);
+        assert_eq!(w.window_elapsed(), Duration::from_secs(30 * 60));
+        assert_eq!(w.time_until_reset(), Duration::from_secs(30 * 60));
+        clock.step(30 * 60_000_000);
+        assert_eq!(w.window_elapsed(), Duration::ZERO);
+        assert_eq!(w.time_until_reset(), Duration::from_secs(3600));
+    }
+
+    #[test]
+    fn verify_calendar_clock_backwards() {
+        let day = Duration::from_secs(86_400);
+        let mut c = CalendarWindowCore::new(1, CalendarUnit::Day, day * 2);
+        assert!(c.try_consume_at(day * 2, 1).is_ok());
+
+        // Stepping back a day neither replenishes nor moves the window
+        assert_eq!(c.tokens_available_at(day), 0);
+        assert!(c.try_consume_at(day, 1).is_err());
+        assert!(!c.self_check_at(day * 2).clock_monotonic);
+        assert!(c.try_consume_at(day * 2, 1).is_err());
+
+        assert!(c.try_consume_at(day * 3, 1).is_ok());
+    }
+}
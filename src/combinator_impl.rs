@@ -0,0 +1,184 @@
+//! Limiter combinators
+
+use core::time::Duration;
+
+use crate::{CantConsume, Diagnostics, Inspect, Limiter, LimiterResult, Refund, Reset, RetryHint};
+
+/// Limiter admitting a consume only if both inner limiters admit it
+///
+/// The first limiter is consumed from first. If the second one rejects, the
+/// tokens are refunded to the first one, so a rejected consume never leaks
+/// tokens. Chain more limiters with [`Refund::and`], e.g.
+/// `per_second.and(per_minute).and(per_hour)`.
+///
+/// # Generic arguments
+/// * `A` - first limiter, must support refunds
+/// * `B` - second limiter
+pub struct AllOf<A, B> {
+    first: A,
+    second: B,
+}
+
+impl<A, B> AllOf<A, B>
+where
+    A: Refund,
+    B: Limiter,
+{
+    /// Combine two limiters
+    ///
+    /// # Arguments
+    /// * `first` - limiter consumed from first, refunded if `second` rejects
+    /// * `second` - limiter consumed from second
+    pub fn new(first: A, second: B) -> Self {
+        Self { first, second }
+    }
+
+    /// Get a reference to the first limiter
+    pub fn first(&self) -> &A {
+        &self.first
+    }
+
+    /// Get a reference to the second limiter
+    pub fn second(&self) -> &B {
+        &self.second
+    }
+
+    /// Unwrap the inner limiters
+    pub fn into_inner(self) -> (A, B) {
+        (self.first, self.second)
+    }
+}
+
+impl<A, B> Limiter for AllOf<A, B>
+where
+    A: Refund,
+    B: Limiter,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        self.first.try_consume(tokens)?;
+        if self.second.try_consume(tokens).is_err() {
+            self.first.refund(tokens);
+            return Err(CantConsume);
+        }
+        Ok(())
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let first = self.first.self_check();
+        let second = self.second.self_check();
+        Diagnostics {
+            clock_monotonic: first.clock_monotonic && second.clock_monotonic,
+            clock_progressing: first.clock_progressing && second.clock_progressing,
+            config_valid: first.config_valid && second.config_valid,
+            saturated: first.saturated || second.saturated,
+        }
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.first.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        self.first.idle_for()
+    }
+
+    fn reset(&mut self) {
+        self.first.reset();
+        self.second.reset();
+    }
+}
+
+impl<A, B> Refund for AllOf<A, B>
+where
+    A: Refund,
+    B: Refund,
+{
+    fn refund(&mut self, tokens: u64) {
+        self.first.refund(tokens);
+        self.second.refund(tokens);
+    }
+}
+
+impl<A, B> Reset for AllOf<A, B>
+where
+    A: Refund + Reset,
+    B: Reset,
+{
+}
+
+impl<A, B> RetryHint for AllOf<A, B>
+where
+    A: Refund + RetryHint,
+    B: RetryHint,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let first = self.first.time_until_available(tokens)?;
+        let second = self.second.time_until_available(tokens)?;
+        Some(first.max(second))
+    }
+}
+
+impl<A, B> Inspect for AllOf<A, B>
+where
+    A: Refund + Inspect,
+    B: Inspect,
+{
+    fn tokens_available(&self) -> u64 {
+        self.first
+            .tokens_available()
+            .min(self.second.tokens_available())
+    }
+}
+
+#[cfg(all(test, feature = "token-bucket"))]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{mock_assets::MockClock, Inspect, Limiter, Refund, RetryHint, TokenBucket};
+
+    #[test]
+    fn verify_all_of() {
+        let clock = MockClock::new();
+        let per_ms = TokenBucket::new_with_time_provider(1000, 5, || clock.step(0));
+        let per_10ms = TokenBucket::new_with_time_provider(100, 3, || clock.step(0));
+        let per_100ms = TokenBucket::new_with_time_provider(10, 10, || clock.step(0));
+        let mut l = per_ms.and(per_10ms).and(per_100ms);
+
+        assert_eq!(l.tokens_available(), 3);
+        assert!(l.try_consume(2).is_ok());
+        // Rejected by the second limiter, the first one is refunded
+        assert!(l.try_consume(2).is_err());
+        assert_eq!(l.first().first().tokens_available(), 3);
+        assert_eq!(l.time_until_available(2), Some(Duration::from_millis(10)));
+
+        clock.step(10_000);
+        assert!(l.try_consume(2).is_ok());
+        assert_eq!(l.second().tokens_available(), 6);
+    }
+
+    #[test]
+    fn verify_rollback_on_second_rejection() {
+        let clock = MockClock::new();
+        // The clock stands still while rejecting, so any leak would show
+        let first = TokenBucket::new_with_time_provider(1, 5, || clock.step(0));
+        let second = TokenBucket::new_with_time_provider(1, 2, || clock.step(0));
+        let mut l = first.and(second);
+
+        assert!(l.try_consume(2).is_ok());
+        assert_eq!(l.first().tokens_available(), 3);
+        // The exhausted second limiter rejects over and over
+        for _ in 0..10 {
+            assert!(l.try_consume_one().is_err());
+        }
+        assert_eq!(l.try_consume_batch(&[1, 1]), 0);
+        // More than the second limiter could ever hold
+        assert!(l.try_consume(3).is_err());
+        assert_eq!(l.first().tokens_available(), 3);
+        assert_eq!(l.second().tokens_available(), 0);
+
+        clock.step(1_000_000);
+        // T = 1s, one token back in both buckets
+        assert!(l.try_consume_one().is_ok());
+        assert_eq!(l.first().tokens_available(), 3);
+    }
+}
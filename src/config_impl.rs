@@ -0,0 +1,213 @@
+//! Runtime limiter selection
+
+#[cfg(feature = "alloc")]
+use alloc::boxed::Box;
+
+#[cfg(feature = "std")]
+use crate::StdClock;
+#[cfg(feature = "alloc")]
+use crate::{Clock, Limiter};
+
+/// Heap-allocated limiter of any algorithm, as returned by [`from_config`]
+#[cfg(feature = "alloc")]
+pub type BoxedLimiter = Box<dyn Limiter + Send>;
+
+/// Algorithm and parameters of a limiter, e.g. read from configuration
+///
+/// Only the algorithms enabled by Cargo features are available.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum LimiterConfig {
+    /// [`crate::TokenBucket`]
+    #[cfg(feature = "token-bucket")]
+    TokenBucket {
+        /// How many consumes should be allowed per second on average
+        rate_per_s: u64,
+        /// Bucket capacity to dictate the burstiness of the limiter
+        capacity: u64,
+    },
+    /// [`crate::FixedWindow`]
+    #[cfg(feature = "fixed-window")]
+    FixedWindow {
+        /// How many consumes are allowed during a single window
+        capacity: u64,
+        /// Window width in milliseconds
+        window_width_ms: u64,
+    },
+    /// [`crate::SlidingWindowCounter`]
+    #[cfg(feature = "sliding-window")]
+    SlidingWindowCounter {
+        /// How many consumes are allowed during a single window
+        capacity: u64,
+        /// Window width in milliseconds
+        window_width_ms: u64,
+    },
+    /// [`crate::SlidingWindowLogDyn`], as the window width of a
+    /// [`crate::SlidingWindowLog`] is fixed at compile time
+    #[cfg(all(feature = "alloc", feature = "sliding-window"))]
+    SlidingWindowLog {
+        /// How many consumes are allowed during a single window
+        capacity: u64,
+        /// Window width in milliseconds
+        window_width_ms: usize,
+    },
+}
+
+#[cfg(feature = "token-bucket")]
+impl From<crate::TokenBucketConfig> for LimiterConfig {
+    fn from(config: crate::TokenBucketConfig) -> Self {
+        LimiterConfig::TokenBucket {
+            rate_per_s: config.rate_per_s,
+            capacity: config.capacity,
+        }
+    }
+}
+
+#[cfg(feature = "fixed-window")]
+impl From<crate::FixedWindowConfig> for LimiterConfig {
+    fn from(config: crate::FixedWindowConfig) -> Self {
+        LimiterConfig::FixedWindow {
+            capacity: config.capacity,
+            window_width_ms: config.window_width_ms,
+        }
+    }
+}
+
+#[cfg(feature = "sliding-window")]
+impl From<crate::SlidingWindowCounterConfig> for LimiterConfig {
+    fn from(config: crate::SlidingWindowCounterConfig) -> Self {
+        LimiterConfig::SlidingWindowCounter {
+            capacity: config.capacity,
+            window_width_ms: config.window_width_ms,
+        }
+    }
+}
+
+/// Build a limiter of the algorithm picked at runtime
+///
+/// # Arguments
+/// * `config` - algorithm and its parameters
+#[cfg(feature = "std")]
+pub fn from_config(config: LimiterConfig) -> BoxedLimiter {
+    from_config_with_time_provider(config, StdClock)
+}
+
+/// Build a limiter of the algorithm picked at runtime utilizing the given timer
+///
+/// # Arguments
+/// * `config` - algorithm and its parameters
+/// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+///   timestamp as [`core::time::Duration`] from some fixed epoch in the past
+///
+/// If you are developing for a `std` target, you probably wish to use [`from_config`]
+#[cfg(feature = "alloc")]
+#[cfg_attr(
+    not(any(
+        feature = "token-bucket",
+        feature = "fixed-window",
+        feature = "sliding-window"
+    )),
+    allow(unused_variables)
+)]
+pub fn from_config_with_time_provider<T>(config: LimiterConfig, time_provider: T) -> BoxedLimiter
+where
+    T: Clock + Send + 'static,
+{
+    match config {
+        #[cfg(feature = "token-bucket")]
+        LimiterConfig::TokenBucket {
+            rate_per_s,
+            capacity,
+        } => Box::new(crate::TokenBucket::new_with_time_provider(
+            rate_per_s,
+            capacity,
+            time_provider,
+        )),
+        #[cfg(feature = "fixed-window")]
+        LimiterConfig::FixedWindow {
+            capacity,
+            window_width_ms,
+        } => Box::new(crate::FixedWindow::new_with_time_provider(
+            capacity,
+            window_width_ms,
+            time_provider,
+        )),
+        #[cfg(feature = "sliding-window")]
+        LimiterConfig::SlidingWindowCounter {
+            capacity,
+            window_width_ms,
+        } => Box::new(crate::SlidingWindowCounter::new_with_time_provider(
+            capacity,
+            window_width_ms,
+            time_provider,
+        )),
+        #[cfg(feature = "sliding-window")]
+        LimiterConfig::SlidingWindowLog {
+            capacity,
+            window_width_ms,
+        } => Box::new(crate::SlidingWindowLogDyn::new_with_time_provider(
+            capacity,
+            window_width_ms,
+            time_provider,
+        )),
+    }
+}
+
+#[cfg(all(
+    test,
+    feature = "alloc",
+    feature = "token-bucket",
+    feature = "fixed-window"
+))]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{Limiter, LimiterKind};
+
+    use super::{from_config_with_time_provider, BoxedLimiter, LimiterConfig};
+
+    fn consume_all(limiter: &mut impl Limiter) -> u64 {
+        let mut consumed = 0;
+        while limiter.try_consume_one().is_ok() {
+            consumed += 1;
+        }
+        consumed
+    }
+
+    #[test]
+    fn verify_from_config() {
+        // The boxed limiters own their clock, so use a frozen one
+        let now = || Duration::ZERO;
+        let mut limiters: [BoxedLimiter; 2] = [
+            from_config_with_time_provider(
+                LimiterConfig::TokenBucket {
+                    rate_per_s: 1,
+                    capacity: 3,
+                },
+                now,
+            ),
+            from_config_with_time_provider(
+                LimiterConfig::FixedWindow {
+                    capacity: 2,
+                    window_width_ms: 1000,
+                },
+                now,
+            ),
+        ];
+
+        assert_eq!(limiters[0].kind(), LimiterKind::TokenBucket);
+        assert_eq!(limiters[1].kind(), LimiterKind::FixedWindow);
+        // Consumed through the blanket impls for `&mut L` and `Box<L>`
+        assert_eq!(consume_all(&mut limiters[0]), 3);
+        assert_eq!(consume_all(&mut &mut limiters[1]), 2);
+
+        // Boxed limiters can be reset without knowing the concrete type
+        for limiter in &mut limiters {
+            limiter.reset();
+        }
+        assert_eq!(consume_all(&mut limiters[0]), 3);
+        assert_eq!(consume_all(&mut limiters[1]), 2);
+    }
+}
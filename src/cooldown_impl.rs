@@ -0,0 +1,443 @@
+//! One-shot cooldown limiter
+
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+use crate::StdClock;
+use crate::{
+    diagnostics_impl::ClockMonitor, CantConsume, Clock, ConfigError, Diagnostics, Inspect, Limiter,
+    LimiterCore, LimiterKind, LimiterResult, Refund, RemainingBudget, Request, Reset, Response,
+    RetryHint,
+};
+
+/// [`Cooldown`] using the system clock, as returned by [`cooldown`]
+#[cfg(feature = "std")]
+pub type StdCooldown = Cooldown<StdClock>;
+
+/// Build a cooldown limiter
+///
+/// # Arguments
+/// * `period` - how long to deny consumes after an admitted one
+#[cfg(feature = "std")]
+pub fn cooldown(period: Duration) -> StdCooldown {
+    Cooldown::new_with_time_provider(period, StdClock)
+}
+
+/// Cooldown -type limiter
+///
+/// Admits a single token, then denies everything until `period` has
+/// elapsed, e.g. for a button that may only be pressed once a second. Works
+/// like a [`crate::FixedWindow`] of capacity one, except that the period
+/// starts with the admitted consume instead of at a window boundary.
+///
+/// The algorithm itself is implemented by [`CooldownCore`], this type only
+/// pairs it with a time provider.
+#[derive(Debug, Clone)]
+pub struct Cooldown<T>
+where
+    T: Clock,
+{
+    core: CooldownCore,
+    time_provider: T,
+}
+
+impl<T> Cooldown<T>
+where
+    T: Clock,
+{
+    /// Initialize a new cooldown limiter utilizing the given timer
+    ///
+    /// The limiter starts out ready.
+    ///
+    /// # Arguments
+    /// * `period` - how long to deny consumes after an admitted one
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`cooldown`]
+    pub fn new_with_time_provider(period: Duration, time_provider: T) -> Self {
+        let time_now = time_provider.now();
+        Self {
+            core: CooldownCore::new(period, time_now),
+            time_provider,
+        }
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting a period that
+    /// would never deny anything
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroWindow`] if `period` is zero
+    pub fn try_new_with_time_provider(
+        period: Duration,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        let time_now = time_provider.now();
+        Ok(Self {
+            core: CooldownCore::try_new(period, time_now)?,
+            time_provider,
+        })
+    }
+
+    /// Assign a name to this limiter, see [`Limiter::name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.core = self.core.with_name(name);
+        self
+    }
+
+    /// Configured cooldown period
+    pub fn period(&self) -> Duration {
+        self.core.period()
+    }
+
+    /// Tokens left, i.e. one when ready and zero while cooling down
+    pub fn remaining(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.remaining_at(now)
+    }
+
+    /// Time left until the cooldown is over, zero when ready
+    pub fn ready_in(&self) -> Duration {
+        let now = self.time_provider.now();
+        self.core.ready_in_at(now)
+    }
+
+    /// Get a reference to the underlying sans-IO state machine
+    pub fn core(&self) -> &CooldownCore {
+        &self.core
+    }
+
+    /// Unwrap the underlying sans-IO state machine, dropping the time provider
+    pub fn into_core(self) -> CooldownCore {
+        self.core
+    }
+
+    /// Take a plain-data snapshot of the limiter state, see [`crate::FixedWindow::snapshot`]
+    pub fn snapshot(&self) -> CooldownSnapshot {
+        self.core.snapshot()
+    }
+
+    /// Resume a limiter from a snapshot taken with [`Self::snapshot`]
+    ///
+    /// The name is not part of the snapshot, see [`Self::with_name`].
+    pub fn restore(snapshot: CooldownSnapshot, time_provider: T) -> Self {
+        Self {
+            core: CooldownCore::restore(snapshot),
+            time_provider,
+        }
+    }
+}
+
+impl<T> Limiter for Cooldown<T>
+where
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens).map(|_| ())
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::Cooldown
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.core.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.time_provider.now();
+        self.core.self_check_at(now)
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.core.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.time_provider.now();
+        Some(self.core.idle_for_at(now))
+    }
+
+    fn reset(&mut self) {
+        let now = self.time_provider.now();
+        self.core.reset_at(now);
+    }
+}
+
+impl<T> RemainingBudget for Cooldown<T>
+where
+    T: Clock,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens)
+    }
+}
+
+impl<T> Reset for Cooldown<T> where T: Clock {}
+
+impl<T> RetryHint for Cooldown<T>
+where
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.time_provider.now();
+        self.core.time_until_available_at(now, tokens)
+    }
+}
+
+impl<T> Inspect for Cooldown<T>
+where
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        self.remaining()
+    }
+}
+
+impl<T> Refund for Cooldown<T>
+where
+    T: Clock,
+{
+    fn refund(&mut self, tokens: u64) {
+        self.core.refund(tokens);
+    }
+}
+
+/// Plain-data snapshot of a [`Cooldown`], see [`Cooldown::snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CooldownSnapshot {
+    /// Configured cooldown period
+    pub period: Duration,
+    /// Timestamp of the admitted consume that started the cooldown, if any
+    pub fired_at: Option<Duration>,
+    /// Latest timestamp seen by the limiter
+    pub last_update: Duration,
+}
+
+/// Sans-IO cooldown state machine
+///
+/// Same algorithm as [`Cooldown`], but without a time provider: the
+/// current time is passed in with every [`LimiterCore::handle`] call.
+#[derive(Debug, Clone, Copy)]
+pub struct CooldownCore {
+    period: Duration,
+    /// Timestamp of the admitted consume that started the cooldown
+    fired_at: Option<Duration>,
+    name: Option<&'static str>,
+    monitor: ClockMonitor,
+}
+
+impl CooldownCore {
+    /// Initialize a new cooldown state machine, starting out ready
+    ///
+    /// # Arguments
+    /// * `period` - how long to deny consumes after an admitted one
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    pub fn new(period: Duration, now: Duration) -> Self {
+        Self {
+            period,
+            fired_at: None,
+            name: None,
+            monitor: ClockMonitor::new(now),
+        }
+    }
+
+    /// Validating constructor, see [`Cooldown::try_new_with_time_provider`]
+    pub fn try_new(period: Duration, now: Duration) -> Result<Self, ConfigError> {
+        if period.is_zero() {
+            return Err(ConfigError::ZeroWindow);
+        }
+        Ok(Self::new(period, now))
+    }
+
+    /// Return to the freshly constructed state at the given time
+    ///
+    /// The configuration, including the name, is kept.
+    pub fn reset_at(&mut self, now: Duration) {
+        self.fired_at = None;
+        self.monitor.reset(now);
+    }
+
+    /// Take a plain-data snapshot, see [`Cooldown::snapshot`]
+    pub fn snapshot(&self) -> CooldownSnapshot {
+        CooldownSnapshot {
+            period: self.period,
+            fired_at: self.fired_at,
+            last_update: self.monitor.latest(),
+        }
+    }
+
+    /// Resume from a snapshot, see [`Cooldown::restore`]
+    pub fn restore(snapshot: CooldownSnapshot) -> Self {
+        Self {
+            period: snapshot.period,
+            fired_at: snapshot.fired_at,
+            name: None,
+            monitor: ClockMonitor::new(snapshot.last_update),
+        }
+    }
+
+    /// Assign a name to this limiter, see [`Cooldown::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Configured cooldown period
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Tokens left at `now`, see [`Cooldown::remaining`]
+    pub fn remaining_at(&self, now: Duration) -> u64 {
+        u64::from(self.ready_in_at(now).is_zero())
+    }
+
+    /// Time left at `now` until the cooldown is over, see [`Cooldown::ready_in`]
+    pub fn ready_in_at(&self, now: Duration) -> Duration {
+        self.fired_at.map_or(Duration::ZERO, |fired_at| {
+            fired_at.saturating_add(self.period).saturating_sub(now)
+        })
+    }
+
+    /// End the cooldown of a consume that didn't take place, see [`Refund::refund`]
+    pub fn refund(&mut self, tokens: u64) {
+        if tokens != 0 {
+            self.fired_at = None;
+        }
+    }
+
+    /// Time until `tokens` could be consumed at `now`, see [`RetryHint::time_until_available`]
+    pub fn time_until_available_at(&self, now: Duration, tokens: u64) -> Option<Duration> {
+        match tokens {
+            0 => Some(Duration::ZERO),
+            1 => Some(self.ready_in_at(now)),
+            _ => None,
+        }
+    }
+
+    /// Timestamp of the most recent consume attempt, see [`Limiter::last_consume_at`]
+    pub fn last_consume_at(&self) -> Option<Duration> {
+        self.monitor.last_consume_at()
+    }
+
+    /// Idle time at `now`, see [`Limiter::idle_for`]
+    pub fn idle_for_at(&self, now: Duration) -> Duration {
+        self.monitor.idle_for(now)
+    }
+
+    /// Run the self-check of [`Limiter::self_check`] at the given time
+    pub fn self_check_at(&self, now: Duration) -> Diagnostics {
+        self.monitor.diagnose(now, !self.period.is_zero())
+    }
+
+    /// Consume at the given time, returning the remaining budget
+    pub(crate) fn try_consume_at(
+        &mut self,
+        now: Duration,
+        tokens: u64,
+    ) -> Result<u64, CantConsume> {
+        self.monitor.observe(now);
+        let remaining = self.remaining_at(now);
+        let remaining = remaining.checked_sub(tokens).ok_or(CantConsume)?;
+        if tokens != 0 {
+            self.fired_at = Some(now);
+        }
+        Ok(remaining)
+    }
+}
+
+impl LimiterCore for CooldownCore {
+    fn handle(&mut self, now: Duration, request: Request) -> Response {
+        match request {
+            Request::Consume(tokens) => self.try_consume_at(now, tokens).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{mock_assets::MockClock, ConfigError, Limiter, Refund, RetryHint};
+
+    use super::{Cooldown, CooldownCore, CooldownSnapshot};
+
+    #[test]
+    fn verify_try_new() {
+        let clock = MockClock::new();
+        assert_eq!(
+            Cooldown::try_new_with_time_provider(Duration::ZERO, || clock.step(0)).err(),
+            Some(ConfigError::ZeroWindow)
+        );
+        assert!(CooldownCore::try_new(Duration::from_millis(10), Duration::ZERO).is_ok());
+        let mut c =
+            Cooldown::try_new_with_time_provider(Duration::from_millis(10), || clock.step(0))
+                .unwrap();
+        assert!(c.try_consume_one().is_ok());
+        assert!(c.try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_snapshot_restore() {
+        let clock = MockClock::new();
+        let mut c = Cooldown::new_with_time_provider(Duration::from_millis(10), || clock.step(0));
+        clock.step(2000);
+        assert!(c.try_consume_one().is_ok());
+        let snapshot = c.snapshot();
+        assert_eq!(
+            snapshot,
+            CooldownSnapshot {
+                period: Duration::from_millis(10),
+                fired_at: Some(Duration::from_millis(2)),
+                last_update: Duration::from_millis(2),
+            }
+        );
+
+        let mut c = Cooldown::restore(snapshot, || clock.step(0));
+        assert_eq!(c.ready_in(), Duration::from_millis(10));
+        assert!(c.try_consume_one().is_err());
+        clock.step(10_000);
+        assert!(c.try_consume_one().is_ok());
+    }
+
+    #[test]
+    fn verify_cooldown() {
+        let clock = MockClock::new();
+        let mut c = Cooldown::new_with_time_provider(Duration::from_millis(10), || clock.step(0));
+        assert_eq!(c.remaining(), 1);
+        assert_eq!(c.ready_in(), Duration::ZERO);
+
+        // T = 0ms
+        assert!(c.try_consume(2).is_err());
+        assert_eq!(c.time_until_available(2), None);
+        assert!(c.try_consume_one().is_ok());
+        assert!(c.try_consume_one().is_err());
+        assert_eq!(c.remaining(), 0);
+        clock.step(4_000);
+        // T = 4ms, rejections don't extend the cooldown
+        assert!(c.try_consume_one().is_err());
+        assert_eq!(c.ready_in(), Duration::from_millis(6));
+        clock.step(6_000);
+        // T = 10ms
+        assert!(c.try_consume_one().is_ok());
+
+        // A refund ends the cooldown
+        c.refund(1);
+        assert_eq!(c.remaining(), 1);
+    }
+
+    #[test]
+    fn verify_cooldown_long_period() {
+        let clock = MockClock::new();
+        let mut c = Cooldown::new_with_time_provider(Duration::MAX, || clock.step(0));
+        clock.step(1_000);
+
+        // T = 1ms, the end of the cooldown saturates instead of overflowing
+        assert!(c.try_consume_one().is_ok());
+        assert_eq!(c.remaining(), 0);
+        assert_eq!(c.ready_in(), Duration::MAX - Duration::from_millis(1));
+    }
+}
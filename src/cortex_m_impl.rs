@@ -0,0 +1,153 @@
+//! Cortex-M cycle and tick counter clocks
+
+use core::{cell::Cell, time::Duration};
+
+use cortex_m::peripheral::{DWT, SYST};
+
+use crate::{tick_clock_impl::ticks_to_duration, Clock, WrappingTickClock};
+
+/// Address of the DWT cycle count register
+///
+/// Read directly instead of through [`DWT::cycle_count`], which is not
+/// compiled in for ARMv6-M targets.
+const DWT_CYCCNT: *const u32 = (DWT::PTR as *const u32).wrapping_add(1);
+
+fn read_cyccnt() -> u32 {
+    // NOTE(unsafe) atomic read with no side effects
+    unsafe { core::ptr::read_volatile(DWT_CYCCNT) }
+}
+
+/// [`Clock`] on the DWT cycle counter (CYCCNT) of Cortex-M3 and later cores
+///
+/// The 32-bit cycle counter wraps around every `2^32 / core_clock_hz` seconds,
+/// e.g. roughly every 27 seconds at 160 MHz. The wraparounds are accumulated
+/// into a 64-bit tick count on each read like in [`WrappingTickClock`], so the
+/// clock must be read at least once per wrap period to stay monotonic.
+/// Timestamps count from a cycle count of zero.
+///
+/// The cycle counter must be enabled before use:
+/// `dcb.enable_trace(); dwt.enable_cycle_counter();`.
+/// ARMv6-M (Cortex-M0/M0+) cores have no cycle counter, use [`SysTickClock`] instead.
+///
+/// The clock is not `Sync`, share it between limiters by reference.
+#[derive(Debug)]
+pub struct DwtClock(WrappingTickClock<fn() -> u32, u32>);
+
+impl DwtClock {
+    /// Create a new clock on the cycle counter
+    ///
+    /// # Arguments
+    /// * `core_clock_hz` - core clock frequency in Hz
+    pub const fn new(core_clock_hz: u32) -> Self {
+        Self(WrappingTickClock::new(read_cyccnt, core_clock_hz))
+    }
+}
+
+impl Clock for DwtClock {
+    fn now(&self) -> Duration {
+        self.0.now()
+    }
+}
+
+impl Clock for &DwtClock {
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+}
+
+/// [`Clock`] on the SysTick counter of any Cortex-M core
+///
+/// SysTick counts down from its reload value to zero and wraps around every
+/// `(reload + 1) / clock_hz` seconds, which is typically well below a second.
+/// The wraparounds are accumulated into a 64-bit tick count on each read, so
+/// the clock must be read at least once per wrap period to stay monotonic,
+/// e.g. from the SysTick exception handler. Timestamps count from the
+/// creation of the clock.
+///
+/// SysTick must be configured and enabled before use, and its reload value
+/// must not change afterwards.
+///
+/// The clock is not `Sync`, share it between limiters by reference.
+#[derive(Debug)]
+pub struct SysTickClock {
+    hz: u32,
+    reload: u32,
+    last: Cell<u32>,
+    ticks: Cell<u64>,
+}
+
+impl SysTickClock {
+    /// Create a new clock on SysTick
+    ///
+    /// # Arguments
+    /// * `clock_hz` - frequency of the SysTick clock source in Hz, i.e. the core clock
+    ///   or the external reference clock
+    /// * `reload` - configured SysTick reload value, see [`SYST::get_reload`]
+    pub const fn new(clock_hz: u32, reload: u32) -> Self {
+        Self {
+            hz: clock_hz,
+            reload,
+            last: Cell::new(reload),
+            ticks: Cell::new(0),
+        }
+    }
+
+    /// Accumulate a raw counter value and convert the total into a timestamp
+    fn now_from(&self, raw: u32) -> Duration {
+        let last = self.last.get();
+        // Counting down, wrapping from zero back to the reload value
+        let elapsed = if raw <= last {
+            u64::from(last - raw)
+        } else {
+            u64::from(last) + u64::from(self.reload) + 1 - u64::from(raw)
+        };
+        let ticks = self.ticks.get() + elapsed;
+        self.last.set(raw);
+        self.ticks.set(ticks);
+        ticks_to_duration(ticks, self.hz)
+    }
+}
+
+impl Clock for SysTickClock {
+    fn now(&self) -> Duration {
+        self.now_from(SYST::get_current())
+    }
+}
+
+impl Clock for &SysTickClock {
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{DwtClock, SysTickClock};
+
+    #[test]
+    fn verify_dwt_clock() {
+        let clock = DwtClock::new(1_000_000);
+        assert_eq!(clock.0.now_from(1_500_000), Duration::from_millis(1_500));
+        assert_eq!(
+            clock.0.now_from(u32::MAX),
+            Duration::from_nanos(4_294_967_295_000)
+        );
+        // Wraps around, one tick per microsecond
+        assert_eq!(clock.0.now_from(9), Duration::from_micros(4_294_967_305));
+    }
+
+    #[test]
+    fn verify_systick_clock() {
+        // Wraps around every 1ms at 1 MHz
+        let clock = SysTickClock::new(1_000_000, 999);
+        assert_eq!(clock.now_from(999), Duration::ZERO);
+        assert_eq!(clock.now_from(499), Duration::from_micros(500));
+        // Wraps around through zero
+        assert_eq!(clock.now_from(899), Duration::from_micros(1_100));
+        assert_eq!(clock.now_from(899), Duration::from_micros(1_100));
+        assert_eq!(clock.now_from(0), Duration::from_micros(1_999));
+        assert_eq!(clock.now_from(999), Duration::from_micros(2_000));
+    }
+}
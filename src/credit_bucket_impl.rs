@@ -0,0 +1,156 @@
+//! Manually refilled credit bucket -type limiter
+
+use core::time::Duration;
+
+use crate::{
+    CantConsume, Diagnostics, Inspect, Limiter, LimiterKind, LimiterResult, RemainingBudget, Reset,
+    RetryHint,
+};
+
+/// Credit bucket -type limiter
+///
+/// A credit bucket works like a [`crate::TokenBucket`], except that tokens
+/// are never replenished by elapsed time. Tokens are only added by
+/// explicit [`CreditBucket::refill`] calls, which models credit-based
+/// flow control where the peer grants credits to the sender.
+///
+/// Since no time is involved, this limiter doesn't need a time provider.
+#[derive(Debug, Clone)]
+pub struct CreditBucket {
+    capacity: u64,
+    tokens: u64,
+    name: Option<&'static str>,
+}
+
+impl CreditBucket {
+    /// Initialize a new credit bucket
+    ///
+    /// # Arguments
+    /// * `capacity` - maximum amount of tokens the bucket can hold
+    /// * `initial_tokens` - amount of tokens initially in the bucket, capped to `capacity`
+    pub const fn new(capacity: u64, initial_tokens: u64) -> Self {
+        let tokens = if initial_tokens < capacity {
+            initial_tokens
+        } else {
+            capacity
+        };
+        Self {
+            capacity,
+            tokens,
+            name: None,
+        }
+    }
+
+    /// Assign a name to this limiter, see [`Limiter::name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Add tokens to the bucket
+    ///
+    /// Tokens exceeding the bucket capacity are discarded.
+    ///
+    /// # Returns
+    /// Amount of tokens actually added
+    pub fn refill(&mut self, tokens: u64) -> u64 {
+        let added = tokens.min(self.capacity - self.tokens);
+        self.tokens += added;
+        added
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Amount of tokens currently in the bucket
+    pub fn tokens(&self) -> u64 {
+        self.tokens
+    }
+}
+
+impl Limiter for CreditBucket {
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        self.tokens = self.tokens.checked_sub(tokens).ok_or(CantConsume)?;
+        Ok(())
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::CreditBucket
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        Diagnostics {
+            config_valid: self.capacity != 0,
+            ..Diagnostics::HEALTHY
+        }
+    }
+
+    fn reset(&mut self) {
+        self.tokens = self.capacity;
+    }
+}
+
+impl Reset for CreditBucket {}
+
+impl RetryHint for CreditBucket {
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        // Tokens are only added by explicit refills
+        (tokens <= self.tokens).then_some(Duration::ZERO)
+    }
+}
+
+impl Inspect for CreditBucket {
+    fn tokens_available(&self) -> u64 {
+        self.tokens
+    }
+}
+
+impl RemainingBudget for CreditBucket {
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        self.try_consume(tokens)?;
+        Ok(self.tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::task::Poll;
+
+    use crate::Limiter;
+
+    use super::CreditBucket;
+
+    #[test]
+    fn verify_refill() {
+        let mut b = CreditBucket::new(10, 3);
+
+        assert!(b.try_consume(3).is_ok());
+        assert!(b.try_consume_one().is_err());
+
+        assert_eq!(b.refill(4), 4);
+        assert!(b.try_consume(5).is_err());
+        assert!(b.try_consume(4).is_ok());
+
+        // Refills are capped to capacity
+        assert_eq!(b.refill(15), 10);
+        assert_eq!(b.tokens(), 10);
+        assert!(b.try_consume(10).is_ok());
+    }
+
+    #[test]
+    fn verify_poll() {
+        let mut b = CreditBucket::new(10, 0);
+
+        assert_eq!(b.poll_consume(2), Poll::Pending);
+        b.refill(1);
+        assert_eq!(b.poll_consume(2), Poll::Pending);
+        b.refill(1);
+        assert_eq!(b.poll_consume(2), Poll::Ready(()));
+    }
+}
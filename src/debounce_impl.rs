@@ -0,0 +1,294 @@
+//! Debounce and throttle utilities
+
+use core::time::Duration;
+
+use crate::Clock;
+#[cfg(feature = "std")]
+use crate::StdClock;
+
+/// [`Debouncer`] using the system clock, as returned by [`debouncer`]
+#[cfg(feature = "std")]
+pub type StdDebouncer = Debouncer<StdClock>;
+
+/// [`Throttler`] using the system clock, as returned by [`throttler`]
+#[cfg(feature = "std")]
+pub type StdThrottler = Throttler<StdClock>;
+
+/// Build a debouncer
+///
+/// # Arguments
+/// * `quiet` - how long events must stay away for the next one to pass
+#[cfg(feature = "std")]
+pub fn debouncer(quiet: Duration) -> StdDebouncer {
+    Debouncer::new_with_time_provider(quiet, StdClock)
+}
+
+/// Build a throttler
+///
+/// # Arguments
+/// * `period` - minimum time between two runs
+#[cfg(feature = "std")]
+pub fn throttler(period: Duration) -> StdThrottler {
+    Throttler::new_with_time_provider(period, StdClock)
+}
+
+/// Suppressor of duplicate events within a quiet period
+///
+/// An event passes [`Self::trigger`] only if no other event, passed or
+/// suppressed, happened during the preceding quiet period. E.g. a bouncing
+/// button contact passes once, however long it bounces. For acting on the
+/// last event of a burst instead, [`Self::poll_settled`] reports once the
+/// events have settled.
+///
+/// # Generic arguments
+/// * `T` - time provider
+#[derive(Debug, Clone)]
+pub struct Debouncer<T>
+where
+    T: Clock,
+{
+    quiet: Duration,
+    last_event: Option<Duration>,
+    pending: bool,
+    time_provider: T,
+}
+
+impl<T> Debouncer<T>
+where
+    T: Clock,
+{
+    /// Initialize a new debouncer utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `quiet` - how long events must stay away for the next one to pass
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`debouncer`]
+    pub fn new_with_time_provider(quiet: Duration, time_provider: T) -> Self {
+        Self {
+            quiet,
+            last_event: None,
+            pending: false,
+            time_provider,
+        }
+    }
+
+    /// Configured quiet period
+    pub fn quiet_period(&self) -> Duration {
+        self.quiet
+    }
+
+    /// Record an event
+    ///
+    /// # Returns
+    /// `true` if the event passes, `false` if it is a duplicate suppressed
+    /// until the events settle
+    pub fn trigger(&mut self) -> bool {
+        let now = self.time_provider.now();
+        let passes = self.settled_at(now);
+        self.last_event = Some(now);
+        self.pending = !passes;
+        passes
+    }
+
+    /// Check whether suppressed events have settled
+    ///
+    /// # Returns
+    /// `true` once after an event was suppressed by [`Self::trigger`] and the
+    /// quiet period has passed since the last event
+    pub fn poll_settled(&mut self) -> bool {
+        let now = self.time_provider.now();
+        let settled = self.pending && self.settled_at(now);
+        if settled {
+            self.pending = false;
+        }
+        settled
+    }
+
+    /// Time left until the next event would pass, zero if it would right away
+    pub fn time_until_settled(&self) -> Duration {
+        let now = self.time_provider.now();
+        self.time_until_settled_at(now)
+    }
+
+    /// Forget all events
+    pub fn reset(&mut self) {
+        self.last_event = None;
+        self.pending = false;
+    }
+
+    fn time_until_settled_at(&self, now: Duration) -> Duration {
+        self.last_event.map_or(Duration::ZERO, |last_event| {
+            last_event.saturating_add(self.quiet).saturating_sub(now)
+        })
+    }
+
+    fn settled_at(&self, now: Duration) -> bool {
+        self.time_until_settled_at(now).is_zero()
+    }
+}
+
+/// Limiter of how often an action runs
+///
+/// [`Self::try_run`] lets the action run at most once per period. Calls
+/// rejected in between are coalesced, and [`Self::poll_pending`] reports
+/// once the period is over, so the latest request is never lost. E.g. a
+/// display refreshes at most every 100 ms, but still shows the final value.
+///
+/// # Generic arguments
+/// * `T` - time provider
+#[derive(Debug, Clone)]
+pub struct Throttler<T>
+where
+    T: Clock,
+{
+    period: Duration,
+    last_run: Option<Duration>,
+    pending: bool,
+    time_provider: T,
+}
+
+impl<T> Throttler<T>
+where
+    T: Clock,
+{
+    /// Initialize a new throttler utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `period` - minimum time between two runs
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`throttler`]
+    pub fn new_with_time_provider(period: Duration, time_provider: T) -> Self {
+        Self {
+            period,
+            last_run: None,
+            pending: false,
+            time_provider,
+        }
+    }
+
+    /// Configured period
+    pub fn period(&self) -> Duration {
+        self.period
+    }
+
+    /// Request a run
+    ///
+    /// # Returns
+    /// `true` if the action should run now, `false` if it ran too recently
+    /// and the request is left pending for [`Self::poll_pending`]
+    pub fn try_run(&mut self) -> bool {
+        let now = self.time_provider.now();
+        let ready = self.ready_at(now);
+        if ready {
+            self.last_run = Some(now);
+        }
+        self.pending = !ready;
+        ready
+    }
+
+    /// Check whether a pending request may run
+    ///
+    /// # Returns
+    /// `true` once after a request was rejected by [`Self::try_run`] and the
+    /// period has passed since the last run, which then counts as a run
+    pub fn poll_pending(&mut self) -> bool {
+        let now = self.time_provider.now();
+        let ready = self.pending && self.ready_at(now);
+        if ready {
+            self.last_run = Some(now);
+            self.pending = false;
+        }
+        ready
+    }
+
+    /// Whether a request is waiting for [`Self::poll_pending`]
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    /// Time left until the action may run again, zero if it may right away
+    pub fn time_until_ready(&self) -> Duration {
+        let now = self.time_provider.now();
+        self.time_until_ready_at(now)
+    }
+
+    /// Forget previous runs and pending requests
+    pub fn reset(&mut self) {
+        self.last_run = None;
+        self.pending = false;
+    }
+
+    fn time_until_ready_at(&self, now: Duration) -> Duration {
+        self.last_run.map_or(Duration::ZERO, |last_run| {
+            last_run.saturating_add(self.period).saturating_sub(now)
+        })
+    }
+
+    fn ready_at(&self, now: Duration) -> bool {
+        self.time_until_ready_at(now).is_zero()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::mock_assets::MockClock;
+
+    use super::{Debouncer, Throttler};
+
+    #[test]
+    fn verify_debounce() {
+        let clock = MockClock::new();
+        let mut d = Debouncer::new_with_time_provider(Duration::from_millis(10), || clock.step(0));
+
+        // T = 0ms
+        assert!(d.trigger());
+        clock.step(6_000);
+        // T = 6ms
+        assert!(!d.trigger());
+        clock.step(6_000);
+        // T = 12ms, the suppressed event restarted the quiet period
+        assert!(!d.poll_settled());
+        assert_eq!(d.time_until_settled(), Duration::from_millis(4));
+        clock.step(4_000);
+        // T = 16ms
+        assert!(d.poll_settled());
+        assert!(!d.poll_settled());
+        assert!(d.trigger());
+
+        // A quiet period reaching past the end of time saturates
+        let mut d = Debouncer::new_with_time_provider(Duration::MAX, || clock.step(0));
+        assert!(d.trigger());
+        assert!(!d.trigger());
+        assert!(!d.poll_settled());
+    }
+
+    #[test]
+    fn verify_throttle() {
+        let clock = MockClock::new();
+        let mut t = Throttler::new_with_time_provider(Duration::from_millis(10), || clock.step(0));
+
+        // T = 0ms
+        assert!(t.try_run());
+        clock.step(6_000);
+        // T = 6ms
+        assert!(!t.try_run());
+        assert!(t.is_pending());
+        clock.step(6_000);
+        // T = 12ms
+        assert!(t.poll_pending());
+        assert!(!t.poll_pending());
+        assert_eq!(t.time_until_ready(), Duration::from_millis(10));
+
+        // A period reaching past the end of time saturates
+        let mut t = Throttler::new_with_time_provider(Duration::MAX, || clock.step(0));
+        assert!(t.try_run());
+        assert!(!t.try_run());
+        assert!(!t.poll_pending());
+    }
+}
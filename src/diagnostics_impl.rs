@@ -0,0 +1,236 @@
+//! Limiter self-diagnostics
+
+use core::time::Duration;
+
+/// Result of a limiter self-check, see [`crate::Limiter::self_check`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Diagnostics {
+    /// The time provider has never returned a timestamp older than a
+    /// previously observed one
+    pub clock_monotonic: bool,
+    /// The time provider has advanced since the limiter was created
+    pub clock_progressing: bool,
+    /// The configuration is internally consistent, e.g. no zero capacities,
+    /// rates or window widths
+    pub config_valid: bool,
+    /// An internal counter has saturated or would have overflowed
+    pub saturated: bool,
+}
+
+impl Diagnostics {
+    /// Diagnostics of a limiter with no detected problems
+    pub const HEALTHY: Diagnostics = Diagnostics {
+        clock_monotonic: true,
+        clock_progressing: true,
+        config_valid: true,
+        saturated: false,
+    };
+
+    /// `true` if no problems were detected
+    ///
+    /// A clock that hasn't progressed is not considered a problem on its
+    /// own, as the limiter may have been created just now.
+    pub fn is_healthy(&self) -> bool {
+        self.clock_monotonic && self.config_valid && !self.saturated
+    }
+}
+
+impl Default for Diagnostics {
+    fn default() -> Self {
+        Self::HEALTHY
+    }
+}
+
+/// Bookkeeping of time provider behavior for [`Diagnostics`]
+// Not every limiter is compiled in with every feature set
+#[cfg_attr(
+    not(all(
+        feature = "token-bucket",
+        feature = "fixed-window",
+        feature = "sliding-window"
+    )),
+    allow(dead_code)
+)]
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct ClockMonitor {
+    created: Duration,
+    latest: Duration,
+    last_consume: Option<Duration>,
+    went_backwards: bool,
+    saturated: bool,
+}
+
+#[cfg_attr(
+    not(all(
+        feature = "token-bucket",
+        feature = "fixed-window",
+        feature = "sliding-window"
+    )),
+    allow(dead_code)
+)]
+impl ClockMonitor {
+    pub(crate) fn new(now: Duration) -> Self {
+        Self {
+            created: now,
+            latest: now,
+            last_consume: None,
+            went_backwards: false,
+            saturated: false,
+        }
+    }
+
+    /// Restart the monitoring on a limiter reset at the given time
+    ///
+    /// A clock that went backwards and a saturated counter are faults of the
+    /// time provider and the configuration rather than of the limiter state,
+    /// so they are kept across resets.
+    pub(crate) fn reset(&mut self, now: Duration) {
+        self.track(now);
+        self.created = now;
+        self.last_consume = None;
+    }
+
+    /// Record a timestamp read from the time provider for a consume
+    pub(crate) fn observe(&mut self, now: Duration) {
+        self.track(now);
+        self.last_consume = Some(self.latest);
+    }
+
+    fn track(&mut self, now: Duration) {
+        if now < self.latest {
+            self.went_backwards = true;
+        } else {
+            self.latest = now;
+        }
+    }
+
+    /// Timestamp of the most recent consume
+    pub(crate) fn last_consume_at(&self) -> Option<Duration> {
+        self.last_consume
+    }
+
+    /// Latest timestamp observed, or the creation time
+    pub(crate) fn latest(&self) -> Duration {
+        self.latest
+    }
+
+    /// Time since the most recent consume, or since creation
+    pub(crate) fn idle_for(&self, now: Duration) -> Duration {
+        now.saturating_sub(self.last_consume.unwrap_or(self.created))
+    }
+
+    /// Record that an internal counter has saturated
+    pub(crate) fn saturate(&mut self) {
+        self.saturated = true;
+    }
+
+    /// Add to an internal counter, recording a saturation on overflow
+    pub(crate) fn saturating_add(&mut self, counter: u64, value: u64) -> u64 {
+        counter.checked_add(value).unwrap_or_else(|| {
+            self.saturate();
+            u64::MAX
+        })
+    }
+
+    pub(crate) fn diagnose(&self, now: Duration, config_valid: bool) -> Diagnostics {
+        Diagnostics {
+            clock_monotonic: !self.went_backwards && now >= self.latest,
+            clock_progressing: now > self.created,
+            config_valid,
+            saturated: self.saturated,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{ClockMonitor, Diagnostics};
+
+    #[test]
+    fn verify_is_healthy() {
+        assert!(Diagnostics::HEALTHY.is_healthy());
+        assert_eq!(Diagnostics::default(), Diagnostics::HEALTHY);
+        // A clock standing still is not a problem on its own
+        assert!(Diagnostics {
+            clock_progressing: false,
+            ..Diagnostics::HEALTHY
+        }
+        .is_healthy());
+        assert!(!Diagnostics {
+            clock_monotonic: false,
+            ..Diagnostics::HEALTHY
+        }
+        .is_healthy());
+        assert!(!Diagnostics {
+            config_valid: false,
+            ..Diagnostics::HEALTHY
+        }
+        .is_healthy());
+        assert!(!Diagnostics {
+            saturated: true,
+            ..Diagnostics::HEALTHY
+        }
+        .is_healthy());
+    }
+
+    #[test]
+    fn verify_backward_step() {
+        let mut monitor = ClockMonitor::new(Duration::from_millis(10));
+        let diagnostics = monitor.diagnose(Duration::from_millis(10), true);
+        assert!(diagnostics.clock_monotonic);
+        assert!(!diagnostics.clock_progressing);
+
+        monitor.observe(Duration::from_millis(20));
+        assert_eq!(monitor.latest(), Duration::from_millis(20));
+        assert_eq!(monitor.last_consume_at(), Some(Duration::from_millis(20)));
+        assert!(monitor
+            .diagnose(Duration::from_millis(20), true)
+            .is_healthy());
+        // A timestamp older than the latest one, even if not yet observed
+        assert!(
+            !monitor
+                .diagnose(Duration::from_millis(15), true)
+                .clock_monotonic
+        );
+
+        monitor.observe(Duration::from_millis(15));
+        assert_eq!(monitor.latest(), Duration::from_millis(20));
+        assert_eq!(monitor.last_consume_at(), Some(Duration::from_millis(20)));
+        let diagnostics = monitor.diagnose(Duration::from_millis(30), true);
+        assert!(!diagnostics.clock_monotonic);
+        assert!(diagnostics.clock_progressing);
+        assert!(!diagnostics.is_healthy());
+
+        // The fault survives a reset
+        monitor.reset(Duration::from_millis(40));
+        assert_eq!(monitor.last_consume_at(), None);
+        assert_eq!(
+            monitor.idle_for(Duration::from_millis(45)),
+            Duration::from_millis(5)
+        );
+        assert!(
+            !monitor
+                .diagnose(Duration::from_millis(50), true)
+                .clock_monotonic
+        );
+    }
+
+    #[test]
+    fn verify_saturation() {
+        let mut monitor = ClockMonitor::new(Duration::ZERO);
+        assert_eq!(monitor.saturating_add(u64::MAX - 1, 1), u64::MAX);
+        assert!(!monitor.diagnose(Duration::ZERO, true).saturated);
+
+        assert_eq!(monitor.saturating_add(u64::MAX, 1), u64::MAX);
+        let diagnostics = monitor.diagnose(Duration::ZERO, true);
+        assert!(diagnostics.saturated);
+        assert!(!diagnostics.is_healthy());
+
+        monitor.reset(Duration::from_millis(1));
+        assert!(monitor.diagnose(Duration::from_millis(1), true).saturated);
+    }
+}
@@ -0,0 +1,499 @@
+//! Dual-rate token bucket -type limiter
+
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+use crate::StdClock;
+use crate::{
+    CantConsume, Clock, ConfigError, Diagnostics, Inspect, Limiter, LimiterCore, LimiterKind,
+    LimiterResult, RemainingBudget, Request, Reset, Response, RetryHint, TokenBucketCore,
+    TokenBucketSnapshot, TokenBucketState,
+};
+
+/// [`DualTokenBucket`] using the system clock, as returned by [`dual_token_bucket`]
+#[cfg(feature = "std")]
+pub type StdDualTokenBucket = DualTokenBucket<StdClock>;
+
+/// Build a dual-rate token bucket limiter
+///
+/// # Arguments
+/// * `committed_rate_per_s` - committed information rate (CIR), the average rate in the long run
+/// * `committed_capacity` - committed burst size (CBS), how many tokens a burst may consume
+/// * `peak_rate_per_s` - peak information rate (PIR), how fast a burst may consume
+/// * `peak_capacity` - peak burst size (PBS), burstiness at the peak rate
+#[cfg(feature = "std")]
+pub fn dual_token_bucket(
+    committed_rate_per_s: u64,
+    committed_capacity: u64,
+    peak_rate_per_s: u64,
+    peak_capacity: u64,
+) -> StdDualTokenBucket {
+    DualTokenBucket::new_with_time_provider(
+        committed_rate_per_s,
+        committed_capacity,
+        peak_rate_per_s,
+        peak_capacity,
+        StdClock,
+    )
+}
+
+/// Dual-rate token bucket -type rate limiter
+///
+/// Enforces a committed rate and a peak rate at the same time with two
+/// linked token buckets, like the two-rate traffic conditioners of RFC 2698.
+/// A consume is admitted only if both buckets hold enough tokens, and then
+/// takes the tokens from both. A rejected consume leaves both buckets untouched.
+///
+/// Typically the peak rate is above the committed rate and the peak capacity
+/// below the committed capacity: bursts of up to the committed capacity are
+/// allowed, but never faster than the peak rate.
+///
+/// The algorithm itself is implemented by [`DualTokenBucketCore`], this type
+/// only pairs it with a time provider.
+#[derive(Debug, Clone)]
+pub struct DualTokenBucket<T>
+where
+    T: Clock,
+{
+    core: DualTokenBucketCore,
+    time_provider: T,
+}
+
+impl<T> DualTokenBucket<T>
+where
+    T: Clock,
+{
+    /// Initialize a new dual-rate token bucket utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `committed_rate_per_s` - committed information rate (CIR), the average rate in the long run
+    /// * `committed_capacity` - committed burst size (CBS), how many tokens a burst may consume
+    /// * `peak_rate_per_s` - peak information rate (PIR), how fast a burst may consume
+    /// * `peak_capacity` - peak burst size (PBS), burstiness at the peak rate
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`dual_token_bucket`]
+    pub fn new_with_time_provider(
+        committed_rate_per_s: u64,
+        committed_capacity: u64,
+        peak_rate_per_s: u64,
+        peak_capacity: u64,
+        time_provider: T,
+    ) -> Self {
+        let time_now = time_provider.now();
+        Self {
+            core: DualTokenBucketCore::new(
+                committed_rate_per_s,
+                committed_capacity,
+                peak_rate_per_s,
+                peak_capacity,
+                time_now,
+            ),
+            time_provider,
+        }
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting configurations
+    /// that would never admit anything or never refill
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroRate`] or [`ConfigError::ZeroCapacity`] if any rate or capacity is zero
+    pub fn try_new_with_time_provider(
+        committed_rate_per_s: u64,
+        committed_capacity: u64,
+        peak_rate_per_s: u64,
+        peak_capacity: u64,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        let time_now = time_provider.now();
+        Ok(Self {
+            core: DualTokenBucketCore::try_new(
+                committed_rate_per_s,
+                committed_capacity,
+                peak_rate_per_s,
+                peak_capacity,
+                time_now,
+            )?,
+            time_provider,
+        })
+    }
+
+    /// Assign a name to this limiter, see [`crate::TokenBucket::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.core = self.core.with_name(name);
+        self
+    }
+
+    /// Current state of both buckets, e.g. for dashboards and debug logs
+    pub fn state(&self) -> DualTokenBucketState {
+        let now = self.time_provider.now();
+        self.core.state_at(now)
+    }
+
+    /// Get a reference to the underlying sans-IO state machine
+    pub fn core(&self) -> &DualTokenBucketCore {
+        &self.core
+    }
+
+    /// Unwrap the underlying sans-IO state machine, dropping the time provider
+    pub fn into_core(self) -> DualTokenBucketCore {
+        self.core
+    }
+
+    /// Take a plain-data snapshot of the limiter state, see [`crate::TokenBucket::snapshot`]
+    pub fn snapshot(&self) -> DualTokenBucketSnapshot {
+        self.core.snapshot()
+    }
+
+    /// Resume a limiter from a snapshot taken with [`Self::snapshot`]
+    ///
+    /// The time provider must share the epoch of the one the snapshot was taken with.
+    /// The name is not part of the snapshot, see [`Self::with_name`].
+    pub fn restore(snapshot: DualTokenBucketSnapshot, time_provider: T) -> Self {
+        Self {
+            core: DualTokenBucketCore::restore(snapshot),
+            time_provider,
+        }
+    }
+}
+
+impl<T> Limiter for DualTokenBucket<T>
+where
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens).map(|_| ())
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::DualTokenBucket
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.core.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.time_provider.now();
+        self.core.self_check_at(now)
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.core.committed.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.time_provider.now();
+        Some(self.core.committed.idle_for_at(now))
+    }
+
+    fn reset(&mut self) {
+        let now = self.time_provider.now();
+        self.core.reset_at(now);
+    }
+}
+
+impl<T> RemainingBudget for DualTokenBucket<T>
+where
+    T: Clock,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens)
+    }
+}
+
+impl<T> Reset for DualTokenBucket<T> where T: Clock {}
+
+impl<T> RetryHint for DualTokenBucket<T>
+where
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.time_provider.now();
+        self.core.time_until_available_at(now, tokens)
+    }
+}
+
+impl<T> Inspect for DualTokenBucket<T>
+where
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.tokens_available_at(now)
+    }
+}
+
+/// Observable state of a [`DualTokenBucket`], see [`DualTokenBucket::state`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct DualTokenBucketState {
+    /// State of the committed rate bucket
+    pub committed: TokenBucketState,
+    /// State of the peak rate bucket
+    pub peak: TokenBucketState,
+}
+
+/// Plain-data snapshot of a [`DualTokenBucket`], see [`DualTokenBucket::snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct DualTokenBucketSnapshot {
+    /// Snapshot of the committed rate bucket
+    pub committed: TokenBucketSnapshot,
+    /// Snapshot of the peak rate bucket
+    pub peak: TokenBucketSnapshot,
+}
+
+/// Sans-IO dual-rate token bucket state machine
+///
+/// Same algorithm as [`DualTokenBucket`], but without a time provider: the
+/// current time is passed in with every [`LimiterCore::handle`] call.
+#[derive(Debug, Clone, Copy)]
+pub struct DualTokenBucketCore {
+    committed: TokenBucketCore,
+    peak: TokenBucketCore,
+    name: Option<&'static str>,
+}
+
+impl DualTokenBucketCore {
+    /// Initialize a new dual-rate token bucket state machine
+    ///
+    /// # Arguments
+    /// * `committed_rate_per_s` - committed information rate (CIR), the average rate in the long run
+    /// * `committed_capacity` - committed burst size (CBS), how many tokens a burst may consume
+    /// * `peak_rate_per_s` - peak information rate (PIR), how fast a burst may consume
+    /// * `peak_capacity` - peak burst size (PBS), burstiness at the peak rate
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    pub fn new(
+        committed_rate_per_s: u64,
+        committed_capacity: u64,
+        peak_rate_per_s: u64,
+        peak_capacity: u64,
+        now: Duration,
+    ) -> Self {
+        Self {
+            committed: TokenBucketCore::new(committed_rate_per_s, committed_capacity, now),
+            peak: TokenBucketCore::new(peak_rate_per_s, peak_capacity, now),
+            name: None,
+        }
+    }
+
+    /// Validating constructor, see [`DualTokenBucket::try_new_with_time_provider`]
+    pub fn try_new(
+        committed_rate_per_s: u64,
+        committed_capacity: u64,
+        peak_rate_per_s: u64,
+        peak_capacity: u64,
+        now: Duration,
+    ) -> Result<Self, ConfigError> {
+        Ok(Self {
+            committed: TokenBucketCore::try_new(committed_rate_per_s, committed_capacity, now)?,
+            peak: TokenBucketCore::try_new(peak_rate_per_s, peak_capacity, now)?,
+            name: None,
+        })
+    }
+
+    /// Return to the freshly constructed state at the given time
+    ///
+    /// The configuration, including the name, is kept.
+    pub fn reset_at(&mut self, now: Duration) {
+        self.committed.reset_at(now);
+        self.peak.reset_at(now);
+    }
+
+    /// Take a plain-data snapshot, see [`DualTokenBucket::snapshot`]
+    pub fn snapshot(&self) -> DualTokenBucketSnapshot {
+        DualTokenBucketSnapshot {
+            committed: self.committed.snapshot(),
+            peak: self.peak.snapshot(),
+        }
+    }
+
+    /// Resume from a snapshot, see [`DualTokenBucket::restore`]
+    pub fn restore(snapshot: DualTokenBucketSnapshot) -> Self {
+        Self {
+            committed: TokenBucketCore::restore(snapshot.committed),
+            peak: TokenBucketCore::restore(snapshot.peak),
+            name: None,
+        }
+    }
+
+    /// Assign a name to this limiter, see [`DualTokenBucket::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// State of the committed rate bucket
+    pub fn committed(&self) -> &TokenBucketCore {
+        &self.committed
+    }
+
+    /// State of the peak rate bucket
+    pub fn peak(&self) -> &TokenBucketCore {
+        &self.peak
+    }
+
+    /// Tokens that could be consumed at `now`, see [`Inspect::tokens_available`]
+    pub fn tokens_available_at(&self, now: Duration) -> u64 {
+        self.committed
+            .tokens_available_at(now)
+            .min(self.peak.tokens_available_at(now))
+    }
+
+    /// Time until `tokens` could be consumed at `now`, see [`RetryHint::time_until_available`]
+    pub fn time_until_available_at(&self, now: Duration, tokens: u64) -> Option<Duration> {
+        let committed = self.committed.time_until_available_at(now, tokens)?;
+        let peak = self.peak.time_until_available_at(now, tokens)?;
+        Some(committed.max(peak))
+    }
+
+    /// State of both buckets at `now`, see [`DualTokenBucket::state`]
+    pub fn state_at(&self, now: Duration) -> DualTokenBucketState {
+        DualTokenBucketState {
+            committed: self.committed.state_at(now),
+            peak: self.peak.state_at(now),
+        }
+    }
+
+    /// Run the self-check of [`Limiter::self_check`] at the given time
+    pub fn self_check_at(&self, now: Duration) -> Diagnostics {
+        let committed = self.committed.self_check_at(now);
+        let peak = self.peak.self_check_at(now);
+        Diagnostics {
+            clock_monotonic: committed.clock_monotonic && peak.clock_monotonic,
+            clock_progressing: committed.clock_progressing,
+            config_valid: committed.config_valid && peak.config_valid,
+            saturated: committed.saturated || peak.saturated,
+        }
+    }
+
+    /// Consume at the given time, returning the remaining budget
+    fn try_consume_at(&mut self, now: Duration, tokens: u64) -> Result<u64, CantConsume> {
+        if self.tokens_available_at(now) < tokens {
+            // Consuming nothing still records the attempt in both buckets
+            let _ = self.committed.try_consume_at(now, 0);
+            let _ = self.peak.try_consume_at(now, 0);
+            return Err(CantConsume);
+        }
+        let committed = self.committed.try_consume_at(now, tokens)?;
+        let peak = self.peak.try_consume_at(now, tokens)?;
+        Ok(committed.min(peak))
+    }
+}
+
+impl LimiterCore for DualTokenBucketCore {
+    fn handle(&mut self, now: Duration, request: Request) -> Response {
+        match request {
+            Request::Consume(tokens) => self.try_consume_at(now, tokens).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{
+        mock_assets::MockClock, ConfigError, Inspect, Limiter, LimiterCore, Request, Response,
+        RetryHint,
+    };
+
+    use super::{DualTokenBucket, DualTokenBucketCore};
+
+    #[test]
+    fn verify_state() {
+        let clock = MockClock::new();
+        let mut b = DualTokenBucket::new_with_time_provider(100, 10, 1000, 4, || clock.step(0));
+        assert!(b.try_consume(3).is_ok());
+
+        let state = b.state();
+        assert_eq!(state.committed.tokens, 7);
+        assert_eq!(state.committed.capacity, 10);
+        assert_eq!(state.committed.rate_per_s, 100);
+        assert_eq!(state.peak.tokens, 1);
+        assert_eq!(state.peak.capacity, 4);
+        assert_eq!(state.peak.rate_per_s, 1000);
+    }
+
+    #[test]
+    fn verify_snapshot_restore() {
+        let clock = MockClock::new();
+        let mut b = DualTokenBucket::new_with_time_provider(100, 10, 1000, 4, || clock.step(0))
+            .with_name("dual");
+        assert!(b.try_consume(3).is_ok());
+        let snapshot = b.snapshot();
+        assert_eq!(snapshot.committed.tokens, 7);
+        assert_eq!(snapshot.committed.capacity, 10);
+        assert_eq!(snapshot.peak.tokens, 1);
+        assert_eq!(snapshot.peak.capacity, 4);
+
+        let mut restored = DualTokenBucket::restore(snapshot, || clock.step(0));
+        assert_eq!(restored.name(), None);
+        assert_eq!(restored.state(), b.state());
+        assert!(restored.try_consume(2).is_err());
+        assert!(restored.try_consume(1).is_ok());
+    }
+
+    #[test]
+    fn verify_try_new() {
+        let clock = MockClock::new();
+        assert_eq!(
+            DualTokenBucket::try_new_with_time_provider(100, 10, 0, 2, || clock.step(0)).err(),
+            Some(ConfigError::ZeroRate)
+        );
+        assert_eq!(
+            DualTokenBucketCore::try_new(100, 0, 1000, 2, Duration::ZERO).err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+        let mut b = DualTokenBucket::try_new_with_time_provider(100, 10, 1000, 2, || clock.step(0))
+            .unwrap();
+        assert!(b.try_consume(2).is_ok());
+    }
+
+    #[test]
+    fn verify_dual_rate() {
+        let clock = MockClock::new();
+        // Committed 1 per 10ms with bursts of 10, peak 1 per 1ms with bursts of 2
+        let mut b = DualTokenBucket::new_with_time_provider(100, 10, 1000, 2, || clock.step(0));
+
+        // Bursts are limited by the peak bucket
+        assert!(b.try_consume(2).is_ok());
+        assert!(b.try_consume_one().is_err());
+        assert_eq!(b.time_until_available(1), Some(Duration::from_millis(1)));
+
+        // Peak refills quickly, until the committed burst runs out
+        for _ in 0..8 {
+            clock.step(1000);
+            assert!(b.try_consume_one().is_ok());
+        }
+        clock.step(1000);
+        // Peak has a token, but the committed bucket doesn't
+        assert_eq!(b.core().peak().tokens_available_at(clock.step(0)), 1);
+        assert!(b.try_consume_one().is_err());
+        // Rejection didn't take the peak token
+        assert_eq!(b.core().peak().tokens_available_at(clock.step(0)), 1);
+
+        // Never more than the smaller capacity at once
+        assert_eq!(b.time_until_available(3), None);
+
+        b.reset();
+        assert_eq!(b.tokens_available(), 2);
+    }
+
+    #[test]
+    fn verify_dual_rate_core() {
+        let mut core = DualTokenBucketCore::new(100, 10, 1000, 2, Duration::ZERO);
+        assert_eq!(
+            core.handle(Duration::ZERO, Request::Consume(1)),
+            Response::Admitted { remaining: 1 }
+        );
+        assert_eq!(
+            core.handle(Duration::ZERO, Request::Consume(2)),
+            Response::Rejected
+        );
+    }
+}
@@ -0,0 +1,204 @@
+//! Async limiter sharing between embassy tasks and embassy-time integration
+
+use core::{
+    future::{poll_fn, Future},
+    pin::pin,
+    task::Poll,
+    time::Duration,
+};
+
+use embassy_sync::{blocking_mutex::raw::RawMutex, mutex::Mutex, signal::Signal};
+use embassy_time::{Instant, Timer};
+
+use crate::{Clock, Limiter, LimiterResult};
+
+/// [`Clock`] reading [`embassy_time::Instant::now`]
+///
+/// Requires an embassy time driver, like any other use of `embassy-time`.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct EmbassyClock;
+
+impl Clock for EmbassyClock {
+    fn now(&self) -> Duration {
+        Duration::from_micros(Instant::now().as_micros())
+    }
+}
+
+/// Async sleep function on the [`embassy_time::Timer`]
+///
+/// Pass it as the sleep function of [`crate::AsyncLimiter::acquire`] or
+/// [`EmbassySharedLimiter::new`], e.g. `bucket.acquire(1, embassy_sleep).await`.
+/// The duration is rounded up to whole microseconds, so the timer never fires early.
+pub fn embassy_sleep(duration: Duration) -> Timer {
+    let micros = duration.as_nanos().div_ceil(1_000);
+    Timer::after(embassy_time::Duration::from_micros(
+        micros.min(u64::MAX as u128) as u64,
+    ))
+}
+
+/// Limiter shared between embassy tasks
+///
+/// Wraps any [`Limiter`] into an [`embassy_sync::mutex::Mutex`] so that
+/// multiple tasks can consume from it through a shared reference, e.g. a
+/// `static`. Tasks waiting in [`EmbassySharedLimiter::acquire`] are served
+/// one at a time: the first waiter retries whenever the limiter is modified
+/// through [`EmbassySharedLimiter::update`] and periodically in between to
+/// catch tokens replenished by elapsed time, while the others wait for
+/// their turn without polling.
+///
+/// # Generic arguments
+/// * `M` - raw mutex type, e.g. `CriticalSectionRawMutex`
+/// * `L` - shared limiter
+/// * `S` - async sleep function, e.g. [`embassy_sleep`]
+pub struct EmbassySharedLimiter<M, L, S, F>
+where
+    M: RawMutex,
+    L: Limiter,
+    S: Fn(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    limiter: Mutex<M, L>,
+    turn: Mutex<M, ()>,
+    signal: Signal<M, ()>,
+    retry_interval: Duration,
+    sleep: S,
+}
+
+impl<M, L, S, F> EmbassySharedLimiter<M, L, S, F>
+where
+    M: RawMutex,
+    L: Limiter,
+    S: Fn(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    /// Share a limiter between tasks
+    ///
+    /// # Arguments
+    /// * `limiter` - limiter to share
+    /// * `retry_interval` - how often a waiting task retries when the limiter isn't updated
+    /// * `sleep` - async sleep function of the used executor
+    pub const fn new(limiter: L, retry_interval: Duration, sleep: S) -> Self {
+        Self {
+            limiter: Mutex::new(limiter),
+            turn: Mutex::new(()),
+            signal: Signal::new(),
+            retry_interval,
+            sleep,
+        }
+    }
+
+    /// Try to consume tokens without waiting for them
+    pub async fn try_consume(&self, tokens: u64) -> LimiterResult {
+        self.limiter.lock().await.try_consume(tokens)
+    }
+
+    /// Consume tokens, waiting until they are available
+    ///
+    /// Never completes if `tokens` exceeds what the limiter can ever admit at once.
+    pub async fn acquire(&self, tokens: u64) {
+        let _turn = self.turn.lock().await;
+        loop {
+            // Consume any stale signal before trying, so that updates made
+            // after the attempt are not missed
+            self.signal.reset();
+            if self.try_consume(tokens).await.is_ok() {
+                return;
+            }
+
+            let mut updated = pin!(self.signal.wait());
+            let mut retry = pin!((self.sleep)(self.retry_interval));
+            poll_fn(|cx| {
+                if updated.as_mut().poll(cx).is_ready() {
+                    return Poll::Ready(());
+                }
+                retry.as_mut().poll(cx)
+            })
+            .await;
+        }
+    }
+
+    /// Access the limiter exclusively, e.g. to refill a [`crate::CreditBucket`]
+    ///
+    /// A task waiting in [`EmbassySharedLimiter::acquire`] retries right after.
+    pub async fn update<R>(&self, f: impl FnOnce(&mut L) -> R) -> R {
+        let result = f(&mut *self.limiter.lock().await);
+        self.signal.signal(());
+        result
+    }
+}
+
+#[cfg(all(test, feature = "token-bucket"))]
+mod tests {
+    use core::{future::Future, pin::pin, task::Poll, time::Duration};
+
+    use embassy_sync::blocking_mutex::raw::NoopRawMutex;
+
+    use embassy_time::MockDriver;
+
+    use crate::{
+        mock_assets::{block_on, yield_n},
+        AsyncLimiter, Clock, CreditBucket, Limiter, TokenBucket,
+    };
+
+    use super::{embassy_sleep, EmbassyClock, EmbassySharedLimiter};
+
+    #[test]
+    fn verify_embassy_shared() {
+        // Retries would only happen after a long time
+        let sleep = |_: Duration| yield_n(1_000);
+        let l = EmbassySharedLimiter::<NoopRawMutex, _, _, _>::new(
+            CreditBucket::new(2, 2),
+            Duration::ZERO,
+            sleep,
+        );
+
+        block_on(l.acquire(2));
+        assert!(block_on(l.try_consume(1)).is_err());
+
+        let mut polls = 0;
+        let (mut first_done, mut second_done) = (false, false);
+        let mut first = pin!(l.acquire(1));
+        let mut second = pin!(l.acquire(1));
+        block_on(core::future::poll_fn(|cx| {
+            polls += 1;
+            first_done = first_done || first.as_mut().poll(cx).is_ready();
+            second_done = second_done || second.as_mut().poll(cx).is_ready();
+            if polls == 10 {
+                assert!(!first_done && !second_done);
+                // Must not block, the waiting tasks don't hold the limiter
+                let mut refill = pin!(l.update(|b| b.refill(2)));
+                assert_eq!(refill.as_mut().poll(cx), Poll::Ready(2));
+            }
+            if first_done && second_done {
+                Poll::Ready(())
+            } else {
+                Poll::Pending
+            }
+        }));
+        // Both waiters were woken by the update instead of the retry interval
+        assert!(polls < 100);
+    }
+
+    #[test]
+    fn verify_embassy_time() {
+        let driver = MockDriver::get();
+        driver.reset();
+        let mut b = TokenBucket::new_with_time_provider(1000, 1, EmbassyClock);
+        assert!(b.try_consume_one().is_ok());
+
+        let mut acquired = false;
+        let mut acquire = pin!(b.acquire(1, embassy_sleep));
+        block_on(core::future::poll_fn(|cx| {
+            assert!(!acquired);
+            acquired = acquire.as_mut().poll(cx).is_ready();
+            if acquired {
+                Poll::Ready(())
+            } else {
+                // One token is refilled per millisecond
+                driver.advance(embassy_time::Duration::from_micros(500));
+                Poll::Pending
+            }
+        }));
+        assert_eq!(EmbassyClock.now(), Duration::from_millis(1));
+    }
+}
@@ -0,0 +1,91 @@
+//! Clock adapters for embedded time crates
+
+use core::time::Duration;
+
+use crate::Clock;
+
+/// [`Clock`] adapter for a source of [`fugit`] instants
+///
+/// Wraps e.g. the `now` function of a monotonic timer driver:
+/// `FugitClock(|| Mono::now())`.
+///
+/// Only 64-bit instants are supported, as 32-bit ones wrap around too soon
+/// to be used as a monotonic timestamp.
+#[cfg(feature = "fugit")]
+#[derive(Debug, Clone, Copy)]
+pub struct FugitClock<F>(pub F);
+
+#[cfg(feature = "fugit")]
+impl<F, const NOM: u32, const DENOM: u32> Clock for FugitClock<F>
+where
+    F: Fn() -> fugit::Instant<u64, NOM, DENOM>,
+{
+    fn now(&self) -> Duration {
+        Duration::from_nanos((self.0)().duration_since_epoch().to_nanos())
+    }
+}
+
+/// [`Clock`] adapter for any [`embedded_time::Clock`]
+///
+/// A clock read that fails is reported as the epoch, which shows up as a
+/// clock going backwards in [`crate::Limiter::self_check`].
+#[cfg(feature = "embedded-time")]
+#[derive(Debug, Clone, Copy)]
+pub struct EmbeddedTimeClock<C>(pub C);
+
+#[cfg(feature = "embedded-time")]
+impl<C> Clock for EmbeddedTimeClock<C>
+where
+    C: embedded_time::Clock,
+    u64: TryFrom<C::T>,
+{
+    fn now(&self) -> Duration {
+        use embedded_time::duration::Nanoseconds;
+
+        self.0
+            .try_now()
+            .ok()
+            .and_then(|instant| Nanoseconds::<u64>::try_from(instant.duration_since_epoch()).ok())
+            .map_or(Duration::ZERO, |nanos| Duration::from_nanos(nanos.0))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::Clock;
+
+    #[cfg(feature = "fugit")]
+    #[test]
+    fn verify_fugit_clock() {
+        use super::FugitClock;
+
+        let clock = FugitClock(|| fugit::TimerInstantU64::<1_000>::from_ticks(1_500));
+        assert_eq!(clock.now(), Duration::from_millis(1_500));
+    }
+
+    #[cfg(feature = "embedded-time")]
+    #[test]
+    fn verify_embedded_time_clock() {
+        use embedded_time::{clock::Error, fraction::Fraction, Instant};
+
+        use super::EmbeddedTimeClock;
+
+        struct TickClock;
+
+        impl embedded_time::Clock for TickClock {
+            type T = u32;
+            const SCALING_FACTOR: Fraction = Fraction::new(1, 1_000);
+
+            fn try_now(&self) -> Result<Instant<Self>, Error> {
+                Ok(Instant::new(2_500))
+            }
+        }
+
+        assert_eq!(
+            EmbeddedTimeClock(TickClock).now(),
+            Duration::from_millis(2_500)
+        );
+    }
+}
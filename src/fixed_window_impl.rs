@@ -3,107 +3,2257 @@
 use core::time::Duration;
 
 #[cfg(feature = "std")]
-use crate::macros::std_time_provider;
-use crate::{CantConsume, Limiter, LimiterResult};
+use crate::StdClock;
+use crate::{
+    diagnostics_impl::ClockMonitor, AdjustableCapacity, CantConsume, Clock, ConfigError,
+    Diagnostics, Inspect, Limiter, LimiterCore, LimiterKind, LimiterResult, PhaseOffset, Refund,
+    RemainingBudget, Request, Reset, Response, RetryHint,
+};
+
+/// [`FixedWindow`] using the system clock, as returned by [`fixed_window`]
+#[cfg(feature = "std")]
+pub type StdFixedWindow = FixedWindow<StdClock>;
 
 /// Build a fixed window limiter
 ///
 /// # Arguments
 /// * `capacity` - how many consumes are allowed during a single window
 /// * `window_width_ms` - window width in milliseconds
+///
+/// # Panics
+/// Consumes panic if `window_width_ms` is zero, see [`FixedWindow::try_new_with_time_provider`]
+#[cfg(feature = "std")]
+pub fn fixed_window(capacity: u64, window_width_ms: u64) -> StdFixedWindow {
+    fixed_window_from_width(capacity, Duration::from_millis(window_width_ms))
+}
+
+/// Build a fixed window limiter with the window width given as a [`Duration`]
+///
+/// # Arguments
+/// * `capacity` - how many consumes are allowed during a single window
+/// * `window_width` - window width, may be shorter than a millisecond
+///
+/// # Panics
+/// Consumes panic if `window_width` is zero
+#[cfg(feature = "std")]
+pub fn fixed_window_from_width(capacity: u64, window_width: Duration) -> StdFixedWindow {
+    FixedWindow::from_width_with_time_provider(capacity, window_width, StdClock)
+}
+
+/// [`FixedMultiWindow`] using the system clock, as returned by [`fixed_multi_window`]
+#[cfg(feature = "std")]
+pub type StdFixedMultiWindow<const K: usize> = FixedMultiWindow<StdClock, K>;
+
+/// Build a fixed window limiter with an additional cap over the last `K` windows
+///
+/// # Arguments
+/// * `capacity` - how many consumes are allowed during a single window
+/// * `window_width_ms` - window width in milliseconds
+/// * `long_capacity` - how many consumes are allowed during the last `K` windows combined
+#[cfg(feature = "std")]
+pub fn fixed_multi_window<const K: usize>(
+    capacity: u64,
+    window_width_ms: u64,
+    long_capacity: u64,
+) -> StdFixedMultiWindow<K> {
+    fixed_multi_window_from_width(
+        capacity,
+        Duration::from_millis(window_width_ms),
+        long_capacity,
+    )
+}
+
+/// Build a fixed window limiter with an additional cap over the last `K` windows,
+/// with the window width given as a [`Duration`]
+///
+/// # Arguments
+/// * `capacity` - how many consumes are allowed during a single window
+/// * `window_width` - window width, may be shorter than a millisecond
+/// * `long_capacity` - how many consumes are allowed during the last `K` windows combined
+#[cfg(feature = "std")]
+pub fn fixed_multi_window_from_width<const K: usize>(
+    capacity: u64,
+    window_width: Duration,
+    long_capacity: u64,
+) -> StdFixedMultiWindow<K> {
+    FixedMultiWindow::<_, K>::from_width_with_time_provider(
+        capacity,
+        window_width,
+        long_capacity,
+        StdClock,
+    )
+}
+
+/// [`ScheduledFixedWindow`] using the system clock, as returned by [`scheduled_fixed_window`]
+#[cfg(feature = "std")]
+pub type StdScheduledFixedWindow<const N: usize> = ScheduledFixedWindow<StdClock, N>;
+
+/// Build a fixed window limiter with a repeating capacity schedule
+///
+/// # Arguments
+/// * `schedule` - capacities of successive windows, repeated indefinitely
+/// * `window_width_ms` - window width in milliseconds
+///
+/// # Panics
+/// Consumes panic if `window_width_ms` is zero, see [`ScheduledFixedWindow::try_new_with_time_provider`]
+#[cfg(feature = "std")]
+pub fn scheduled_fixed_window<const N: usize>(
+    schedule: [u64; N],
+    window_width_ms: u64,
+) -> StdScheduledFixedWindow<N> {
+    scheduled_fixed_window_from_width(schedule, Duration::from_millis(window_width_ms))
+}
+
+/// Build a fixed window limiter with a repeating capacity schedule,
+/// with the window width given as a [`Duration`]
+///
+/// # Arguments
+/// * `schedule` - capacities of successive windows, repeated indefinitely
+/// * `window_width` - window width, may be shorter than a millisecond
+///
+/// # Panics
+/// Consumes panic if `window_width` is zero
+#[cfg(feature = "std")]
+pub fn scheduled_fixed_window_from_width<const N: usize>(
+    schedule: [u64; N],
+    window_width: Duration,
+) -> StdScheduledFixedWindow<N> {
+    ScheduledFixedWindow::from_width_with_time_provider(schedule, window_width, StdClock)
+}
+
 #[cfg(feature = "std")]
-pub fn fixed_window(capacity: u64, window_width_ms: u64) -> FixedWindow<impl Fn() -> Duration> {
-    FixedWindow::new_with_time_provider(capacity, window_width_ms, std_time_provider!())
+impl FixedWindow<StdClock> {
+    /// Build a fixed window limiter from a [`FixedWindowConfig`]
+    ///
+    /// # Arguments
+    /// * `config` - limiter parameters, e.g. deserialized from a configuration file
+    ///
+    /// # Returns
+    /// [`ConfigError`] for parameters rejected by [`Self::try_new_with_time_provider`]
+    pub fn from_config(config: FixedWindowConfig) -> Result<Self, ConfigError> {
+        Self::from_config_with_time_provider(config, StdClock)
+    }
+}
+
+/// Fixed window -type rate limiter
+///
+/// A Fixed window limiter splits the timeline into time windows
+/// of defined size and allocates a certain amount of tokens for
+/// each window. Consumes are successfull as long as the current
+/// time window still holds enought tokens.
+///
+/// The algorithm itself is implemented by [`FixedWindowCore`], this type
+/// only pairs it with a time provider.
+///
+/// # Generic arguments
+/// * `T` - time provider
+/// * `S` - capacity schedule, see [`FixedWindow::with_capacity_schedule`]
+#[derive(Debug, Clone)]
+pub struct FixedWindow<T, S = fn(u64) -> u64>
+where
+    T: Clock,
+    S: CapacitySchedule,
+{
+    core: FixedWindowCore<S>,
+    time_provider: T,
+}
+
+impl<T> FixedWindow<T>
+where
+    T: Clock,
+{
+    /// Initialize a new fixed window limiter utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width_ms` - window width in milliseconds
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`fixed_window`]
+    ///
+    /// # Panics
+    /// Consumes panic on a division by zero if `window_width_ms` is zero,
+    /// use [`Self::try_new_with_time_provider`] to validate the configuration.
+    pub fn new_with_time_provider(capacity: u64, window_width_ms: u64, time_provider: T) -> Self {
+        Self::from_width_with_time_provider(
+            capacity,
+            Duration::from_millis(window_width_ms),
+            time_provider,
+        )
+    }
+
+    /// Initialize a new fixed window limiter with the window width given as a
+    /// [`Duration`] utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width` - window width, may be shorter than a millisecond
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`fixed_window_from_width`]
+    ///
+    /// # Panics
+    /// Consumes panic on a division by zero if `window_width` is zero
+    pub fn from_width_with_time_provider(
+        capacity: u64,
+        window_width: Duration,
+        time_provider: T,
+    ) -> Self {
+        let time_now = time_provider.now();
+        Self {
+            core: FixedWindowCore::from_width(capacity, window_width, time_now),
+            time_provider,
+        }
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting invalid configurations
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroWindow`] or [`ConfigError::ZeroCapacity`] for zero arguments
+    pub fn try_new_with_time_provider(
+        capacity: u64,
+        window_width_ms: u64,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        let time_now = time_provider.now();
+        Ok(Self {
+            core: FixedWindowCore::try_new(capacity, window_width_ms, time_now)?,
+            time_provider,
+        })
+    }
+
+    /// Initialize a new fixed window limiter from a [`FixedWindowConfig`] utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `config` - limiter parameters, e.g. deserialized from a configuration file
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// # Returns
+    /// [`ConfigError`] for parameters rejected by [`Self::try_new_with_time_provider`]
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`FixedWindow::from_config`]
+    pub fn from_config_with_time_provider(
+        config: FixedWindowConfig,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        Self::try_new_with_time_provider(config.capacity, config.window_width_ms, time_provider)
+    }
+
+    /// Resume a limiter from a snapshot taken with [`Self::snapshot`]
+    ///
+    /// The time provider must share the epoch of the one the snapshot was
+    /// taken with, e.g. a real-time clock kept running during deep sleep.
+    /// Time elapsed in between is accounted for like it had been running.
+    /// The name is not part of the snapshot, see [`Self::with_name`].
+    pub fn restore(snapshot: FixedWindowSnapshot, time_provider: T) -> Self {
+        Self {
+            core: FixedWindowCore::restore(snapshot),
+            time_provider,
+        }
+    }
+
+    /// Resume a limiter with a capacity schedule from a snapshot, see
+    /// [`Self::restore`] and [`Self::with_capacity_schedule`]
+    pub fn restore_with_schedule<F>(
+        snapshot: FixedWindowSnapshot,
+        schedule: F,
+        time_provider: T,
+    ) -> FixedWindow<T, F>
+    where
+        F: Fn(u64) -> u64,
+    {
+        FixedWindow {
+            core: FixedWindowCore::restore_with_schedule(snapshot, schedule),
+            time_provider,
+        }
+    }
+}
+
+impl<T, S> FixedWindow<T, S>
+where
+    T: Clock,
+    S: CapacitySchedule,
+{
+    /// Assign a name to this limiter, see [`Limiter::name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.core = self.core.with_name(name);
+        self
+    }
+
+    /// Shift the window boundaries by the given phase offset
+    ///
+    /// Windows are normally aligned to the creation of the limiter. With a
+    /// phase offset the current window ends `offset` earlier, so a fleet of
+    /// limiters created at the same moment can be configured with different
+    /// offsets to avoid all of them resetting and bursting at the same instant.
+    ///
+    /// # Arguments
+    /// * `offset` - phase offset, taken modulo the window width
+    pub fn with_phase_offset(mut self, offset: Duration) -> Self {
+        self.core = self.core.with_phase_offset(offset);
+        self
+    }
+
+    /// Align the window boundaries to multiples of the window width since `anchor`
+    ///
+    /// Windows are normally aligned to the creation of the limiter, so
+    /// replicas created at different moments disagree on the boundaries.
+    /// Aligned windows start at `anchor + n * window_width` instead, e.g.
+    /// with a [`crate::SystemClock`] and a zero anchor every replica resets at
+    /// the same wall-clock instants. The boundaries survive [`Limiter::reset`].
+    ///
+    /// # Arguments
+    /// * `anchor` - timestamp of a window boundary, not later than the current time
+    pub fn with_aligned_windows(mut self, anchor: Duration) -> Self {
+        self.core = self.core.with_aligned_windows(anchor);
+        self
+    }
+
+    /// Start with the given amount of tokens already used in the first window
+    ///
+    /// Allows a restarted service to seed the limiter from persisted usage.
+    pub fn with_initial_usage(mut self, used: u64) -> Self {
+        self.core = self.core.with_initial_usage(used);
+        self
+    }
+
+    /// Roll tokens left unused at the end of a window over into the next one
+    ///
+    /// The next window then admits its capacity plus up to `max_carry`
+    /// carried tokens, e.g. for metering contracts allowing unused quota to
+    /// carry forward one period. Carried tokens don't compound beyond
+    /// `max_carry`, and an idle window carries its full capacity.
+    pub fn with_carry_over(mut self, max_carry: u64) -> Self {
+        self.core = self.core.with_carry_over(max_carry);
+        self
+    }
+
+    /// Take the capacity of each window from a schedule
+    ///
+    /// The schedule is called with the index of the window, counted from the
+    /// start of the first window or from the anchor of
+    /// [`Self::with_aligned_windows`]. With aligned windows the index maps to
+    /// the time of day, e.g. for an uplink budget that is larger at night:
+    ///
+    /// ```
+    /// use core::time::Duration;
+    /// use burster::FixedWindow;
+    ///
+    /// // Any clock counting from the Unix epoch, e.g. `burster::SystemClock`
+    /// let wall_clock = || Duration::from_secs(1_700_000_000);
+    /// // Hourly windows, 100 tokens during the day and 1000 at night (UTC)
+    /// let uplink = FixedWindow::new_with_time_provider(100, 3_600_000, wall_clock)
+    ///     .with_aligned_windows(Duration::ZERO)
+    ///     .with_capacity_schedule(|hour| match hour % 24 {
+    ///         6..=21 => 100,
+    ///         _ => 1000,
+    ///     });
+    /// ```
+    ///
+    /// The schedule replaces the configured capacity, which is still
+    /// reported by [`Self::capacity`], and any schedule set before. For a
+    /// table repeating every `N` windows see also [`ScheduledFixedWindow`].
+    ///
+    /// # Arguments
+    /// * `schedule` - capacity of the window of the given index
+    pub fn with_capacity_schedule<F>(self, schedule: F) -> FixedWindow<T, F>
+    where
+        F: Fn(u64) -> u64,
+    {
+        FixedWindow {
+            core: self.core.with_capacity_schedule(schedule),
+            time_provider: self.time_provider,
+        }
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.core.capacity()
+    }
+
+    /// Capacity of the current window, differing from [`Self::capacity`] with a schedule
+    pub fn window_capacity(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.window_capacity_at(now)
+    }
+
+    /// Configured window width
+    pub fn window_width(&self) -> Duration {
+        self.core.window_width()
+    }
+
+    /// Time left until the current window ends and the budget is replenished
+    pub fn time_until_reset(&self) -> Duration {
+        let now = self.time_provider.now();
+        self.core.time_until_reset_at(now)
+    }
+
+    /// How far through the current window we are
+    pub fn window_elapsed(&self) -> Duration {
+        let now = self.time_provider.now();
+        self.core.window_elapsed_at(now)
+    }
+
+    /// Current state of the window, e.g. for dashboards and debug logs
+    pub fn state(&self) -> FixedWindowState {
+        let now = self.time_provider.now();
+        self.core.state_at(now)
+    }
+
+    /// Get a reference to the underlying sans-IO state machine
+    pub fn core(&self) -> &FixedWindowCore<S> {
+        &self.core
+    }
+
+    /// Unwrap the underlying sans-IO state machine, dropping the time provider
+    pub fn into_core(self) -> FixedWindowCore<S> {
+        self.core
+    }
+
+    /// Take a plain-data snapshot of the limiter state
+    ///
+    /// The snapshot can be stashed e.g. in battery-backed RAM and later passed
+    /// to [`Self::restore`], without requiring `serde`.
+    ///
+    /// A capacity schedule is not part of the snapshot, see
+    /// [`FixedWindow::restore_with_schedule`].
+    pub fn snapshot(&self) -> FixedWindowSnapshot {
+        self.core.snapshot()
+    }
+}
+
+impl<T, S> Limiter for FixedWindow<T, S>
+where
+    T: Clock,
+    S: CapacitySchedule,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens).map(|_| ())
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        let now = self.time_provider.now();
+        costs
+            .iter()
+            .take_while(|&&tokens| self.core.try_consume_at(now, tokens).is_ok())
+            .count()
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::FixedWindow
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.core.config.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.time_provider.now();
+        self.core.self_check_at(now)
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.core.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.time_provider.now();
+        Some(self.core.idle_for_at(now))
+    }
+
+    fn reset(&mut self) {
+        let now = self.time_provider.now();
+        self.core.reset_at(now);
+    }
+}
+
+impl<T, S> RemainingBudget for FixedWindow<T, S>
+where
+    T: Clock,
+    S: CapacitySchedule,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens)
+    }
+}
+
+impl<T, S> Reset for FixedWindow<T, S>
+where
+    T: Clock,
+    S: CapacitySchedule,
+{
+}
+
+impl<T, S> RetryHint for FixedWindow<T, S>
+where
+    T: Clock,
+    S: CapacitySchedule,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.time_provider.now();
+        self.core.time_until_available_at(now, tokens)
+    }
+}
+
+impl<T, S> Inspect for FixedWindow<T, S>
+where
+    T: Clock,
+    S: CapacitySchedule,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.tokens_available_at(now)
+    }
+}
+
+impl<T, S> Refund for FixedWindow<T, S>
+where
+    T: Clock,
+    S: CapacitySchedule,
+{
+    fn refund(&mut self, tokens: u64) {
+        self.core.refund(tokens);
+    }
+}
+
+impl<T, S> AdjustableCapacity for FixedWindow<T, S>
+where
+    T: Clock,
+    S: CapacitySchedule,
+{
+    fn current_capacity(&self) -> u64 {
+        self.core.capacity()
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        self.core.set_capacity(capacity);
+    }
+}
+
+impl<T, S> PhaseOffset for FixedWindow<T, S>
+where
+    T: Clock,
+    S: CapacitySchedule,
+{
+    fn window_width(&self) -> Duration {
+        self.core.window_width()
+    }
+
+    fn with_phase_offset(mut self, offset: Duration) -> Self {
+        self.core = self.core.with_phase_offset(offset);
+        self
+    }
+}
+
+/// Observable state of a [`FixedWindow`], a [`FixedMultiWindow`] or a
+/// [`ScheduledFixedWindow`], see e.g. [`FixedWindow::state`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FixedWindowState {
+    /// Tokens left in the current window
+    pub tokens: u64,
+    /// Capacity of the current window
+    pub capacity: u64,
+    /// Configured window width
+    pub window_width: Duration,
+    /// Timestamp the current window started at
+    pub window_start: Duration,
+}
+
+/// Plain-data snapshot of a [`FixedWindow`], see [`FixedWindow::snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedWindowSnapshot {
+    /// Configured capacity
+    pub capacity: u64,
+    /// Configured window width
+    pub window_width: Duration,
+    /// Tokens left in the window `window_index`
+    pub tokens: u64,
+    /// Index of the latest window consumed from, counted from `start_time`
+    pub window_index: u64,
+    /// Timestamp the windows are counted from
+    pub start_time: Duration,
+    /// Window phase shift, see [`FixedWindow::with_phase_offset`]
+    pub phase_offset: Duration,
+    /// Window boundary, see [`FixedWindow::with_aligned_windows`]
+    pub anchor: Option<Duration>,
+    /// Tokens that may roll over into the next window, see [`FixedWindow::with_carry_over`]
+    pub max_carry: u64,
+    /// Latest timestamp seen by the limiter
+    pub last_update: Duration,
+}
+
+/// Parameters of a [`FixedWindow`], e.g. deserialized from a configuration file
+///
+/// See [`FixedWindow::from_config`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct FixedWindowConfig {
+    /// How many consumes are allowed during a single window
+    pub capacity: u64,
+    /// Window width in milliseconds
+    pub window_width_ms: u64,
+}
+
+/// How many windows ahead [`FixedWindowCore::time_until_available_at`] looks
+/// for enough tokens
+const SCHEDULE_HORIZON: u64 = 1024;
+
+/// Capacity of each window of a fixed window limiter by window index
+///
+/// Implemented for all closures and functions `Fn(u64) -> u64`, see
+/// [`FixedWindow::with_capacity_schedule`].
+pub trait CapacitySchedule {
+    /// Capacity of the window with the given index
+    fn capacity_of(&self, index: u64) -> u64;
+}
+
+impl<F> CapacitySchedule for F
+where
+    F: Fn(u64) -> u64,
+{
+    fn capacity_of(&self, index: u64) -> u64 {
+        self(index)
+    }
+}
+
+/// Capacity schedule of a [`ScheduledFixedWindow`], repeating every `N` windows
+#[derive(Debug, Clone, Copy)]
+struct RepeatingSchedule<const N: usize>([u64; N]);
+
+impl<const N: usize> CapacitySchedule for RepeatingSchedule<N> {
+    fn capacity_of(&self, index: u64) -> u64 {
+        index
+            .checked_rem(N as u64)
+            .map_or(0, |slot| self.0[slot as usize])
+    }
+}
+
+/// Sans-IO fixed window state machine
+///
+/// Same algorithm as [`FixedWindow`], but without a time provider: the
+/// current time is passed in with every [`LimiterCore::handle`] call.
+///
+/// # Generic arguments
+/// * `S` - capacity schedule, see [`FixedWindow::with_capacity_schedule`]
+#[derive(Debug, Clone, Copy)]
+pub struct FixedWindowCore<S = fn(u64) -> u64>
+where
+    S: CapacitySchedule,
+{
+    config: FixedWindowParams,
+    tokens: u64,
+    window_index: u64,
+    start_time: Duration,
+    phase_offset: Duration,
+    /// Fixed window boundary, if not aligned to the creation
+    anchor: Option<Duration>,
+    /// Tokens that may roll over into the next window
+    max_carry: u64,
+    /// Capacity of each window by index, overriding the configured capacity
+    schedule: Option<S>,
+    monitor: ClockMonitor,
+}
+
+impl FixedWindowCore {
+    /// Resume from a snapshot, see [`FixedWindow::restore`]
+    pub fn restore(snapshot: FixedWindowSnapshot) -> Self {
+        Self::from_snapshot(snapshot, None)
+    }
+
+    /// Resume with a capacity schedule, see [`FixedWindow::restore_with_schedule`]
+    pub fn restore_with_schedule<F>(
+        snapshot: FixedWindowSnapshot,
+        schedule: F,
+    ) -> FixedWindowCore<F>
+    where
+        F: CapacitySchedule,
+    {
+        FixedWindowCore::from_snapshot(snapshot, Some(schedule))
+    }
+
+    /// Initialize a new fixed window state machine
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width_ms` - window width in milliseconds
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    ///
+    /// # Panics
+    /// Consumes panic on a division by zero if `window_width_ms` is zero,
+    /// use [`Self::try_new`] to validate the configuration.
+    pub fn new(capacity: u64, window_width_ms: u64, now: Duration) -> Self {
+        Self::from_width(capacity, Duration::from_millis(window_width_ms), now)
+    }
+
+    /// Initialize a new fixed window state machine with the window width given as a [`Duration`]
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width` - window width, may be shorter than a millisecond
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    ///
+    /// # Panics
+    /// Consumes panic on a division by zero if `window_width` is zero
+    pub fn from_width(capacity: u64, window_width: Duration, now: Duration) -> Self {
+        Self {
+            config: FixedWindowParams::new(capacity, window_width),
+            tokens: capacity,
+            window_index: 0,
+            start_time: now,
+            phase_offset: Duration::ZERO,
+            anchor: None,
+            max_carry: 0,
+            schedule: None,
+            monitor: ClockMonitor::new(now),
+        }
+    }
+
+    /// Validating constructor, see [`FixedWindow::try_new_with_time_provider`]
+    pub fn try_new(
+        capacity: u64,
+        window_width_ms: u64,
+        now: Duration,
+    ) -> Result<Self, ConfigError> {
+        if window_width_ms == 0 {
+            return Err(ConfigError::ZeroWindow);
+        }
+        if capacity == 0 {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        Ok(Self::new(capacity, window_width_ms, now))
+    }
+}
+
+impl<S> FixedWindowCore<S>
+where
+    S: CapacitySchedule,
+{
+    /// Return to the freshly constructed state at the given time
+    ///
+    /// The configuration, including the name, is kept.
+    pub fn reset_at(&mut self, now: Duration) {
+        self.start_time = self.anchor.unwrap_or(now);
+        self.window_index = self.config.window_index(self.phased_delta(now));
+        self.tokens = self.capacity_of(self.window_index);
+        self.monitor.reset(now);
+    }
+
+    /// Assign a name to this limiter, see [`FixedWindow::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.config.name = Some(name);
+        self
+    }
+
+    /// Take a plain-data snapshot, see [`FixedWindow::snapshot`]
+    pub fn snapshot(&self) -> FixedWindowSnapshot {
+        FixedWindowSnapshot {
+            capacity: self.config.capacity,
+            window_width: self.config.width,
+            tokens: self.tokens,
+            window_index: self.window_index,
+            start_time: self.start_time,
+            phase_offset: self.phase_offset,
+            anchor: self.anchor,
+            max_carry: self.max_carry,
+            last_update: self.monitor.latest(),
+        }
+    }
+
+    /// Rebuild a state machine from a snapshot and its capacity schedule
+    fn from_snapshot(snapshot: FixedWindowSnapshot, schedule: Option<S>) -> Self {
+        Self {
+            config: FixedWindowParams::new(snapshot.capacity, snapshot.window_width),
+            tokens: snapshot.tokens,
+            window_index: snapshot.window_index,
+            start_time: snapshot.start_time,
+            phase_offset: snapshot.phase_offset,
+            anchor: snapshot.anchor,
+            max_carry: snapshot.max_carry,
+            schedule,
+            monitor: ClockMonitor::new(snapshot.last_update),
+        }
+    }
+
+    /// Shift the window boundaries, see [`FixedWindow::with_phase_offset`]
+    pub fn with_phase_offset(mut self, offset: Duration) -> Self {
+        let width_ns = self.window_width().as_nanos().max(1);
+        self.phase_offset = Duration::from_nanos((offset.as_nanos() % width_ns) as u64);
+        self.rebase();
+        self
+    }
+
+    /// Align the window boundaries, see [`FixedWindow::with_aligned_windows`]
+    pub fn with_aligned_windows(mut self, anchor: Duration) -> Self {
+        self.anchor = Some(anchor);
+        self.start_time = anchor;
+        self.rebase();
+        self
+    }
+
+    /// Take window capacities from a schedule, see [`FixedWindow::with_capacity_schedule`]
+    pub fn with_capacity_schedule<F>(self, schedule: F) -> FixedWindowCore<F>
+    where
+        F: CapacitySchedule,
+    {
+        let used = self
+            .capacity_of(self.window_index)
+            .saturating_sub(self.tokens);
+        let mut core = FixedWindowCore {
+            config: self.config,
+            tokens: 0,
+            window_index: self.window_index,
+            start_time: self.start_time,
+            phase_offset: self.phase_offset,
+            anchor: self.anchor,
+            max_carry: self.max_carry,
+            schedule: Some(schedule),
+            monitor: self.monitor,
+        };
+        core.tokens = core.capacity_of(core.window_index).saturating_sub(used);
+        core
+    }
+
+    /// Move the usage of the first window over to the window containing the
+    /// latest time, after the window boundaries were changed
+    fn rebase(&mut self) {
+        let used = self
+            .capacity_of(self.window_index)
+            .saturating_sub(self.tokens);
+        self.window_index = self
+            .config
+            .window_index(self.phased_delta(self.monitor.latest()));
+        self.tokens = self.capacity_of(self.window_index).saturating_sub(used);
+    }
+
+    /// Start with tokens already used, see [`FixedWindow::with_initial_usage`]
+    pub fn with_initial_usage(mut self, used: u64) -> Self {
+        self.tokens = self.capacity_of(self.window_index).saturating_sub(used);
+        self
+    }
+
+    /// Roll unused tokens over, see [`FixedWindow::with_carry_over`]
+    pub fn with_carry_over(mut self, max_carry: u64) -> Self {
+        self.max_carry = max_carry;
+        self
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.config.capacity
+    }
+
+    /// Configured window width
+    pub fn window_width(&self) -> Duration {
+        self.config.width
+    }
+
+    /// Tokens that could be consumed at `now`, see [`Inspect::tokens_available`]
+    pub fn tokens_available_at(&self, now: Duration) -> u64 {
+        self.tokens_in(self.window_index_at(now))
+    }
+
+    /// Capacity of the window containing `now`, see [`FixedWindow::window_capacity`]
+    pub fn window_capacity_at(&self, now: Duration) -> u64 {
+        self.capacity_of(self.window_index_at(now))
+    }
+
+    /// Capacity of the window `index`
+    fn capacity_of(&self, index: u64) -> u64 {
+        self.schedule
+            .as_ref()
+            .map_or(self.config.capacity, |schedule| schedule.capacity_of(index))
+    }
+
+    /// Tokens left in the window `index`, replenished if it is a new one
+    fn tokens_in(&self, index: u64) -> u64 {
+        self.checked_tokens_in(index).unwrap_or(u64::MAX)
+    }
+
+    /// Tokens left in the window `index`, `None` if the carry-over overflows
+    fn checked_tokens_in(&self, index: u64) -> Option<u64> {
+        if index <= self.window_index {
+            return Some(self.tokens);
+        }
+        if self.max_carry == 0 {
+            return Some(self.capacity_of(index));
+        }
+        // Windows skipped in between were left unused, each one carrying its
+        // tokens over into the next. After a long idle period, start from a
+        // window left unused entirely.
+        let (mut window, mut available) = if index - self.window_index <= SCHEDULE_HORIZON {
+            (self.window_index, self.tokens)
+        } else {
+            let window = index.saturating_sub(SCHEDULE_HORIZON.min(index).max(1));
+            (window, self.capacity_of(window))
+        };
+        while window < index {
+            window += 1;
+            let carried = available.min(self.max_carry);
+            let following = self.capacity_of(window).checked_add(carried)?;
+            if self.schedule.is_none() && following == available {
+                // The carry-over settled
+                break;
+            }
+            available = following;
+        }
+        Some(available)
+    }
+
+    /// Change the capacity, keeping the usage of the current window
+    pub fn set_capacity(&mut self, capacity: u64) {
+        let used = self
+            .capacity_of(self.window_index)
+            .saturating_sub(self.tokens);
+        self.config.capacity = capacity;
+        self.tokens = self.capacity_of(self.window_index).saturating_sub(used);
+    }
+
+    /// Return tokens of an admitted consume to the latest window, see [`Refund::refund`]
+    pub fn refund(&mut self, tokens: u64) {
+        let max_tokens = self
+            .capacity_of(self.window_index)
+            .saturating_add(self.max_carry);
+        self.tokens = self
+            .monitor
+            .saturating_add(self.tokens, tokens)
+            .min(max_tokens);
+    }
+
+    /// State of the window at `now`, see [`FixedWindow::state`]
+    pub fn state_at(&self, now: Duration) -> FixedWindowState {
+        FixedWindowState {
+            tokens: self.tokens_available_at(now),
+            capacity: self.window_capacity_at(now),
+            window_width: self.window_width(),
+            window_start: now.saturating_sub(self.window_elapsed_at(now)),
+        }
+    }
+
+    /// Time left at `now` until the current window ends and the budget is replenished
+    pub fn time_until_reset_at(&self, now: Duration) -> Duration {
+        self.window_width() - self.window_elapsed_at(now)
+    }
+
+    /// How far through the window containing `now` we are
+    pub fn window_elapsed_at(&self, now: Duration) -> Duration {
+        let width_ns = self.window_width().as_nanos().max(1);
+        let delta_ns = self.phased_delta(now).as_nanos();
+        Duration::from_nanos((delta_ns % width_ns) as u64)
+    }
+
+    /// Time until `tokens` could be consumed at `now`, see [`RetryHint::time_until_available`]
+    pub fn time_until_available_at(&self, now: Duration, tokens: u64) -> Option<Duration> {
+        if self.tokens_available_at(now) >= tokens {
+            return Some(Duration::ZERO);
+        }
+        // Simulate the windows ahead being left unused, each one carrying its
+        // tokens over into the next. Without a schedule the windows stop
+        // differing once the carry-over settles.
+        let index = self.window_index_at(now);
+        let mut available = self.tokens_in(index + 1);
+        let mut next = 1;
+        while available < tokens {
+            let carried = available.min(self.max_carry);
+            let following = self.capacity_of(index + next + 1).saturating_add(carried);
+            if next == SCHEDULE_HORIZON || (self.schedule.is_none() && following == available) {
+                return None;
+            }
+            available = following;
+            next += 1;
+        }
+        // Windows far enough ahead may not fit in a Duration
+        let ahead = self.window_width().checked_mul((next - 1) as u32)?;
+        self.time_until_reset_at(now).checked_add(ahead)
+    }
+
+    /// Timestamp of the most recent consume attempt, see [`Limiter::last_consume_at`]
+    pub fn last_consume_at(&self) -> Option<Duration> {
+        self.monitor.last_consume_at()
+    }
+
+    /// Idle time at `now`, see [`Limiter::idle_for`]
+    pub fn idle_for_at(&self, now: Duration) -> Duration {
+        self.monitor.idle_for(now)
+    }
+
+    /// Run the self-check of [`Limiter::self_check`] at the given time
+    pub fn self_check_at(&self, now: Duration) -> Diagnostics {
+        self.monitor.diagnose(
+            now,
+            self.config.capacity != 0 && !self.config.width.is_zero(),
+        )
+    }
+
+    /// Index of the window containing `now`, never before the latest window consumed from
+    fn window_index_at(&self, now: Duration) -> u64 {
+        self.config
+            .window_index(self.phased_delta(now))
+            .max(self.window_index)
+    }
+
+    /// Time elapsed since the start of the first window
+    fn phased_delta(&self, now: Duration) -> Duration {
+        now.saturating_sub(self.start_time) + self.phase_offset
+    }
+
+    /// Consume at the given time, returning the remaining budget
+    pub(crate) fn try_consume_at(
+        &mut self,
+        now: Duration,
+        tokens: u64,
+    ) -> Result<u64, CantConsume> {
+        self.monitor.observe(now);
+        // Get current window index
+        let index = self.config.window_index(self.phased_delta(now));
+
+        // A clock stepping back into an earlier window is flagged by the
+        // monitor, and the current budget is kept instead of replenished
+        if index > self.window_index {
+            // New window. Replenish tokens, carrying over unused ones if configured.
+            self.tokens = self.checked_tokens_in(index).unwrap_or_else(|| {
+                self.monitor.saturate();
+                u64::MAX
+            });
+            self.window_index = index;
+        }
+
+        self.tokens = self.tokens.checked_sub(tokens).ok_or(CantConsume)?;
+        Ok(self.tokens)
+    }
+}
+
+impl<S> LimiterCore for FixedWindowCore<S>
+where
+    S: CapacitySchedule,
+{
+    fn handle(&mut self, now: Duration, request: Request) -> Response {
+        match request {
+            Request::Consume(tokens) => self.try_consume_at(now, tokens).into(),
+        }
+    }
+}
+
+/// Fixed window -type rate limiter with a multi-window burst cap
+///
+/// Works like [`FixedWindow`], but additionally limits the amount of tokens
+/// consumed during the last `K` windows combined, e.g. max 100 per minute
+/// *and* max 4000 per hour. Both limits share the same clock and window
+/// bookkeeping, so a consume either counts against both or neither.
+///
+/// The algorithm itself is implemented by [`FixedMultiWindowCore`], this
+/// type only pairs it with a time provider.
+///
+/// # Generic arguments
+/// * `K` - Number of windows covered by the long cap, including the current one. Must be nonzero.
+#[derive(Debug, Clone)]
+pub struct FixedMultiWindow<T, const K: usize>
+where
+    T: Clock,
+{
+    core: FixedMultiWindowCore<K>,
+    time_provider: T,
+}
+
+impl<T, const K: usize> FixedMultiWindow<T, K>
+where
+    T: Clock,
+{
+    /// Initialize a new multi-window fixed window limiter utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width_ms` - window width in milliseconds
+    /// * `long_capacity` - how many consumes are allowed during the last `K` windows combined
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`fixed_multi_window`]
+    ///
+    /// # Panics
+    /// Consumes panic on a division by zero if `window_width_ms` or `K` is zero,
+    /// use [`Self::try_new_with_time_provider`] to validate the configuration.
+    pub fn new_with_time_provider(
+        capacity: u64,
+        window_width_ms: u64,
+        long_capacity: u64,
+        time_provider: T,
+    ) -> Self {
+        Self::from_width_with_time_provider(
+            capacity,
+            Duration::from_millis(window_width_ms),
+            long_capacity,
+            time_provider,
+        )
+    }
+
+    /// Initialize a new multi-window fixed window limiter with the window width
+    /// given as a [`Duration`] utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width` - window width, may be shorter than a millisecond
+    /// * `long_capacity` - how many consumes are allowed during the last `K` windows combined
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use
+    /// [`fixed_multi_window_from_width`]
+    pub fn from_width_with_time_provider(
+        capacity: u64,
+        window_width: Duration,
+        long_capacity: u64,
+        time_provider: T,
+    ) -> Self {
+        let time_now = time_provider.now();
+        Self {
+            core: FixedMultiWindowCore::from_width(capacity, window_width, long_capacity, time_now),
+            time_provider,
+        }
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting invalid configurations
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroLength`] if `K` is zero, [`ConfigError::ZeroWindow`] or
+    /// [`ConfigError::ZeroCapacity`] for zero arguments
+    pub fn try_new_with_time_provider(
+        capacity: u64,
+        window_width_ms: u64,
+        long_capacity: u64,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        let time_now = time_provider.now();
+        Ok(Self {
+            core: FixedMultiWindowCore::try_new(
+                capacity,
+                window_width_ms,
+                long_capacity,
+                time_now,
+            )?,
+            time_provider,
+        })
+    }
+
+    /// Assign a name to this limiter, see [`Limiter::name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.core = self.core.with_name(name);
+        self
+    }
+
+    /// Configured capacity of a single window
+    pub fn capacity(&self) -> u64 {
+        self.core.capacity()
+    }
+
+    /// Configured capacity of the last `K` windows combined
+    pub fn long_capacity(&self) -> u64 {
+        self.core.long_capacity()
+    }
+
+    /// Configured window width
+    pub fn window_width(&self) -> Duration {
+        self.core.window_width()
+    }
+
+    /// Time left until the current window ends and the budget of a single window is replenished
+    pub fn time_until_reset(&self) -> Duration {
+        let now = self.time_provider.now();
+        self.core.time_until_reset_at(now)
+    }
+
+    /// How far through the current window we are
+    pub fn window_elapsed(&self) -> Duration {
+        let now = self.time_provider.now();
+        self.core.window_elapsed_at(now)
+    }
+
+    /// Current state of the window, e.g. for dashboards and debug logs
+    pub fn state(&self) -> FixedWindowState {
+        let now = self.time_provider.now();
+        self.core.state_at(now)
+    }
+
+    /// Get a reference to the underlying sans-IO state machine
+    pub fn core(&self) -> &FixedMultiWindowCore<K> {
+        &self.core
+    }
+
+    /// Unwrap the underlying sans-IO state machine, dropping the time provider
+    pub fn into_core(self) -> FixedMultiWindowCore<K> {
+        self.core
+    }
+
+    /// Take a plain-data snapshot of the limiter state
+    ///
+    /// The snapshot can be stashed e.g. in battery-backed RAM and later passed
+    /// to [`Self::restore`], without requiring `serde`.
+    pub fn snapshot(&self) -> FixedMultiWindowSnapshot<K> {
+        self.core.snapshot()
+    }
+
+    /// Resume a limiter from a snapshot taken with [`Self::snapshot`]
+    ///
+    /// The time provider must share the epoch of the one the snapshot was
+    /// taken with, e.g. a real-time clock kept running during deep sleep.
+    /// Time elapsed in between is accounted for like it had been running.
+    /// The name is not part of the snapshot, see [`Self::with_name`].
+    pub fn restore(snapshot: FixedMultiWindowSnapshot<K>, time_provider: T) -> Self {
+        Self {
+            core: FixedMultiWindowCore::restore(snapshot),
+            time_provider,
+        }
+    }
+}
+
+impl<T, const K: usize> Limiter for FixedMultiWindow<T, K>
+where
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens).map(|_| ())
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        let now = self.time_provider.now();
+        costs
+            .iter()
+            .take_while(|&&tokens| self.core.try_consume_at(now, tokens).is_ok())
+            .count()
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::FixedMultiWindow
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.core.config.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.time_provider.now();
+        self.core.self_check_at(now)
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.core.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.time_provider.now();
+        Some(self.core.idle_for_at(now))
+    }
+
+    fn reset(&mut self) {
+        let now = self.time_provider.now();
+        self.core.reset_at(now);
+    }
+}
+
+impl<T, const K: usize> Reset for FixedMultiWindow<T, K> where T: Clock {}
+
+impl<T, const K: usize> RetryHint for FixedMultiWindow<T, K>
+where
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.time_provider.now();
+        self.core.time_until_available_at(now, tokens)
+    }
+}
+
+impl<T, const K: usize> Inspect for FixedMultiWindow<T, K>
+where
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.tokens_available_at(now)
+    }
+}
+
+impl<T, const K: usize> RemainingBudget for FixedMultiWindow<T, K>
+where
+    T: Clock,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens)
+    }
+}
+
+/// Plain-data snapshot of a [`FixedMultiWindow`], see [`FixedMultiWindow::snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct FixedMultiWindowSnapshot<const K: usize> {
+    /// Configured capacity of a single window
+    pub capacity: u64,
+    /// Configured window width
+    pub window_width: Duration,
+    /// Configured capacity of the last `K` windows combined
+    pub long_capacity: u64,
+    /// Tokens used per window, indexed by window index modulo `K`
+    pub usage: [u64; K],
+    /// Index of the latest window consumed from, counted from `start_time`
+    pub window_index: u64,
+    /// Timestamp the windows are counted from
+    pub start_time: Duration,
+    /// Latest timestamp seen by the limiter
+    pub last_update: Duration,
+}
+
+/// Sans-IO multi-window fixed window state machine
+///
+/// Same algorithm as [`FixedMultiWindow`], but without a time provider: the
+/// current time is passed in with every [`LimiterCore::handle`] call.
+#[derive(Debug, Clone, Copy)]
+pub struct FixedMultiWindowCore<const K: usize> {
+    config: FixedWindowParams,
+    long_capacity: u64,
+    /// Tokens used per window, indexed by window index modulo `K`
+    usage: [u64; K],
+    window_index: u64,
+    start_time: Duration,
+    monitor: ClockMonitor,
+}
+
+impl<const K: usize> FixedMultiWindowCore<K> {
+    /// Initialize a new multi-window fixed window state machine
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width_ms` - window width in milliseconds
+    /// * `long_capacity` - how many consumes are allowed during the last `K` windows combined
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    ///
+    /// # Panics
+    /// Consumes panic on a division by zero if `window_width_ms` or `K` is zero,
+    /// use [`Self::try_new`] to validate the configuration.
+    pub fn new(capacity: u64, window_width_ms: u64, long_capacity: u64, now: Duration) -> Self {
+        Self::from_width(
+            capacity,
+            Duration::from_millis(window_width_ms),
+            long_capacity,
+            now,
+        )
+    }
+
+    /// Initialize a new multi-window fixed window state machine with the window
+    /// width given as a [`Duration`]
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width` - window width, may be shorter than a millisecond
+    /// * `long_capacity` - how many consumes are allowed during the last `K` windows combined
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    pub fn from_width(
+        capacity: u64,
+        window_width: Duration,
+        long_capacity: u64,
+        now: Duration,
+    ) -> Self {
+        Self {
+            config: FixedWindowParams::new(capacity, window_width),
+            long_capacity,
+            usage: [0; K],
+            window_index: 0,
+            start_time: now,
+            monitor: ClockMonitor::new(now),
+        }
+    }
+
+    /// Return to the freshly constructed state at the given time
+    ///
+    /// The configuration, including the name, is kept.
+    pub fn reset_at(&mut self, now: Duration) {
+        self.usage = [0; K];
+        self.window_index = 0;
+        self.start_time = now;
+        self.monitor.reset(now);
+    }
+
+    /// Take a plain-data snapshot, see [`FixedMultiWindow::snapshot`]
+    pub fn snapshot(&self) -> FixedMultiWindowSnapshot<K> {
+        FixedMultiWindowSnapshot {
+            capacity: self.config.capacity,
+            window_width: self.config.width,
+            long_capacity: self.long_capacity,
+            usage: self.usage,
+            window_index: self.window_index,
+            start_time: self.start_time,
+            last_update: self.monitor.latest(),
+        }
+    }
+
+    /// Resume from a snapshot, see [`FixedMultiWindow::restore`]
+    pub fn restore(snapshot: FixedMultiWindowSnapshot<K>) -> Self {
+        Self {
+            config: FixedWindowParams::new(snapshot.capacity, snapshot.window_width),
+            long_capacity: snapshot.long_capacity,
+            usage: snapshot.usage,
+            window_index: snapshot.window_index,
+            start_time: snapshot.start_time,
+            monitor: ClockMonitor::new(snapshot.last_update),
+        }
+    }
+
+    /// Validating constructor, see [`FixedMultiWindow::try_new_with_time_provider`]
+    pub fn try_new(
+        capacity: u64,
+        window_width_ms: u64,
+        long_capacity: u64,
+        now: Duration,
+    ) -> Result<Self, ConfigError> {
+        if K == 0 {
+            return Err(ConfigError::ZeroLength);
+        }
+        if window_width_ms == 0 {
+            return Err(ConfigError::ZeroWindow);
+        }
+        if capacity == 0 || long_capacity == 0 {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        Ok(Self::new(capacity, window_width_ms, long_capacity, now))
+    }
+
+    /// Assign a name to this limiter, see [`FixedMultiWindow::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.config.name = Some(name);
+        self
+    }
+
+    /// Configured capacity of a single window
+    pub fn capacity(&self) -> u64 {
+        self.config.capacity
+    }
+
+    /// Configured capacity of the last `K` windows combined
+    pub fn long_capacity(&self) -> u64 {
+        self.long_capacity
+    }
+
+    /// Configured window width
+    pub fn window_width(&self) -> Duration {
+        self.config.width
+    }
+
+    /// Tokens that could be consumed at `now`, see [`Inspect::tokens_available`]
+    pub fn tokens_available_at(&self, now: Duration) -> u64 {
+        let index = self.window_index_at(now);
+        // Windows older than K windows are forgotten on the next consume
+        let usage = self.usage_at(index);
+        let used_long = usage.iter().sum::<u64>();
+        let used = usage[(index % K as u64) as usize];
+        (self.config.capacity.saturating_sub(used))
+            .min(self.long_capacity.saturating_sub(used_long))
+    }
+
+    /// Usage per window slot as seen from window `index`
+    ///
+    /// Windows older than `K` windows are forgotten on the next consume.
+    fn usage_at(&self, index: u64) -> [u64; K] {
+        let skipped = index.saturating_sub(self.window_index).min(K as u64);
+        let mut usage = self.usage;
+        for i in 1..=skipped {
+            usage[((self.window_index + i) % K as u64) as usize] = 0;
+        }
+        usage
+    }
+
+    /// Time until `tokens` could be consumed at `now`, see [`RetryHint::time_until_available`]
+    pub fn time_until_available_at(&self, now: Duration, tokens: u64) -> Option<Duration> {
+        if tokens > self.config.capacity || tokens > self.long_capacity {
+            return None;
+        }
+        if self.tokens_available_at(now) >= tokens {
+            return Some(Duration::ZERO);
+        }
+
+        let index = self.window_index_at(now);
+        let usage = self.usage_at(index);
+        // `j` windows ahead only the windows younger than `K - j` are remembered
+        (1..=K as u64)
+            .find(|&j| {
+                let used_long = (0..K as u64 - j)
+                    .filter_map(|age| index.checked_sub(age))
+                    .map(|window| usage[(window % K as u64) as usize])
+                    .sum::<u64>();
+                used_long.saturating_add(tokens) <= self.long_capacity
+            })
+            .map(|j| self.window_start(index + j).saturating_sub(now))
+    }
+
+    /// Time left at `now` until the current window ends and the budget of a
+    /// single window is replenished
+    pub fn time_until_reset_at(&self, now: Duration) -> Duration {
+        self.config.width - self.window_elapsed_at(now)
+    }
+
+    /// How far through the window containing `now` we are
+    pub fn window_elapsed_at(&self, now: Duration) -> Duration {
+        let width_ns = self.config.width.as_nanos().max(1);
+        let delta_ns = now.saturating_sub(self.start_time).as_nanos();
+        Duration::from_nanos((delta_ns % width_ns) as u64)
+    }
+
+    /// Index of the window containing `now`, never before the latest window consumed from
+    fn window_index_at(&self, now: Duration) -> u64 {
+        self.config
+            .window_index(now.saturating_sub(self.start_time))
+            .max(self.window_index)
+    }
+
+    /// Start time of the window with the given index
+    fn window_start(&self, index: u64) -> Duration {
+        self.start_time + self.config.window_offset(index)
+    }
+
+    /// State of the windows at `now`, see [`FixedMultiWindow::state`]
+    ///
+    /// The tokens are limited by the capacity of the last `K` windows as well.
+    pub fn state_at(&self, now: Duration) -> FixedWindowState {
+        let index = self.window_index_at(now);
+        FixedWindowState {
+            tokens: self.tokens_available_at(now),
+            capacity: self.config.capacity,
+            window_width: self.config.width,
+            window_start: self.window_start(index),
+        }
+    }
+
+    /// Timestamp of the most recent consume attempt, see [`Limiter::last_consume_at`]
+    pub fn last_consume_at(&self) -> Option<Duration> {
+        self.monitor.last_consume_at()
+    }
+
+    /// Idle time at `now`, see [`Limiter::idle_for`]
+    pub fn idle_for_at(&self, now: Duration) -> Duration {
+        self.monitor.idle_for(now)
+    }
+
+    /// Run the self-check of [`Limiter::self_check`] at the given time
+    pub fn self_check_at(&self, now: Duration) -> Diagnostics {
+        self.monitor.diagnose(
+            now,
+            self.config.capacity != 0
+                && !self.config.width.is_zero()
+                && K != 0
+                && self.long_capacity != 0,
+        )
+    }
+
+    /// Consume at the given time, returning the remaining budget
+    pub(crate) fn try_consume_at(
+        &mut self,
+        now: Duration,
+        tokens: u64,
+    ) -> Result<u64, CantConsume> {
+        self.monitor.observe(now);
+        // Get current window index, a clock stepping back into an earlier
+        // window is flagged by the monitor and keeps counting in the latest one
+        let index = self.window_index_at(now);
+
+        if index != self.window_index {
+            // New window. Forget the usage of windows that are now older than K windows.
+            let skipped = index.saturating_sub(self.window_index).min(K as u64);
+            for i in 1..=skipped {
+                self.usage[((self.window_index + i) % K as u64) as usize] = 0;
+            }
+            self.window_index = index;
+        }
+
+        let slot = (index % K as u64) as usize;
+        let used_long = self.usage.iter().sum::<u64>();
+        if self.usage[slot].saturating_add(tokens) > self.config.capacity
+            || used_long.saturating_add(tokens) > self.long_capacity
+        {
+            return Err(CantConsume);
+        }
+
+        self.usage[slot] += tokens;
+        Ok((self.config.capacity - self.usage[slot]).min(self.long_capacity - used_long - tokens))
+    }
+}
+
+impl<const K: usize> LimiterCore for FixedMultiWindowCore<K> {
+    fn handle(&mut self, now: Duration, request: Request) -> Response {
+        match request {
+            Request::Consume(tokens) => self.try_consume_at(now, tokens).into(),
+        }
+    }
+}
+
+/// Fixed window -type rate limiter with a repeating capacity schedule
+///
+/// Works like [`FixedWindow`], but the capacity of each window is taken from
+/// a schedule that repeats every `N` windows. E.g. the schedule
+/// `[100, 100, 0, 0]` allows bursts during two windows followed by two quiet
+/// windows, as required for radio duty-cycling.
+///
+/// The algorithm itself is implemented by [`ScheduledFixedWindowCore`], this
+/// type only pairs it with a time provider.
+///
+/// # Generic arguments
+/// * `N` - Length of the schedule in windows. An empty schedule never admits
+///   anything, see [`ScheduledFixedWindow::try_new_with_time_provider`].
+#[derive(Debug, Clone)]
+pub struct ScheduledFixedWindow<T, const N: usize>
+where
+    T: Clock,
+{
+    core: ScheduledFixedWindowCore<N>,
+    time_provider: T,
+}
+
+impl<T, const N: usize> ScheduledFixedWindow<T, N>
+where
+    T: Clock,
+{
+    /// Initialize a new scheduled fixed window limiter utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `schedule` - capacities of successive windows, repeated indefinitely
+    /// * `window_width_ms` - window width in milliseconds
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`scheduled_fixed_window`]
+    ///
+    /// # Panics
+    /// Consumes panic on a division by zero if `window_width_ms` is zero,
+    /// use [`Self::try_new_with_time_provider`] to validate the configuration.
+    pub fn new_with_time_provider(
+        schedule: [u64; N],
+        window_width_ms: u64,
+        time_provider: T,
+    ) -> Self {
+        Self::from_width_with_time_provider(
+            schedule,
+            Duration::from_millis(window_width_ms),
+            time_provider,
+        )
+    }
+
+    /// Initialize a new scheduled fixed window limiter with the window width
+    /// given as a [`Duration`] utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `schedule` - capacities of successive windows, repeated indefinitely
+    /// * `window_width` - window width, may be shorter than a millisecond
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use
+    /// [`scheduled_fixed_window_from_width`]
+    ///
+    /// # Panics
+    /// Consumes panic on a division by zero if `window_width` is zero
+    pub fn from_width_with_time_provider(
+        schedule: [u64; N],
+        window_width: Duration,
+        time_provider: T,
+    ) -> Self {
+        let time_now = time_provider.now();
+        Self {
+            core: ScheduledFixedWindowCore::from_width(schedule, window_width, time_now),
+            time_provider,
+        }
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting invalid configurations
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroLength`] for an empty schedule, [`ConfigError::ZeroWindow`]
+    /// for a zero window width or [`ConfigError::ZeroCapacity`] if no window of
+    /// the schedule admits anything
+    pub fn try_new_with_time_provider(
+        schedule: [u64; N],
+        window_width_ms: u64,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        let time_now = time_provider.now();
+        Ok(Self {
+            core: ScheduledFixedWindowCore::try_new(schedule, window_width_ms, time_now)?,
+            time_provider,
+        })
+    }
+
+    /// Assign a name to this limiter, see [`Limiter::name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.core = self.core.with_name(name);
+        self
+    }
+
+    /// Highest capacity of any window in the schedule
+    pub fn capacity(&self) -> u64 {
+        self.core.capacity()
+    }
+
+    /// Configured capacity schedule
+    pub fn schedule(&self) -> &[u64; N] {
+        self.core.schedule()
+    }
+
+    /// Configured window width
+    pub fn window_width(&self) -> Duration {
+        self.core.window_width()
+    }
+
+    /// Time left until the current window ends and the budget is replenished
+    pub fn time_until_reset(&self) -> Duration {
+        let now = self.time_provider.now();
+        self.core.time_until_reset_at(now)
+    }
+
+    /// How far through the current window we are
+    pub fn window_elapsed(&self) -> Duration {
+        let now = self.time_provider.now();
+        self.core.window_elapsed_at(now)
+    }
+
+    /// Current state of the window, e.g. for dashboards and debug logs
+    pub fn state(&self) -> FixedWindowState {
+        let now = self.time_provider.now();
+        self.core.state_at(now)
+    }
+
+    /// Get a reference to the underlying sans-IO state machine
+    pub fn core(&self) -> &ScheduledFixedWindowCore<N> {
+        &self.core
+    }
+
+    /// Unwrap the underlying sans-IO state machine, dropping the time provider
+    pub fn into_core(self) -> ScheduledFixedWindowCore<N> {
+        self.core
+    }
+
+    /// Take a plain-data snapshot of the limiter state
+    ///
+    /// The snapshot can be stashed e.g. in battery-backed RAM and later passed
+    /// to [`Self::restore`], without requiring `serde`.
+    pub fn snapshot(&self) -> ScheduledFixedWindowSnapshot<N> {
+        self.core.snapshot()
+    }
+
+    /// Resume a limiter from a snapshot taken with [`Self::snapshot`]
+    ///
+    /// The time provider must share the epoch of the one the snapshot was
+    /// taken with, e.g. a real-time clock kept running during deep sleep.
+    /// Time elapsed in between is accounted for like it had been running.
+    /// The name is not part of the snapshot, see [`Self::with_name`].
+    pub fn restore(snapshot: ScheduledFixedWindowSnapshot<N>, time_provider: T) -> Self {
+        Self {
+            core: ScheduledFixedWindowCore::restore(snapshot),
+            time_provider,
+        }
+    }
+}
+
+impl<T, const N: usize> Limiter for ScheduledFixedWindow<T, N>
+where
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens).map(|_| ())
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        let now = self.time_provider.now();
+        costs
+            .iter()
+            .take_while(|&&tokens| self.core.try_consume_at(now, tokens).is_ok())
+            .count()
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::ScheduledFixedWindow
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.core.core.config.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.time_provider.now();
+        self.core.self_check_at(now)
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.core.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.time_provider.now();
+        Some(self.core.idle_for_at(now))
+    }
+
+    fn reset(&mut self) {
+        let now = self.time_provider.now();
+        self.core.reset_at(now);
+    }
 }
 
-/// Fixed window -type rate limiter
-///
-/// A Fixed window limiter splits the timeline into time windows
-/// of defined size and allocates a certain amount of tokens for
-/// each window. Consumes are successfull as long as the current
-/// time window still holds enought tokens.
-pub struct FixedWindow<T>
+impl<T, const N: usize> Reset for ScheduledFixedWindow<T, N> where T: Clock {}
+
+impl<T, const N: usize> RetryHint for ScheduledFixedWindow<T, N>
 where
-    T: Fn() -> Duration,
+    T: Clock,
 {
-    config: FixedWindowConfig<T>,
-    tokens: u64,
-    window_index: u64,
-    start_time: Duration,
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.time_provider.now();
+        self.core.time_until_available_at(now, tokens)
+    }
 }
 
-impl<T> FixedWindow<T>
+impl<T, const N: usize> Inspect for ScheduledFixedWindow<T, N>
 where
-    T: Fn() -> Duration,
+    T: Clock,
 {
-    /// Initialize a new fixed window limiter utilizing the given timer
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.tokens_available_at(now)
+    }
+}
+
+impl<T, const N: usize> RemainingBudget for ScheduledFixedWindow<T, N>
+where
+    T: Clock,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens)
+    }
+}
+
+/// Plain-data snapshot of a [`ScheduledFixedWindow`], see [`ScheduledFixedWindow::snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ScheduledFixedWindowSnapshot<const N: usize> {
+    /// Capacities of successive windows, repeated indefinitely
+    pub schedule: [u64; N],
+    /// Snapshot of the window state
+    pub window: FixedWindowSnapshot,
+}
+
+/// Sans-IO scheduled fixed window state machine
+///
+/// Same algorithm as [`ScheduledFixedWindow`], but without a time provider:
+/// the current time is passed in with every [`LimiterCore::handle`] call.
+///
+/// Built on the capacity schedule support of [`FixedWindowCore`].
+#[derive(Debug, Clone, Copy)]
+pub struct ScheduledFixedWindowCore<const N: usize> {
+    core: FixedWindowCore<RepeatingSchedule<N>>,
+}
+
+impl<const N: usize> ScheduledFixedWindowCore<N> {
+    /// Initialize a new scheduled fixed window state machine
     ///
     /// # Arguments
-    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `schedule` - capacities of successive windows, repeated indefinitely
     /// * `window_width_ms` - window width in milliseconds
-    /// * `time_provider_t` - closure that returns a monotonically nondecreasing
-    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    pub fn new(schedule: [u64; N], window_width_ms: u64, now: Duration) -> Self {
+        Self::from_width(schedule, Duration::from_millis(window_width_ms), now)
+    }
+
+    /// Initialize a new scheduled fixed window state machine with the window
+    /// width given as a [`Duration`]
     ///
-    /// If you are developing for a `std` target, you probably wish to use [`fixed_window`]
-    pub fn new_with_time_provider(capacity: u64, window_width_ms: u64, time_provider: T) -> Self {
-        let time_now = time_provider();
-        let config = FixedWindowConfig::new(capacity, window_width_ms, time_provider);
+    /// # Arguments
+    /// * `schedule` - capacities of successive windows, repeated indefinitely
+    /// * `window_width` - window width, may be shorter than a millisecond
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    pub fn from_width(schedule: [u64; N], window_width: Duration, now: Duration) -> Self {
+        let peak = schedule.iter().copied().max().unwrap_or(0);
         Self {
-            config,
-            tokens: capacity,
-            window_index: 0,
-            start_time: time_now,
+            core: FixedWindowCore::from_width(peak, window_width, now)
+                .with_capacity_schedule(RepeatingSchedule(schedule)),
         }
     }
-}
 
-impl<T> Limiter for FixedWindow<T>
-where
-    T: Fn() -> Duration,
-{
-    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
-        // Get current window index
-        let now = (self.config.time_provider)();
-        let delta_t = now.saturating_sub(self.start_time);
-        let index = delta_t.as_millis() as u64 / self.config.width_ms;
+    /// Validating constructor, see [`ScheduledFixedWindow::try_new_with_time_provider`]
+    pub fn try_new(
+        schedule: [u64; N],
+        window_width_ms: u64,
+        now: Duration,
+    ) -> Result<Self, ConfigError> {
+        if N == 0 {
+            return Err(ConfigError::ZeroLength);
+        }
+        if window_width_ms == 0 {
+            return Err(ConfigError::ZeroWindow);
+        }
+        if schedule.iter().all(|&capacity| capacity == 0) {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        Ok(Self::new(schedule, window_width_ms, now))
+    }
 
-        if index != self.window_index {
-            // New window. Replenish tokens.
-            self.tokens = self.config.capacity;
-            self.window_index = index;
+    /// Return to the freshly constructed state at the given time
+    ///
+    /// The configuration, including the name, is kept.
+    pub fn reset_at(&mut self, now: Duration) {
+        self.core.reset_at(now);
+    }
+
+    /// Take a plain-data snapshot, see [`ScheduledFixedWindow::snapshot`]
+    pub fn snapshot(&self) -> ScheduledFixedWindowSnapshot<N> {
+        ScheduledFixedWindowSnapshot {
+            schedule: self.core.schedule.map_or([0; N], |schedule| schedule.0),
+            window: self.core.snapshot(),
         }
+    }
 
-        self.tokens = self.tokens.checked_sub(tokens).ok_or(CantConsume)?;
-        Ok(())
+    /// Resume from a snapshot, see [`ScheduledFixedWindow::restore`]
+    pub fn restore(snapshot: ScheduledFixedWindowSnapshot<N>) -> Self {
+        Self {
+            core: FixedWindowCore::from_snapshot(
+                snapshot.window,
+                Some(RepeatingSchedule(snapshot.schedule)),
+            ),
+        }
+    }
+
+    /// Assign a name to this limiter, see [`ScheduledFixedWindow::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.core = self.core.with_name(name);
+        self
+    }
+
+    /// Highest capacity of any window in the schedule
+    pub fn capacity(&self) -> u64 {
+        self.core.capacity()
+    }
+
+    /// Configured capacity schedule
+    pub fn schedule(&self) -> &[u64; N] {
+        let RepeatingSchedule(schedule) = self
+            .core
+            .schedule
+            .as_ref()
+            .expect("scheduled window has a schedule");
+        schedule
+    }
+
+    /// Configured window width
+    pub fn window_width(&self) -> Duration {
+        self.core.window_width()
+    }
+
+    /// Tokens that could be consumed at `now`, see [`Inspect::tokens_available`]
+    pub fn tokens_available_at(&self, now: Duration) -> u64 {
+        self.core.tokens_available_at(now)
+    }
+
+    /// Time until `tokens` could be consumed at `now`, see [`RetryHint::time_until_available`]
+    pub fn time_until_available_at(&self, now: Duration, tokens: u64) -> Option<Duration> {
+        if tokens > self.capacity() {
+            // No window of the schedule holds enough tokens
+            return None;
+        }
+        self.core.time_until_available_at(now, tokens)
+    }
+
+    /// State of the window at `now`, see [`ScheduledFixedWindow::state`]
+    pub fn state_at(&self, now: Duration) -> FixedWindowState {
+        self.core.state_at(now)
+    }
+
+    /// Time left at `now` until the current window ends and the budget is replenished
+    pub fn time_until_reset_at(&self, now: Duration) -> Duration {
+        self.core.time_until_reset_at(now)
+    }
+
+    /// How far through the window containing `now` we are
+    pub fn window_elapsed_at(&self, now: Duration) -> Duration {
+        self.core.window_elapsed_at(now)
+    }
+
+    /// Timestamp of the most recent consume attempt, see [`Limiter::last_consume_at`]
+    pub fn last_consume_at(&self) -> Option<Duration> {
+        self.core.last_consume_at()
+    }
+
+    /// Idle time at `now`, see [`Limiter::idle_for`]
+    pub fn idle_for_at(&self, now: Duration) -> Duration {
+        self.core.idle_for_at(now)
+    }
+
+    /// Run the self-check of [`Limiter::self_check`] at the given time
+    pub fn self_check_at(&self, now: Duration) -> Diagnostics {
+        self.core.self_check_at(now)
+    }
+
+    /// Consume at the given time, returning the remaining budget
+    pub(crate) fn try_consume_at(
+        &mut self,
+        now: Duration,
+        tokens: u64,
+    ) -> Result<u64, CantConsume> {
+        self.core.try_consume_at(now, tokens)
     }
 }
 
-/// Configuration for a fixed window limiter
-#[derive(Clone, Copy)]
-struct FixedWindowConfig<T>
-where
-    T: Fn() -> Duration,
-{
+impl<const N: usize> LimiterCore for ScheduledFixedWindowCore<N> {
+    fn handle(&mut self, now: Duration, request: Request) -> Response {
+        match request {
+            Request::Consume(tokens) => self.try_consume_at(now, tokens).into(),
+        }
+    }
+}
+
+/// Parameters of a fixed window limiter
+#[derive(Debug, Clone, Copy)]
+struct FixedWindowParams {
     capacity: u64,
-    width_ms: u64,
-    time_provider: T,
+    width: Duration,
+    name: Option<&'static str>,
 }
 
-impl<T: Fn() -> Duration> FixedWindowConfig<T> {
-    fn new(capacity: u64, width_ms: u64, time_provider: T) -> Self {
+impl FixedWindowParams {
+    fn new(capacity: u64, width: Duration) -> Self {
         Self {
             capacity,
-            width_ms,
-            time_provider,
+            width,
+            name: None,
         }
     }
+
+    /// Index of the window `delta` after the start of the first window
+    fn window_index(&self, delta: Duration) -> u64 {
+        (delta.as_nanos() / self.width.as_nanos()) as u64
+    }
+
+    /// Offset of the start of the window with the given index from the start of the first one
+    fn window_offset(&self, index: u64) -> Duration {
+        let offset_ns = self.width.as_nanos().saturating_mul(index as u128);
+        Duration::from_nanos(offset_ns.min(u64::MAX as u128) as u64)
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{mock_assets::MockClock, Limiter};
+    use core::time::Duration;
+
+    use crate::{mock_assets::MockClock, ConfigError, Inspect, Limiter, RetryHint};
+
+    use super::{
+        FixedMultiWindow, FixedMultiWindowCore, FixedWindow, FixedWindowCore, FixedWindowSnapshot,
+        FixedWindowState, ScheduledFixedWindow,
+    };
+
+    #[test]
+    fn verify_state() {
+        let clock = MockClock::new();
+        let mut w = FixedWindow::new_with_time_provider(5, 10, || clock.step(0));
+        clock.step(12_500);
+        assert!(w.try_consume(2).is_ok());
+        assert_eq!(
+            w.state(),
+            FixedWindowState {
+                tokens: 3,
+                capacity: 5,
+                window_width: Duration::from_millis(10),
+                window_start: Duration::from_millis(10),
+            }
+        );
+    }
+
+    #[test]
+    fn verify_snapshot_restore() {
+        let clock = MockClock::new();
+        let mut w = FixedWindow::new_with_time_provider(5, 10, || clock.step(0))
+            .with_carry_over(2)
+            .with_name("window");
+        clock.step(12_500);
+        assert!(w.try_consume(2).is_ok());
+        let snapshot = w.snapshot();
+        assert_eq!(
+            snapshot,
+            FixedWindowSnapshot {
+                capacity: 5,
+                window_width: Duration::from_millis(10),
+                tokens: 5,
+                window_index: 1,
+                start_time: Duration::ZERO,
+                phase_offset: Duration::ZERO,
+                anchor: None,
+                max_carry: 2,
+                last_update: Duration::from_micros(12_500),
+            }
+        );
+
+        let mut restored = FixedWindow::restore(snapshot, || clock.step(0));
+        assert_eq!(restored.name(), None);
+        assert_eq!(restored.state(), w.state());
+        assert!(restored.try_consume(6).is_err());
+        // The unused tokens carry over to the next window
+        clock.step(10_000);
+        assert!(restored.try_consume(7).is_ok());
+
+        let scheduled =
+            FixedWindow::restore_with_schedule(snapshot, |index| index, || clock.step(0));
+        assert_eq!(scheduled.state().capacity, 2);
+    }
+
+    #[test]
+    fn verify_snapshot_restore_multi_window() {
+        let clock = MockClock::new();
+        let mut m = FixedMultiWindow::<_, 2>::new_with_time_provider(5, 10, 8, || clock.step(0));
+        let mut s = ScheduledFixedWindow::new_with_time_provider([5, 1], 10, || clock.step(0));
+        assert!(m.try_consume(5).is_ok());
+        assert!(s.try_consume(4).is_ok());
+
+        let m_snapshot = m.snapshot();
+        assert_eq!(m_snapshot.usage, [5, 0]);
+        assert_eq!(m_snapshot.long_capacity, 8);
+        let s_snapshot = s.snapshot();
+        assert_eq!(s_snapshot.schedule, [5, 1]);
+        assert_eq!(s_snapshot.window.tokens, 1);
+
+        let mut m = FixedMultiWindow::restore(m_snapshot, || clock.step(0));
+        let mut s = ScheduledFixedWindow::restore(s_snapshot, || clock.step(0));
+        clock.step(12_500);
+        // T = 12.5ms, the long window still limits the second window
+        assert!(m.try_consume(4).is_err());
+        assert!(m.try_consume(3).is_ok());
+        assert!(s.try_consume(2).is_err());
+        assert!(s.try_consume(1).is_ok());
+    }
+
+    #[test]
+    fn verify_state_multi_window() {
+        let clock = MockClock::new();
+        let mut m = FixedMultiWindow::<_, 2>::new_with_time_provider(5, 10, 8, || clock.step(0));
+        let mut s = ScheduledFixedWindow::new_with_time_provider([5, 1], 10, || clock.step(0));
+        assert!(m.try_consume(5).is_ok());
+        assert!(s.try_consume(4).is_ok());
+
+        clock.step(12_500);
+        // T = 12.5ms, the long window limits the second window
+        assert_eq!(
+            m.state(),
+            FixedWindowState {
+                tokens: 3,
+                capacity: 5,
+                window_width: Duration::from_millis(10),
+                window_start: Duration::from_millis(10),
+            }
+        );
+        assert_eq!(
+            s.state(),
+            FixedWindowState {
+                tokens: 1,
+                capacity: 1,
+                window_width: Duration::from_millis(10),
+                window_start: Duration::from_millis(10),
+            }
+        );
+    }
+
+    #[test]
+    fn verify_try_new() {
+        let clock = MockClock::new();
+        assert_eq!(
+            FixedWindow::try_new_with_time_provider(100, 0, || clock.step(0)).err(),
+            Some(ConfigError::ZeroWindow)
+        );
+        assert_eq!(
+            FixedWindowCore::try_new(0, 100, Duration::ZERO).err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+        let mut w = FixedWindow::try_new_with_time_provider(1, 100, || clock.step(0)).unwrap();
+        assert!(w.try_consume_one().is_ok());
+        assert!(w.try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_carry_over() {
+        let clock = MockClock::new();
+        let mut w =
+            FixedWindow::new_with_time_provider(10, 10, || clock.step(0)).with_carry_over(5);
+        assert_eq!(w.time_until_available(16), None);
 
-    use super::FixedWindow;
+        // 3 tokens left unused roll over
+        assert!(w.try_consume(7).is_ok());
+        assert_eq!(w.time_until_available(14), Some(Duration::from_millis(20)));
+        clock.step(10_000);
+        assert_eq!(w.tokens_available(), 13);
+        assert!(w.try_consume(13).is_ok());
+
+        // Nothing left to carry
+        clock.step(10_000);
+        assert_eq!(w.tokens_available(), 10);
+
+        // Idle windows carry at most `max_carry`
+        clock.step(30_000);
+        assert_eq!(w.tokens_available(), 15);
+        assert!(w.try_consume(15).is_ok());
+        assert!(w.try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_carry_over_compounding() {
+        let clock = MockClock::new();
+        let mut w =
+            FixedWindow::new_with_time_provider(10, 10, || clock.step(0)).with_carry_over(20);
+
+        // T = 0ms, each window left unused carries up to 20 into the next
+        assert!(w.try_consume(10).is_ok());
+        assert_eq!(w.time_until_available(25), Some(Duration::from_millis(30)));
+        assert_eq!(w.time_until_available(31), None);
+        // T = 30ms, without consumes in between
+        clock.step(30_000);
+        assert_eq!(w.tokens_available(), 30);
+        assert!(w.try_consume(25).is_ok());
+        assert!(w.try_consume(6).is_err());
+    }
+
+    #[test]
+    fn verify_carry_over_saturation() {
+        let clock = MockClock::new();
+        let mut w =
+            FixedWindow::new_with_time_provider(u64::MAX, 10, || clock.step(0)).with_carry_over(1);
+
+        assert!(w.try_consume_one().is_ok());
+        assert!(!w.self_check().saturated);
+        // T = 20ms, the carried token no longer fits the counter
+        clock.step(20_000);
+        assert!(w.try_consume_one().is_ok());
+        assert!(w.self_check().saturated);
+        w.reset();
+        assert!(w.self_check().saturated);
+    }
+
+    #[test]
+    fn verify_from_width() {
+        let clock = MockClock::new();
+        // 250us windows
+        let width = Duration::from_micros(250);
+        let mut w = FixedWindow::from_width_with_time_provider(2, width, || clock.step(0));
+        assert_eq!(w.window_width(), width);
+
+        assert!(w.try_consume(2).is_ok());
+        assert!(w.try_consume_one().is_err());
+        assert_eq!(w.time_until_available(1), Some(width));
+        clock.step(200);
+        assert!(w.try_consume_one().is_err());
+        assert_eq!(w.time_until_available(1), Some(Duration::from_micros(50)));
+        clock.step(50);
+        assert!(w.try_consume(2).is_ok());
+
+        // Millisecond constructors are unchanged
+        let w = FixedWindow::new_with_time_provider(2, 10, || clock.step(0));
+        assert_eq!(w.window_width(), Duration::from_millis(10));
+    }
 
     #[test]
     fn verify_rate() {
@@ -136,4 +2286,359 @@ mod tests {
         // T = 1100us, tokens left = 1
         assert!(w.try_consume(2).is_err());
     }
+
+    #[test]
+    fn verify_batch() {
+        let clock = MockClock::new();
+        // Each clock read steps the clock 500us forward
+        let mut w = FixedWindow::new_with_time_provider(10, 1, || clock.step(500));
+
+        // T = 500us, whole batch evaluated in the first window
+        assert_eq!(w.try_consume_batch(&[4, 4, 4, 1]), 2);
+        // T = 1000us, new window
+        assert_eq!(w.try_consume_batch(&[5, 5]), 2);
+        assert_eq!(w.try_consume_batch(&[]), 0);
+    }
+
+    #[test]
+    fn verify_reset_timing() {
+        let clock = MockClock::new();
+        let w = FixedWindow::new_with_time_provider(10, 10, || clock.step(0));
+
+        clock.step(3_500);
+        assert_eq!(w.window_elapsed(), Duration::from_micros(3_500));
+        assert_eq!(w.time_until_reset(), Duration::from_micros(6_500));
+        clock.step(6_500);
+        assert_eq!(w.window_elapsed(), Duration::ZERO);
+        assert_eq!(w.time_until_reset(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn verify_phase_offset() {
+        let clock = MockClock::new();
+        let mut w = FixedWindow::new_with_time_provider(1, 10, || clock.step(0))
+            .with_phase_offset(Duration::from_millis(23));
+        assert_eq!(w.time_until_reset(), Duration::from_millis(7));
+
+        // T = 0ms
+        assert!(w.try_consume_one().is_ok());
+        clock.step(6_000);
+        // T = 6ms, still in the first window
+        assert!(w.try_consume_one().is_err());
+        clock.step(1_000);
+        // T = 7ms, first window ended early
+        assert!(w.try_consume_one().is_ok());
+        assert_eq!(w.time_until_reset(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn verify_aligned_windows() {
+        let clock = MockClock::new();
+        clock.step(3_000);
+        // Replicas created at T = 3ms and T = 7ms agree on the boundaries
+        let mut a = FixedWindow::new_with_time_provider(2, 10, || clock.step(0))
+            .with_aligned_windows(Duration::ZERO)
+            .with_initial_usage(1);
+        clock.step(4_000);
+        let mut b = FixedWindow::new_with_time_provider(2, 10, || clock.step(0))
+            .with_aligned_windows(Duration::ZERO);
+        assert_eq!(a.time_until_reset(), Duration::from_millis(3));
+        assert_eq!(b.time_until_reset(), Duration::from_millis(3));
+
+        // T = 7ms, the initial usage counts against the current window
+        assert!(a.try_consume(2).is_err());
+        assert!(a.try_consume_one().is_ok());
+        assert!(b.try_consume(2).is_ok());
+        assert_eq!(a.time_until_available(1), Some(Duration::from_millis(3)));
+        clock.step(3_000);
+        // T = 10ms, both reset
+        assert_eq!(a.tokens_available(), 2);
+        assert_eq!(b.tokens_available(), 2);
+
+        // Resets keep the alignment
+        clock.step(5_000);
+        b.reset();
+        assert_eq!(b.time_until_reset(), Duration::from_millis(5));
+    }
+
+    #[test]
+    fn verify_capacity_schedule() {
+        let clock = MockClock::new();
+        // One token per window, except five in every fourth one
+        let mut w = FixedWindow::new_with_time_provider(1, 10, || clock.step(0))
+            .with_capacity_schedule(|index| match index % 4 {
+                3 => 5,
+                _ => 1,
+            })
+            .with_initial_usage(1);
+        assert_eq!(w.capacity(), 1);
+        assert_eq!(w.window_capacity(), 1);
+
+        // T = 0ms
+        assert!(w.try_consume_one().is_err());
+        assert_eq!(w.time_until_available(1), Some(Duration::from_millis(10)));
+        assert_eq!(w.time_until_available(3), Some(Duration::from_millis(30)));
+        assert_eq!(w.time_until_available(6), None);
+        clock.step(30_000);
+        // T = 30ms
+        assert_eq!(w.state().capacity, 5);
+        assert!(w.try_consume(5).is_ok());
+        clock.step(10_000);
+        // T = 40ms, the schedule repeats
+        assert_eq!(w.tokens_available(), 1);
+    }
+
+    #[test]
+    fn verify_capacity_schedule_overflow() {
+        let clock = MockClock::new();
+        let width = Duration::from_secs(u64::MAX / 2);
+        // Enough tokens only three windows ahead, further than a Duration reaches
+        let w = FixedWindow::from_width_with_time_provider(1, width, || clock.step(0))
+            .with_capacity_schedule(|index| if index < 3 { 1 } else { 5 });
+        assert_eq!(w.time_until_available(1), Some(Duration::ZERO));
+        assert_eq!(w.time_until_available(2), None);
+    }
+
+    #[test]
+    fn verify_capacity_schedule_closure() {
+        let clock = MockClock::new();
+        // The schedule may capture its table
+        let budgets = [2, 0, 4];
+        let mut w = FixedWindow::new_with_time_provider(1, 10, || clock.step(0))
+            .with_capacity_schedule(move |index| budgets[(index % 3) as usize])
+            .with_carry_over(1);
+
+        // T = 0ms
+        assert!(w.try_consume(1).is_ok());
+        clock.step(10_000);
+        // T = 10ms, one token carried into a quiet window
+        assert_eq!(w.window_capacity(), 0);
+        assert_eq!(w.tokens_available(), 1);
+        assert_eq!(w.time_until_available(5), Some(Duration::from_millis(10)));
+
+        // Same table as a scheduled fixed window, sharing the schedule path
+        let table = ScheduledFixedWindow::new_with_time_provider(budgets, 10, || clock.step(0));
+        assert_eq!(table.schedule(), &budgets);
+        assert_eq!(
+            table.time_until_available(4),
+            Some(Duration::from_millis(20))
+        );
+        assert_eq!(table.time_until_available(5), None);
+    }
+
+    #[test]
+    fn verify_multi_window() {
+        let clock = MockClock::new();
+        // Max 10 per 1ms window and max 15 per 3 windows
+        let mut w = FixedMultiWindow::<_, 3>::new_with_time_provider(10, 1, 15, || clock.step(0));
+
+        // T = 0ms
+        assert!(w.try_consume(11).is_err());
+        assert!(w.try_consume(10).is_ok());
+        clock.step(1000);
+        // T = 1ms, 5 left for the long cap
+        assert!(w.try_consume(6).is_err());
+        assert!(w.try_consume(5).is_ok());
+        clock.step(1000);
+        // T = 2ms, long cap exhausted
+        assert!(w.try_consume_one().is_err());
+        clock.step(1000);
+        // T = 3ms, first window has slid out of the long cap
+        assert!(w.try_consume(10).is_ok());
+        clock.step(10_000);
+        // T = 13ms, everything has slid out
+        assert!(w.try_consume(10).is_ok());
+        assert!(w.try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_clock_backwards() {
+        let ms = Duration::from_millis(1);
+        let mut c = FixedWindowCore::new(2, 10, Duration::ZERO);
+        assert!(c.try_consume_at(ms * 20, 2).is_ok());
+
+        // Stepping back a window neither replenishes nor moves the window
+        assert_eq!(c.tokens_available_at(ms * 5), 0);
+        assert!(c.try_consume_at(ms * 5, 1).is_err());
+        assert!(!c.self_check_at(ms * 20).clock_monotonic);
+        assert!(c.try_consume_at(ms * 25, 1).is_err());
+
+        assert!(c.try_consume_at(ms * 30, 2).is_ok());
+    }
+
+    #[test]
+    fn verify_multi_window_clock_backwards() {
+        let ms = Duration::from_millis(1);
+        let mut c = FixedMultiWindowCore::<3>::new(10, 1, 15, Duration::ZERO);
+        assert!(c.try_consume_at(ms * 2, 10).is_ok());
+
+        // Stepping back counts against the latest window
+        assert_eq!(c.tokens_available_at(Duration::ZERO), 0);
+        assert!(c.try_consume_at(Duration::ZERO, 1).is_err());
+
+        // The usage at 2ms is still within the last 3 windows
+        assert!(c.try_consume_at(ms * 3, 6).is_err());
+        assert!(c.try_consume_at(ms * 3, 5).is_ok());
+    }
+
+    #[test]
+    fn verify_multi_window_reset_timing() {
+        let clock = MockClock::new();
+        let w = FixedMultiWindow::<_, 3>::new_with_time_provider(10, 10, 15, || clock.step(0));
+
+        clock.step(23_500);
+        assert_eq!(w.window_elapsed(), Duration::from_micros(3_500));
+        assert_eq!(w.time_until_reset(), Duration::from_micros(6_500));
+        clock.step(6_500);
+        assert_eq!(w.window_elapsed(), Duration::ZERO);
+        assert_eq!(w.time_until_reset(), Duration::from_millis(10));
+    }
+
+    #[test]
+    fn verify_multi_window_try_new() {
+        let now = || Duration::ZERO;
+        assert_eq!(
+            FixedMultiWindow::<_, 0>::try_new_with_time_provider(10, 1, 15, now).err(),
+            Some(ConfigError::ZeroLength)
+        );
+        assert_eq!(
+            FixedMultiWindow::<_, 3>::try_new_with_time_provider(10, 0, 15, now).err(),
+            Some(ConfigError::ZeroWindow)
+        );
+        assert_eq!(
+            FixedMultiWindow::<_, 3>::try_new_with_time_provider(10, 1, 0, now).err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+
+        let mut w = FixedMultiWindow::<_, 3>::try_new_with_time_provider(10, 1, 15, now).unwrap();
+        assert!(w.try_consume(10).is_ok());
+    }
+
+    #[test]
+    fn verify_schedule() {
+        let clock = MockClock::new();
+        let mut w = ScheduledFixedWindow::new_with_time_provider([3, 1, 0], 1, || clock.step(0));
+        assert_eq!(w.capacity(), 3);
+
+        // T = 0ms
+        assert!(w.try_consume(3).is_ok());
+        assert!(w.try_consume_one().is_err());
+        clock.step(1000);
+        // T = 1ms
+        assert!(w.try_consume_one().is_ok());
+        assert!(w.try_consume_one().is_err());
+        clock.step(1000);
+        // T = 2ms, quiet window
+        assert!(w.try_consume_one().is_err());
+        clock.step(4000);
+        // T = 6ms, schedule has repeated twice
+        assert!(w.try_consume(3).is_ok());
+    }
+
+    #[test]
+    fn verify_schedule_reset_timing() {
+        let clock = MockClock::new();
+        let w = ScheduledFixedWindow::new_with_time_provider([3, 1], 10, || clock.step(0));
+
+        clock.step(13_500);
+        assert_eq!(w.window_elapsed(), Duration::from_micros(3_500));
+        assert_eq!(w.time_until_reset(), Duration::from_micros(6_500));
+        assert_eq!(
+            w.core().time_until_reset_at(Duration::from_millis(20)),
+            Duration::from_millis(10)
+        );
+    }
+
+    #[test]
+    fn verify_schedule_try_new() {
+        let now = || Duration::ZERO;
+        assert_eq!(
+            ScheduledFixedWindow::try_new_with_time_provider([], 1, now).err(),
+            Some(ConfigError::ZeroLength)
+        );
+        assert_eq!(
+            ScheduledFixedWindow::try_new_with_time_provider([1, 0], 0, now).err(),
+            Some(ConfigError::ZeroWindow)
+        );
+        assert_eq!(
+            ScheduledFixedWindow::try_new_with_time_provider([0, 0], 1, now).err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+
+        // An empty schedule never admits anything instead of panicking
+        let mut w = ScheduledFixedWindow::new_with_time_provider([], 1, now);
+        assert!(w.try_consume_one().is_err());
+        assert_eq!(w.time_until_available(1), None);
+        assert!(!w.self_check().config_valid);
+
+        let mut w = ScheduledFixedWindow::try_new_with_time_provider([0, 2], 1, now).unwrap();
+        assert!(w.try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_tokens_available() {
+        let clock = MockClock::new();
+        let mut w = FixedWindow::new_with_time_provider(5, 10, || clock.step(0));
+        let mut m = FixedMultiWindow::<_, 2>::new_with_time_provider(5, 10, 8, || clock.step(0));
+        let mut s = ScheduledFixedWindow::new_with_time_provider([5, 1], 10, || clock.step(0));
+
+        assert_eq!(w.tokens_available(), 5);
+        assert!(w.try_consume(3).is_ok());
+        assert!(m.try_consume(5).is_ok());
+        assert!(s.try_consume(4).is_ok());
+        // Inspecting doesn't consume
+        assert_eq!(w.tokens_available(), 2);
+        assert_eq!(w.tokens_available(), 2);
+        assert_eq!(m.tokens_available(), 0);
+        assert_eq!(s.tokens_available(), 1);
+
+        clock.step(10_000);
+        // T = 10ms, next window
+        assert_eq!(w.tokens_available(), 5);
+        assert_eq!(m.tokens_available(), 3);
+        assert_eq!(s.tokens_available(), 1);
+        clock.step(10_000);
+        // T = 20ms, the long window has forgotten the first window
+        assert_eq!(m.tokens_available(), 5);
+        assert_eq!(s.tokens_available(), 5);
+    }
+
+    #[test]
+    fn verify_partial_consume() {
+        let clock = MockClock::new();
+        let mut w = FixedWindow::new_with_time_provider(5, 10, || clock.step(0));
+
+        assert_eq!(w.try_consume_up_to(3), 3);
+        assert_eq!(w.try_consume_up_to(3), 2);
+        assert_eq!(w.try_consume_up_to(3), 0);
+        clock.step(10_000);
+        assert_eq!(w.try_consume_up_to(u64::MAX), 5);
+    }
+
+    #[test]
+    fn verify_retry_hint() {
+        let ms = Duration::from_millis;
+        let clock = MockClock::new();
+        let mut w = FixedWindow::new_with_time_provider(5, 10, || clock.step(0));
+        let mut m = FixedMultiWindow::<_, 2>::new_with_time_provider(5, 10, 8, || clock.step(0));
+        let mut s = ScheduledFixedWindow::new_with_time_provider([5, 1], 10, || clock.step(0));
+
+        assert!(w.try_consume(5).is_ok());
+        assert!(m.try_consume(5).is_ok());
+        assert!(s.try_consume(4).is_ok());
+        clock.step(3_000);
+        // T = 3ms
+        assert_eq!(w.time_until_available(1), Some(ms(7)));
+        assert_eq!(w.time_until_available(6), None);
+        assert_eq!(s.time_until_available(1), Some(Duration::ZERO));
+        // The next window only allows a single token
+        assert_eq!(s.time_until_available(2), Some(ms(17)));
+        assert_eq!(s.time_until_available(6), None);
+
+        clock.step(10_000);
+        // T = 13ms, the long cap is the limiting one
+        assert!(m.try_consume(3).is_ok());
+        assert_eq!(m.time_until_available(1), Some(ms(7)));
+        assert_eq!(m.time_until_available(9), None);
+    }
 }
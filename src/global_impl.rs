@@ -0,0 +1,123 @@
+//! Process-global named limiters
+
+use std::{
+    collections::HashMap,
+    sync::{Mutex, OnceLock, PoisonError},
+};
+
+use crate::{CantConsume, Limiter, LimiterInfo, LimiterResult};
+
+type Registry = Mutex<HashMap<&'static str, Box<dyn Limiter + Send>>>;
+
+static REGISTRY: OnceLock<Registry> = OnceLock::new();
+
+fn registry() -> &'static Registry {
+    REGISTRY.get_or_init(Default::default)
+}
+
+/// Register a process-global limiter under the given name
+///
+/// Registered limiters can be consumed from anywhere in the process with
+/// [`try_consume_global`] or the [`throttle!`](crate::throttle) macro,
+/// without passing limiter handles around.
+///
+/// # Arguments
+/// * `name` - name to register the limiter with
+/// * `limiter` - limiter to register
+///
+/// # Returns
+/// `true` if a previously registered limiter with the same name was replaced
+pub fn register_global(name: &'static str, limiter: impl Limiter + Send + 'static) -> bool {
+    registry()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .insert(name, Box::new(limiter))
+        .is_some()
+}
+
+/// Try to consume tokens from a process-global limiter
+///
+/// # Arguments
+/// * `name` - name the limiter was registered with, see [`register_global`]
+/// * `tokens` - how many tokens to consume
+///
+/// # Returns
+/// * `Ok(())` - token consumed
+/// * `Err(CantConsume)` - not enough tokens left, or no limiter is registered
+///   with the given name
+pub fn try_consume_global(name: &str, tokens: u64) -> LimiterResult {
+    registry()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .get_mut(name)
+        .ok_or(CantConsume)?
+        .try_consume(tokens)
+}
+
+/// List the process-global limiters
+///
+/// # Returns
+/// Name each limiter was registered with, along with the kind and the
+/// user-assigned name of the limiter itself, in no particular order
+pub fn global_limiters() -> Vec<(&'static str, LimiterInfo)> {
+    registry()
+        .lock()
+        .unwrap_or_else(PoisonError::into_inner)
+        .iter()
+        .map(|(&name, limiter)| (name, limiter.info()))
+        .collect()
+}
+
+/// Consume from a process-global limiter registered with [`register_global`]
+///
+/// Expands to [`try_consume_global`], consuming a single token if the
+/// amount is omitted.
+///
+/// ```
+/// burster::register_global("api", burster::token_bucket(100, 10));
+///
+/// if burster::throttle!("api").is_ok() {
+///     // Not limited
+/// }
+/// assert!(burster::throttle!("api", 5).is_ok());
+/// ```
+#[macro_export]
+macro_rules! throttle {
+    ($name:expr) => {
+        $crate::try_consume_global($name, 1)
+    };
+    ($name:expr, $tokens:expr) => {
+        $crate::try_consume_global($name, $tokens)
+    };
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{CreditBucket, LimiterInfo, LimiterKind};
+
+    use super::{global_limiters, register_global, try_consume_global};
+
+    #[test]
+    fn verify_global() {
+        assert!(!register_global("verify_global", CreditBucket::new(3, 3)));
+
+        assert!(crate::throttle!("verify_global").is_ok());
+        assert!(crate::throttle!("verify_global", 2).is_ok());
+        assert!(crate::throttle!("verify_global").is_err());
+        // Unknown limiters always reject
+        assert!(try_consume_global("verify_global_unknown", 0).is_err());
+
+        assert!(register_global(
+            "verify_global",
+            CreditBucket::new(3, 3).with_name("credits")
+        ));
+        assert!(try_consume_global("verify_global", 3).is_ok());
+        assert!(global_limiters().contains(&(
+            "verify_global",
+            LimiterInfo {
+                kind: LimiterKind::CreditBucket,
+                name: Some("credits")
+            }
+        )));
+    }
+}
@@ -0,0 +1,288 @@
+//! Expiring token grants -type wrapper
+
+use core::{fmt, time::Duration};
+
+use crate::{Clock, Diagnostics, Inspect, Limiter, LimiterKind, LimiterResult, Reset, RetryHint};
+
+/// Temporary quota deposited into a [`WithGrants`] wrapper
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Grant {
+    /// Tokens left in this grant
+    pub tokens: u64,
+    /// Timestamp after which the unused tokens disappear
+    pub valid_until: Duration,
+}
+
+/// Error type indicating that a grant can't be deposited
+/// because all grant slots are in use.
+#[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct GrantsFull;
+
+impl fmt::Display for GrantsFull {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "No free grant slots left")
+    }
+}
+
+// core::error::Error trait stabilised at release 1.81
+#[rustversion::since(1.81)]
+impl core::error::Error for GrantsFull {}
+
+/// Expiring token grants wrapper for any [`Limiter`]
+///
+/// Grants are temporary quota boosts tracked separately from the base
+/// budget of the wrapped limiter. Consumes draw from the grants that
+/// expire soonest first, and only the part not covered by grants is
+/// consumed from the wrapped limiter. Unused grant tokens disappear once
+/// their expiry time has passed.
+///
+/// # Generic arguments
+/// * `L` - wrapped limiter
+/// * `T` - time provider, must share the epoch of grant expiry timestamps
+/// * `N` - maximum number of simultaneously active grants
+pub struct WithGrants<L, T, const N: usize>
+where
+    L: Limiter,
+    T: Clock,
+{
+    inner: L,
+    grants: [Option<Grant>; N],
+    time_provider: T,
+}
+
+impl<L, T, const N: usize> WithGrants<L, T, N>
+where
+    L: Limiter,
+    T: Clock,
+{
+    /// Wrap a limiter with support for expiring grants
+    ///
+    /// # Arguments
+    /// * `inner` - limiter providing the base budget
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    pub fn new_with_time_provider(inner: L, time_provider: T) -> Self {
+        Self {
+            inner,
+            grants: [None; N],
+            time_provider,
+        }
+    }
+
+    /// Deposit temporary quota
+    ///
+    /// # Arguments
+    /// * `tokens` - how many tokens to grant
+    /// * `valid_until` - timestamp after which the unused tokens disappear
+    ///
+    /// # Returns
+    /// * `Ok(())` - grant deposited
+    /// * `Err(GrantsFull)` - all `N` grant slots are in use
+    pub fn grant(&mut self, tokens: u64, valid_until: Duration) -> Result<(), GrantsFull> {
+        let now = self.time_provider.now();
+        self.expire(now);
+
+        let slot = self
+            .grants
+            .iter_mut()
+            .find(|g| g.is_none())
+            .ok_or(GrantsFull)?;
+        *slot = Some(Grant {
+            tokens,
+            valid_until,
+        });
+        Ok(())
+    }
+
+    /// Tokens currently available from unexpired grants
+    pub fn granted_tokens(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.grants
+            .iter()
+            .flatten()
+            .filter(|g| g.valid_until > now)
+            .fold(0u64, |acc, g| acc.saturating_add(g.tokens))
+    }
+
+    /// Iterate over the currently stored grants
+    pub fn grants(&self) -> impl Iterator<Item = &Grant> {
+        self.grants.iter().flatten()
+    }
+
+    /// Get a reference to the wrapped limiter
+    pub fn inner(&self) -> &L {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped limiter
+    pub fn inner_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    /// Unwrap the wrapped limiter, dropping all grants
+    pub fn into_inner(self) -> L {
+        self.inner
+    }
+
+    /// Drop expired and exhausted grants
+    fn expire(&mut self, now: Duration) {
+        for slot in self.grants.iter_mut() {
+            if matches!(slot, Some(g) if g.valid_until <= now || g.tokens == 0) {
+                *slot = None;
+            }
+        }
+    }
+}
+
+impl<L, T, const N: usize> Limiter for WithGrants<L, T, N>
+where
+    L: Limiter,
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        self.expire(now);
+
+        let granted = self
+            .grants
+            .iter()
+            .flatten()
+            .fold(0u64, |acc, g| acc.saturating_add(g.tokens));
+
+        // Consume the part not covered by grants from the base budget first,
+        // so that grants are left untouched if the base budget can't cover it.
+        let remainder = tokens.saturating_sub(granted);
+        if remainder != 0 {
+            self.inner.try_consume(remainder)?;
+        }
+
+        // Take the rest from the grants expiring soonest
+        let mut from_grants = tokens - remainder;
+        while from_grants != 0 {
+            let grant = self
+                .grants
+                .iter_mut()
+                .flatten()
+                .filter(|g| g.tokens != 0)
+                .min_by_key(|g| g.valid_until)
+                .expect("grant tokens were counted above");
+            let take = grant.tokens.min(from_grants);
+            grant.tokens -= take;
+            from_grants -= take;
+        }
+
+        Ok(())
+    }
+
+    fn kind(&self) -> LimiterKind {
+        self.inner.kind()
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.inner.name()
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        self.inner.self_check()
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.inner.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        self.inner.idle_for()
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+        self.grants = [None; N];
+    }
+}
+
+impl<L, T, const N: usize> Reset for WithGrants<L, T, N>
+where
+    L: Reset,
+    T: Clock,
+{
+}
+
+impl<L, T, const N: usize> RetryHint for WithGrants<L, T, N>
+where
+    L: RetryHint,
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.time_provider.now();
+        let granted = self
+            .grants
+            .iter()
+            .flatten()
+            .filter(|g| g.valid_until > now)
+            .fold(0u64, |acc, g| acc.saturating_add(g.tokens));
+        match tokens.saturating_sub(granted) {
+            0 => Some(Duration::ZERO),
+            remainder => self.inner.time_until_available(remainder),
+        }
+    }
+}
+
+impl<L, T, const N: usize> Inspect for WithGrants<L, T, N>
+where
+    L: Inspect,
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.grants
+            .iter()
+            .flatten()
+            .filter(|g| g.valid_until > now)
+            .fold(self.inner.tokens_available(), |acc, g| {
+                acc.saturating_add(g.tokens)
+            })
+    }
+}
+
+#[cfg(all(test, feature = "fixed-window"))]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{mock_assets::MockClock, FixedWindow, Limiter};
+
+    use super::WithGrants;
+
+    #[test]
+    fn verify_grants() {
+        let clock = MockClock::new();
+        let base_clock = MockClock::new();
+        // Base budget of 10 tokens that never replenishes during the test
+        let w = FixedWindow::new_with_time_provider(10, 1000, || base_clock.step(0));
+        // Each call steps the clock 1ms forward
+        let mut g = WithGrants::<_, _, 2>::new_with_time_provider(w, || clock.step(1000));
+
+        // T = 0ms, grants expiring at T = 5ms and T = 10ms
+        assert!(g.grant(5, Duration::from_millis(10)).is_ok());
+        // T = 1ms
+        assert!(g.grant(5, Duration::from_millis(5)).is_ok());
+        // T = 2ms, no free slots
+        assert!(g.grant(5, Duration::from_millis(5)).is_err());
+
+        // T = 3ms, takes 3 from the grant expiring at 5ms
+        assert!(g.try_consume(3).is_ok());
+        // T = 4ms, grants = 2 + 5, base = 10, grants are left untouched on failure
+        assert!(g.try_consume(18).is_err());
+        // T = 5ms, remaining 2 tokens from first grant have expired
+        assert_eq!(g.granted_tokens(), 5);
+        // T = 6ms, takes 5 from the grant and 5 from the base budget
+        assert!(g.try_consume(10).is_ok());
+        // T = 7ms, 5 tokens left in base budget
+        assert!(g.try_consume(6).is_err());
+        // T = 8ms
+        assert!(g.try_consume(5).is_ok());
+    }
+}
@@ -0,0 +1,440 @@
+//! Hierarchical token bucket -type limiter
+
+use core::time::Duration;
+
+use crate::{
+    CantConsume, Clock, ConfigError, Diagnostics, Inspect, Limiter, LimiterKind, LimiterResult,
+    RetryHint, TokenBucketCore,
+};
+
+/// Hierarchical token bucket with `N` child buckets under a shared parent
+///
+/// Like the HTB queueing discipline of Linux traffic control, every child
+/// has an assured rate of its own and can borrow tokens from the parent
+/// when its own bucket runs dry. The parent is charged for all traffic of
+/// its children, so the children together can't exceed the parent rate
+/// by borrowing, while the share of idle children is left to the parent
+/// for others to borrow.
+///
+/// A child within its own budget is always admitted, even if the parent is
+/// exhausted. To keep the total below the parent rate, the child rates
+/// shouldn't add up to more than the parent rate.
+///
+/// Consumes go through a [`ChildBucket`] handle, see [`HierarchicalTokenBucket::child`].
+///
+/// # Generic arguments
+/// * `T` - time provider
+/// * `N` - number of child buckets
+#[derive(Debug, Clone)]
+pub struct HierarchicalTokenBucket<T, const N: usize>
+where
+    T: Clock,
+{
+    parent: TokenBucketCore,
+    children: [TokenBucketCore; N],
+    time_provider: T,
+    name: Option<&'static str>,
+}
+
+impl<T, const N: usize> HierarchicalTokenBucket<T, N>
+where
+    T: Clock,
+{
+    /// Initialize a new hierarchy with identical children
+    ///
+    /// # Arguments
+    /// * `parent_rate_per_s` - how many consumes all children may make per second in total
+    /// * `parent_capacity` - parent bucket capacity, the burst available for borrowing
+    /// * `child_rate_per_s` - assured rate of each child
+    /// * `child_capacity` - bucket capacity of each child
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    pub fn new_with_time_provider(
+        parent_rate_per_s: u64,
+        parent_capacity: u64,
+        child_rate_per_s: u64,
+        child_capacity: u64,
+        time_provider: T,
+    ) -> Self {
+        let now = time_provider.now();
+        Self {
+            parent: TokenBucketCore::new(parent_rate_per_s, parent_capacity, now),
+            children: [TokenBucketCore::new(child_rate_per_s, child_capacity, now); N],
+            time_provider,
+            name: None,
+        }
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting invalid configurations
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroLength`] if `N` is zero, [`ConfigError::ZeroRate`] or
+    /// [`ConfigError::ZeroCapacity`] if any rate or capacity is zero
+    pub fn try_new_with_time_provider(
+        parent_rate_per_s: u64,
+        parent_capacity: u64,
+        child_rate_per_s: u64,
+        child_capacity: u64,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        if N == 0 {
+            return Err(ConfigError::ZeroLength);
+        }
+        let now = time_provider.now();
+        Ok(Self {
+            parent: TokenBucketCore::try_new(parent_rate_per_s, parent_capacity, now)?,
+            children: [TokenBucketCore::try_new(child_rate_per_s, child_capacity, now)?; N],
+            time_provider,
+            name: None,
+        })
+    }
+
+    /// Configure a single child differently from the others
+    ///
+    /// Use [`Self::try_with_child`] to validate the configuration.
+    ///
+    /// # Panics
+    /// If `index >= N`
+    pub fn with_child(mut self, index: usize, rate_per_s: u64, capacity: u64) -> Self {
+        let now = self.time_provider.now();
+        self.children[index] = TokenBucketCore::new(rate_per_s, capacity, now);
+        self
+    }
+
+    /// Like [`Self::with_child`], but rejecting invalid configurations
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroRate`] or [`ConfigError::ZeroCapacity`] for zero arguments
+    ///
+    /// # Panics
+    /// If `index >= N`
+    pub fn try_with_child(
+        mut self,
+        index: usize,
+        rate_per_s: u64,
+        capacity: u64,
+    ) -> Result<Self, ConfigError> {
+        let now = self.time_provider.now();
+        self.children[index] = TokenBucketCore::try_new(rate_per_s, capacity, now)?;
+        Ok(self)
+    }
+
+    /// Assign a name to this limiter, reported by each of its children,
+    /// see [`crate::TokenBucket::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Get a handle to a single child bucket
+    ///
+    /// # Panics
+    /// If `index >= N`
+    pub fn child(&mut self, index: usize) -> ChildBucket<'_, T, N> {
+        assert!(index < N, "child index out of range");
+        ChildBucket { htb: self, index }
+    }
+
+    /// Try to consume tokens from a child bucket, see [`ChildBucket`]
+    ///
+    /// # Panics
+    /// If `index >= N`
+    pub fn try_consume_child(&mut self, index: usize, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        let own = self.children[index].tokens_available_at(now);
+        let borrowed = tokens.saturating_sub(own);
+        if borrowed > self.parent.tokens_available_at(now) {
+            return Err(CantConsume);
+        }
+
+        // Borrowed tokens must come from the parent, the rest is charged
+        // to the parent as far as it has tokens left
+        let from_own = tokens - borrowed;
+        let charged = tokens.min(self.parent.tokens_available_at(now));
+        self.children[index].try_consume_at(now, from_own)?;
+        self.parent.try_consume_at(now, charged)?;
+        Ok(())
+    }
+
+    /// Get the state of the parent bucket
+    pub fn parent_core(&self) -> &TokenBucketCore {
+        &self.parent
+    }
+
+    /// Get the state of a child bucket
+    ///
+    /// # Panics
+    /// If `index >= N`
+    pub fn child_core(&self, index: usize) -> &TokenBucketCore {
+        &self.children[index]
+    }
+}
+
+/// Handle to a single child of a [`HierarchicalTokenBucket`]
+///
+/// Consumes are covered by the child's own tokens first, and the rest is
+/// borrowed from the parent. A consume is rejected if the parent can't lend enough.
+pub struct ChildBucket<'a, T, const N: usize>
+where
+    T: Clock,
+{
+    htb: &'a mut HierarchicalTokenBucket<T, N>,
+    index: usize,
+}
+
+impl<T, const N: usize> ChildBucket<'_, T, N>
+where
+    T: Clock,
+{
+    /// Index of this child
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<T, const N: usize> Limiter for ChildBucket<'_, T, N>
+where
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        self.htb.try_consume_child(self.index, tokens)
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::HierarchicalTokenBucket
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.htb.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.htb.time_provider.now();
+        let child = self.htb.children[self.index].self_check_at(now);
+        let parent = self.htb.parent.self_check_at(now);
+        Diagnostics {
+            clock_monotonic: child.clock_monotonic && parent.clock_monotonic,
+            clock_progressing: child.clock_progressing,
+            config_valid: child.config_valid && parent.config_valid,
+            saturated: child.saturated || parent.saturated,
+        }
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.htb.children[self.index].last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.htb.time_provider.now();
+        Some(self.htb.children[self.index].idle_for_at(now))
+    }
+
+    fn reset(&mut self) {
+        // The parent also refills whatever the other children borrowed
+        let now = self.htb.time_provider.now();
+        self.htb.children[self.index].reset_at(now);
+        self.htb.parent.reset_at(now);
+    }
+}
+
+impl<T, const N: usize> RetryHint for ChildBucket<'_, T, N>
+where
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.htb.time_provider.now();
+        let child = &self.htb.children[self.index];
+        let own = child.tokens_available_at(now);
+        // Either the own bucket refills, or the parent has enough to lend
+        let by_own = child.time_until_available_at(now, tokens);
+        let by_borrowing = self
+            .htb
+            .parent
+            .time_until_available_at(now, tokens.saturating_sub(own));
+        match (by_own, by_borrowing) {
+            (Some(a), Some(b)) => Some(a.min(b)),
+            (a, b) => a.or(b),
+        }
+    }
+}
+
+impl<T, const N: usize> Inspect for ChildBucket<'_, T, N>
+where
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.htb.time_provider.now();
+        self.htb.children[self.index]
+            .tokens_available_at(now)
+            .saturating_add(self.htb.parent.tokens_available_at(now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{mock_assets::MockClock, ConfigError, Inspect, Limiter, RetryHint};
+
+    use super::HierarchicalTokenBucket;
+
+    #[test]
+    fn verify_try_new() {
+        let clock = MockClock::new();
+        assert_eq!(
+            HierarchicalTokenBucket::<_, 0>::try_new_with_time_provider(1000, 10, 200, 2, || {
+                clock.step(0)
+            })
+            .err(),
+            Some(ConfigError::ZeroLength)
+        );
+        assert_eq!(
+            HierarchicalTokenBucket::<_, 2>::try_new_with_time_provider(0, 10, 200, 2, || {
+                clock.step(0)
+            })
+            .err(),
+            Some(ConfigError::ZeroRate)
+        );
+        assert_eq!(
+            HierarchicalTokenBucket::<_, 2>::try_new_with_time_provider(1000, 10, 200, 0, || {
+                clock.step(0)
+            })
+            .err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+        let mut htb =
+            HierarchicalTokenBucket::<_, 2>::try_new_with_time_provider(1000, 10, 200, 2, || {
+                clock.step(0)
+            })
+            .unwrap();
+        assert!(htb.child(0).try_consume(12).is_ok());
+    }
+
+    #[test]
+    fn verify_try_with_child() {
+        let clock = MockClock::new();
+        let htb = HierarchicalTokenBucket::<_, 2>::new_with_time_provider(1000, 10, 200, 2, || {
+            clock.step(0)
+        });
+        assert_eq!(
+            htb.clone().try_with_child(1, 0, 2).err(),
+            Some(ConfigError::ZeroRate)
+        );
+        assert_eq!(
+            htb.clone().try_with_child(1, 200, 0).err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+        let mut htb = htb.try_with_child(1, 400, 4).unwrap();
+        assert_eq!(htb.child_core(1).capacity(), 4);
+        assert!(htb.child(1).try_consume(14).is_ok());
+
+        // Unvalidated children are reported by the self-check
+        let mut htb = htb.with_child(0, 0, 2).with_name("htb");
+        assert_eq!(htb.child(0).name(), Some("htb"));
+        assert!(!htb.child(0).self_check().config_valid);
+        assert!(htb.child(1).self_check().is_healthy());
+    }
+
+    #[test]
+    fn verify_refill() {
+        let clock = MockClock::new();
+        let now = || clock.step(0);
+        // Parent 10 tokens per 10ms, children 2 tokens per 10ms each
+        let mut htb =
+            HierarchicalTokenBucket::<_, 2>::new_with_time_provider(1000, 10, 200, 2, now);
+        // The parent is charged for all traffic, borrowed or not
+        assert!(htb.child(0).try_consume(6).is_ok());
+        assert!(htb.child(1).try_consume(6).is_ok());
+        assert_eq!(htb.child(0).tokens_available(), 0);
+        assert_eq!(htb.child(1).tokens_available(), 0);
+
+        // Both children refill their own buckets, and share the parent refill
+        clock.step(5000);
+        assert_eq!(htb.child_core(0).tokens_available_at(now()), 1);
+        assert_eq!(htb.child_core(1).tokens_available_at(now()), 1);
+        assert_eq!(htb.parent_core().tokens_available_at(now()), 5);
+        assert!(htb.child(0).try_consume(5).is_ok());
+        assert!(htb.child(1).try_consume(2).is_err());
+        assert!(htb.child(1).try_consume_one().is_ok());
+
+        // Fully refilled after the slowest bucket
+        clock.step(10_000);
+        assert_eq!(htb.child(0).tokens_available(), 12);
+        assert_eq!(htb.child(1).tokens_available(), 12);
+    }
+
+    #[test]
+    fn verify_borrowing() {
+        let clock = MockClock::new();
+        // Parent 10 tokens per 10ms, children 2 tokens per 10ms each
+        let mut htb =
+            HierarchicalTokenBucket::<_, 3>::new_with_time_provider(1000, 10, 200, 2, || {
+                clock.step(0)
+            });
+
+        // Busy child uses its own tokens and borrows the rest from the parent
+        assert_eq!(htb.child(0).tokens_available(), 12);
+        assert!(htb.child(0).try_consume(9).is_ok());
+        assert_eq!(htb.parent_core().tokens_available_at(Duration::ZERO), 1);
+        // Can't borrow more than the parent has left
+        assert!(htb.child(0).try_consume(2).is_err());
+        assert!(htb.child(0).try_consume_one().is_ok());
+
+        // Another child is always admitted within its own budget
+        assert!(htb.child(1).try_consume(2).is_ok());
+        assert!(htb.child(1).try_consume_one().is_err());
+
+        // A token per millisecond to the parent, one per 5ms to the children
+        assert_eq!(
+            htb.child(1).time_until_available(1),
+            Some(Duration::from_millis(1))
+        );
+        clock.step(1000);
+        assert!(htb.child(2).try_consume(3).is_ok());
+        assert!(htb.child(0).try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_parent_rejection_leaks_nothing() {
+        let clock = MockClock::new();
+        let now = Duration::ZERO;
+        let mut htb =
+            HierarchicalTokenBucket::<_, 2>::new_with_time_provider(1000, 10, 200, 2, || {
+                clock.step(0)
+            });
+        // Child 0 drains the parent
+        assert!(htb.child(0).try_consume(12).is_ok());
+        assert_eq!(htb.parent_core().tokens_available_at(now), 0);
+
+        // Child 1 has tokens of its own, but not enough without borrowing
+        for _ in 0..10 {
+            assert!(htb.child(1).try_consume(3).is_err());
+        }
+        assert_eq!(htb.child_core(1).tokens_available_at(now), 2);
+        assert_eq!(htb.parent_core().tokens_available_at(now), 0);
+        assert_eq!(htb.child(1).tokens_available(), 2);
+
+        // The own budget is still intact
+        assert!(htb.child(1).try_consume(2).is_ok());
+        assert!(htb.child(1).try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_reset() {
+        let clock = MockClock::new();
+        let now = Duration::ZERO;
+        let mut htb =
+            HierarchicalTokenBucket::<_, 2>::new_with_time_provider(1000, 10, 200, 2, || {
+                clock.step(0)
+            });
+        assert!(htb.child(0).try_consume(12).is_ok());
+        assert!(htb.child(1).try_consume(2).is_ok());
+
+        // Resetting a child refills its own bucket and the parent
+        htb.child(0).reset();
+        assert_eq!(htb.child_core(0).tokens_available_at(now), 2);
+        assert_eq!(htb.parent_core().tokens_available_at(now), 10);
+        assert_eq!(htb.child_core(1).tokens_available_at(now), 0);
+        assert!(htb.child(0).try_consume(12).is_ok());
+    }
+}
@@ -0,0 +1,204 @@
+//! Callback hooks on consume decisions
+
+use core::time::Duration;
+
+use crate::{
+    Diagnostics, Inspect, Limiter, LimiterInfo, LimiterKind, LimiterResult, Reset, RetryHint,
+};
+
+/// Wrapper invoking callbacks on the decisions of any [`Limiter`]
+///
+/// Wires metrics, logging or e.g. a blinking LED into a limiter without
+/// touching its call sites. The reject hook is invoked with the requested
+/// tokens and the [`RetryHint::time_until_available`] of the rejection,
+/// the accept hook with the consumed tokens. Both hooks also receive the
+/// [`LimiterInfo`] of the wrapped limiter, so that one hook can serve
+/// several limiters.
+///
+/// # Generic arguments
+/// * `L` - wrapped limiter
+/// * `R` - reject callback
+/// * `A` - accept callback
+pub struct WithHooks<L, R = fn(LimiterInfo, u64, Option<Duration>), A = fn(LimiterInfo, u64)>
+where
+    L: RetryHint,
+    R: FnMut(LimiterInfo, u64, Option<Duration>),
+    A: FnMut(LimiterInfo, u64),
+{
+    inner: L,
+    on_reject: R,
+    on_accept: A,
+}
+
+impl<L> WithHooks<L>
+where
+    L: RetryHint,
+{
+    /// Wrap a limiter, without any hooks yet
+    ///
+    /// # Arguments
+    /// * `inner` - limiter to wrap
+    pub fn new(inner: L) -> Self {
+        Self {
+            inner,
+            on_reject: |_, _, _| {},
+            on_accept: |_, _| {},
+        }
+    }
+}
+
+impl<L, R, A> WithHooks<L, R, A>
+where
+    L: RetryHint,
+    R: FnMut(LimiterInfo, u64, Option<Duration>),
+    A: FnMut(LimiterInfo, u64),
+{
+    /// Invoke `on_reject` with the limiter info, requested tokens and retry hint of each rejection
+    pub fn on_reject<F>(self, on_reject: F) -> WithHooks<L, F, A>
+    where
+        F: FnMut(LimiterInfo, u64, Option<Duration>),
+    {
+        WithHooks {
+            inner: self.inner,
+            on_reject,
+            on_accept: self.on_accept,
+        }
+    }
+
+    /// Invoke `on_accept` with the limiter info and consumed tokens of each admitted consume
+    pub fn on_accept<F>(self, on_accept: F) -> WithHooks<L, R, F>
+    where
+        F: FnMut(LimiterInfo, u64),
+    {
+        WithHooks {
+            inner: self.inner,
+            on_reject: self.on_reject,
+            on_accept,
+        }
+    }
+
+    /// Get a reference to the wrapped limiter
+    pub fn inner(&self) -> &L {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped limiter
+    pub fn inner_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    /// Unwrap the wrapped limiter
+    pub fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+impl<L, R, A> Limiter for WithHooks<L, R, A>
+where
+    L: RetryHint,
+    R: FnMut(LimiterInfo, u64, Option<Duration>),
+    A: FnMut(LimiterInfo, u64),
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let result = self.inner.try_consume(tokens);
+        match result {
+            Ok(()) => (self.on_accept)(self.inner.info(), tokens),
+            Err(_) => (self.on_reject)(
+                self.inner.info(),
+                tokens,
+                self.inner.time_until_available(tokens),
+            ),
+        }
+        result
+    }
+
+    fn kind(&self) -> LimiterKind {
+        self.inner.kind()
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.inner.name()
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        self.inner.self_check()
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.inner.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        self.inner.idle_for()
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+impl<L, R, A> Reset for WithHooks<L, R, A>
+where
+    L: RetryHint + Reset,
+    R: FnMut(LimiterInfo, u64, Option<Duration>),
+    A: FnMut(LimiterInfo, u64),
+{
+}
+
+impl<L, R, A> RetryHint for WithHooks<L, R, A>
+where
+    L: RetryHint,
+    R: FnMut(LimiterInfo, u64, Option<Duration>),
+    A: FnMut(LimiterInfo, u64),
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        self.inner.time_until_available(tokens)
+    }
+}
+
+impl<L, R, A> Inspect for WithHooks<L, R, A>
+where
+    L: RetryHint + Inspect,
+    R: FnMut(LimiterInfo, u64, Option<Duration>),
+    A: FnMut(LimiterInfo, u64),
+{
+    fn tokens_available(&self) -> u64 {
+        self.inner.tokens_available()
+    }
+}
+
+#[cfg(all(test, feature = "token-bucket"))]
+mod tests {
+    use core::{cell::Cell, time::Duration};
+
+    use crate::{mock_assets::MockClock, Limiter, LimiterKind, TokenBucket};
+
+    use super::WithHooks;
+
+    #[test]
+    fn verify_hooks() {
+        let clock = MockClock::new();
+        let accepted = Cell::new(0);
+        let rejected = Cell::new(None);
+        let b = TokenBucket::new_with_time_provider(1000, 5, || clock.step(0)).with_name("api");
+        let mut h = WithHooks::new(b)
+            .on_accept(|info, tokens| {
+                assert_eq!(info.name, Some("api"));
+                accepted.set(accepted.get() + tokens)
+            })
+            .on_reject(|info, tokens, retry_after| {
+                assert_eq!(info.kind, LimiterKind::TokenBucket);
+                rejected.set(Some((tokens, retry_after)))
+            });
+
+        assert!(h.try_consume(4).is_ok());
+        assert!(h.try_consume(3).is_err());
+        assert!(h.try_consume_one().is_ok());
+        assert_eq!(accepted.get(), 5);
+        assert_eq!(rejected.get(), Some((3, Some(Duration::from_millis(2)))));
+
+        // No hooks at all
+        let b = TokenBucket::new_with_time_provider(1000, 5, || clock.step(0));
+        assert!(WithHooks::new(b).try_consume(5).is_ok());
+    }
+}
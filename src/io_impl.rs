@@ -0,0 +1,278 @@
+//! Bandwidth limiting for `std::io` readers and writers
+
+use std::{
+    io::{self, Read, Write},
+    thread,
+};
+
+use crate::{blocking_impl::MIN_SLEEP, CantConsume, Inspect, RateLimited, RetryHint};
+
+/// Consume tokens for up to `len` bytes without waiting
+///
+/// # Returns
+/// * `Ok(bytes)` - how many bytes the consumed tokens cover, rounded up to whole blocks
+/// * `Err(RateLimited)` - nothing was consumed
+pub(crate) fn try_acquire_bytes<L>(
+    limiter: &mut L,
+    block_size: usize,
+    len: usize,
+) -> Result<usize, RateLimited>
+where
+    L: Inspect + RetryHint,
+{
+    let blocks = len.div_ceil(block_size) as u64;
+    match limiter.try_consume_up_to(blocks) {
+        0 => Err(RateLimited {
+            retry_after: limiter.time_until_available(1),
+        }),
+        granted => Ok((granted as usize).saturating_mul(block_size)),
+    }
+}
+
+/// Error returned when the limiter can never admit a single block
+pub(crate) fn never_available() -> io::Error {
+    io::Error::other(CantConsume.to_string())
+}
+
+/// Consume tokens for up to `len` bytes, sleeping until at least one block is granted
+///
+/// # Returns
+/// How many bytes may be transferred, at most `len`
+fn acquire_bytes<L>(limiter: &mut L, block_size: usize, len: usize) -> io::Result<usize>
+where
+    L: Inspect + RetryHint,
+{
+    loop {
+        match try_acquire_bytes(limiter, block_size, len) {
+            Ok(bytes) => return Ok(bytes.min(len)),
+            Err(RateLimited {
+                retry_after: Some(wait),
+            }) => thread::sleep(wait.max(MIN_SLEEP)),
+            Err(RateLimited { retry_after: None }) => return Err(never_available()),
+        }
+    }
+}
+
+/// Bandwidth limiting wrapper for any [`Read`]
+///
+/// Consumes one token per block of bytes before reading, sleeping the current
+/// thread while the limiter is exhausted. Reads are shortened to what the
+/// limiter currently admits, so large buffers never need more tokens than the
+/// limiter can hold. A partial block at the end of a read consumes a whole token.
+///
+/// Reading fails with [`io::ErrorKind::Other`] if the limiter can never admit
+/// a single block.
+///
+/// # Generic arguments
+/// * `R` - wrapped reader
+/// * `L` - limiter, e.g. a [`crate::TokenBucket`] with a rate in blocks per second
+pub struct ThrottledReader<R, L> {
+    inner: R,
+    limiter: L,
+    block_size: usize,
+}
+
+impl<R, L> ThrottledReader<R, L>
+where
+    R: Read,
+    L: Inspect + RetryHint,
+{
+    /// Wrap a reader, consuming one token per byte
+    ///
+    /// # Arguments
+    /// * `inner` - reader to wrap
+    /// * `limiter` - limiter shaping the bandwidth
+    pub fn new(inner: R, limiter: L) -> Self {
+        Self::with_block_size(inner, limiter, 1)
+    }
+
+    /// Wrap a reader, consuming one token per block of bytes
+    ///
+    /// # Arguments
+    /// * `inner` - reader to wrap
+    /// * `limiter` - limiter shaping the bandwidth
+    /// * `block_size` - how many bytes a single token covers, at least one
+    pub fn with_block_size(inner: R, limiter: L, block_size: usize) -> Self {
+        Self {
+            inner,
+            limiter,
+            block_size: block_size.max(1),
+        }
+    }
+
+    /// Get a reference to the wrapped reader
+    pub fn get_ref(&self) -> &R {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped reader
+    pub fn get_mut(&mut self) -> &mut R {
+        &mut self.inner
+    }
+
+    /// Get a reference to the limiter
+    pub fn limiter(&self) -> &L {
+        &self.limiter
+    }
+
+    /// Get a mutable reference to the limiter
+    pub fn limiter_mut(&mut self) -> &mut L {
+        &mut self.limiter
+    }
+
+    /// Unwrap the wrapped reader
+    pub fn into_inner(self) -> R {
+        self.inner
+    }
+}
+
+impl<R, L> Read for ThrottledReader<R, L>
+where
+    R: Read,
+    L: Inspect + RetryHint,
+{
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return self.inner.read(buf);
+        }
+        let len = acquire_bytes(&mut self.limiter, self.block_size, buf.len())?;
+        self.inner.read(&mut buf[..len])
+    }
+}
+
+/// Bandwidth limiting wrapper for any [`Write`]
+///
+/// Consumes one token per block of bytes before writing, sleeping the current
+/// thread while the limiter is exhausted. Writes are shortened to what the
+/// limiter currently admits, [`Write::write_all`] takes care of the rest.
+/// A partial block at the end of a write consumes a whole token.
+///
+/// Writing fails with [`io::ErrorKind::Other`] if the limiter can never admit
+/// a single block.
+///
+/// # Generic arguments
+/// * `W` - wrapped writer
+/// * `L` - limiter, e.g. a [`crate::TokenBucket`] with a rate in blocks per second
+pub struct ThrottledWriter<W, L> {
+    inner: W,
+    limiter: L,
+    block_size: usize,
+}
+
+impl<W, L> ThrottledWriter<W, L>
+where
+    W: Write,
+    L: Inspect + RetryHint,
+{
+    /// Wrap a writer, consuming one token per byte
+    ///
+    /// # Arguments
+    /// * `inner` - writer to wrap
+    /// * `limiter` - limiter shaping the bandwidth
+    pub fn new(inner: W, limiter: L) -> Self {
+        Self::with_block_size(inner, limiter, 1)
+    }
+
+    /// Wrap a writer, consuming one token per block of bytes
+    ///
+    /// # Arguments
+    /// * `inner` - writer to wrap
+    /// * `limiter` - limiter shaping the bandwidth
+    /// * `block_size` - how many bytes a single token covers, at least one
+    pub fn with_block_size(inner: W, limiter: L, block_size: usize) -> Self {
+        Self {
+            inner,
+            limiter,
+            block_size: block_size.max(1),
+        }
+    }
+
+    /// Get a reference to the wrapped writer
+    pub fn get_ref(&self) -> &W {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped writer
+    pub fn get_mut(&mut self) -> &mut W {
+        &mut self.inner
+    }
+
+    /// Get a reference to the limiter
+    pub fn limiter(&self) -> &L {
+        &self.limiter
+    }
+
+    /// Get a mutable reference to the limiter
+    pub fn limiter_mut(&mut self) -> &mut L {
+        &mut self.limiter
+    }
+
+    /// Unwrap the wrapped writer
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W, L> Write for ThrottledWriter<W, L>
+where
+    W: Write,
+    L: Inspect + RetryHint,
+{
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        if buf.is_empty() {
+            return self.inner.write(buf);
+        }
+        let len = acquire_bytes(&mut self.limiter, self.block_size, buf.len())?;
+        self.inner.write(&buf[..len])
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(all(test, feature = "token-bucket"))]
+mod tests {
+    use std::{
+        io::{Read, Write},
+        time::{Duration, Instant},
+    };
+
+    use crate::token_bucket;
+
+    use super::{ThrottledReader, ThrottledWriter};
+
+    #[test]
+    fn verify_throttled_reader() {
+        let data = [7u8; 10];
+        let mut r = ThrottledReader::new(&data[..], token_bucket(1000, 4));
+        let start = Instant::now();
+
+        // Shortened to the available budget
+        let mut buf = [0u8; 10];
+        assert_eq!(r.read(&mut buf).unwrap(), 4);
+
+        let mut rest = Vec::new();
+        assert_eq!(r.read_to_end(&mut rest).unwrap(), 6);
+        assert_eq!(rest, [7u8; 6]);
+        // Six bytes had to be waited for
+        assert!(start.elapsed() >= Duration::from_millis(6));
+
+        // Can never succeed
+        let mut r = ThrottledReader::new(&data[..], token_bucket(1000, 0));
+        assert!(r.read(&mut buf).is_err());
+    }
+
+    #[test]
+    fn verify_throttled_writer() {
+        // Two blocks of two bytes at once
+        let mut w = ThrottledWriter::with_block_size(Vec::new(), token_bucket(1000, 2), 2);
+        let start = Instant::now();
+
+        assert_eq!(w.write(&[1, 2, 3, 4, 5]).unwrap(), 4);
+        w.write_all(&[6, 7, 8, 9, 10]).unwrap();
+        assert_eq!(w.get_ref(), &[1, 2, 3, 4, 6, 7, 8, 9, 10]);
+        // The last block of the write_all had to be waited for
+        assert!(start.elapsed() >= Duration::from_millis(1));
+    }
+}
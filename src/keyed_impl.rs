@@ -0,0 +1,1035 @@
+//! Keyed limiters holding a separate limiter per key
+
+#[cfg(feature = "alloc")]
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{
+    hash::{Hash, Hasher},
+    time::Duration,
+};
+#[cfg(feature = "std")]
+use std::collections::HashMap;
+
+use crate::{Limiter, LimiterResult, PhaseOffset, Stats};
+
+/// Seeded FNV-1a hasher with a final avalanche step
+///
+/// Deterministic across processes and builds, unlike the randomly seeded
+/// `std` hasher, so e.g. every replica derives the same phase for a key.
+pub(crate) struct SeededHasher(u64);
+
+impl SeededHasher {
+    pub(crate) fn new(seed: u64) -> Self {
+        Self(0xcbf2_9ce4_8422_2325 ^ seed.wrapping_mul(0x9e37_79b9_7f4a_7c15))
+    }
+}
+
+impl Hasher for SeededHasher {
+    fn write(&mut self, bytes: &[u8]) {
+        for &byte in bytes {
+            self.0 ^= byte as u64;
+            self.0 = self.0.wrapping_mul(0x0100_0000_01b3);
+        }
+    }
+
+    fn finish(&self) -> u64 {
+        // splitmix64 finalizer
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58_476d_1ce4_e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d0_49bb_1331_11eb);
+        z ^ (z >> 31)
+    }
+}
+
+/// Storage of the per-key limiters of a keyed limiter
+trait KeyStore<K, V> {
+    /// Get the value of the key, inserting one from `make` if missing
+    fn get_or_insert_with(&mut self, key: &K, make: impl FnOnce(&K) -> V) -> &mut V;
+
+    /// Get the value of the key, if it is stored
+    fn get(&self, key: &K) -> Option<&V>;
+
+    /// Drop the value of the key, returning it if it was stored
+    fn remove(&mut self, key: &K) -> Option<V>;
+
+    /// Keep only the values for which the predicate returns `true`
+    fn retain(&mut self, predicate: impl FnMut(&K, &mut V) -> bool);
+
+    /// Number of stored keys
+    fn len(&self) -> usize;
+
+    /// Call the closure for every stored key and value
+    #[cfg(feature = "alloc")]
+    fn for_each(&self, f: impl FnMut(&K, &V));
+}
+
+#[cfg(feature = "std")]
+impl<K, V> KeyStore<K, V> for HashMap<K, V>
+where
+    K: Eq + Hash + Clone,
+{
+    fn get_or_insert_with(&mut self, key: &K, make: impl FnOnce(&K) -> V) -> &mut V {
+        if !self.contains_key(key) {
+            let value = make(key);
+            self.insert(key.clone(), value);
+        }
+        self.get_mut(key).expect("limiter was just inserted")
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        HashMap::get(self, key)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        HashMap::remove(self, key)
+    }
+
+    fn retain(&mut self, mut predicate: impl FnMut(&K, &mut V) -> bool) {
+        HashMap::retain(self, |key, value| predicate(key, value));
+    }
+
+    fn len(&self) -> usize {
+        HashMap::len(self)
+    }
+
+    fn for_each(&self, mut f: impl FnMut(&K, &V)) {
+        self.iter().for_each(|(key, value)| f(key, value));
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<K, V> KeyStore<K, V> for BTreeMap<K, V>
+where
+    K: Ord + Clone,
+{
+    fn get_or_insert_with(&mut self, key: &K, make: impl FnOnce(&K) -> V) -> &mut V {
+        if !self.contains_key(key) {
+            let value = make(key);
+            self.insert(key.clone(), value);
+        }
+        self.get_mut(key).expect("limiter was just inserted")
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        BTreeMap::get(self, key)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        BTreeMap::remove(self, key)
+    }
+
+    fn retain(&mut self, mut predicate: impl FnMut(&K, &mut V) -> bool) {
+        BTreeMap::retain(self, |key, value| predicate(key, value));
+    }
+
+    fn len(&self) -> usize {
+        BTreeMap::len(self)
+    }
+
+    fn for_each(&self, mut f: impl FnMut(&K, &V)) {
+        self.iter().for_each(|(key, value)| f(key, value));
+    }
+}
+
+/// Slot of a [`LruSlots`]
+#[derive(Debug, Clone)]
+struct Slot<K, V> {
+    key: K,
+    value: V,
+    /// Value of the use counter at the latest access
+    last_used: u64,
+}
+
+/// Inline storage of up to `N` keys, evicting the least recently used one
+#[derive(Debug, Clone)]
+struct LruSlots<K, V, const N: usize> {
+    slots: [Option<Slot<K, V>>; N],
+    uses: u64,
+    evictions: u64,
+}
+
+impl<K, V, const N: usize> LruSlots<K, V, N>
+where
+    K: Eq,
+{
+    fn new() -> Self {
+        Self {
+            slots: core::array::from_fn(|_| None),
+            uses: 0,
+            evictions: 0,
+        }
+    }
+
+    fn iter(&self) -> impl Iterator<Item = (&K, &V)> {
+        self.slots.iter().flatten().map(|s| (&s.key, &s.value))
+    }
+
+    fn position(&self, key: &K) -> Option<usize> {
+        self.slots
+            .iter()
+            .position(|slot| matches!(slot, Some(s) if s.key == *key))
+    }
+
+    fn free_slot(&self) -> Option<usize> {
+        self.slots.iter().position(|slot| slot.is_none())
+    }
+
+    fn least_recently_used(&self) -> Option<usize> {
+        self.slots
+            .iter()
+            .enumerate()
+            .filter_map(|(i, slot)| slot.as_ref().map(|s| (i, s.last_used)))
+            .min_by_key(|(_, last_used)| *last_used)
+            .map(|(i, _)| i)
+    }
+}
+
+impl<K, V, const N: usize> KeyStore<K, V> for LruSlots<K, V, N>
+where
+    K: Eq + Clone,
+{
+    /// # Panics
+    /// If `N` is zero
+    fn get_or_insert_with(&mut self, key: &K, make: impl FnOnce(&K) -> V) -> &mut V {
+        self.uses += 1;
+        let index = match self.position(key) {
+            Some(index) => index,
+            None => {
+                let index = self.free_slot().unwrap_or_else(|| {
+                    self.evictions += 1;
+                    self.least_recently_used()
+                        .expect("at least one key slot required")
+                });
+                self.slots[index] = Some(Slot {
+                    key: key.clone(),
+                    value: make(key),
+                    last_used: 0,
+                });
+                index
+            }
+        };
+        let slot = self.slots[index].as_mut().expect("slot was just looked up");
+        slot.last_used = self.uses;
+        &mut slot.value
+    }
+
+    fn get(&self, key: &K) -> Option<&V> {
+        self.iter().find(|(k, _)| *k == key).map(|(_, v)| v)
+    }
+
+    fn remove(&mut self, key: &K) -> Option<V> {
+        let index = self.position(key)?;
+        self.slots[index].take().map(|s| s.value)
+    }
+
+    fn retain(&mut self, mut predicate: impl FnMut(&K, &mut V) -> bool) {
+        for slot in self.slots.iter_mut() {
+            let keep = match slot {
+                Some(s) => predicate(&s.key, &mut s.value),
+                None => true,
+            };
+            if !keep {
+                *slot = None;
+            }
+        }
+    }
+
+    fn len(&self) -> usize {
+        self.slots.iter().flatten().count()
+    }
+
+    #[cfg(feature = "alloc")]
+    fn for_each(&self, mut f: impl FnMut(&K, &V)) {
+        self.iter().for_each(|(key, value)| f(key, value));
+    }
+}
+
+/// Tokens consumed and rejected for a key over a single accounting window
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct UsageTotals {
+    /// Total amount of tokens consumed by successful consumes
+    pub consumed: u64,
+    /// Total amount of tokens asked for by rejected consumes
+    pub rejected: u64,
+}
+
+impl UsageTotals {
+    /// Record the result of a single consume
+    pub fn record(&mut self, tokens: u64, result: &LimiterResult) {
+        if result.is_ok() {
+            self.consumed = self.consumed.saturating_add(tokens);
+        } else {
+            self.rejected = self.rejected.saturating_add(tokens);
+        }
+    }
+}
+
+/// Usage of a single key of a keyed limiter
+///
+/// The accounting windows are delimited by the user, see
+/// [`BoundedKeyedLimiter::roll_usage`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct KeyUsage {
+    /// Totals of the ongoing accounting window
+    pub current: UsageTotals,
+    /// Totals of the latest completed accounting window
+    pub previous: UsageTotals,
+}
+
+/// Per-key limiter together with its usage
+#[derive(Debug, Clone)]
+struct Tracked<L> {
+    limiter: L,
+    usage: KeyUsage,
+}
+
+/// Logic shared by all keyed limiters, generic over the key storage
+#[derive(Debug, Clone)]
+struct KeyedCore<S, F, B> {
+    store: S,
+    factory: F,
+    bypass: Option<B>,
+    stats: Stats,
+    bypassed: u64,
+}
+
+impl<S, F, B> KeyedCore<S, F, B> {
+    fn new(store: S, factory: F) -> Self {
+        Self {
+            store,
+            factory,
+            bypass: None,
+            stats: Stats::default(),
+            bypassed: 0,
+        }
+    }
+
+    fn with_bypass<P>(self, predicate: P) -> KeyedCore<S, F, P> {
+        KeyedCore {
+            store: self.store,
+            factory: self.factory,
+            bypass: Some(predicate),
+            stats: self.stats,
+            bypassed: self.bypassed,
+        }
+    }
+
+    fn with_hashed_phase<K, L>(self) -> KeyedCore<S, impl FnMut(&K) -> L, B>
+    where
+        K: Hash,
+        L: PhaseOffset,
+        F: FnMut(&K) -> L,
+    {
+        let mut factory = self.factory;
+        KeyedCore {
+            store: self.store,
+            factory: move |key: &K| {
+                let limiter = factory(key);
+                let mut hasher = SeededHasher::new(0);
+                key.hash(&mut hasher);
+                let width_ns = limiter.window_width().as_nanos().max(1);
+                let offset_ns = (hasher.finish() as u128 % width_ns) as u64;
+                limiter.with_phase_offset(Duration::from_nanos(offset_ns))
+            },
+            bypass: self.bypass,
+            stats: self.stats,
+            bypassed: self.bypassed,
+        }
+    }
+
+    fn try_consume<K, L>(&mut self, key: &K, tokens: u64) -> LimiterResult
+    where
+        S: KeyStore<K, Tracked<L>>,
+        L: Limiter,
+        F: FnMut(&K) -> L,
+        B: Fn(&K) -> bool,
+    {
+        let result = if matches!(&self.bypass, Some(bypass) if bypass(key)) {
+            self.bypassed = self.bypassed.saturating_add(1);
+            Ok(())
+        } else {
+            let tracked = self.tracked_mut(key);
+            let result = tracked.limiter.try_consume(tokens);
+            tracked.usage.current.record(tokens, &result);
+            result
+        };
+        self.stats.record(tokens, &result);
+        result
+    }
+
+    fn tracked_mut<K, L>(&mut self, key: &K) -> &mut Tracked<L>
+    where
+        S: KeyStore<K, Tracked<L>>,
+        F: FnMut(&K) -> L,
+    {
+        let factory = &mut self.factory;
+        self.store.get_or_insert_with(key, |key| Tracked {
+            limiter: factory(key),
+            usage: KeyUsage::default(),
+        })
+    }
+
+    fn remove_idle<K, L>(&mut self, idle: Duration)
+    where
+        S: KeyStore<K, Tracked<L>>,
+        L: Limiter,
+    {
+        self.store
+            .retain(|_, tracked| !matches!(tracked.limiter.idle_for(), Some(i) if i >= idle));
+    }
+
+    fn update_usage<K, L>(&mut self, mut update: impl FnMut(&mut KeyUsage))
+    where
+        S: KeyStore<K, Tracked<L>>,
+    {
+        self.store.retain(|_, tracked| {
+            update(&mut tracked.usage);
+            true
+        });
+    }
+
+    #[cfg(feature = "alloc")]
+    fn usage_report<K, L>(&self) -> Vec<(K, KeyUsage)>
+    where
+        S: KeyStore<K, Tracked<L>>,
+        K: Clone,
+    {
+        let mut report = Vec::with_capacity(self.store.len());
+        self.store
+            .for_each(|key, tracked| report.push((key.clone(), tracked.usage)));
+        report.sort_by_key(|(_, usage)| core::cmp::Reverse(usage.current.consumed));
+        report
+    }
+}
+
+/// Constructor shared by all keyed limiters
+macro_rules! keyed_new {
+    ($store:expr) => {
+        /// Initialize a new empty keyed limiter
+        ///
+        /// # Arguments
+        /// * `factory` - closure creating the limiter for a key seen for the first time
+        pub fn new(factory: F) -> Self {
+            Self {
+                core: KeyedCore::new($store, factory),
+            }
+        }
+    };
+}
+
+/// Inherent methods shared by all keyed limiters, delegating to their [`KeyedCore`]
+macro_rules! keyed_methods {
+    ($name:ident, $with_bypass:ty, $with_hashed_phase:ty) => {
+        /// Let the keys matching the predicate skip limiting entirely
+        ///
+        /// Consumes for bypassed keys are always admitted without creating a
+        /// limiter for the key, but are still counted in [`Self::stats`].
+        /// E.g. for health checks or an allowlist of internal addresses.
+        ///
+        /// # Arguments
+        /// * `predicate` - closure returning `true` for keys to bypass
+        pub fn with_bypass<P>(self, predicate: P) -> $with_bypass
+        where
+            P: Fn(&K) -> bool,
+        {
+            $name {
+                core: self.core.with_bypass(predicate),
+            }
+        }
+
+        /// Offset the window phase of each key by a hash of the key
+        ///
+        /// Per-key windows created at the same moment, e.g. when a burst of
+        /// clients connects right after a deploy, would otherwise all reset
+        /// and admit their next bursts at the same instant. The offset is a
+        /// deterministic hash of the key modulo the window width, so every
+        /// replica shifts the windows of a key alike. Only limiters created
+        /// afterwards are shifted.
+        pub fn with_hashed_phase(self) -> $with_hashed_phase
+        where
+            K: Hash,
+            L: PhaseOffset,
+        {
+            $name {
+                core: self.core.with_hashed_phase(),
+            }
+        }
+
+        /// Try to consume tokens from the limiter of the given key
+        ///
+        /// # Arguments
+        /// * `key` - key to consume for, its limiter is created if needed
+        /// * `tokens` - how many tokens to consume
+        ///
+        /// # Returns
+        /// * `Ok(())` - token consumed, or the key is bypassed
+        /// * `Err(CantConsume)` - not enough tokens left for this key
+        pub fn try_consume(&mut self, key: &K, tokens: u64) -> LimiterResult {
+            self.core.try_consume(key, tokens)
+        }
+
+        /// Get the limiter of the given key, creating it if needed
+        ///
+        /// A [`BoundedKeyedLimiter`] with all slots in use evicts its least
+        /// recently used key first. Consumes made directly on the limiter are
+        /// not counted in [`Self::stats`].
+        ///
+        /// # Panics
+        /// If the limiter is a [`BoundedKeyedLimiter`] with `N` of zero
+        pub fn limiter_mut(&mut self, key: &K) -> &mut L {
+            &mut self.core.tracked_mut(key).limiter
+        }
+
+        /// Get the limiter of the given key, if it has been created
+        pub fn get(&self, key: &K) -> Option<&L> {
+            self.core.store.get(key).map(|tracked| &tracked.limiter)
+        }
+
+        /// Drop the limiter of the given key, returning it if it existed
+        ///
+        /// The usage of the key is dropped along with it.
+        pub fn remove(&mut self, key: &K) -> Option<L> {
+            self.core.store.remove(key).map(|tracked| tracked.limiter)
+        }
+
+        /// Keep only the limiters for which the predicate returns `true`
+        pub fn retain(&mut self, mut predicate: impl FnMut(&K, &mut L) -> bool) {
+            self.core
+                .store
+                .retain(|key, tracked| predicate(key, &mut tracked.limiter));
+        }
+
+        /// Drop the limiters that haven't been consumed from for at least `idle`
+        ///
+        /// Limiters not tracking their idle time, see [`Limiter::idle_for`], are kept.
+        pub fn remove_idle(&mut self, idle: Duration) {
+            self.core.remove_idle(idle);
+        }
+
+        /// Number of keys with a limiter
+        pub fn len(&self) -> usize {
+            self.core.store.len()
+        }
+
+        /// `true` if no limiters have been created
+        pub fn is_empty(&self) -> bool {
+            self.len() == 0
+        }
+
+        /// Statistics of all consumes through [`Self::try_consume`], bypassed ones included
+        pub fn stats(&self) -> &Stats {
+            &self.core.stats
+        }
+
+        /// How many consumes were admitted because the key was bypassed
+        pub fn bypassed(&self) -> u64 {
+            self.core.bypassed
+        }
+
+        /// Restart collecting [`Self::stats`] and [`Self::bypassed`] from zero
+        pub fn reset_stats(&mut self) {
+            self.core.stats = Stats::default();
+            self.core.bypassed = 0;
+        }
+
+        /// Usage of the given key, if it has a limiter
+        ///
+        /// Only consumes through [`Self::try_consume`] are accounted, bypassed
+        /// keys have no usage of their own.
+        pub fn usage(&self, key: &K) -> Option<KeyUsage> {
+            self.core.store.get(key).map(|tracked| tracked.usage)
+        }
+
+        /// End the current accounting window of all keys
+        ///
+        /// The current totals become the previous ones, and the current ones
+        /// restart from zero. Call this e.g. on every billing period.
+        pub fn roll_usage(&mut self) {
+            self.core.update_usage(|usage| {
+                usage.previous = usage.current;
+                usage.current = UsageTotals::default();
+            });
+        }
+
+        /// Clear both the current and the previous usage of all keys
+        pub fn reset_usage(&mut self) {
+            self.core.update_usage(|usage| *usage = KeyUsage::default());
+        }
+
+        /// Usage of all keys, the heaviest consumers of the current window first
+        ///
+        /// The report is a plain [`Vec`], so it can be sorted by any other
+        /// criteria as well.
+        #[cfg(feature = "alloc")]
+        pub fn usage_report(&self) -> Vec<(K, KeyUsage)> {
+            self.core.usage_report()
+        }
+
+        /// Take the [`Self::usage_report`] and [`Self::roll_usage`] in one go
+        #[cfg(feature = "alloc")]
+        pub fn drain_usage_report(&mut self) -> Vec<(K, KeyUsage)> {
+            let report = self.usage_report();
+            self.roll_usage();
+            report
+        }
+    };
+}
+
+/// Keyed limiter backed by a [`BTreeMap`]
+///
+/// Lazily creates a separate limiter for each key from a factory closure,
+/// e.g. for rate limiting per client or peer address. Only requires an
+/// allocator, so it works on `no_std` targets with a heap as well.
+///
+/// Entries are never dropped automatically, use
+/// [`BTreeKeyedLimiter::remove_idle`] or [`BTreeKeyedLimiter::retain`] to
+/// bound the memory usage under key churn.
+///
+/// # Generic arguments
+/// * `K` - key type
+/// * `L` - per-key limiter
+/// * `F` - factory closure creating the limiter of a new key
+/// * `B` - bypass predicate, see [`BTreeKeyedLimiter::with_bypass`]
+#[cfg(feature = "alloc")]
+#[derive(Debug, Clone)]
+pub struct BTreeKeyedLimiter<K, L, F, B = fn(&K) -> bool>
+where
+    K: Ord + Clone,
+    L: Limiter,
+    F: FnMut(&K) -> L,
+    B: Fn(&K) -> bool,
+{
+    core: KeyedCore<BTreeMap<K, Tracked<L>>, F, B>,
+}
+
+#[cfg(feature = "alloc")]
+impl<K, L, F> BTreeKeyedLimiter<K, L, F>
+where
+    K: Ord + Clone,
+    L: Limiter,
+    F: FnMut(&K) -> L,
+{
+    keyed_new!(BTreeMap::new());
+}
+
+#[cfg(feature = "alloc")]
+impl<K, L, F, B> BTreeKeyedLimiter<K, L, F, B>
+where
+    K: Ord + Clone,
+    L: Limiter,
+    F: FnMut(&K) -> L,
+    B: Fn(&K) -> bool,
+{
+    keyed_methods!(
+        BTreeKeyedLimiter,
+        BTreeKeyedLimiter<K, L, F, P>,
+        BTreeKeyedLimiter<K, L, impl FnMut(&K) -> L, B>
+    );
+
+    /// Iterate over all keys and their limiters in key order
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &L)> {
+        self.core
+            .store
+            .iter()
+            .map(|(key, tracked)| (key, &tracked.limiter))
+    }
+}
+
+/// Keyed limiter backed by a [`HashMap`]
+///
+/// Lazily creates a separate limiter for each key from a factory closure,
+/// e.g. for rate limiting per client or IP address.
+///
+/// Entries are never dropped automatically, use
+/// [`KeyedLimiter::remove_idle`] or [`KeyedLimiter::retain`] to bound the
+/// memory usage under key churn.
+///
+/// # Generic arguments
+/// * `K` - key type
+/// * `L` - per-key limiter
+/// * `F` - factory closure creating the limiter of a new key
+/// * `B` - bypass predicate, see [`KeyedLimiter::with_bypass`]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone)]
+pub struct KeyedLimiter<K, L, F, B = fn(&K) -> bool>
+where
+    K: Eq + Hash + Clone,
+    L: Limiter,
+    F: FnMut(&K) -> L,
+    B: Fn(&K) -> bool,
+{
+    core: KeyedCore<HashMap<K, Tracked<L>>, F, B>,
+}
+
+#[cfg(feature = "std")]
+impl<K, L, F> KeyedLimiter<K, L, F>
+where
+    K: Eq + Hash + Clone,
+    L: Limiter,
+    F: FnMut(&K) -> L,
+{
+    keyed_new!(HashMap::new());
+}
+
+#[cfg(feature = "std")]
+impl<K, L, F, B> KeyedLimiter<K, L, F, B>
+where
+    K: Eq + Hash + Clone,
+    L: Limiter,
+    F: FnMut(&K) -> L,
+    B: Fn(&K) -> bool,
+{
+    keyed_methods!(
+        KeyedLimiter,
+        KeyedLimiter<K, L, F, P>,
+        KeyedLimiter<K, L, impl FnMut(&K) -> L, B>
+    );
+
+    /// Iterate over all keys and their limiters in arbitrary order
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &L)> {
+        self.core
+            .store
+            .iter()
+            .map(|(key, tracked)| (key, &tracked.limiter))
+    }
+}
+
+/// Keyed limiter with a fixed maximum number of keys
+///
+/// Like [`KeyedLimiter`](crate::KeyedLimiter), but stores up to `N` keys
+/// inline without any allocations, so the memory usage stays bounded under
+/// key churn and it works on `no_std` targets without a heap. When a new key
+/// is seen with all slots in use, the least recently used key is evicted.
+/// Keys can also be evicted by idle time with [`BoundedKeyedLimiter::remove_idle`].
+///
+/// Lookups scan all slots, so `N` is best kept small.
+///
+/// # Generic arguments
+/// * `K` - key type
+/// * `L` - per-key limiter
+/// * `F` - factory closure creating the limiter of a new key
+/// * `N` - maximum number of keys
+/// * `B` - bypass predicate, see [`BoundedKeyedLimiter::with_bypass`]
+#[derive(Debug, Clone)]
+pub struct BoundedKeyedLimiter<K, L, F, const N: usize, B = fn(&K) -> bool>
+where
+    K: Eq + Clone,
+    L: Limiter,
+    F: FnMut(&K) -> L,
+    B: Fn(&K) -> bool,
+{
+    core: KeyedCore<LruSlots<K, Tracked<L>, N>, F, B>,
+}
+
+impl<K, L, F, const N: usize> BoundedKeyedLimiter<K, L, F, N>
+where
+    K: Eq + Clone,
+    L: Limiter,
+    F: FnMut(&K) -> L,
+{
+    keyed_new!(LruSlots::new());
+}
+
+impl<K, L, F, const N: usize, B> BoundedKeyedLimiter<K, L, F, N, B>
+where
+    K: Eq + Clone,
+    L: Limiter,
+    F: FnMut(&K) -> L,
+    B: Fn(&K) -> bool,
+{
+    keyed_methods!(
+        BoundedKeyedLimiter,
+        BoundedKeyedLimiter<K, L, F, N, P>,
+        BoundedKeyedLimiter<K, L, impl FnMut(&K) -> L, N, B>
+    );
+
+    /// Iterate over all keys and their limiters in arbitrary order
+    pub fn iter(&self) -> impl Iterator<Item = (&K, &L)> {
+        self.core
+            .store
+            .iter()
+            .map(|(key, tracked)| (key, &tracked.limiter))
+    }
+
+    /// Maximum number of keys
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// How many keys have been evicted to make room for new ones
+    pub fn evictions(&self) -> u64 {
+        self.core.store.evictions
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::CreditBucket;
+
+    use super::BoundedKeyedLimiter;
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn verify_keyed() {
+        use super::BTreeKeyedLimiter;
+
+        let mut k = BTreeKeyedLimiter::new(|_: &u32| CreditBucket::new(2, 2));
+        assert!(k.is_empty());
+
+        assert!(k.try_consume(&1, 2).is_ok());
+        assert!(k.try_consume(&1, 1).is_err());
+        assert!(k.try_consume(&2, 1).is_ok());
+        assert_eq!(k.len(), 2);
+        assert_eq!(k.get(&2).map(|l| l.tokens()), Some(1));
+
+        // Drop exhausted limiters
+        k.retain(|_, l| l.tokens() > 0);
+        assert!(k.get(&1).is_none());
+        assert!(k.try_consume(&1, 2).is_ok());
+    }
+
+    #[cfg(all(feature = "std", feature = "token-bucket"))]
+    #[test]
+    fn verify_hash_keyed() {
+        use core::time::Duration;
+
+        use crate::{mock_assets::MockClock, TokenBucket};
+
+        use super::KeyedLimiter;
+
+        let clock = MockClock::new();
+        let mut k = KeyedLimiter::new(|_: &&str| {
+            TokenBucket::new_with_time_provider(1, 2, || clock.step(0))
+        });
+
+        assert!(k.try_consume(&"a", 2).is_ok());
+        assert!(k.try_consume(&"a", 1).is_err());
+        assert!(k.try_consume(&"b", 1).is_ok());
+        assert_eq!(k.len(), 2);
+
+        // T = 2s, "a" and "b" last consumed at 0s, "c" now
+        clock.step(2_000_000);
+        assert!(k.try_consume(&"c", 1).is_ok());
+        k.remove_idle(Duration::from_secs(1));
+        assert_eq!(k.len(), 1);
+        assert!(k.get(&"c").is_some());
+    }
+
+    #[cfg(feature = "fixed-window")]
+    #[test]
+    fn verify_hashed_phase() {
+        use core::time::Duration;
+
+        use crate::{mock_assets::MockClock, FixedWindow};
+
+        let clock = MockClock::new();
+        let mut k = BoundedKeyedLimiter::<_, _, _, 2>::new(|_: &u32| {
+            FixedWindow::new_with_time_provider(1, 10, || clock.step(0))
+        })
+        .with_hashed_phase();
+        assert!(k.try_consume(&1, 1).is_ok());
+        assert!(k.try_consume(&2, 1).is_ok());
+
+        // Windows of different keys end at different instants
+        let reset = |k: &BoundedKeyedLimiter<_, FixedWindow<_>, _, 2>, key| {
+            k.get(&key).unwrap().time_until_reset()
+        };
+        let (first, second) = (reset(&k, 1), reset(&k, 2));
+        assert_ne!(first, second);
+        assert!(first < Duration::from_millis(10) && second < Duration::from_millis(10));
+
+        // Only the key whose window ends first is replenished
+        let (early, late) = if first < second { (1, 2) } else { (2, 1) };
+        clock.step(first.min(second).as_micros() as u64 + 1);
+        assert!(k.try_consume(&early, 1).is_ok());
+        assert!(k.try_consume(&late, 1).is_err());
+    }
+
+    #[test]
+    fn verify_bounded_keyed() {
+        let mut k = BoundedKeyedLimiter::<_, _, _, 2>::new(|_: &u32| CreditBucket::new(2, 2));
+        assert_eq!(k.capacity(), 2);
+
+        assert!(k.try_consume(&1, 2).is_ok());
+        assert!(k.try_consume(&2, 1).is_ok());
+        assert!(k.try_consume(&1, 1).is_err());
+        assert_eq!(k.len(), 2);
+
+        // Key 2 is the least recently used one
+        assert!(k.try_consume(&3, 1).is_ok());
+        assert_eq!(k.evictions(), 1);
+        assert!(k.get(&2).is_none());
+        assert_eq!(k.get(&1).map(|l| l.tokens()), Some(0));
+
+        // Key 1 evicted now, and starts over when seen again
+        assert!(k.try_consume(&2, 2).is_ok());
+        assert!(k.get(&1).is_none());
+        assert!(k.try_consume(&1, 2).is_ok());
+        assert_eq!(k.evictions(), 3);
+
+        assert!(k.remove(&1).is_some());
+        assert_eq!(k.len(), 1);
+
+        // Same retain as the map backed limiters
+        k.retain(|_, l| l.tokens() > 0);
+        assert!(k.is_empty());
+    }
+
+    #[test]
+    fn verify_bypass() {
+        let mut k = BoundedKeyedLimiter::<_, _, _, 2>::new(|_: &u32| CreditBucket::new(1, 1))
+            .with_bypass(|key: &u32| *key == 0);
+
+        assert!(k.try_consume(&1, 1).is_ok());
+        assert!(k.try_consume(&1, 1).is_err());
+        // The bypassed key isn't limited, and doesn't take up a slot
+        assert!(k.try_consume(&0, 5).is_ok());
+        assert!(k.try_consume(&0, 5).is_ok());
+        assert!(k.get(&0).is_none());
+        assert_eq!(k.len(), 1);
+
+        // Bypassed consumes still show up in the stats
+        assert_eq!(k.bypassed(), 2);
+        assert_eq!(k.stats().accepted, 3);
+        assert_eq!(k.stats().rejected, 1);
+        assert_eq!(k.stats().tokens_granted, 11);
+
+        k.reset_stats();
+        assert_eq!(k.bypassed(), 0);
+        assert_eq!(*k.stats(), crate::Stats::default());
+    }
+
+    #[cfg(feature = "std")]
+    #[test]
+    fn verify_map_bypass() {
+        use std::collections::HashSet;
+
+        use super::{BTreeKeyedLimiter, KeyedLimiter};
+
+        let allowlist: HashSet<&str> = ["health"].into_iter().collect();
+        let mut hash = KeyedLimiter::new(|_: &&str| CreditBucket::new(1, 1))
+            .with_bypass(|key: &&str| allowlist.contains(key));
+        let mut tree = BTreeKeyedLimiter::new(|_: &&str| CreditBucket::new(1, 1))
+            .with_bypass(|key: &&str| allowlist.contains(key));
+
+        for _ in 0..3 {
+            assert!(hash.try_consume(&"health", 1).is_ok());
+            assert!(tree.try_consume(&"health", 1).is_ok());
+        }
+        assert!(hash.try_consume(&"client", 2).is_err());
+        assert!(tree.try_consume(&"client", 2).is_err());
+
+        assert!(hash.get(&"health").is_none());
+        assert!(tree.get(&"health").is_none());
+        assert_eq!(hash.bypassed(), 3);
+        assert_eq!(tree.bypassed(), 3);
+        assert_eq!(hash.stats(), tree.stats());
+        assert_eq!(hash.stats().rejected, 1);
+    }
+
+    #[test]
+    fn verify_usage() {
+        use super::{KeyUsage, UsageTotals};
+
+        let mut k = BoundedKeyedLimiter::<_, _, _, 2>::new(|_: &u32| CreditBucket::new(3, 3));
+        assert!(k.usage(&1).is_none());
+
+        assert!(k.try_consume(&1, 2).is_ok());
+        assert!(k.try_consume(&1, 2).is_err());
+        assert_eq!(
+            k.usage(&1),
+            Some(KeyUsage {
+                current: UsageTotals {
+                    consumed: 2,
+                    rejected: 2
+                },
+                previous: UsageTotals::default(),
+            })
+        );
+
+        k.roll_usage();
+        assert!(k.try_consume(&1, 1).is_ok());
+        let usage = k.usage(&1).unwrap();
+        assert_eq!(usage.current.consumed, 1);
+        assert_eq!(usage.previous.consumed, 2);
+
+        k.reset_usage();
+        assert_eq!(k.usage(&1), Some(KeyUsage::default()));
+    }
+
+    #[cfg(feature = "alloc")]
+    #[test]
+    fn verify_usage_report() {
+        use alloc::vec::Vec;
+
+        use super::{BTreeKeyedLimiter, UsageTotals};
+
+        let mut k = BTreeKeyedLimiter::new(|_: &u32| CreditBucket::new(5, 5))
+            .with_bypass(|key: &u32| *key == 0);
+        assert!(k.try_consume(&1, 1).is_ok());
+        assert!(k.try_consume(&2, 4).is_ok());
+        assert!(k.try_consume(&2, 4).is_err());
+        assert!(k.try_consume(&0, 9).is_ok());
+
+        // Heaviest consumer first, bypassed keys aren't accounted
+        let report = k.drain_usage_report();
+        let keys: Vec<_> = report.iter().map(|(key, _)| *key).collect();
+        assert_eq!(keys, [2, 1]);
+        assert_eq!(
+            report[0].1.current,
+            UsageTotals {
+                consumed: 4,
+                rejected: 4
+            }
+        );
+
+        // Draining rolled the window
+        let report = k.usage_report();
+        assert!(report
+            .iter()
+            .all(|(_, u)| u.current == UsageTotals::default()));
+        assert_eq!(
+            report.iter().map(|(_, u)| u.previous.consumed).sum::<u64>(),
+            5
+        );
+    }
+
+    #[cfg(all(feature = "alloc", feature = "token-bucket"))]
+    #[test]
+    fn verify_remove_idle() {
+        use core::time::Duration;
+
+        use crate::{mock_assets::MockClock, TokenBucket};
+
+        use super::BTreeKeyedLimiter;
+
+        let clock = MockClock::new();
+        let bucket = |_: &u32| TokenBucket::new_with_time_provider(1, 2, || clock.step(0));
+        let mut tree = BTreeKeyedLimiter::new(bucket);
+        let mut bounded = BoundedKeyedLimiter::<_, _, _, 4>::new(bucket);
+
+        assert!(tree.try_consume(&1, 1).is_ok());
+        assert!(bounded.try_consume(&1, 1).is_ok());
+        // T = 2s, key 1 idle since 0s, key 2 consumed now
+        clock.step(2_000_000);
+        assert!(tree.try_consume(&2, 1).is_ok());
+        assert!(bounded.try_consume(&2, 1).is_ok());
+
+        tree.remove_idle(Duration::from_secs(1));
+        bounded.remove_idle(Duration::from_secs(1));
+        assert_eq!(
+            tree.iter().map(|(k, _)| *k).collect::<alloc::vec::Vec<_>>(),
+            [2]
+        );
+        assert_eq!(
+            bounded
+                .iter()
+                .map(|(k, _)| *k)
+                .collect::<alloc::vec::Vec<_>>(),
+            [2]
+        );
+    }
+}
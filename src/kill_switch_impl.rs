@@ -0,0 +1,254 @@
+//! Emergency kill switch -type wrapper
+
+use core::{
+    ops::Deref,
+    sync::atomic::{AtomicU8, Ordering},
+    time::Duration,
+};
+
+use crate::{
+    CantConsume, Diagnostics, Inspect, Limiter, LimiterKind, LimiterResult, Reset, RetryHint,
+};
+
+/// Operating mode of a [`Switch`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum SwitchMode {
+    /// Consumes are passed through to the wrapped limiter
+    Normal,
+    /// All consumes fail regardless of the wrapped limiter's budget
+    Engaged,
+    /// All consumes succeed without touching the wrapped limiter
+    Bypass,
+}
+
+impl SwitchMode {
+    const fn to_raw(self) -> u8 {
+        match self {
+            SwitchMode::Normal => 0,
+            SwitchMode::Engaged => 1,
+            SwitchMode::Bypass => 2,
+        }
+    }
+
+    const fn from_raw(raw: u8) -> Self {
+        match raw {
+            1 => SwitchMode::Engaged,
+            2 => SwitchMode::Bypass,
+            _ => SwitchMode::Normal,
+        }
+    }
+}
+
+/// Atomic flag controlling one or more [`KillSwitch`] wrappers
+///
+/// The switch only needs a shared reference to be flipped, so a single
+/// `static` switch (or one behind an `Arc`) can control every limiter
+/// in the application at once.
+#[derive(Debug)]
+pub struct Switch(AtomicU8);
+
+impl Switch {
+    /// Create a new switch in [`SwitchMode::Normal`] mode
+    pub const fn new() -> Self {
+        Self(AtomicU8::new(SwitchMode::Normal.to_raw()))
+    }
+
+    /// Make all consumes fail
+    pub fn engage(&self) {
+        self.set_mode(SwitchMode::Engaged);
+    }
+
+    /// Make all consumes succeed, bypassing limiting entirely
+    pub fn bypass(&self) {
+        self.set_mode(SwitchMode::Bypass);
+    }
+
+    /// Return to normal operation
+    pub fn release(&self) {
+        self.set_mode(SwitchMode::Normal);
+    }
+
+    /// Set the operating mode
+    pub fn set_mode(&self, mode: SwitchMode) {
+        self.0.store(mode.to_raw(), Ordering::Relaxed);
+    }
+
+    /// Get the current operating mode
+    pub fn mode(&self) -> SwitchMode {
+        SwitchMode::from_raw(self.0.load(Ordering::Relaxed))
+    }
+}
+
+impl Default for Switch {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Kill switch wrapper for any [`Limiter`]
+///
+/// Consults a [`Switch`] before each consume. While the switch is
+/// engaged every consume fails, and while it is in bypass mode every
+/// consume succeeds. The wrapped limiter is not touched in either case.
+///
+/// # Generic arguments
+/// * `L` - wrapped limiter
+/// * `S` - handle to the switch, e.g. `&'static Switch` or `Arc<Switch>`
+pub struct KillSwitch<L, S>
+where
+    L: Limiter,
+    S: Deref<Target = Switch>,
+{
+    inner: L,
+    switch: S,
+}
+
+impl<L, S> KillSwitch<L, S>
+where
+    L: Limiter,
+    S: Deref<Target = Switch>,
+{
+    /// Wrap a limiter with a kill switch
+    ///
+    /// # Arguments
+    /// * `inner` - limiter to wrap
+    /// * `switch` - handle to the controlling switch
+    pub fn new(inner: L, switch: S) -> Self {
+        Self { inner, switch }
+    }
+
+    /// Get a reference to the controlling switch
+    pub fn switch(&self) -> &Switch {
+        &self.switch
+    }
+
+    /// Get a reference to the wrapped limiter
+    pub fn inner(&self) -> &L {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped limiter
+    pub fn inner_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    /// Unwrap the wrapped limiter
+    pub fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+impl<L, S> Limiter for KillSwitch<L, S>
+where
+    L: Limiter,
+    S: Deref<Target = Switch>,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        match self.switch.mode() {
+            SwitchMode::Normal => self.inner.try_consume(tokens),
+            SwitchMode::Engaged => Err(CantConsume),
+            SwitchMode::Bypass => Ok(()),
+        }
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        match self.switch.mode() {
+            SwitchMode::Normal => self.inner.try_consume_batch(costs),
+            SwitchMode::Engaged => 0,
+            SwitchMode::Bypass => costs.len(),
+        }
+    }
+
+    fn kind(&self) -> LimiterKind {
+        self.inner.kind()
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.inner.name()
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        self.inner.self_check()
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.inner.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        self.inner.idle_for()
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+    }
+}
+
+impl<L, S> Reset for KillSwitch<L, S>
+where
+    L: Reset,
+    S: Deref<Target = Switch>,
+{
+}
+
+impl<L, S> RetryHint for KillSwitch<L, S>
+where
+    L: RetryHint,
+    S: Deref<Target = Switch>,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        match self.switch.mode() {
+            SwitchMode::Normal => self.inner.time_until_available(tokens),
+            SwitchMode::Engaged => None,
+            SwitchMode::Bypass => Some(Duration::ZERO),
+        }
+    }
+}
+
+impl<L, S> Inspect for KillSwitch<L, S>
+where
+    L: Inspect,
+    S: Deref<Target = Switch>,
+{
+    fn tokens_available(&self) -> u64 {
+        match self.switch.mode() {
+            SwitchMode::Normal => self.inner.tokens_available(),
+            SwitchMode::Engaged => 0,
+            SwitchMode::Bypass => u64::MAX,
+        }
+    }
+}
+
+#[cfg(all(test, feature = "fixed-window"))]
+mod tests {
+    use crate::{mock_assets::MockClock, FixedWindow, Limiter};
+
+    use super::{KillSwitch, Switch, SwitchMode};
+
+    #[test]
+    fn verify_modes() {
+        let clock = MockClock::new();
+        let switch = Switch::new();
+        // Each call steps the clock 1us forward, so we stay in the first window
+        let w = FixedWindow::new_with_time_provider(10, 1000, || clock.step(1));
+        let mut k = KillSwitch::new(w, &switch);
+
+        assert_eq!(switch.mode(), SwitchMode::Normal);
+        assert!(k.try_consume(5).is_ok());
+
+        // Engaged, nothing goes through and inner budget is untouched
+        switch.engage();
+        assert!(k.try_consume_one().is_err());
+
+        // Bypass, anything goes through and inner budget is untouched
+        switch.bypass();
+        assert!(k.try_consume(100).is_ok());
+
+        // Back to normal, 5 tokens left in the inner limiter
+        switch.release();
+        assert!(k.try_consume(5).is_ok());
+        assert!(k.try_consume_one().is_err());
+    }
+}
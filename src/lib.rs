@@ -6,9 +6,53 @@
 //! ## Available limiters
 //!
 //! * [`TokenBucket`] - basic token bucket type limiter
+//! * [`DualTokenBucket`] - token bucket enforcing both a committed and a peak rate
+//! * [`AdaptiveLimiter`] - token bucket adapting its rate to downstream pressure (AIMD)
 //! * [`FixedWindow`] - fixed window type limiter
+//! * [`FixedMultiWindow`] - fixed window type limiter with an additional cap over several windows
+//! * [`ScheduledFixedWindow`] - fixed window type limiter with a repeating per-window capacity schedule
+//! * [`TieredLimiter`] - several fixed window quotas of different widths, e.g. per second, minute and hour
+//! * `CalendarWindow` - fixed window type limiter resetting at calendar boundaries, e.g. per UTC day
+//!   or month (requires the `calendar` feature)
 //! * [`SlidingWindowLog`] - sliding window type limiter
+//! * [`SlidingWindowLogDyn`] - sliding window type limiter with a runtime window width, requires `alloc`
 //! * [`SlidingWindowCounter`] - sliding window counter type limiter (an approximation of [`SlidingWindowLog`])
+//!   over one or more buckets per window
+//! * [`SparseSlidingWindowLog`] - sliding window type limiter storing only the moments tokens were consumed
+//! * [`SlidingWindowLogExact`] - sliding window type limiter storing the exact timestamps of consumes
+//! * [`SketchLimiter`] - approximate per-key fixed window limiter for unbounded key spaces in constant memory
+//! * [`CreditBucket`] - token bucket refilled only by explicit calls, for credit-based flow control
+//! * [`Cooldown`] - admits a single consume, then denies everything until a period has elapsed
+//! * [`AtomicTokenBucket`] - lock-free token bucket with externally stored state, e.g. in shared memory
+//! * [`BTreeKeyedLimiter`] - separate limiter per key, requires only an allocator
+//! * [`KeyedLimiter`] - separate limiter per key backed by a `HashMap`, requires `std`
+//! * [`BoundedKeyedLimiter`] - separate limiter per key for a bounded number of keys with LRU eviction
+//! * [`InFlight`] - bound on simultaneously in-flight operations, released by guards or explicitly
+//! * [`PartitionedQuota`] - global quota split into per-worker partitions with periodic rebalancing
+//! * [`HierarchicalTokenBucket`] - per-child token buckets borrowing unused tokens from a shared parent
+//!
+//! ## Wrappers
+//!
+//! * [`KillSwitch`] - emergency switch to fail or bypass all consumes of a limiter
+//! * [`WithGrants`] - temporary quota boosts that expire if left unused
+//! * [`WithReporting`] - periodic statistics reporting without a timer thread
+//! * [`WithHooks`] - callbacks on admitted and rejected consumes, e.g. for metrics or logging
+//! * [`WithStats`] - accept/reject counts and time spent saturated, e.g. for capacity planning
+//! * [`TuningAdvisor`] - evaluate alternative limiter configurations against real traffic
+//! * [`WithAutoscaling`] - grow or shrink the capacity of a limiter based on observed utilization
+//! * `WithFallback` - delegate decisions to a remote `RemoteLimiter`, falling back to a local limiter
+//!   (requires the `async` feature)
+//! * [`SharedLimiter`] - cloneable handle for consuming from the same limiter on many threads,
+//!   requires `std`
+//! * [`AllOf`] - consume from several limiters at once, refunding on rejection, see [`Refund::and`].
+//!   [`TokenBucket`], [`FixedWindow`], [`SlidingWindowLog`] and [`SlidingWindowCounter`] support refunds
+//! * [`Reservation`] - two-phase consume committed or cancelled later, see [`Refund::reserve`]
+//! * [`AdmissionController`] - admit requests only if both a rate limit and an [`InFlight`] bound allow
+//!
+//! ## Event utilities
+//!
+//! * [`Debouncer`] - suppress duplicate events within a quiet period, e.g. of a bouncing button
+//! * [`Throttler`] - run an action at most once per period, without losing the latest request
 //!
 //! ## Platform support
 //!
@@ -16,42 +60,509 @@
 //! functions for instantiating the limiters:
 //!
 //! * [`token_bucket`]
+//! * [`dual_token_bucket`]
 //! * [`fixed_window`]
+//! * [`fixed_multi_window`]
+//! * [`scheduled_fixed_window`]
+//! * [`tiered_limiter`]
+//! * [`sketch_limiter`]
 //! * [`sliding_window_log`]
+//! * [`sliding_window_log_dyn`]
 //! * [`sliding_window_counter`]
+//! * [`sparse_sliding_window_log`]
+//! * [`sliding_window_log_exact`]
+//! * [`cooldown`]
+//! * [`debouncer`] and [`throttler`]
+//!
+//! Window widths are given in milliseconds. Each windowed helper also has a
+//! `_from_width` variant taking a [`Duration`] instead, e.g.
+//! [`fixed_window_from_width`]. Fixed window limiters honor sub-millisecond
+//! widths, the others round down to whole milliseconds. Likewise,
+//! [`token_bucket_from_rate`] takes the refill rate as tokens per [`Duration`]
+//! for slow or fractional rates like one token every ten seconds.
+//!
+//! To pick the algorithm at runtime, e.g. from a configuration file, [`from_config`]
+//! builds a [`BoxedLimiter`] from a [`LimiterConfig`]. Without a heap,
+//! [`AnyLimiter`] does the same with enum dispatch. When the algorithm is known,
+//! parameter structs like [`TokenBucketConfig`] or [`FixedWindowConfig`] build the
+//! limiter directly, e.g. [`TokenBucket::from_config`]. With the `parse` feature,
+//! a `Rate` parsed from strings like `"100/s"` or `"5/min"` converts into a
+//! [`LimiterConfig`].
+//!
+//! They use the monotonic [`StdClock`] and return nameable types, e.g. [`StdTokenBucket`],
+//! that can be stored in struct fields. For wall-clock timestamps, e.g. to share
+//! a limiter state between processes, pass the [`SystemClock`] to the constructors
+//! instead.
+//!
+//! The [`BlockingConsume`] extension trait adds consumes that put the thread
+//! to sleep until the tokens are available. With the `async` feature, the
+//! `AsyncLimiter` extension trait does the same by awaiting the sleep function of
+//! any async runtime. [`IteratorRateLimitExt`] paces the items of an iterator
+//! the same way, e.g. for batch jobs calling external APIs. [`ThrottledReader`] and
+//! [`ThrottledWriter`] limit the bandwidth of any `std::io` reader or writer.
 //!
 //! On `no_std` targets you'll have to provide bindings to your platforms timing
 //! functionalities and use the constructor methods:
 //!
 //! * [`TokenBucket::new_with_time_provider`]
+//! * [`DualTokenBucket::new_with_time_provider`]
 //! * [`FixedWindow::new_with_time_provider`]
+//! * [`FixedMultiWindow::new_with_time_provider`]
+//! * [`ScheduledFixedWindow::new_with_time_provider`]
+//! * [`TieredLimiter::new_with_time_provider`]
+//! * [`SketchLimiter::new_with_time_provider`]
 //! * [`SlidingWindowLog::new_with_time_provider`]
+//! * [`SlidingWindowLogDyn::new_with_time_provider`]
 //! * [`SlidingWindowCounter::new_with_time_provider`]
+//! * [`SparseSlidingWindowLog::new_with_time_provider`]
+//! * [`SlidingWindowLogExact::new_with_time_provider`]
+//! * [`Cooldown::new_with_time_provider`]
+//! * [`Debouncer::new_with_time_provider`] and [`Throttler::new_with_time_provider`]
 //!
-//! You must provide timer access in the form of a closuse that returns current system
+//! You must provide timer access in the form of a [`Clock`] that returns current system
 //! timestamp as a [`core::time::Duration`] from some fixed epoch in the past.
 //! It's a bit silly, but we use `Duration` instead of `Instant` because `Instant` requires `std`.
+//! Any `Fn() -> Duration` closure is a clock, but implementing [`Clock`] for your
+//! own type lets you name the limiter types, e.g. `TokenBucket<MyClock>`.
+//! Free-running hardware counters that wrap around can be adapted with [`WrappingTickClock`].
+//!
+//! ## Sans-IO cores
+//!
+//! If even a time provider is too much, each limiter is also available as a
+//! pure state machine implementing [`LimiterCore`], e.g. [`TokenBucketCore`].
+//! The current time is passed in with every request, so the cores can be
+//! driven from event loops, simulations or across FFI boundaries.
+//!
+//! The limiter state can also be taken out as plain data: e.g.
+//! [`TokenBucket::snapshot`] returns a [`TokenBucketSnapshot`] with public
+//! fields, and [`TokenBucket::restore`] resumes from it, so a limiter can be
+//! stashed in battery-backed RAM across deep sleep without `serde`.
+//!
+//! ## Thread safety
+//!
+//! All limiters returned by the `std` utility functions are `Send` and can be
+//! moved into spawned threads. Consuming requires exclusive access though, so
+//! for sharing a single limiter between threads use [`token_bucket_sync`], which
+//! returns a lock-free limiter that can be consumed through a shared reference.
+//!
+//! ## Cargo features
+//!
+//! * `std` *(default)* - utility functions using the system clock, implies `alloc`
+//! * `alloc` - limiters requiring a heap, for `no_std` targets with an allocator
+//! * `token-bucket` *(default)* - [`TokenBucket`], [`DualTokenBucket`], [`AdaptiveLimiter`],
+//!   [`AtomicTokenBucket`] and [`HierarchicalTokenBucket`]
+//! * `fixed-window` *(default)* - [`FixedWindow`], [`FixedMultiWindow`], [`ScheduledFixedWindow`],
+//!   [`TieredLimiter`] and [`SketchLimiter`]
+//! * `sliding-window` *(default)* - [`SlidingWindowLog`], [`SlidingWindowCounter`]
+//!   [`SparseSlidingWindowLog`] and [`SlidingWindowLogExact`]. With `alloc`, also [`SlidingWindowLogDyn`]
+//! * `global` - process-global named limiters and the `throttle!` macro
+//! * `async` - async APIs, runtime agnostic through pluggable sleep functions
+//! * `futures` - `StreamRateLimitExt` for pacing the items of a `futures` stream, implies `async`.
+//!   With `std`, also `futures::io` bandwidth limiting with `AsyncThrottledReader` and
+//!   `AsyncThrottledWriter`
+//! * `embassy` - `EmbassySharedLimiter` for sharing a limiter between embassy
+//!   tasks on `no_std` targets, and the `EmbassyClock` and `embassy_sleep`
+//!   integrations with `embassy-time`
+//! * `fugit` - `FugitClock` adapting a source of `fugit` instants into a [`Clock`]
+//! * `embedded-time` - `EmbeddedTimeClock` adapting an `embedded_time::Clock` into a [`Clock`]
+//! * `cortex-m` - `DwtClock` and `SysTickClock` on the Cortex-M cycle and tick counters,
+//!   handling the counter wraparound
+//! * `tower` - `RateLimitLayer` middleware admitting requests to a `tower` service
+//!   through any [`Limiter`], e.g. in hyper, axum or tonic stacks
+//! * `tokio` - `RateLimitedQueue` for pacing the drain of a work queue on the tokio timer,
+//!   and `tokio::io` bandwidth limiting with `AsyncThrottledReader` and `AsyncThrottledWriter`
+//! * `shared-memory` - `shared_token_bucket` for sharing a single limit between
+//!   processes through POSIX shared memory (unix only)
+//! * `wasm` - `wasm-bindgen` bindings exposing [`TokenBucket`] and [`FixedWindow`]
+//!   to JavaScript, using the browser clock
+//! * `serde` - `Serialize` and `Deserialize` for decisions, errors, statistics,
+//!   diagnostics and limiter configurations, e.g. for structured logs and config files
+//! * `defmt` - `defmt::Format` for the same types, for logging limiter decisions
+//!   from firmware over RTT
+//! * `parse` - `Rate` parsed from human-readable strings like `"1000/h"`
+//! * `calendar` - `CalendarWindow` with quotas per calendar minute, hour, UTC day or month.
+//!   With `std`, also `calendar_window` on the [`SystemClock`]
+//!
+//! Limiter algorithms you don't use can be left out to reduce code size,
+//! e.g. `default-features = false, features = ["token-bucket"]`.
 
 // Support no_std
 #![cfg_attr(not(feature = "std"), no_std)]
 
+#[cfg(feature = "alloc")]
+extern crate alloc;
+
+#[cfg(feature = "token-bucket")]
+mod adaptive_impl;
+#[cfg(target_has_atomic = "ptr")]
+mod admission_impl;
+#[cfg(any(
+    feature = "token-bucket",
+    feature = "fixed-window",
+    feature = "sliding-window"
+))]
+mod any_limiter_impl;
+#[cfg(feature = "async")]
+mod async_impl;
+#[cfg(any(feature = "tokio", all(feature = "futures", feature = "std")))]
+mod async_io_impl;
+#[cfg(all(feature = "token-bucket", target_has_atomic = "64"))]
+mod atomic_token_bucket_impl;
+mod autoscale_impl;
+#[cfg(feature = "std")]
+mod blocking_impl;
+#[cfg(feature = "calendar")]
+mod calendar_impl;
+mod combinator_impl;
+mod config_impl;
+mod cooldown_impl;
+#[cfg(feature = "cortex-m")]
+mod cortex_m_impl;
+mod credit_bucket_impl;
+mod debounce_impl;
+mod diagnostics_impl;
+#[cfg(feature = "token-bucket")]
+mod dual_token_bucket_impl;
+#[cfg(feature = "embassy")]
+mod embassy_impl;
+#[cfg(any(feature = "fugit", feature = "embedded-time"))]
+mod embedded_clock_impl;
+#[cfg(feature = "fixed-window")]
 mod fixed_window_impl;
+#[cfg(feature = "global")]
+mod global_impl;
+mod grants_impl;
+#[cfg(feature = "token-bucket")]
+mod hierarchical_impl;
+mod hooks_impl;
+#[cfg(feature = "std")]
+mod io_impl;
+mod keyed_impl;
+mod kill_switch_impl;
+#[cfg(target_has_atomic = "64")]
+mod partitioned_impl;
+#[cfg(feature = "tokio")]
+mod queue_impl;
+#[cfg(feature = "parse")]
+mod rate_impl;
+#[cfg(feature = "async")]
+mod remote_impl;
+mod reservation_impl;
+#[cfg(feature = "std")]
+mod shared_impl;
+#[cfg(feature = "fixed-window")]
+mod sketch_impl;
+#[cfg(all(feature = "alloc", feature = "sliding-window"))]
+mod sliding_window_dyn_impl;
+#[cfg(feature = "sliding-window")]
 mod sliding_window_impl;
+mod stats_impl;
+#[cfg(feature = "futures")]
+mod stream_impl;
+mod tick_clock_impl;
+#[cfg(feature = "fixed-window")]
+mod tiered_impl;
+#[cfg(feature = "token-bucket")]
 mod token_bucket_impl;
+#[cfg(feature = "tower")]
+mod tower_impl;
+#[cfg(feature = "wasm")]
+mod wasm_impl;
+
+use core::{fmt, task::Poll, time::Duration};
+
+#[cfg(all(feature = "std", feature = "token-bucket"))]
+pub use token_bucket_impl::{token_bucket, token_bucket_from_rate, StdTokenBucket};
+#[cfg(feature = "token-bucket")]
+pub use token_bucket_impl::{
+    TokenBucket, TokenBucketConfig, TokenBucketCore, TokenBucketSnapshot, TokenBucketState,
+};
+
+#[cfg(all(feature = "std", feature = "fixed-window"))]
+pub use fixed_window_impl::{
+    fixed_multi_window, fixed_multi_window_from_width, fixed_window, fixed_window_from_width,
+    scheduled_fixed_window, scheduled_fixed_window_from_width, StdFixedMultiWindow, StdFixedWindow,
+    StdScheduledFixedWindow,
+};
+#[cfg(feature = "fixed-window")]
+pub use fixed_window_impl::{
+    CapacitySchedule, FixedMultiWindow, FixedMultiWindowCore, FixedMultiWindowSnapshot,
+    FixedWindow, FixedWindowConfig, FixedWindowCore, FixedWindowSnapshot, FixedWindowState,
+    ScheduledFixedWindow, ScheduledFixedWindowCore, ScheduledFixedWindowSnapshot,
+};
 
-use core::fmt;
+#[cfg(all(feature = "std", feature = "fixed-window"))]
+pub use sketch_impl::{sketch_limiter, sketch_limiter_from_width, StdSketchLimiter};
+#[cfg(feature = "fixed-window")]
+pub use sketch_impl::{SketchLimiter, SketchLimiterState};
 
+#[cfg(all(feature = "alloc", feature = "sliding-window"))]
+pub use sliding_window_dyn_impl::SlidingWindowLogDyn;
+#[cfg(all(feature = "std", feature = "sliding-window"))]
+pub use sliding_window_dyn_impl::{
+    sliding_window_log_dyn, sliding_window_log_dyn_from_width, StdSlidingWindowLogDyn,
+};
+#[cfg(all(feature = "std", feature = "sliding-window"))]
+pub use sliding_window_impl::{
+    sliding_window_counter, sliding_window_counter_from_width, sliding_window_log,
+    sliding_window_log_exact, sparse_sliding_window_log, sparse_sliding_window_log_from_width,
+    StdSlidingWindowCounter, StdSlidingWindowLog, StdSlidingWindowLogExact,
+    StdSparseSlidingWindowLog,
+};
+#[cfg(feature = "sliding-window")]
+pub use sliding_window_impl::{
+    ApproximationBound, SlidingWindowCounter, SlidingWindowCounterConfig, SlidingWindowCounterCore,
+    SlidingWindowCounterSnapshot, SlidingWindowLog, SlidingWindowLogCore, SlidingWindowLogExact,
+    SlidingWindowLogExactCore, SlidingWindowLogExactSnapshot, SlidingWindowLogSnapshot,
+    SlidingWindowState, SparseSlidingWindowLog, SparseSlidingWindowLogCore,
+    SparseSlidingWindowLogSnapshot,
+};
+
+#[cfg(all(feature = "shared-memory", unix))]
+pub use atomic_token_bucket_impl::{shared_token_bucket, SharedMemory};
+#[cfg(all(feature = "std", feature = "token-bucket", target_has_atomic = "64"))]
+pub use atomic_token_bucket_impl::{token_bucket_sync, StdAtomicTokenBucket};
+#[cfg(all(feature = "token-bucket", target_has_atomic = "64"))]
+pub use atomic_token_bucket_impl::{AtomicTokenBucket, AtomicTokenBucketState};
+
+#[cfg(feature = "token-bucket")]
+pub use adaptive_impl::{AdaptiveLimiter, AimdConfig};
+#[cfg(target_has_atomic = "ptr")]
+pub use admission_impl::{
+    AdmissionController, AdmissionError, InFlight, InFlightGuard, InFlightPermit,
+};
+#[cfg(any(
+    feature = "token-bucket",
+    feature = "fixed-window",
+    feature = "sliding-window"
+))]
+pub use any_limiter_impl::AnyLimiter;
+#[cfg(feature = "async")]
+pub use async_impl::AsyncLimiter;
+#[cfg(any(feature = "tokio", all(feature = "futures", feature = "std")))]
+pub use async_io_impl::{AsyncThrottledReader, AsyncThrottledWriter};
+pub use autoscale_impl::{AutoscaleConfig, WithAutoscaling};
+#[cfg(feature = "std")]
+pub use blocking_impl::{BlockingConsume, IteratorRateLimitExt, RateLimitedIter};
+#[cfg(all(feature = "calendar", feature = "std"))]
+pub use calendar_impl::{calendar_window, StdCalendarWindow};
+#[cfg(feature = "calendar")]
+pub use calendar_impl::{CalendarUnit, CalendarWindow, CalendarWindowCore, CalendarWindowSnapshot};
+pub use combinator_impl::AllOf;
+#[cfg(feature = "std")]
+pub use config_impl::from_config;
+pub use config_impl::LimiterConfig;
+#[cfg(feature = "alloc")]
+pub use config_impl::{from_config_with_time_provider, BoxedLimiter};
 #[cfg(feature = "std")]
-pub use token_bucket_impl::token_bucket;
-pub use token_bucket_impl::TokenBucket;
+pub use cooldown_impl::{cooldown, StdCooldown};
+pub use cooldown_impl::{Cooldown, CooldownCore, CooldownSnapshot};
+#[cfg(feature = "cortex-m")]
+pub use cortex_m_impl::{DwtClock, SysTickClock};
+pub use credit_bucket_impl::CreditBucket;
+#[cfg(feature = "std")]
+pub use debounce_impl::{debouncer, throttler, StdDebouncer, StdThrottler};
+pub use debounce_impl::{Debouncer, Throttler};
+pub use diagnostics_impl::Diagnostics;
+#[cfg(all(feature = "std", feature = "token-bucket"))]
+pub use dual_token_bucket_impl::{dual_token_bucket, StdDualTokenBucket};
+#[cfg(feature = "token-bucket")]
+pub use dual_token_bucket_impl::{
+    DualTokenBucket, DualTokenBucketCore, DualTokenBucketSnapshot, DualTokenBucketState,
+};
+#[cfg(feature = "embassy")]
+pub use embassy_impl::{embassy_sleep, EmbassyClock, EmbassySharedLimiter};
+#[cfg(feature = "embedded-time")]
+pub use embedded_clock_impl::EmbeddedTimeClock;
+#[cfg(feature = "fugit")]
+pub use embedded_clock_impl::FugitClock;
+
+#[cfg(target_has_atomic = "64")]
+pub use partitioned_impl::{Partition, PartitionedQuota};
 
+#[cfg(feature = "global")]
+pub use global_impl::{global_limiters, register_global, try_consume_global};
+pub use grants_impl::{Grant, GrantsFull, WithGrants};
+#[cfg(feature = "token-bucket")]
+pub use hierarchical_impl::{ChildBucket, HierarchicalTokenBucket};
+pub use hooks_impl::WithHooks;
+#[cfg(feature = "std")]
+pub use io_impl::{ThrottledReader, ThrottledWriter};
+#[cfg(feature = "alloc")]
+pub use keyed_impl::BTreeKeyedLimiter;
 #[cfg(feature = "std")]
-pub use fixed_window_impl::fixed_window;
-pub use fixed_window_impl::FixedWindow;
+pub use keyed_impl::KeyedLimiter;
+pub use keyed_impl::{BoundedKeyedLimiter, KeyUsage, UsageTotals};
+pub use kill_switch_impl::{KillSwitch, Switch, SwitchMode};
+#[cfg(feature = "tokio")]
+pub use queue_impl::{OverflowPolicy, QueueSender, RateLimitedQueue};
+#[cfg(feature = "parse")]
+pub use rate_impl::{ParseRateError, Rate};
+#[cfg(feature = "async")]
+pub use remote_impl::{DecisionSource, RemoteLimiter, WithFallback};
+pub use reservation_impl::Reservation;
+#[cfg(feature = "std")]
+pub use shared_impl::SharedLimiter;
+pub use stats_impl::{Stats, TuningAdvisor, WithReporting, WithStats};
+#[cfg(feature = "futures")]
+pub use stream_impl::{RateLimitedStream, StreamRateLimitExt};
+pub use tick_clock_impl::{WrappingTick, WrappingTickClock};
+#[cfg(all(feature = "std", feature = "fixed-window"))]
+pub use tiered_impl::{tiered_limiter, tiered_limiter_from_widths, StdTieredLimiter};
+#[cfg(feature = "fixed-window")]
+pub use tiered_impl::{TieredLimiter, TieredLimiterCore};
+#[cfg(feature = "tower")]
+pub use tower_impl::{
+    BoxError, RateLimitLayer, RateLimitService, RejectWithError, RejectionHandler, ResponseFuture,
+};
+#[cfg(feature = "wasm")]
+pub use wasm_impl::{WasmFixedWindow, WasmTokenBucket};
 
+// Compile-time guarantees for the thread safety of the std utility functions,
+// and that their limiters can be debug printed and forked
 #[cfg(feature = "std")]
-pub use sliding_window_impl::{sliding_window_counter, sliding_window_log};
-pub use sliding_window_impl::{SlidingWindowCounter, SlidingWindowLog};
+const _: () = {
+    #[cfg(any(
+        feature = "token-bucket",
+        feature = "fixed-window",
+        feature = "sliding-window"
+    ))]
+    fn assert_send<L: Limiter + Send + fmt::Debug + Clone>(_: &L) {}
+    #[cfg(all(feature = "token-bucket", target_has_atomic = "64"))]
+    fn assert_send_sync<L: Limiter + Send + Sync>(_: &L) {}
+
+    #[allow(dead_code)]
+    fn assert_std_limiters() {
+        // The utility functions return nameable types
+        #[cfg(feature = "token-bucket")]
+        {
+            assert_send::<StdTokenBucket>(&token_bucket(1, 1));
+            assert_send::<StdDualTokenBucket>(&dual_token_bucket(1, 1, 1, 1));
+        }
+        #[cfg(feature = "fixed-window")]
+        {
+            assert_send::<StdFixedWindow>(&fixed_window(1, 1));
+            assert_send::<StdFixedMultiWindow<1>>(&fixed_multi_window(1, 1, 1));
+            assert_send::<StdScheduledFixedWindow<1>>(&scheduled_fixed_window([1], 1));
+            assert_send::<StdTieredLimiter<1>>(&tiered_limiter([(1, 1)]));
+        }
+        #[cfg(feature = "sliding-window")]
+        {
+            assert_send::<StdSlidingWindowLog<1>>(&sliding_window_log(1));
+            assert_send::<StdSlidingWindowLogDyn>(&sliding_window_log_dyn(1, 1));
+            assert_send::<StdSlidingWindowCounter>(&sliding_window_counter(1, 1));
+            assert_send::<StdSparseSlidingWindowLog<1>>(&sparse_sliding_window_log(1, 1));
+            assert_send::<StdSlidingWindowLogExact<1>>(&sliding_window_log_exact(
+                1,
+                Duration::from_millis(1),
+            ));
+        }
+        #[cfg(all(feature = "token-bucket", target_has_atomic = "64"))]
+        assert_send_sync::<StdAtomicTokenBucket>(&token_bucket_sync(1, 1));
+    }
+};
+
+/// Source of timestamps for the limiters
+///
+/// Implemented for every `Fn() -> Duration` closure. Implement it for your
+/// own type to name the limiter types in struct fields, e.g.
+/// `TokenBucket<MyClock>`. To share a single clock between many limiters,
+/// implement it for a reference as well:
+///
+/// ```
+/// use core::time::Duration;
+/// use burster::{Clock, Limiter, TokenBucket};
+///
+/// struct MyClock;
+///
+/// impl Clock for &MyClock {
+///     fn now(&self) -> Duration {
+///         Duration::ZERO
+///     }
+/// }
+///
+/// static CLOCK: MyClock = MyClock;
+/// let mut bucket: TokenBucket<&MyClock> = TokenBucket::new_with_time_provider(1, 1, &CLOCK);
+/// assert!(bucket.try_consume_one().is_ok());
+/// ```
+pub trait Clock {
+    /// Current timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// Must be monotonically nondecreasing.
+    fn now(&self) -> Duration;
+}
+
+impl<F> Clock for F
+where
+    F: Fn() -> Duration,
+{
+    fn now(&self) -> Duration {
+        self()
+    }
+}
+
+/// Unsigned integer type storing token counts in limiter state
+///
+/// Limiters whose memory scales with their usage history, like the slots of
+/// a sliding window log, are generic over this type, so that small targets
+/// can trade headroom for memory with e.g. `u32` or `u16` counters. The
+/// limiter API still takes and reports `u64` tokens; a consume overflowing
+/// the storage type is rejected and reported by [`Limiter::self_check`].
+///
+/// Limiters with a fixed amount of state, like the token buckets and fixed
+/// windows, always count in `u64`. Their refill math is done in `u128`, so
+/// `u64` counters already cover e.g. 10 Gb/s counted in bits for decades,
+/// and there's no wider counter type.
+pub trait TokenCount: Copy + Default + Into<u64> + TryFrom<u64> {
+    /// Largest storable token count
+    const MAX: Self;
+}
+
+macro_rules! impl_token_count {
+    ($($t:ty),*) => {
+        $(impl TokenCount for $t {
+            const MAX: Self = <$t>::MAX;
+        })*
+    };
+}
+
+impl_token_count!(u8, u16, u32, u64);
+
+/// Monotonic [`Clock`] used by the `std` utility functions
+///
+/// Based on [`std::time::Instant`], so it never jumps with NTP adjustments.
+/// The epoch is the first time any `StdClock` is read in the process, so all
+/// limiters of a process share it.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct StdClock;
+
+#[cfg(feature = "std")]
+impl Clock for StdClock {
+    fn now(&self) -> Duration {
+        static EPOCH: std::sync::OnceLock<std::time::Instant> = std::sync::OnceLock::new();
+        EPOCH.get_or_init(std::time::Instant::now).elapsed()
+    }
+}
+
+/// Wall-clock [`Clock`] reading the system time since the Unix epoch
+///
+/// Unlike [`StdClock`], timestamps are comparable across processes and
+/// restarts, but may jump with NTP adjustments. Opt in by passing it to the
+/// `new_with_time_provider` constructors.
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct SystemClock;
+
+#[cfg(feature = "std")]
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        // Only fails for system times before 1970
+        std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or(Duration::ZERO)
+    }
+}
 
 /// Common trait for all rate limiter implementations
 pub trait Limiter {
@@ -73,6 +584,515 @@ pub trait Limiter {
     fn try_consume_one(&mut self) -> LimiterResult {
         self.try_consume(1)
     }
+
+    /// Poll for consuming tokens
+    ///
+    /// Same as [`Limiter::try_consume`], but reports the outcome as a
+    /// [`Poll`] for integrating waiting semantics into superloops and custom
+    /// schedulers. No waker is registered, the caller is expected to poll
+    /// again later.
+    ///
+    /// # Returns
+    /// * `Poll::Ready(())` - tokens consumed
+    /// * `Poll::Pending` - not enough tokens left yet, try again later
+    fn poll_consume(&mut self, tokens: u64) -> Poll<()> {
+        match self.try_consume(tokens) {
+            Ok(()) => Poll::Ready(()),
+            Err(CantConsume) => Poll::Pending,
+        }
+    }
+
+    /// Try to consume a batch of requests evaluated against a single timestamp
+    ///
+    /// Requests are admitted in order until the first one that can't be
+    /// consumed. Limiters of this crate read their time provider only once
+    /// per batch, the default implementation falls back to calling
+    /// [`Limiter::try_consume`] for each request.
+    ///
+    /// # Arguments
+    /// * `costs` - how many tokens each request consumes
+    ///
+    /// # Returns
+    /// How many of the leading requests were admitted
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        costs
+            .iter()
+            .take_while(|&&tokens| self.try_consume(tokens).is_ok())
+            .count()
+    }
+
+    /// Algorithm implemented by this limiter
+    ///
+    /// Wrappers report the kind of the limiter they wrap.
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::Other
+    }
+
+    /// User-assigned name of this limiter, if any
+    fn name(&self) -> Option<&'static str> {
+        None
+    }
+
+    /// Kind and name of this limiter
+    fn info(&self) -> LimiterInfo {
+        LimiterInfo {
+            kind: self.kind(),
+            name: self.name(),
+        }
+    }
+
+    /// Check the health of this limiter
+    ///
+    /// Reports whether the time provider appears monotonic and progressing,
+    /// whether the configuration is internally consistent and whether any
+    /// internal counter has saturated. Limiters that don't support
+    /// diagnostics report no problems. Wrappers report the diagnostics of
+    /// the limiter they wrap.
+    fn self_check(&self) -> Diagnostics {
+        Diagnostics::HEALTHY
+    }
+
+    /// Timestamp of the most recent consume attempt
+    ///
+    /// Reported on the time scale of the time provider, both admitted and
+    /// rejected consumes count as activity. `None` if nothing has been
+    /// consumed yet or the limiter doesn't track activity. Wrappers report
+    /// the activity of the limiter they wrap.
+    fn last_consume_at(&self) -> Option<Duration> {
+        None
+    }
+
+    /// How long the limiter has been idle
+    ///
+    /// Time since the most recent consume attempt, or since the creation of
+    /// the limiter if nothing has been consumed yet. Useful for detecting
+    /// dormant flows, e.g. for evicting idle keys. `None` if the limiter
+    /// doesn't track activity.
+    fn idle_for(&self) -> Option<Duration> {
+        None
+    }
+
+    /// Return to the freshly constructed state
+    ///
+    /// Callable through `dyn Limiter` as well, e.g. on a [`BoxedLimiter`].
+    /// Limiters implementing [`Reset`] forward to their core, handles into
+    /// a shared limiter reset their own share of it. Wrappers reset the
+    /// limiter they wrap along with their own state.
+    fn reset(&mut self);
+}
+
+impl<L> Limiter for &mut L
+where
+    L: Limiter + ?Sized,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        (**self).try_consume(tokens)
+    }
+
+    fn try_consume_one(&mut self) -> LimiterResult {
+        (**self).try_consume_one()
+    }
+
+    fn poll_consume(&mut self, tokens: u64) -> Poll<()> {
+        (**self).poll_consume(tokens)
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        (**self).try_consume_batch(costs)
+    }
+
+    fn kind(&self) -> LimiterKind {
+        (**self).kind()
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        (**self).name()
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        (**self).self_check()
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        (**self).last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        (**self).idle_for()
+    }
+
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+}
+
+#[cfg(feature = "alloc")]
+impl<L> Limiter for alloc::boxed::Box<L>
+where
+    L: Limiter + ?Sized,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        (**self).try_consume(tokens)
+    }
+
+    fn try_consume_one(&mut self) -> LimiterResult {
+        (**self).try_consume_one()
+    }
+
+    fn poll_consume(&mut self, tokens: u64) -> Poll<()> {
+        (**self).poll_consume(tokens)
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        (**self).try_consume_batch(costs)
+    }
+
+    fn kind(&self) -> LimiterKind {
+        (**self).kind()
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        (**self).name()
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        (**self).self_check()
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        (**self).last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        (**self).idle_for()
+    }
+
+    fn reset(&mut self) {
+        (**self).reset()
+    }
+}
+
+/// Limiter reporting the remaining budget on successful consumes
+///
+/// Callers typically need the remaining budget right after a consume, e.g.
+/// for `X-RateLimit-Remaining` headers or adaptive behavior. This trait
+/// extends [`Limiter`] instead of changing it, so existing [`Limiter`]
+/// implementations keep working unchanged. All limiters of this crate
+/// implement it.
+pub trait RemainingBudget: Limiter {
+    /// Try to consume tokens, reporting the remaining budget
+    ///
+    /// # Arguments
+    /// * `tokens` - how many tokens to consume
+    ///
+    /// # Returns
+    /// * `Ok(remaining)` - tokens consumed, `remaining` more could be consumed right now
+    /// * `Err(CantConsume)` - not enough tokens left for this time window
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume>;
+}
+
+/// Limiter that can report its budget without consuming
+///
+/// Useful e.g. for `X-RateLimit-Remaining` headers. Inspecting doesn't
+/// modify the limiter, so the reported amount is only a snapshot: another
+/// consume or elapsed time may change it right after.
+pub trait Inspect: Limiter {
+    /// How many tokens could be consumed right now
+    fn tokens_available(&self) -> u64;
+
+    /// Dry run of [`Limiter::try_consume`]
+    ///
+    /// Evaluates whether `tokens` would be admitted right now without
+    /// recording the consume, e.g. for shadow-mode rollouts logging what
+    /// would have been rejected before turning enforcement on.
+    fn would_consume(&self, tokens: u64) -> bool {
+        self.tokens_available() >= tokens
+    }
+
+    /// Consume as many tokens as available, up to the requested amount
+    ///
+    /// Useful e.g. for bandwidth shaping, where sending a partial chunk now
+    /// is better than sending nothing.
+    ///
+    /// # Returns
+    /// How many tokens were consumed, possibly zero
+    fn try_consume_up_to(&mut self, tokens: u64) -> u64 {
+        let granted = tokens.min(self.tokens_available());
+        if granted != 0 && self.try_consume(granted).is_ok() {
+            granted
+        } else {
+            0
+        }
+    }
+}
+
+/// Limiter that can tell when a rejected consume could succeed
+///
+/// Callers like HTTP servers need this for `Retry-After` headers and
+/// backoff scheduling. Like [`Inspect`], this doesn't modify the limiter.
+pub trait RetryHint: Limiter {
+    /// Time until `tokens` could plausibly be consumed
+    ///
+    /// # Returns
+    /// * `Some(Duration::ZERO)` - the tokens could be consumed right now
+    /// * `Some(wait)` - the tokens should become available after `wait`, unless consumed by others
+    /// * `None` - waiting alone won't make the tokens available, e.g. they exceed the capacity
+    fn time_until_available(&self, tokens: u64) -> Option<Duration>;
+
+    /// Try to consume tokens, reporting when to retry on failure
+    ///
+    /// # Arguments
+    /// * `tokens` - how many tokens to consume
+    ///
+    /// # Returns
+    /// * `Ok(())` - tokens consumed
+    /// * `Err(RateLimited)` - not enough tokens left, see [`RateLimited::retry_after`]
+    fn try_consume_with_retry(&mut self, tokens: u64) -> Result<(), RateLimited> {
+        self.try_consume(tokens).map_err(|_| RateLimited {
+            retry_after: self.time_until_available(tokens),
+        })
+    }
+
+    /// Whether `tokens` could ever be consumed by waiting
+    ///
+    /// Tells a temporary exhaustion apart from a request that fails
+    /// forever, e.g. one exceeding the capacity, so the latter can be
+    /// rejected permanently instead of retried.
+    fn can_ever_consume(&self, tokens: u64) -> bool {
+        self.time_until_available(tokens).is_some()
+    }
+}
+
+/// Limiter whose capacity can be changed at runtime
+///
+/// Implemented by [`TokenBucket`] and [`FixedWindow`], used by
+/// [`WithAutoscaling`] to tune the capacity of the wrapped limiter.
+pub trait AdjustableCapacity: Limiter {
+    /// Currently configured capacity
+    fn current_capacity(&self) -> u64;
+
+    /// Change the capacity
+    ///
+    /// Tokens already consumed stay consumed, so shrinking the capacity
+    /// below the current usage rejects consumes until the usage drops.
+    fn set_capacity(&mut self, capacity: u64);
+}
+
+/// Windowed limiter whose window boundaries can be shifted
+///
+/// Implemented by [`FixedWindow`], used by the keyed limiters, e.g.
+/// [`BoundedKeyedLimiter::with_hashed_phase`], to spread the resets of the
+/// per-key windows.
+pub trait PhaseOffset: Limiter {
+    /// Width of a single window
+    fn window_width(&self) -> Duration;
+
+    /// Shift the window boundaries by the given phase offset
+    ///
+    /// # Arguments
+    /// * `offset` - phase offset, taken modulo the window width
+    fn with_phase_offset(self, offset: Duration) -> Self
+    where
+        Self: Sized;
+}
+
+/// Limiter that can take back tokens of an admitted consume
+///
+/// Lets callers give back the tokens of an operation that was cancelled or
+/// failed before doing any real work, so that e.g. a burst of client-side
+/// cancellations doesn't burn quota. Also used by [`AllOf`] to roll back a
+/// consume that a later limiter rejects. Refunded tokens never exceed the
+/// capacity of the limiter.
+pub trait Refund: Limiter {
+    /// Return tokens of an admitted consume that didn't take place
+    ///
+    /// # Arguments
+    /// * `tokens` - how many tokens to return
+    fn refund(&mut self, tokens: u64);
+
+    /// Return tokens of an admitted consume that didn't take place, same as [`Self::refund`]
+    ///
+    /// # Arguments
+    /// * `tokens` - how many tokens to return
+    fn give_back(&mut self, tokens: u64) {
+        self.refund(tokens);
+    }
+
+    /// Combine with another limiter, admitting only consumes both admit
+    ///
+    /// # Arguments
+    /// * `other` - limiter to consume from after this one
+    fn and<L>(self, other: L) -> AllOf<Self, L>
+    where
+        Self: Sized,
+        L: Limiter,
+    {
+        AllOf::new(self, other)
+    }
+
+    /// Consume tokens in two phases, committing or cancelling later
+    ///
+    /// Holds the tokens e.g. while validating a request, without the race of
+    /// checking [`Inspect::tokens_available`] and consuming afterwards.
+    ///
+    /// # Returns
+    /// * `Ok(Reservation)` - tokens consumed, refunded unless committed
+    /// * `Err(CantConsume)` - not enough tokens left
+    fn reserve(&mut self, tokens: u64) -> Result<Reservation<'_, Self>, CantConsume>
+    where
+        Self: Sized,
+    {
+        Reservation::new(self, tokens)
+    }
+}
+
+/// Limiter that can be returned to its freshly constructed state
+///
+/// Marks the limiters whose [`Limiter::reset`] is supported, e.g. as a bound
+/// in generic code. Resetting fills token buckets and empties windows while
+/// keeping the configuration, so a limiter instance can be reused e.g.
+/// across logical sessions without rebuilding it. A clock going backwards
+/// or a saturated counter reported by [`Limiter::self_check`] are kept
+/// across resets, as they point at the time provider or the configuration.
+pub trait Reset: Limiter {}
+
+/// Request to a sans-IO [`LimiterCore`]
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Request {
+    /// Consume the given amount of tokens
+    Consume(u64),
+}
+
+/// Response of a sans-IO [`LimiterCore`]
+#[non_exhaustive]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum Response {
+    /// Request admitted, `remaining` more tokens could be consumed right now
+    Admitted {
+        /// Remaining budget after the request
+        remaining: u64,
+    },
+    /// Request rejected
+    Rejected,
+}
+
+impl From<Result<u64, CantConsume>> for Response {
+    fn from(result: Result<u64, CantConsume>) -> Self {
+        match result {
+            Ok(remaining) => Response::Admitted { remaining },
+            Err(CantConsume) => Response::Rejected,
+        }
+    }
+}
+
+/// Sans-IO limiter state machine
+///
+/// A pure state machine with no time provider: the current time is passed
+/// in with every request instead. This makes the algorithms trivial to test
+/// deterministically, easy to drive over FFI and usable inside other sans-IO
+/// protocol stacks. The closure based limiters of this crate are thin layers
+/// on top of their cores, e.g. [`TokenBucket`] over [`TokenBucketCore`].
+///
+/// Timestamps must be monotonically nondecreasing [`Duration`]s from some
+/// fixed epoch in the past, the same as returned by a time provider.
+pub trait LimiterCore {
+    /// Handle a single request at the given time
+    fn handle(&mut self, now: Duration, request: Request) -> Response;
+}
+
+/// Rate limiting algorithm implemented by a [`Limiter`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum LimiterKind {
+    /// [`TokenBucket`]
+    TokenBucket,
+    /// [`AtomicTokenBucket`]
+    AtomicTokenBucket,
+    /// [`FixedWindow`]
+    FixedWindow,
+    /// [`FixedMultiWindow`]
+    FixedMultiWindow,
+    /// [`ScheduledFixedWindow`]
+    ScheduledFixedWindow,
+    /// [`TieredLimiter`]
+    Tiered,
+    /// [`SlidingWindowLog`]
+    SlidingWindowLog,
+    /// [`SparseSlidingWindowLog`]
+    SparseSlidingWindowLog,
+    /// [`SlidingWindowLogExact`]
+    SlidingWindowLogExact,
+    /// [`SlidingWindowCounter`]
+    SlidingWindowCounter,
+    /// [`CreditBucket`]
+    CreditBucket,
+    /// [`Partition`] of a [`PartitionedQuota`]
+    PartitionedQuota,
+    /// [`ChildBucket`] of a [`HierarchicalTokenBucket`]
+    HierarchicalTokenBucket,
+    /// [`DualTokenBucket`]
+    DualTokenBucket,
+    /// [`AdaptiveLimiter`]
+    Adaptive,
+    /// `CalendarWindow`
+    CalendarWindow,
+    /// [`Cooldown`]
+    Cooldown,
+    /// Limiter implemented outside of this crate
+    Other,
+}
+
+impl LimiterKind {
+    /// Short snake case identifier, suitable e.g. for metric labels
+    pub const fn as_str(&self) -> &'static str {
+        match self {
+            LimiterKind::TokenBucket => "token_bucket",
+            LimiterKind::AtomicTokenBucket => "atomic_token_bucket",
+            LimiterKind::FixedWindow => "fixed_window",
+            LimiterKind::FixedMultiWindow => "fixed_multi_window",
+            LimiterKind::ScheduledFixedWindow => "scheduled_fixed_window",
+            LimiterKind::Tiered => "tiered",
+            LimiterKind::SlidingWindowLog => "sliding_window_log",
+            LimiterKind::SparseSlidingWindowLog => "sparse_sliding_window_log",
+            LimiterKind::SlidingWindowLogExact => "sliding_window_log_exact",
+            LimiterKind::SlidingWindowCounter => "sliding_window_counter",
+            LimiterKind::CreditBucket => "credit_bucket",
+            LimiterKind::PartitionedQuota => "partitioned_quota",
+            LimiterKind::HierarchicalTokenBucket => "hierarchical_token_bucket",
+            LimiterKind::DualTokenBucket => "dual_token_bucket",
+            LimiterKind::Adaptive => "adaptive",
+            LimiterKind::CalendarWindow => "calendar_window",
+            LimiterKind::Cooldown => "cooldown",
+            LimiterKind::Other => "other",
+        }
+    }
+}
+
+impl fmt::Display for LimiterKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Identity of a limiter, for attributing its decisions e.g. in metrics
+///
+/// Passed to the callbacks of [`WithReporting`] and [`WithHooks`], and
+/// listed for the process-global limiters by `global_limiters`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct LimiterInfo {
+    /// Algorithm of the limiter, see [`Limiter::kind`]
+    pub kind: LimiterKind,
+    /// User-assigned name of the limiter, see [`Limiter::name`]
+    pub name: Option<&'static str>,
 }
 
 /// Error type indicating that the requested amount of
@@ -80,6 +1100,8 @@ pub trait Limiter {
 ///
 /// I.e. the limiter *limits*
 #[derive(Debug)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
 pub struct CantConsume;
 
 impl fmt::Display for CantConsume {
@@ -92,6 +1114,85 @@ impl fmt::Display for CantConsume {
 #[rustversion::since(1.81)]
 impl core::error::Error for CantConsume {}
 
+/// Error type of [`RetryHint::try_consume_with_retry`]
+///
+/// Like [`CantConsume`], but also tells when the consume could succeed.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct RateLimited {
+    /// Time until the consume could plausibly succeed, see [`RetryHint::time_until_available`]
+    pub retry_after: Option<Duration>,
+}
+
+impl RateLimited {
+    /// Whether waiting won't help, i.e. the consume should not be retried
+    ///
+    /// See [`RetryHint::can_ever_consume`].
+    pub fn is_permanent(&self) -> bool {
+        self.retry_after.is_none()
+    }
+}
+
+impl fmt::Display for RateLimited {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.retry_after {
+            Some(retry_after) => write!(f, "Rate limited, retry after {retry_after:?}"),
+            None => write!(f, "Rate limited"),
+        }
+    }
+}
+
+// core::error::Error trait stabilised at release 1.81
+#[rustversion::since(1.81)]
+impl core::error::Error for RateLimited {}
+
+/// Error type of the fallible `try_new` constructors
+///
+/// Returned for configurations that would never admit anything or that the
+/// limiter could not operate on.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+#[non_exhaustive]
+pub enum ConfigError {
+    /// Capacity is zero, so nothing would ever be admitted
+    ZeroCapacity,
+    /// Refill rate is zero, so tokens would never be replenished
+    ZeroRate,
+    /// Window width is zero
+    ZeroWindow,
+    /// Capacity does not fit in the limiter's token counters
+    CapacityOverflow,
+    /// Length given as a const generic, e.g. of an entry buffer or schedule, is zero
+    ZeroLength,
+    /// Rate is finer than the limiter's time resolution can represent
+    RateOverflow,
+}
+
+impl fmt::Display for ConfigError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ConfigError::ZeroCapacity => write!(f, "Limiter capacity is zero"),
+            ConfigError::ZeroRate => write!(f, "Limiter rate is zero"),
+            ConfigError::ZeroWindow => write!(f, "Limiter window width is zero"),
+            ConfigError::CapacityOverflow => write!(f, "Limiter capacity overflows its counters"),
+            ConfigError::ZeroLength => write!(f, "Limiter buffer length is zero"),
+            ConfigError::RateOverflow => write!(f, "Limiter rate exceeds its time resolution"),
+        }
+    }
+}
+
+// core::error::Error trait stabilised at release 1.81
+#[rustversion::since(1.81)]
+impl core::error::Error for ConfigError {}
+
+impl From<RateLimited> for CantConsume {
+    fn from(_: RateLimited) -> Self {
+        CantConsume
+    }
+}
+
 /// Limiter consume action result type
 ///
 /// There are no actual errors that can be returned,
@@ -99,22 +1200,8 @@ impl core::error::Error for CantConsume {}
 /// that the requested amount of tokens cannot be consumed.
 pub type LimiterResult = Result<(), CantConsume>;
 
-#[cfg(feature = "std")]
-mod macros {
-    macro_rules! std_time_provider {
-        () => {
-            || {
-                std::time::SystemTime::now()
-                    .duration_since(std::time::UNIX_EPOCH)
-                    .expect("Time went backwards")
-            }
-        };
-    }
-
-    pub(crate) use std_time_provider;
-}
-
 #[cfg(test)]
+#[allow(dead_code)]
 pub(crate) mod mock_assets {
     use core::{
         sync::atomic::{AtomicU64, Ordering},
@@ -132,4 +1219,43 @@ pub(crate) mod mock_assets {
             Duration::from_micros(self.0.fetch_add(step, Ordering::Relaxed))
         }
     }
+
+    /// Minimal executor for driving futures in tests
+    #[cfg(any(feature = "async", feature = "tower"))]
+    pub fn block_on<F: core::future::Future>(future: F) -> F::Output {
+        use core::task::{Context, Poll, RawWaker, RawWakerVTable, Waker};
+
+        fn noop_raw_waker() -> RawWaker {
+            fn clone(_: *const ()) -> RawWaker {
+                noop_raw_waker()
+            }
+            fn noop(_: *const ()) {}
+            static VTABLE: RawWakerVTable = RawWakerVTable::new(clone, noop, noop, noop);
+            RawWaker::new(core::ptr::null(), &VTABLE)
+        }
+
+        // SAFETY: the vtable functions are all no-ops
+        let waker = unsafe { Waker::from_raw(noop_raw_waker()) };
+        let mut cx = Context::from_waker(&waker);
+        let mut future = core::pin::pin!(future);
+        loop {
+            if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+                return output;
+            }
+        }
+    }
+
+    /// Future that is pending for `n` polls
+    #[cfg(feature = "async")]
+    pub fn yield_n(mut n: usize) -> impl core::future::Future<Output = ()> {
+        core::future::poll_fn(move |cx| {
+            if n == 0 {
+                core::task::Poll::Ready(())
+            } else {
+                n -= 1;
+                cx.waker().wake_by_ref();
+                core::task::Poll::Pending
+            }
+        })
+    }
 }
@@ -0,0 +1,262 @@
+//! Partitioned quota -type limiter for multiple workers
+
+use core::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+use crate::{
+    CantConsume, ConfigError, Inspect, Limiter, LimiterKind, LimiterResult, RemainingBudget,
+    RetryHint,
+};
+
+/// Global quota statically split into `N` partitions
+///
+/// Each worker thread or task gets its own [`Partition`] and consumes
+/// from it without contending with other workers. The quota is replenished
+/// and redistributed by calling [`PartitionedQuota::rebalance`] periodically,
+/// e.g. once per second for a per-second quota.
+///
+/// On each rebalance every partition is guaranteed half of its fair share,
+/// and the rest of the quota is distributed in proportion to how many tokens
+/// each partition consumed during the previous period. Unused tokens are not
+/// carried over, so the workers admit at most `quota` tokens per period in
+/// total, apart from consumes racing with an ongoing rebalance.
+///
+/// # Generic arguments
+/// * `N` - number of partitions, must be nonzero
+#[derive(Debug)]
+pub struct PartitionedQuota<const N: usize> {
+    quota: u64,
+    available: [AtomicU64; N],
+    used: [AtomicU64; N],
+}
+
+impl<const N: usize> PartitionedQuota<N> {
+    /// Initialize a new partitioned quota with an even split
+    ///
+    /// # Arguments
+    /// * `quota` - how many tokens all partitions may consume in total per period
+    ///
+    /// # Panics
+    /// On a division by zero if `N` is zero, use [`Self::try_new`] to
+    /// validate the configuration.
+    pub fn new(quota: u64) -> Self {
+        let this = Self {
+            quota,
+            available: core::array::from_fn(|_| AtomicU64::new(0)),
+            used: core::array::from_fn(|_| AtomicU64::new(0)),
+        };
+        this.distribute(|_| 0, 0);
+        this
+    }
+
+    /// Like [`Self::new`], but rejecting configurations that would never admit anything
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroLength`] if `N` is zero, or [`ConfigError::ZeroCapacity`]
+    /// for a zero quota
+    pub fn try_new(quota: u64) -> Result<Self, ConfigError> {
+        if N == 0 {
+            return Err(ConfigError::ZeroLength);
+        }
+        if quota == 0 {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        Ok(Self::new(quota))
+    }
+
+    /// Get a handle to a single partition
+    ///
+    /// # Panics
+    /// If `index >= N`
+    pub fn partition(&self, index: usize) -> Partition<'_, N> {
+        assert!(index < N, "partition index out of range");
+        Partition { quota: self, index }
+    }
+
+    /// Start a new period, replenishing and redistributing the quota
+    pub fn rebalance(&self) {
+        let mut used = [0u64; N];
+        for (u, counter) in used.iter_mut().zip(self.used.iter()) {
+            *u = counter.swap(0, Ordering::Relaxed);
+        }
+        let total_used = used.iter().fold(0u64, |acc, u| acc.saturating_add(*u));
+        self.distribute(|i| used[i], total_used);
+    }
+
+    /// Configured total quota per period
+    pub fn quota(&self) -> u64 {
+        self.quota
+    }
+
+    /// Tokens currently available in the given partition
+    pub fn available(&self, index: usize) -> u64 {
+        self.available[index].load(Ordering::Relaxed)
+    }
+
+    /// Share of the given partition after an even split
+    fn initial_share(&self, index: usize) -> u64 {
+        let guaranteed = self.quota / (2 * N as u64);
+        let share = guaranteed + (self.quota - guaranteed * N as u64) / N as u64;
+        if index == 0 {
+            // Rounding leftovers of the split
+            share + self.quota - share * N as u64
+        } else {
+            share
+        }
+    }
+
+    fn distribute(&self, used: impl Fn(usize) -> u64, total_used: u64) {
+        let guaranteed = self.quota / (2 * N as u64);
+        let shared = self.quota - guaranteed * N as u64;
+
+        let mut given = 0;
+        for (i, available) in self.available.iter().enumerate() {
+            let weighted = if total_used == 0 {
+                shared / N as u64
+            } else {
+                (shared as u128 * used(i) as u128 / total_used as u128) as u64
+            };
+            let share = guaranteed + weighted;
+            given += share;
+            available.store(share, Ordering::Relaxed);
+        }
+
+        // Rounding leftovers go to the first partition
+        self.available[0].fetch_add(self.quota - given, Ordering::Relaxed);
+    }
+}
+
+/// Handle to a single partition of a [`PartitionedQuota`]
+pub struct Partition<'a, const N: usize> {
+    quota: &'a PartitionedQuota<N>,
+    index: usize,
+}
+
+impl<const N: usize> Partition<'_, N> {
+    /// Try to consume tokens through a shared reference
+    ///
+    /// Same as [`Limiter::try_consume`], but doesn't require exclusive access.
+    pub fn try_consume_shared(&self, tokens: u64) -> LimiterResult {
+        self.consume(tokens).map(|_| ())
+    }
+
+    /// Consume tokens, returning the remaining budget
+    fn consume(&self, tokens: u64) -> Result<u64, CantConsume> {
+        let available = self.quota.available[self.index]
+            .fetch_update(Ordering::Relaxed, Ordering::Relaxed, |available| {
+                available.checked_sub(tokens)
+            })
+            .map_err(|_| CantConsume)?;
+        self.quota.used[self.index].fetch_add(tokens, Ordering::Relaxed);
+        Ok(available - tokens)
+    }
+
+    /// Index of this partition
+    pub fn index(&self) -> usize {
+        self.index
+    }
+}
+
+impl<const N: usize> Limiter for Partition<'_, N> {
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        self.try_consume_shared(tokens)
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::PartitionedQuota
+    }
+
+    fn reset(&mut self) {
+        // Other partitions keep their shares until the next rebalance
+        let share = self.quota.initial_share(self.index);
+        self.quota.available[self.index].store(share, Ordering::Relaxed);
+        self.quota.used[self.index].store(0, Ordering::Relaxed);
+    }
+}
+
+impl<const N: usize> RetryHint for Partition<'_, N> {
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        // Partitions are only replenished by rebalancing
+        (tokens <= self.tokens_available()).then_some(Duration::ZERO)
+    }
+}
+
+impl<const N: usize> Inspect for Partition<'_, N> {
+    fn tokens_available(&self) -> u64 {
+        self.quota.available[self.index].load(Ordering::Relaxed)
+    }
+}
+
+impl<const N: usize> RemainingBudget for Partition<'_, N> {
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        self.consume(tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{ConfigError, Limiter};
+
+    use super::PartitionedQuota;
+
+    #[test]
+    fn verify_rebalance() {
+        let q = PartitionedQuota::<2>::new(100);
+        let mut busy = q.partition(0);
+        let idle = q.partition(1);
+
+        // Even split initially
+        assert!(busy.try_consume(50).is_ok());
+        assert!(busy.try_consume_one().is_err());
+        assert_eq!(q.available(1), 50);
+
+        // Only the busy partition consumed, it gets the whole shared part
+        q.rebalance();
+        assert_eq!(q.available(0), 75);
+        assert_eq!(q.available(1), 25);
+        assert!(busy.try_consume(75).is_ok());
+        assert!(idle.try_consume_shared(25).is_ok());
+
+        // Both consumed in 3:1 ratio
+        q.rebalance();
+        assert_eq!(q.available(0), 25 + 37 + 1);
+        assert_eq!(q.available(1), 25 + 12);
+    }
+
+    #[test]
+    fn verify_try_new() {
+        assert!(matches!(
+            PartitionedQuota::<0>::try_new(100),
+            Err(ConfigError::ZeroLength)
+        ));
+        assert!(matches!(
+            PartitionedQuota::<2>::try_new(0),
+            Err(ConfigError::ZeroCapacity)
+        ));
+
+        let q = PartitionedQuota::<3>::try_new(10).unwrap();
+        assert_eq!(q.available(0) + q.available(1) + q.available(2), 10);
+    }
+
+    #[test]
+    fn verify_reset() {
+        let q = PartitionedQuota::<3>::new(10);
+        let fresh = [q.available(0), q.available(1), q.available(2)];
+        let mut a = q.partition(0);
+        let mut b = q.partition(1);
+        assert!(a.try_consume(fresh[0]).is_ok());
+        assert!(b.try_consume_one().is_ok());
+
+        // Only the reset partition gets its initial share back
+        a.reset();
+        assert_eq!(q.available(0), fresh[0]);
+        assert_eq!(q.available(1), fresh[1] - 1);
+
+        // Consumes before the reset don't weigh in the next rebalance
+        q.rebalance();
+        assert_eq!(q.available(1), 10 - q.available(0) - q.available(2));
+        assert!(q.available(1) > q.available(2));
+    }
+}
@@ -0,0 +1,315 @@
+//! Rate limited work queue for tokio
+
+use std::{
+    collections::VecDeque,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+    time::Duration,
+};
+
+use crate::{Limiter, RetryHint};
+
+/// What a [`RateLimitedQueue`] does when an item is pushed to a full queue
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum OverflowPolicy {
+    /// Reject the new item
+    RejectNew,
+    /// Drop the oldest queued item to make room for the new one
+    DropOldest,
+}
+
+/// Bounded work queue drained no faster than a limiter allows
+///
+/// Items are pushed without waiting, and popped in FIFO order at the pace
+/// of the wrapped limiter, each item consuming a single token. While the
+/// limiter rejects, the drain sleeps on the tokio timer until the limiter
+/// expects a token to be available, see [`RetryHint`].
+///
+/// Producers running concurrently with the drain push through a
+/// [`QueueSender`], see [`Self::sender`], as the drain holds on to the queue
+/// itself.
+///
+/// # Generic arguments
+/// * `T` - queued item
+/// * `L` - limiter pacing the drain
+#[derive(Debug)]
+pub struct RateLimitedQueue<T, L>
+where
+    L: Limiter + RetryHint,
+{
+    limiter: L,
+    items: QueueSender<T>,
+    retry_interval: Duration,
+}
+
+/// Cloneable handle for pushing items to a [`RateLimitedQueue`]
+///
+/// All clones push to the same queue, and pushing only needs a shared
+/// reference, so producer tasks can keep pushing while the queue is drained.
+///
+/// # Generic arguments
+/// * `T` - queued item
+#[derive(Debug)]
+pub struct QueueSender<T> {
+    inner: Arc<Items<T>>,
+}
+
+/// Queued items and the overflow configuration shared with the senders
+#[derive(Debug)]
+struct Items<T> {
+    items: Mutex<VecDeque<T>>,
+    max_depth: usize,
+    overflow: OverflowPolicy,
+}
+
+impl<T, L> RateLimitedQueue<T, L>
+where
+    L: Limiter + RetryHint,
+{
+    /// Initialize a new empty queue
+    ///
+    /// # Arguments
+    /// * `limiter` - limiter pacing the drain
+    /// * `max_depth` - maximum number of queued items
+    /// * `overflow` - what to do when pushing to a full queue
+    /// * `retry_interval` - how long to sleep while the limiter rejects although
+    ///   its retry hint claims a token available, e.g. when the limiter is shared
+    ///
+    /// # Panics
+    /// Panics if `retry_interval` is zero, as the drain would spin instead of sleeping
+    pub fn new(
+        limiter: L,
+        max_depth: usize,
+        overflow: OverflowPolicy,
+        retry_interval: Duration,
+    ) -> Self {
+        assert!(!retry_interval.is_zero(), "retry interval must be nonzero");
+        Self {
+            limiter,
+            items: QueueSender {
+                inner: Arc::new(Items {
+                    items: Mutex::new(VecDeque::new()),
+                    max_depth,
+                    overflow,
+                }),
+            },
+            retry_interval,
+        }
+    }
+
+    /// Get a handle for pushing items while the queue is drained
+    pub fn sender(&self) -> QueueSender<T> {
+        self.items.clone()
+    }
+
+    /// Push an item to the back of the queue, see [`QueueSender::push`]
+    pub fn push(&self, item: T) -> Option<T> {
+        self.items.push(item)
+    }
+
+    /// Pop the next item as soon as the limiter allows
+    ///
+    /// # Returns
+    /// * `Some(item)` - next item
+    /// * `None` - the queue is empty, or the limiter can never admit a
+    ///   single token, see [`RetryHint::can_ever_consume`]. The items stay
+    ///   queued in the latter case.
+    pub async fn pop(&mut self) -> Option<T> {
+        if self.is_empty() {
+            return None;
+        }
+        while self.limiter.try_consume_one().is_err() {
+            if !self.limiter.can_ever_consume(1) {
+                return None;
+            }
+            let retry_after = self
+                .limiter
+                .time_until_available(1)
+                .filter(|wait| !wait.is_zero())
+                .unwrap_or(self.retry_interval);
+            tokio::time::sleep(retry_after).await;
+        }
+        // Items may have been dropped by a concurrent push in the meantime
+        self.items.lock().pop_front()
+    }
+
+    /// Deliver queued items to the handler at the pace of the limiter
+    ///
+    /// Items pushed through a [`QueueSender`] during the drain are delivered
+    /// as well. Returns once the queue is empty, or once the limiter can
+    /// never admit another item, see [`Self::pop`].
+    pub async fn drain(&mut self, mut handler: impl FnMut(T)) {
+        while let Some(item) = self.pop().await {
+            handler(item);
+        }
+    }
+
+    /// Number of queued items
+    pub fn len(&self) -> usize {
+        self.items.len()
+    }
+
+    /// `true` if no items are queued
+    pub fn is_empty(&self) -> bool {
+        self.items.is_empty()
+    }
+
+    /// Get a reference to the wrapped limiter
+    pub fn limiter(&self) -> &L {
+        &self.limiter
+    }
+
+    /// Get a mutable reference to the wrapped limiter, e.g. to refill a
+    /// [`crate::CreditBucket`] the drain stopped at
+    pub fn limiter_mut(&mut self) -> &mut L {
+        &mut self.limiter
+    }
+}
+
+impl<T> QueueSender<T> {
+    /// Push an item to the back of the queue
+    ///
+    /// # Returns
+    /// * `None` - item queued without dropping anything
+    /// * `Some(item)` - the queue was full and this item was dropped
+    ///   according to the [`OverflowPolicy`]
+    pub fn push(&self, item: T) -> Option<T> {
+        let mut items = self.lock();
+        if items.len() < self.inner.max_depth {
+            items.push_back(item);
+            return None;
+        }
+
+        if self.inner.overflow == OverflowPolicy::DropOldest {
+            if let Some(oldest) = items.pop_front() {
+                items.push_back(item);
+                return Some(oldest);
+            }
+        }
+        Some(item)
+    }
+
+    /// Number of queued items
+    pub fn len(&self) -> usize {
+        self.lock().len()
+    }
+
+    /// `true` if no items are queued
+    pub fn is_empty(&self) -> bool {
+        self.lock().is_empty()
+    }
+
+    /// A poisoned lock is ignored, as pushes and pops leave the queue consistent
+    fn lock(&self) -> MutexGuard<'_, VecDeque<T>> {
+        self.inner
+            .items
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl<T> Clone for QueueSender<T> {
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+#[cfg(all(test, feature = "fixed-window"))]
+mod tests {
+    use std::time::Duration;
+
+    use tokio::time::Instant;
+
+    use crate::FixedWindow;
+
+    use super::{OverflowPolicy, RateLimitedQueue};
+
+    #[tokio::test(start_paused = true)]
+    async fn verify_paced_drain() {
+        let start = Instant::now();
+        let w = FixedWindow::new_with_time_provider(2, 100, move || start.elapsed());
+        let mut q =
+            RateLimitedQueue::new(w, 4, OverflowPolicy::DropOldest, Duration::from_millis(10));
+
+        for i in 0..5 {
+            assert_eq!(q.push(i), if i == 4 { Some(0) } else { None });
+        }
+
+        let mut delivered = Vec::new();
+        q.drain(|i| delivered.push((i, start.elapsed().as_millis() / 100)))
+            .await;
+        // Two items per 100ms window
+        assert_eq!(delivered, [(1, 0), (2, 0), (3, 1), (4, 1)]);
+        assert!(q.pop().await.is_none());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn verify_sleeps_on_retry_hint() {
+        let start = Instant::now();
+        let w = FixedWindow::new_with_time_provider(1, 100, move || start.elapsed());
+        // The interval is only a fallback for limiters without a retry hint
+        let mut q =
+            RateLimitedQueue::new(w, 2, OverflowPolicy::RejectNew, Duration::from_secs(3600));
+        q.push(1);
+        q.push(2);
+
+        assert_eq!(q.pop().await, Some(1));
+        assert_eq!(q.pop().await, Some(2));
+        assert_eq!(start.elapsed(), Duration::from_millis(100));
+    }
+
+    #[tokio::test]
+    async fn verify_reject_new() {
+        let w = crate::CreditBucket::new(1, 1);
+        let mut q =
+            RateLimitedQueue::new(w, 1, OverflowPolicy::RejectNew, Duration::from_millis(10));
+        assert_eq!(q.push(1), None);
+        assert_eq!(q.push(2), Some(2));
+        assert_eq!(q.pop().await, Some(1));
+        assert!(q.is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn verify_concurrent_producer() {
+        let start = Instant::now();
+        let w = FixedWindow::new_with_time_provider(1, 100, move || start.elapsed());
+        let mut q =
+            RateLimitedQueue::new(w, 4, OverflowPolicy::RejectNew, Duration::from_millis(10));
+        q.push(0);
+        q.push(1);
+
+        let sender = q.sender();
+        let producer = tokio::spawn(async move {
+            for (i, at) in [(2, 50), (3, 150)] {
+                tokio::time::sleep_until(start + Duration::from_millis(at)).await;
+                assert_eq!(sender.push(i), None);
+            }
+        });
+
+        let mut delivered = Vec::new();
+        q.drain(|i| delivered.push((i, start.elapsed().as_millis() / 100)))
+            .await;
+        // Items pushed while the drain waits are delivered by the same drain
+        assert_eq!(delivered, [(0, 0), (1, 1), (2, 2), (3, 3)]);
+        producer.await.unwrap();
+    }
+
+    #[tokio::test]
+    async fn verify_never_admitted() {
+        let w = crate::CreditBucket::new(1, 0);
+        let mut q =
+            RateLimitedQueue::new(w, 2, OverflowPolicy::RejectNew, Duration::from_millis(10));
+        q.push(1);
+
+        // The credit bucket is never refilled by waiting
+        assert_eq!(q.pop().await, None);
+        q.drain(|_| unreachable!()).await;
+        assert_eq!(q.len(), 1);
+
+        q.limiter_mut().refill(1);
+        assert_eq!(q.pop().await, Some(1));
+    }
+}
@@ -0,0 +1,184 @@
+//! Human-readable rates
+
+use core::{fmt, str::FromStr, time::Duration};
+
+#[cfg(any(feature = "fixed-window", feature = "sliding-window"))]
+use crate::LimiterConfig;
+
+/// Number of tokens allowed per period, e.g. parsed from `"100/s"`
+///
+/// Rates parse from `<tokens>/<period>` strings where the period is one of
+/// `ms`, `s`, `min`, `h` or `d` with an optional count, e.g. `"5/min"`,
+/// `"1000/h"` or `"10/30s"`. Longer unit names like `sec`, `minute` or
+/// `hours` are accepted as well.
+///
+/// ```
+/// use core::time::Duration;
+/// use burster::Rate;
+///
+/// let rate: Rate = "5/min".parse().unwrap();
+/// assert_eq!(rate, Rate::new(5, Duration::from_secs(60)));
+/// ```
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Rate {
+    /// Tokens allowed per period
+    pub tokens: u64,
+    /// Length of the period
+    pub per: Duration,
+}
+
+impl Rate {
+    /// Define a rate
+    ///
+    /// # Arguments
+    /// * `tokens` - tokens allowed per period
+    /// * `per` - length of the period
+    pub const fn new(tokens: u64, per: Duration) -> Self {
+        Self { tokens, per }
+    }
+
+    /// Period in whole milliseconds
+    pub fn per_ms(&self) -> u64 {
+        self.per.as_millis().min(u64::MAX as u128) as u64
+    }
+
+    /// Fixed window admitting the rate per window
+    #[cfg(feature = "fixed-window")]
+    pub fn fixed_window(&self) -> LimiterConfig {
+        LimiterConfig::FixedWindow {
+            capacity: self.tokens,
+            window_width_ms: self.per_ms(),
+        }
+    }
+
+    /// Sliding window counter admitting the rate per window
+    #[cfg(feature = "sliding-window")]
+    pub fn sliding_window_counter(&self) -> LimiterConfig {
+        LimiterConfig::SlidingWindowCounter {
+            capacity: self.tokens,
+            window_width_ms: self.per_ms(),
+        }
+    }
+}
+
+impl fmt::Display for Rate {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let ms = self.per.as_millis();
+        let (count, unit) = [
+            (86_400_000, "d"),
+            (3_600_000, "h"),
+            (60_000, "min"),
+            (1000, "s"),
+        ]
+        .into_iter()
+        .find(|(unit_ms, _)| ms != 0 && ms / unit_ms * unit_ms == ms)
+        .map_or((ms, "ms"), |(unit_ms, unit)| (ms / unit_ms, unit));
+        if count == 1 {
+            write!(f, "{}/{}", self.tokens, unit)
+        } else {
+            write!(f, "{}/{}{}", self.tokens, count, unit)
+        }
+    }
+}
+
+/// Error type of parsing a [`Rate`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub enum ParseRateError {
+    /// No `/` between the tokens and the period
+    MissingSeparator,
+    /// Token or period count is not an unsigned integer
+    InvalidCount,
+    /// Period unit is not recognized
+    InvalidUnit,
+    /// Period is zero or too long
+    InvalidPeriod,
+}
+
+impl fmt::Display for ParseRateError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ParseRateError::MissingSeparator => write!(f, "Rate is missing a '/' separator"),
+            ParseRateError::InvalidCount => write!(f, "Rate has an invalid count"),
+            ParseRateError::InvalidUnit => write!(f, "Rate has an unknown period unit"),
+            ParseRateError::InvalidPeriod => write!(f, "Rate period is zero or too long"),
+        }
+    }
+}
+
+// core::error::Error trait stabilised at release 1.81
+#[rustversion::since(1.81)]
+impl core::error::Error for ParseRateError {}
+
+impl FromStr for Rate {
+    type Err = ParseRateError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (tokens, period) = s.split_once('/').ok_or(ParseRateError::MissingSeparator)?;
+        let tokens = tokens
+            .trim()
+            .parse()
+            .map_err(|_| ParseRateError::InvalidCount)?;
+
+        // Optional period count before the unit, e.g. "30s"
+        let period = period.trim();
+        let unit_start = period
+            .find(|c: char| !c.is_ascii_digit())
+            .ok_or(ParseRateError::InvalidUnit)?;
+        let (count, unit) = period.split_at(unit_start);
+        let count: u64 = if count.is_empty() {
+            1
+        } else {
+            count.parse().map_err(|_| ParseRateError::InvalidCount)?
+        };
+        let unit_ms = match unit.trim_start() {
+            "ms" => 1,
+            "s" | "sec" | "secs" | "second" | "seconds" => 1000,
+            "m" | "min" | "mins" | "minute" | "minutes" => 60_000,
+            "h" | "hr" | "hour" | "hours" => 3_600_000,
+            "d" | "day" | "days" => 86_400_000,
+            _ => return Err(ParseRateError::InvalidUnit),
+        };
+        match count.checked_mul(unit_ms) {
+            Some(0) | None => Err(ParseRateError::InvalidPeriod),
+            Some(ms) => Ok(Rate::new(tokens, Duration::from_millis(ms))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use super::{ParseRateError, Rate};
+
+    #[test]
+    fn verify_parse_rate() {
+        let parse = |s: &str| s.parse::<Rate>();
+        assert_eq!(parse("100/s"), Ok(Rate::new(100, Duration::from_secs(1))));
+        assert_eq!(parse("5/min"), Ok(Rate::new(5, Duration::from_secs(60))));
+        assert_eq!(
+            parse("1000/h"),
+            Ok(Rate::new(1000, Duration::from_secs(3600)))
+        );
+        assert_eq!(
+            parse(" 10 / 30 seconds "),
+            Ok(Rate::new(10, Duration::from_secs(30)))
+        );
+        assert_eq!(
+            parse("1/250ms"),
+            Ok(Rate::new(1, Duration::from_millis(250)))
+        );
+
+        assert_eq!(parse("100"), Err(ParseRateError::MissingSeparator));
+        assert_eq!(parse("-1/s"), Err(ParseRateError::InvalidCount));
+        assert_eq!(parse("1/fortnight"), Err(ParseRateError::InvalidUnit));
+        assert_eq!(parse("1/0s"), Err(ParseRateError::InvalidPeriod));
+
+        // Round trip through Display
+        #[cfg(feature = "alloc")]
+        for s in ["100/s", "5/min", "1000/h", "10/30s", "1/250ms", "7/2d"] {
+            assert_eq!(alloc::string::ToString::to_string(&parse(s).unwrap()), s);
+        }
+    }
+}
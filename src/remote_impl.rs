@@ -0,0 +1,201 @@
+//! Remote decision delegation with local fallback
+
+use core::{
+    future::{poll_fn, Future},
+    pin::pin,
+    task::Poll,
+    time::Duration,
+};
+
+use crate::{Limiter, LimiterResult};
+
+/// Rate limiter whose decisions are made by an external service
+///
+/// The trait is sans-IO friendly: implementations are free to perform the
+/// actual request over any transport. Burster only awaits the returned future.
+pub trait RemoteLimiter {
+    /// Error type for failed remote requests
+    type Error;
+
+    /// Ask the remote service to consume tokens
+    ///
+    /// # Returns
+    /// * `Ok(Ok(()))` - tokens consumed
+    /// * `Ok(Err(CantConsume))` - the remote service denied the consume
+    /// * `Err(Self::Error)` - the remote service couldn't be reached
+    fn try_consume(
+        &mut self,
+        tokens: u64,
+    ) -> impl Future<Output = Result<LimiterResult, Self::Error>>;
+}
+
+/// Which limiter made a decision of a [`WithFallback`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub enum DecisionSource {
+    /// The remote limiter answered in time
+    Remote,
+    /// The remote limiter failed or timed out and the local limiter decided
+    Local,
+}
+
+/// Remote limiter with a local fallback
+///
+/// Each consume is delegated to the [`RemoteLimiter`]. If the remote fails
+/// or doesn't answer within the configured timeout, the decision is made
+/// by the local [`Limiter`] instead.
+///
+/// # Generic arguments
+/// * `R` - remote limiter
+/// * `L` - local fallback limiter
+/// * `S` - async sleep function of the used runtime, e.g. `tokio::time::sleep`
+pub struct WithFallback<R, L, S, F>
+where
+    R: RemoteLimiter,
+    L: Limiter,
+    S: FnMut(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    remote: R,
+    local: L,
+    timeout: Duration,
+    sleep: S,
+    fallbacks: u64,
+}
+
+impl<R, L, S, F> WithFallback<R, L, S, F>
+where
+    R: RemoteLimiter,
+    L: Limiter,
+    S: FnMut(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    /// Combine a remote limiter with a local fallback
+    ///
+    /// # Arguments
+    /// * `remote` - limiter to delegate decisions to
+    /// * `local` - limiter deciding when the remote is slow or unavailable
+    /// * `timeout` - how long to wait for the remote decision
+    /// * `sleep` - async sleep function of the used runtime
+    pub fn new(remote: R, local: L, timeout: Duration, sleep: S) -> Self {
+        Self {
+            remote,
+            local,
+            timeout,
+            sleep,
+            fallbacks: 0,
+        }
+    }
+
+    /// Try to consume tokens
+    ///
+    /// Same as [`WithFallback::try_consume_with_source`] without reporting
+    /// which limiter made the decision.
+    pub async fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        self.try_consume_with_source(tokens).await.0
+    }
+
+    /// Try to consume tokens, reporting which limiter made the decision
+    pub async fn try_consume_with_source(
+        &mut self,
+        tokens: u64,
+    ) -> (LimiterResult, DecisionSource) {
+        let remote_result = {
+            let mut remote = pin!(self.remote.try_consume(tokens));
+            let mut timeout = pin!((self.sleep)(self.timeout));
+            poll_fn(|cx| {
+                if let Poll::Ready(result) = remote.as_mut().poll(cx) {
+                    return Poll::Ready(result.ok());
+                }
+                timeout.as_mut().poll(cx).map(|()| None)
+            })
+            .await
+        };
+
+        match remote_result {
+            Some(result) => (result, DecisionSource::Remote),
+            None => {
+                self.fallbacks = self.fallbacks.saturating_add(1);
+                (self.local.try_consume(tokens), DecisionSource::Local)
+            }
+        }
+    }
+
+    /// How many decisions have been made by the local limiter
+    pub fn fallbacks(&self) -> u64 {
+        self.fallbacks
+    }
+
+    /// Get a reference to the remote limiter
+    pub fn remote(&self) -> &R {
+        &self.remote
+    }
+
+    /// Get a reference to the local limiter
+    pub fn local(&self) -> &L {
+        &self.local
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{future::Future, time::Duration};
+
+    use crate::{
+        mock_assets::{block_on, yield_n},
+        CantConsume, CreditBucket, LimiterResult,
+    };
+
+    use super::{DecisionSource, RemoteLimiter, WithFallback};
+
+    /// Remote that answers after `delay` polls, or fails if `delay` is `None`
+    struct MockRemote {
+        delay: Option<usize>,
+        allow: bool,
+    }
+
+    impl RemoteLimiter for MockRemote {
+        type Error = ();
+
+        fn try_consume(
+            &mut self,
+            _tokens: u64,
+        ) -> impl Future<Output = Result<LimiterResult, Self::Error>> {
+            let delay = self.delay;
+            let allow = self.allow;
+            async move {
+                let delay = delay.ok_or(())?;
+                yield_n(delay).await;
+                Ok(if allow { Ok(()) } else { Err(CantConsume) })
+            }
+        }
+    }
+
+    #[test]
+    fn verify_fallback() {
+        let remote = MockRemote {
+            delay: Some(1),
+            allow: false,
+        };
+        // Timeout elapses after 5 polls
+        let sleep = |_: Duration| yield_n(5);
+        let mut f = WithFallback::new(remote, CreditBucket::new(1, 1), Duration::ZERO, sleep);
+
+        // Remote answers in time
+        let (result, source) = block_on(f.try_consume_with_source(1));
+        assert!(result.is_err());
+        assert_eq!(source, DecisionSource::Remote);
+
+        // Remote is too slow
+        f.remote.delay = Some(10);
+        let (result, source) = block_on(f.try_consume_with_source(1));
+        assert!(result.is_ok());
+        assert_eq!(source, DecisionSource::Local);
+
+        // Remote fails
+        f.remote.delay = None;
+        assert!(block_on(f.try_consume(1)).is_err());
+        assert_eq!(f.fallbacks(), 2);
+    }
+}
@@ -0,0 +1,79 @@
+//! Two-phase consumes
+
+use crate::{CantConsume, Refund};
+
+/// Tokens held from a limiter until committed or cancelled
+///
+/// Returned by [`Refund::reserve`]. The tokens are consumed right away, so
+/// there is no race between checking and consuming. Committing keeps them
+/// consumed, cancelling or dropping the reservation refunds them.
+///
+/// # Generic arguments
+/// * `L` - limiter the tokens were reserved from
+#[must_use = "dropping a reservation cancels it"]
+pub struct Reservation<'a, L>
+where
+    L: Refund,
+{
+    limiter: &'a mut L,
+    tokens: u64,
+}
+
+impl<'a, L> Reservation<'a, L>
+where
+    L: Refund,
+{
+    /// Reserve tokens from a limiter, see [`Refund::reserve`]
+    pub(crate) fn new(limiter: &'a mut L, tokens: u64) -> Result<Self, CantConsume> {
+        limiter.try_consume(tokens)?;
+        Ok(Self { limiter, tokens })
+    }
+
+    /// How many tokens are held
+    pub fn tokens(&self) -> u64 {
+        self.tokens
+    }
+
+    /// Keep the tokens consumed
+    pub fn commit(mut self) {
+        self.tokens = 0;
+    }
+
+    /// Return the tokens to the limiter
+    pub fn cancel(self) {}
+}
+
+impl<L> Drop for Reservation<'_, L>
+where
+    L: Refund,
+{
+    fn drop(&mut self) {
+        if self.tokens != 0 {
+            self.limiter.refund(self.tokens);
+        }
+    }
+}
+
+#[cfg(all(test, feature = "token-bucket"))]
+mod tests {
+    use crate::{mock_assets::MockClock, Inspect, Refund, TokenBucket};
+
+    #[test]
+    fn verify_reservation() {
+        let clock = MockClock::new();
+        let mut b = TokenBucket::new_with_time_provider(1000, 10, || clock.step(0));
+
+        let reservation = b.reserve(4).unwrap();
+        assert_eq!(reservation.tokens(), 4);
+        reservation.cancel();
+        assert_eq!(b.tokens_available(), 10);
+
+        b.reserve(4).unwrap().commit();
+        assert_eq!(b.tokens_available(), 6);
+
+        // Dropping cancels
+        drop(b.reserve(6).unwrap());
+        assert!(b.reserve(7).is_err());
+        assert_eq!(b.tokens_available(), 6);
+    }
+}
@@ -0,0 +1,341 @@
+//! Limiter shared between threads
+
+use std::{
+    sync::{Arc, Condvar, Mutex, MutexGuard, PoisonError},
+    time::{Duration, Instant},
+};
+
+use crate::{
+    AdjustableCapacity, CantConsume, Diagnostics, Inspect, Limiter, LimiterKind, LimiterResult,
+    Refund, Reset, RetryHint,
+};
+
+/// Limiter shared between threads
+///
+/// Cheaply cloneable handle to a limiter behind an `Arc<Mutex<_>>`. All
+/// clones consume from the same limiter, and consumes only need a shared
+/// reference. The handle is `Send + Sync` whenever the limiter is `Send`,
+/// which covers every limiter returned by the `std` utility functions.
+///
+/// Threads can also block until a consume is admitted, see
+/// [`SharedLimiter::consume_blocking`]. Blocked threads sleep until the
+/// limiter expects the tokens to be available, and are woken right away
+/// when another handle refunds tokens, resets the limiter or changes its
+/// capacity.
+///
+/// A poisoned lock is ignored, as the limiters stay consistent even if a
+/// thread panics while holding it.
+///
+/// # Generic arguments
+/// * `L` - shared limiter
+pub struct SharedLimiter<L>
+where
+    L: Limiter,
+{
+    inner: Arc<Shared<L>>,
+}
+
+/// Limiter and the condition variable its waiters block on
+struct Shared<L> {
+    limiter: Mutex<L>,
+    /// Notified when tokens may have become available outside of a refill
+    changed: Condvar,
+}
+
+impl<L> SharedLimiter<L>
+where
+    L: Limiter,
+{
+    /// Share a limiter between threads
+    pub fn new(limiter: L) -> Self {
+        Self {
+            inner: Arc::new(Shared {
+                limiter: Mutex::new(limiter),
+                changed: Condvar::new(),
+            }),
+        }
+    }
+
+    /// Try to consume tokens
+    ///
+    /// # Returns
+    /// * `Ok(())` - token consumed
+    /// * `Err(CantConsume)` - not enough tokens left
+    pub fn try_consume(&self, tokens: u64) -> LimiterResult {
+        self.lock().try_consume(tokens)
+    }
+
+    /// Try to consume a single token
+    pub fn try_consume_one(&self) -> LimiterResult {
+        self.try_consume(1)
+    }
+
+    /// Consume tokens, blocking the calling thread until they are admitted
+    ///
+    /// Waits forever if the limiter never admits `tokens`, e.g. when they
+    /// exceed its capacity, see [`Self::consume_timeout`] for a bounded wait.
+    pub fn consume_blocking(&self, tokens: u64)
+    where
+        L: RetryHint,
+    {
+        // Without a deadline the wait only ends with an admitted consume
+        let _ = self.wait_consume(tokens, None);
+    }
+
+    /// Consume tokens, blocking the calling thread until they are admitted
+    /// or the timeout elapses
+    ///
+    /// # Returns
+    /// * `Ok(())` - tokens consumed
+    /// * `Err(CantConsume)` - the tokens weren't admitted within `timeout`
+    pub fn consume_timeout(&self, tokens: u64, timeout: Duration) -> LimiterResult
+    where
+        L: RetryHint,
+    {
+        self.wait_consume(tokens, Instant::now().checked_add(timeout))
+    }
+
+    /// Return tokens of an admitted consume, waking up blocked threads
+    pub fn refund(&self, tokens: u64)
+    where
+        L: Refund,
+    {
+        self.lock().refund(tokens);
+        self.notify_waiters();
+    }
+
+    /// Return the limiter to the freshly constructed state, waking up blocked threads
+    pub fn reset(&self)
+    where
+        L: Reset,
+    {
+        self.lock().reset();
+        self.notify_waiters();
+    }
+
+    /// Change the capacity of the limiter, waking up blocked threads
+    pub fn set_capacity(&self, capacity: u64)
+    where
+        L: AdjustableCapacity,
+    {
+        self.lock().set_capacity(capacity);
+        self.notify_waiters();
+    }
+
+    /// Wake up the threads blocked in [`Self::consume_blocking`] to retry their consumes
+    ///
+    /// Only needed after making tokens available through [`Self::lock`].
+    pub fn notify_waiters(&self) {
+        self.inner.changed.notify_all();
+    }
+
+    /// Lock the limiter for exclusive access, e.g. for several consumes at once
+    ///
+    /// Blocked threads aren't woken up by changes made through the guard,
+    /// see [`Self::notify_waiters`].
+    pub fn lock(&self) -> MutexGuard<'_, L> {
+        self.inner
+            .limiter
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+
+    /// Block until `tokens` are admitted, or until `deadline` if given
+    fn wait_consume(&self, tokens: u64, deadline: Option<Instant>) -> LimiterResult
+    where
+        L: RetryHint,
+    {
+        let mut limiter = self.lock();
+        loop {
+            if limiter.try_consume(tokens).is_ok() {
+                return Ok(());
+            }
+            let remaining = match deadline {
+                Some(deadline) => match deadline.checked_duration_since(Instant::now()) {
+                    Some(remaining) if !remaining.is_zero() => Some(remaining),
+                    _ => return Err(CantConsume),
+                },
+                None => None,
+            };
+            // Sleep until the tokens are expected, or until notified if the
+            // limiter doesn't expect them at all
+            let wait = match (limiter.time_until_available(tokens), remaining) {
+                (Some(hint), Some(remaining)) => Some(hint.min(remaining)),
+                (hint, remaining) => hint.or(remaining),
+            };
+            limiter = match wait {
+                Some(wait) => {
+                    self.inner
+                        .changed
+                        .wait_timeout(limiter, wait)
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .0
+                }
+                None => self
+                    .inner
+                    .changed
+                    .wait(limiter)
+                    .unwrap_or_else(PoisonError::into_inner),
+            };
+        }
+    }
+}
+
+impl<L> Clone for SharedLimiter<L>
+where
+    L: Limiter,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: Arc::clone(&self.inner),
+        }
+    }
+}
+
+impl<L> Limiter for SharedLimiter<L>
+where
+    L: Limiter,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        SharedLimiter::try_consume(self, tokens)
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        self.lock().try_consume_batch(costs)
+    }
+
+    fn kind(&self) -> LimiterKind {
+        self.lock().kind()
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.lock().name()
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        self.lock().self_check()
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.lock().last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        self.lock().idle_for()
+    }
+
+    fn reset(&mut self) {
+        self.lock().reset();
+        self.notify_waiters();
+    }
+}
+
+impl<L> Reset for SharedLimiter<L> where L: Reset {}
+
+impl<L> Refund for SharedLimiter<L>
+where
+    L: Refund,
+{
+    fn refund(&mut self, tokens: u64) {
+        SharedLimiter::refund(self, tokens);
+    }
+}
+
+impl<L> AdjustableCapacity for SharedLimiter<L>
+where
+    L: AdjustableCapacity,
+{
+    fn current_capacity(&self) -> u64 {
+        self.lock().current_capacity()
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        SharedLimiter::set_capacity(self, capacity);
+    }
+}
+
+impl<L> RetryHint for SharedLimiter<L>
+where
+    L: RetryHint,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        self.lock().time_until_available(tokens)
+    }
+}
+
+impl<L> Inspect for SharedLimiter<L>
+where
+    L: Inspect,
+{
+    fn tokens_available(&self) -> u64 {
+        self.lock().tokens_available()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{thread, time::Duration};
+
+    use crate::CreditBucket;
+
+    use super::SharedLimiter;
+
+    #[test]
+    fn verify_shared() {
+        let shared = SharedLimiter::new(CreditBucket::new(100, 100));
+
+        let workers: Vec<_> = (0..4)
+            .map(|_| {
+                let s = shared.clone();
+                thread::spawn(move || (0..50).filter(|_| s.try_consume_one().is_ok()).count())
+            })
+            .collect();
+        let consumed: usize = workers.into_iter().map(|w| w.join().unwrap()).sum();
+
+        assert_eq!(consumed, 100);
+        assert!(shared.try_consume_one().is_err());
+        assert_eq!(shared.lock().refill(10), 10);
+        assert!(shared.try_consume(10).is_ok());
+    }
+
+    #[cfg(feature = "fixed-window")]
+    #[test]
+    fn verify_refund_wakes_waiter() {
+        // The window only resets after an hour
+        let shared = SharedLimiter::new(crate::fixed_window(1, 3_600_000));
+        assert!(shared.try_consume_one().is_ok());
+
+        let waiter = {
+            let s = shared.clone();
+            thread::spawn(move || {
+                let start = std::time::Instant::now();
+                s.consume_blocking(1);
+                start.elapsed()
+            })
+        };
+        thread::sleep(Duration::from_millis(20));
+        shared.refund(1);
+
+        assert!(waiter.join().unwrap() < Duration::from_secs(60));
+        assert!(shared.try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_consume_timeout() {
+        let shared = SharedLimiter::new(CreditBucket::new(1, 1));
+        assert!(shared.consume_timeout(1, Duration::ZERO).is_ok());
+        // Never refilled, and nobody notifies
+        assert!(shared
+            .consume_timeout(1, Duration::from_millis(10))
+            .is_err());
+
+        // A reset wakes the waiter up
+        let waiter = {
+            let s = shared.clone();
+            thread::spawn(move || s.consume_timeout(1, Duration::from_secs(60)))
+        };
+        thread::sleep(Duration::from_millis(20));
+        shared.reset();
+        assert!(waiter.join().unwrap().is_ok());
+    }
+}
@@ -0,0 +1,347 @@
+//! Approximate keyed limiting with a count-min sketch
+
+use core::{
+    hash::{Hash, Hasher},
+    time::Duration,
+};
+
+#[cfg(feature = "std")]
+use crate::StdClock;
+use crate::{keyed_impl::SeededHasher, CantConsume, Clock, ConfigError, LimiterResult};
+
+/// [`SketchLimiter`] using the system clock, as returned by [`sketch_limiter`]
+#[cfg(feature = "std")]
+pub type StdSketchLimiter<const W: usize, const D: usize> = SketchLimiter<StdClock, W, D>;
+
+/// Build an approximate keyed fixed window limiter
+///
+/// # Arguments
+/// * `capacity` - how many consumes are allowed per key during a single window
+/// * `window_width_ms` - window width in milliseconds
+#[cfg(feature = "std")]
+pub fn sketch_limiter<const W: usize, const D: usize>(
+    capacity: u64,
+    window_width_ms: u64,
+) -> StdSketchLimiter<W, D> {
+    SketchLimiter::new_with_time_provider(capacity, window_width_ms, StdClock)
+}
+
+/// Build an approximate keyed fixed window limiter with the window width given as a [`Duration`]
+///
+/// # Arguments
+/// * `capacity` - how many consumes are allowed per key during a single window
+/// * `window_width` - window width, rounded down to whole milliseconds
+#[cfg(feature = "std")]
+pub fn sketch_limiter_from_width<const W: usize, const D: usize>(
+    capacity: u64,
+    window_width: Duration,
+) -> StdSketchLimiter<W, D> {
+    SketchLimiter::from_width_with_time_provider(capacity, window_width, StdClock)
+}
+
+/// Approximate keyed fixed window -type rate limiter
+///
+/// Tracks the usage of each key in a count-min sketch of `D` rows with `W`
+/// counters each, so an unbounded key space can be limited in constant
+/// memory. The sketch is cleared at the start of every window.
+///
+/// Hash collisions can only make the estimated usage of a key larger than
+/// its real usage, so a key is never allowed more than `capacity` tokens
+/// per window, but may occasionally be limited early. Increasing `W`
+/// reduces the overestimate, increasing `D` reduces its probability.
+///
+/// # Generic arguments
+/// * `W` - Number of counters per row. Must be nonzero.
+/// * `D` - Number of rows, i.e. independent hash functions. Must be nonzero.
+#[derive(Debug, Clone)]
+pub struct SketchLimiter<T, const W: usize, const D: usize>
+where
+    T: Clock,
+{
+    state: SketchLimiterState<W, D>,
+    time_provider: T,
+}
+
+/// Plain-data state of a [`SketchLimiter`], see [`SketchLimiter::snapshot`]
+#[derive(Debug, Clone, Copy)]
+pub struct SketchLimiterState<const W: usize, const D: usize> {
+    capacity: u64,
+    width_ms: u64,
+    counters: [[u64; W]; D],
+    window_index: u64,
+    start_time: Duration,
+    name: Option<&'static str>,
+}
+
+impl<T, const W: usize, const D: usize> SketchLimiter<T, W, D>
+where
+    T: Clock,
+{
+    /// Initialize a new sketch limiter utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed per key during a single window
+    /// * `window_width_ms` - window width in milliseconds
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`sketch_limiter`]
+    ///
+    /// # Panics
+    /// Consumes panic on a division by zero if `window_width_ms` or `W` is zero,
+    /// use [`Self::try_new_with_time_provider`] to validate the configuration.
+    pub fn new_with_time_provider(capacity: u64, window_width_ms: u64, time_provider: T) -> Self {
+        let time_now = time_provider.now();
+        Self {
+            state: SketchLimiterState {
+                capacity,
+                width_ms: window_width_ms,
+                counters: [[0; W]; D],
+                window_index: 0,
+                start_time: time_now,
+                name: None,
+            },
+            time_provider,
+        }
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting configurations
+    /// that would never admit anything or divide by zero
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroLength`] if `W` or `D` is zero, [`ConfigError::ZeroWindow`]
+    /// or [`ConfigError::ZeroCapacity`] for zero arguments
+    pub fn try_new_with_time_provider(
+        capacity: u64,
+        window_width_ms: u64,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        if W == 0 || D == 0 {
+            return Err(ConfigError::ZeroLength);
+        }
+        if window_width_ms == 0 {
+            return Err(ConfigError::ZeroWindow);
+        }
+        if capacity == 0 {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        Ok(Self::new_with_time_provider(
+            capacity,
+            window_width_ms,
+            time_provider,
+        ))
+    }
+
+    /// Initialize a new sketch limiter with the window width given as a
+    /// [`Duration`] utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed per key during a single window
+    /// * `window_width` - window width, rounded down to whole milliseconds
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use
+    /// [`sketch_limiter_from_width`]
+    pub fn from_width_with_time_provider(
+        capacity: u64,
+        window_width: Duration,
+        time_provider: T,
+    ) -> Self {
+        Self::new_with_time_provider(capacity, window_width.as_millis() as u64, time_provider)
+    }
+
+    /// Take a plain-data snapshot of the limiter state
+    ///
+    /// The snapshot can be stashed e.g. in battery-backed RAM and later passed
+    /// to [`SketchLimiter::restore`], without requiring `serde`.
+    pub fn snapshot(&self) -> SketchLimiterState<W, D> {
+        self.state
+    }
+
+    /// Resume a limiter from a snapshot taken with [`SketchLimiter::snapshot`]
+    ///
+    /// The time provider must share the epoch of the one the snapshot was
+    /// taken with.
+    pub fn restore(snapshot: SketchLimiterState<W, D>, time_provider: T) -> Self {
+        Self {
+            state: snapshot,
+            time_provider,
+        }
+    }
+
+    /// Assign a name to this limiter
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.state.name = Some(name);
+        self
+    }
+
+    /// Name assigned with [`SketchLimiter::with_name`]
+    pub fn name(&self) -> Option<&'static str> {
+        self.state.name
+    }
+
+    /// Configured capacity per key
+    pub fn capacity(&self) -> u64 {
+        self.state.capacity
+    }
+
+    /// Configured window width
+    pub fn window_width(&self) -> Duration {
+        Duration::from_millis(self.state.width_ms)
+    }
+
+    /// Try to consume tokens for the given key
+    ///
+    /// # Arguments
+    /// * `key` - key to consume for
+    /// * `tokens` - how many tokens to consume
+    ///
+    /// # Returns
+    /// * `Ok(())` - token consumed
+    /// * `Err(CantConsume)` - the key has (likely) used up its budget for this window
+    pub fn try_consume_key<K: Hash + ?Sized>(&mut self, key: &K, tokens: u64) -> LimiterResult {
+        self.roll_window();
+        let slots = Self::slots(key);
+        let used = self.estimate_slots(&slots);
+        let needed = used.checked_add(tokens).ok_or(CantConsume)?;
+        if needed > self.state.capacity {
+            return Err(CantConsume);
+        }
+
+        // Conservative update: only raise counters that would otherwise
+        // underestimate the new usage of this key
+        for (row, &slot) in self.state.counters.iter_mut().zip(slots.iter()) {
+            row[slot] = row[slot].max(needed);
+        }
+        Ok(())
+    }
+
+    /// Estimated usage of the given key during the current window
+    ///
+    /// Never smaller than the real usage.
+    pub fn estimate<K: Hash + ?Sized>(&self, key: &K) -> u64 {
+        let now = self.time_provider.now();
+        if self.index_at(now) != self.state.window_index {
+            return 0;
+        }
+        self.estimate_slots(&Self::slots(key))
+    }
+
+    fn estimate_slots(&self, slots: &[usize; D]) -> u64 {
+        self.state
+            .counters
+            .iter()
+            .zip(slots.iter())
+            .map(|(row, &slot)| row[slot])
+            .min()
+            .unwrap_or(0)
+    }
+
+    /// Counter index of the key on each row
+    fn slots<K: Hash + ?Sized>(key: &K) -> [usize; D] {
+        let mut slots = [0; D];
+        for (row, slot) in slots.iter_mut().enumerate() {
+            let mut hasher = SeededHasher::new(row as u64);
+            key.hash(&mut hasher);
+            *slot = (hasher.finish() % W as u64) as usize;
+        }
+        slots
+    }
+
+    fn index_at(&self, now: Duration) -> u64 {
+        let delta_t = now.saturating_sub(self.state.start_time);
+        delta_t.as_millis() as u64 / self.state.width_ms
+    }
+
+    /// Clear the sketch if a new window has started
+    fn roll_window(&mut self) {
+        let now = self.time_provider.now();
+        let index = self.index_at(now);
+        if index != self.state.window_index {
+            self.state.counters = [[0; W]; D];
+            self.state.window_index = index;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{mock_assets::MockClock, ConfigError};
+
+    use super::SketchLimiter;
+
+    #[test]
+    fn verify_keyed_limits() {
+        let clock = MockClock::new();
+        let mut s = SketchLimiter::<_, 64, 4>::new_with_time_provider(3, 1, || clock.step(0));
+
+        assert!(s.try_consume_key("a", 3).is_ok());
+        assert!(s.try_consume_key("a", 1).is_err());
+        // Other keys have their own budget
+        assert!(s.try_consume_key("b", 2).is_ok());
+        assert!(s.try_consume_key(&42u32, 3).is_ok());
+        assert!(s.estimate("a") >= 3);
+
+        clock.step(1000);
+        // New window
+        assert_eq!(s.estimate("a"), 0);
+        assert!(s.try_consume_key("a", 3).is_ok());
+    }
+
+    #[test]
+    fn verify_never_overadmits() {
+        let clock = MockClock::new();
+        // Tiny sketch, collisions are guaranteed
+        let mut s = SketchLimiter::<_, 4, 2>::new_with_time_provider(5, 1000, || clock.step(0));
+
+        let mut admitted = [0u64; 32];
+        for _ in 0..10 {
+            for (key, count) in admitted.iter_mut().enumerate() {
+                if s.try_consume_key(&key, 1).is_ok() {
+                    *count += 1;
+                }
+            }
+        }
+        assert!(admitted.iter().all(|&count| count <= 5));
+        assert!(admitted.iter().any(|&count| count > 0));
+    }
+
+    #[test]
+    fn verify_snapshot_restore() {
+        let clock = MockClock::new();
+        let mut s = SketchLimiter::<_, 64, 4>::new_with_time_provider(3, 10, || clock.step(0));
+        assert!(s.try_consume_key("a", 3).is_ok());
+
+        let mut s = SketchLimiter::restore(s.snapshot(), || clock.step(0));
+        assert!(s.try_consume_key("a", 1).is_err());
+        clock.step(10_000);
+        assert!(s.try_consume_key("a", 3).is_ok());
+    }
+
+    #[test]
+    fn verify_try_new() {
+        let now = || Duration::ZERO;
+        assert!(matches!(
+            SketchLimiter::<_, 0, 4>::try_new_with_time_provider(3, 10, now),
+            Err(ConfigError::ZeroLength)
+        ));
+        assert!(matches!(
+            SketchLimiter::<_, 64, 0>::try_new_with_time_provider(3, 10, now),
+            Err(ConfigError::ZeroLength)
+        ));
+        assert!(matches!(
+            SketchLimiter::<_, 64, 4>::try_new_with_time_provider(3, 0, now),
+            Err(ConfigError::ZeroWindow)
+        ));
+        assert!(matches!(
+            SketchLimiter::<_, 64, 4>::try_new_with_time_provider(0, 10, now),
+            Err(ConfigError::ZeroCapacity)
+        ));
+
+        let mut s = SketchLimiter::<_, 64, 4>::try_new_with_time_provider(3, 10, now).unwrap();
+        assert!(s.try_consume_key("a", 3).is_ok());
+    }
+}
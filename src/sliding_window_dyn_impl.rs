@@ -0,0 +1,356 @@
+//! Heap-backed sliding window -type limiter
+
+use alloc::collections::VecDeque;
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+use crate::StdClock;
+use crate::{
+    diagnostics_impl::ClockMonitor, CantConsume, Clock, ConfigError, Diagnostics, Inspect, Limiter,
+    LimiterKind, LimiterResult, RemainingBudget, Reset, RetryHint,
+};
+
+/// [`SlidingWindowLogDyn`] using the system clock, as returned by [`sliding_window_log_dyn`]
+#[cfg(feature = "std")]
+pub type StdSlidingWindowLogDyn = SlidingWindowLogDyn<StdClock>;
+
+/// Build a sliding window limiter with a runtime window width
+///
+/// # Arguments
+/// * `capacity` - how many consumes are allowed during a single window
+/// * `window_width_ms` - window width in milliseconds
+#[cfg(feature = "std")]
+pub fn sliding_window_log_dyn(capacity: u64, window_width_ms: usize) -> StdSlidingWindowLogDyn {
+    SlidingWindowLogDyn::new_with_time_provider(capacity, window_width_ms, StdClock)
+}
+
+/// Build a sliding window limiter with a runtime window width given as a [`Duration`]
+///
+/// # Arguments
+/// * `capacity` - how many consumes are allowed during a single window
+/// * `window_width` - window width, rounded down to whole milliseconds
+#[cfg(feature = "std")]
+pub fn sliding_window_log_dyn_from_width(
+    capacity: u64,
+    window_width: Duration,
+) -> StdSlidingWindowLogDyn {
+    SlidingWindowLogDyn::from_width_with_time_provider(capacity, window_width, StdClock)
+}
+
+/// Sliding window log -type rate limiter with a runtime window width
+///
+/// Same algorithm as [`crate::SlidingWindowLog`], but the per-millisecond
+/// usage log is allocated from the heap, so the window width can be picked
+/// e.g. from runtime configuration. A running total of the log is kept, so
+/// consumes cost `O(1)` plus the milliseconds elapsed since the last one.
+///
+/// # Generic arguments
+/// * `T` - time provider
+#[derive(Debug, Clone)]
+pub struct SlidingWindowLogDyn<T>
+where
+    T: Clock,
+{
+    capacity: u64,
+    name: Option<&'static str>,
+    /// Tokens used during each millisecond, the current one first
+    slots: VecDeque<u64>,
+    /// Sum of all slots
+    used: u64,
+    last_update_time: Duration,
+    monitor: ClockMonitor,
+    time_provider: T,
+}
+
+impl<T> SlidingWindowLogDyn<T>
+where
+    T: Clock,
+{
+    /// Initialize a new sliding window limiter utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width_ms` - window width in milliseconds, at least one
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`sliding_window_log_dyn`]
+    pub fn new_with_time_provider(capacity: u64, window_width_ms: usize, time_provider: T) -> Self {
+        let time_now = time_provider.now();
+        let mut slots = VecDeque::new();
+        slots.resize(window_width_ms.max(1), 0);
+        Self {
+            capacity,
+            name: None,
+            slots,
+            used: 0,
+            last_update_time: time_now,
+            monitor: ClockMonitor::new(time_now),
+            time_provider,
+        }
+    }
+
+    /// Initialize a new sliding window limiter with the window width given as a
+    /// [`Duration`] utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width` - window width, rounded down to whole milliseconds, at least one
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use
+    /// [`sliding_window_log_dyn_from_width`]
+    pub fn from_width_with_time_provider(
+        capacity: u64,
+        window_width: Duration,
+        time_provider: T,
+    ) -> Self {
+        let window_width_ms = usize::try_from(window_width.as_millis()).unwrap_or(usize::MAX);
+        Self::new_with_time_provider(capacity, window_width_ms, time_provider)
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting invalid configurations
+    /// instead of rounding a zero window width up to one millisecond
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroWindow`] or [`ConfigError::ZeroCapacity`] for zero arguments
+    pub fn try_new_with_time_provider(
+        capacity: u64,
+        window_width_ms: usize,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        if window_width_ms == 0 {
+            return Err(ConfigError::ZeroWindow);
+        }
+        if capacity == 0 {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        Ok(Self::new_with_time_provider(
+            capacity,
+            window_width_ms,
+            time_provider,
+        ))
+    }
+
+    /// Assign a name to this limiter, see [`Limiter::name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.capacity
+    }
+
+    /// Configured window width
+    pub fn window_width(&self) -> Duration {
+        Duration::from_millis(self.slots.len() as u64)
+    }
+
+    /// Milliseconds elapsed since the last update at `now`
+    fn elapsed_ms(&self, now: Duration) -> usize {
+        let delta_t = now.saturating_sub(self.last_update_time).as_millis();
+        delta_t.min(usize::MAX as u128) as usize
+    }
+
+    /// Tokens counted in the window at `now`, before sliding it
+    fn used_at(&self, elapsed: usize) -> u64 {
+        let expired = self.slots.iter().rev().take(elapsed).sum::<u64>();
+        self.used - expired
+    }
+
+    /// Slide the window forward to `now`
+    fn advance(&mut self, now: Duration) {
+        let elapsed = self.elapsed_ms(now);
+        if elapsed == 0 {
+            return;
+        }
+        if elapsed >= self.slots.len() {
+            self.last_update_time = now;
+            self.slots.iter_mut().for_each(|slot| *slot = 0);
+            self.used = 0;
+            return;
+        }
+        // Advance by whole milliseconds to keep the slot boundaries in place
+        self.last_update_time += Duration::from_millis(elapsed as u64);
+        for _ in 0..elapsed {
+            let expired = self.slots.pop_back().unwrap_or(0);
+            self.used -= expired;
+            self.slots.push_front(0);
+        }
+    }
+
+    /// Consume at the given time, returning the remaining budget
+    fn try_consume_at(&mut self, now: Duration, tokens: u64) -> Result<u64, CantConsume> {
+        self.monitor.observe(now);
+        self.advance(now);
+
+        let tokens_left = self.capacity.saturating_sub(self.used);
+        if tokens_left >= tokens {
+            self.slots[0] += tokens;
+            self.used += tokens;
+            Ok(tokens_left - tokens)
+        } else {
+            Err(CantConsume)
+        }
+    }
+}
+
+impl<T> Limiter for SlidingWindowLogDyn<T>
+where
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        self.try_consume_at(now, tokens).map(|_| ())
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        let now = self.time_provider.now();
+        costs
+            .iter()
+            .take_while(|&&tokens| self.try_consume_at(now, tokens).is_ok())
+            .count()
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::SlidingWindowLog
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.time_provider.now();
+        self.monitor.diagnose(now, self.capacity != 0)
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.monitor.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.time_provider.now();
+        Some(self.monitor.idle_for(now))
+    }
+
+    fn reset(&mut self) {
+        let now = self.time_provider.now();
+        self.slots.iter_mut().for_each(|slot| *slot = 0);
+        self.used = 0;
+        self.last_update_time = now;
+        self.monitor.reset(now);
+    }
+}
+
+impl<T> Reset for SlidingWindowLogDyn<T> where T: Clock {}
+
+impl<T> RetryHint for SlidingWindowLogDyn<T>
+where
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        if tokens > self.capacity {
+            return None;
+        }
+        let now = self.time_provider.now();
+        let elapsed = self.elapsed_ms(now);
+        if elapsed >= self.slots.len() {
+            return Some(Duration::ZERO);
+        }
+
+        // Each millisecond the oldest counted slot slides out of the window
+        let counted = self.slots.len() - elapsed;
+        let mut used = self.used_at(elapsed);
+        for slid in 0..=counted {
+            if used.saturating_add(tokens) <= self.capacity {
+                if slid == 0 {
+                    return Some(Duration::ZERO);
+                }
+                let slid = Duration::from_millis((elapsed + slid) as u64);
+                return Some((self.last_update_time + slid).saturating_sub(now));
+            }
+            used -= self.slots[counted - 1 - slid];
+        }
+        None
+    }
+}
+
+impl<T> Inspect for SlidingWindowLogDyn<T>
+where
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        let elapsed = self.elapsed_ms(now);
+        if elapsed >= self.slots.len() {
+            return self.capacity;
+        }
+        self.capacity.saturating_sub(self.used_at(elapsed))
+    }
+}
+
+impl<T> RemainingBudget for SlidingWindowLogDyn<T>
+where
+    T: Clock,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        let now = self.time_provider.now();
+        self.try_consume_at(now, tokens)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{
+        mock_assets::MockClock, ConfigError, Inspect, Limiter, RetryHint, SlidingWindowLog,
+    };
+
+    use super::SlidingWindowLogDyn;
+
+    #[test]
+    fn verify_try_new_sliding_dyn() {
+        let clock = MockClock::new();
+        assert_eq!(
+            SlidingWindowLogDyn::try_new_with_time_provider(5, 0, || clock.step(0)).err(),
+            Some(ConfigError::ZeroWindow)
+        );
+        assert_eq!(
+            SlidingWindowLogDyn::try_new_with_time_provider(0, 10, || clock.step(0)).err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+        let mut d =
+            SlidingWindowLogDyn::try_new_with_time_provider(5, 10, || clock.step(0)).unwrap();
+        assert!(d.try_consume(5).is_ok());
+    }
+
+    #[test]
+    fn verify_rate_sliding_dyn() {
+        let clock = MockClock::new();
+        let mut d = SlidingWindowLogDyn::new_with_time_provider(5, 10, || clock.step(0));
+        let mut log = SlidingWindowLog::<_, 10>::new_with_time_provider(5, || clock.step(0));
+
+        // Same decisions as the fixed-width log
+        for step in [0, 3_000, 4_000, 1_000, 2_500, 12_000, 500, 9_000] {
+            clock.step(step);
+            for tokens in [2, 1, 3] {
+                assert_eq!(
+                    d.try_consume(tokens).is_ok(),
+                    log.try_consume(tokens).is_ok()
+                );
+                assert_eq!(d.tokens_available(), log.tokens_available());
+                assert_eq!(d.time_until_available(4), log.time_until_available(4));
+            }
+        }
+
+        assert_eq!(d.window_width(), Duration::from_millis(10));
+        d.reset();
+        assert!(d.try_consume(5).is_ok());
+        assert!(d.try_consume_one().is_err());
+    }
+}
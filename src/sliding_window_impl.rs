@@ -3,33 +3,107 @@
 use core::time::Duration;
 
 #[cfg(feature = "std")]
-use crate::macros::std_time_provider;
-use crate::{CantConsume, Limiter, LimiterResult};
+use crate::StdClock;
+use crate::{
+    diagnostics_impl::ClockMonitor, CantConsume, Clock, ConfigError, Diagnostics, Inspect, Limiter,
+    LimiterCore, LimiterKind, LimiterResult, Refund, RemainingBudget, Request, Reset, Response,
+    RetryHint, TokenCount,
+};
+
+/// [`SlidingWindowLog`] using the system clock, as returned by [`sliding_window_log`]
+#[cfg(feature = "std")]
+pub type StdSlidingWindowLog<const W: usize> = SlidingWindowLog<StdClock, W>;
 
 /// Build a sliding window limiter
 ///
-/// Window width is defined by the generic argument `W: usize`
+/// Window width in milliseconds is defined by the generic argument `W: usize`,
+/// see [`SlidingWindowLog::with_slot_width`] for longer windows
 ///
 /// # Arguments
 /// * `capacity` - how many consumes are allowed during a single window
 #[cfg(feature = "std")]
-pub fn sliding_window_log<const W: usize>(
-    capacity: u64,
-) -> SlidingWindowLog<impl Fn() -> Duration, W> {
-    SlidingWindowLog::<_, W>::new_with_time_provider(capacity, std_time_provider!())
+pub fn sliding_window_log<const W: usize>(capacity: u64) -> StdSlidingWindowLog<W> {
+    SlidingWindowLog::<_, W>::new_with_time_provider(capacity, StdClock)
 }
 
+/// [`SlidingWindowCounter`] using the system clock, as returned by [`sliding_window_counter`]
+#[cfg(feature = "std")]
+pub type StdSlidingWindowCounter = SlidingWindowCounter<StdClock>;
+
 /// Build a sliding window counter limiter
 ///
 /// # Arguments
 /// * `capacity` - how many consumes are allowed during a single window
 /// * `window_width_ms` - window width in milliseconds
 #[cfg(feature = "std")]
-pub fn sliding_window_counter(
+pub fn sliding_window_counter(capacity: u64, window_width_ms: u64) -> StdSlidingWindowCounter {
+    SlidingWindowCounter::new_with_time_provider(capacity, window_width_ms, StdClock)
+}
+
+/// Build a sliding window counter limiter with the window width given as a [`Duration`]
+///
+/// # Arguments
+/// * `capacity` - how many consumes are allowed during a single window
+/// * `window_width` - window width, rounded down to whole milliseconds
+#[cfg(feature = "std")]
+pub fn sliding_window_counter_from_width(
+    capacity: u64,
+    window_width: Duration,
+) -> StdSlidingWindowCounter {
+    SlidingWindowCounter::from_width_with_time_provider(capacity, window_width, StdClock)
+}
+
+/// [`SparseSlidingWindowLog`] using the system clock, as returned by [`sparse_sliding_window_log`]
+#[cfg(feature = "std")]
+pub type StdSparseSlidingWindowLog<const N: usize> = SparseSlidingWindowLog<StdClock, N>;
+
+/// Build a sparse sliding window log limiter
+///
+/// Maximum number of stored entries is defined by the generic argument `N: usize`
+///
+/// # Arguments
+/// * `capacity` - how many consumes are allowed during a single window
+/// * `window_width_ms` - window width in milliseconds
+#[cfg(feature = "std")]
+pub fn sparse_sliding_window_log<const N: usize>(
     capacity: u64,
     window_width_ms: u64,
-) -> SlidingWindowCounter<impl Fn() -> Duration> {
-    SlidingWindowCounter::new_with_time_provider(capacity, window_width_ms, std_time_provider!())
+) -> StdSparseSlidingWindowLog<N> {
+    SparseSlidingWindowLog::<_, N>::new_with_time_provider(capacity, window_width_ms, StdClock)
+}
+
+/// Build a sparse sliding window log limiter with the window width given as a [`Duration`]
+///
+/// Maximum number of stored entries is defined by the generic argument `N: usize`
+///
+/// # Arguments
+/// * `capacity` - how many consumes are allowed during a single window
+/// * `window_width` - window width, rounded down to whole milliseconds
+#[cfg(feature = "std")]
+pub fn sparse_sliding_window_log_from_width<const N: usize>(
+    capacity: u64,
+    window_width: Duration,
+) -> StdSparseSlidingWindowLog<N> {
+    SparseSlidingWindowLog::<_, N>::from_width_with_time_provider(capacity, window_width, StdClock)
+}
+
+/// [`SlidingWindowLogExact`] using the system clock, as returned by [`sliding_window_log_exact`]
+#[cfg(feature = "std")]
+pub type StdSlidingWindowLogExact<const N: usize> = SlidingWindowLogExact<StdClock, N>;
+
+/// Build an exact sliding window log limiter
+///
+/// Maximum number of stored entries is defined by the generic argument `N: usize`
+///
+/// # Arguments
+/// * `capacity` - how many consumes are allowed during a single window
+/// * `window_width` - window width
+#[cfg(feature = "std")]
+pub fn sliding_window_log_exact<const N: usize>(
+    capacity: u64,
+    window_width: Duration,
+) -> StdSlidingWindowLogExact<N> {
+    SlidingWindowLogExact::<_, N>::new_with_time_provider(capacity, window_width, StdClock)
 }
 
 /// Sliding window log -type rate limiter
@@ -39,85 +113,632 @@ pub fn sliding_window_counter(
 /// most recent consume and limits usage if that number grows
 /// larger than the defined limit.
 ///
+/// The algorithm itself is implemented by [`SlidingWindowLogCore`], this
+/// type only pairs it with a time provider.
+///
+/// Usage is logged in `W` slots of one millisecond each by default. For
+/// long windows, [`SlidingWindowLog::with_slot_width`] trades precision for
+/// memory, e.g. a minute long window fits in 60 slots of one second.
+///
 /// # Generic arguments
-/// * `W` - Window width in milliseconds
+/// * `W` - Window width in slots, i.e. in milliseconds with the default slot width
+/// * `C` - storage type of each slot, see [`TokenCount`]. E.g. `u32` halves
+///   the memory taken by the slots on small targets.
 ///
 /// # Notes
-/// This limiter requires copying on each access with general complexity
-/// of `O(window_width)`. If you are running on a low-powered target and
-/// need a more performant variant, take a look at [`SlidingWindowCounter`].
-pub struct SlidingWindowLog<T, const W: usize>
+/// The slots form a ring buffer with a running total, so a consume costs
+/// `O(1)` plus the number of slots elapsed since the previous one, at most
+/// `W`. The limiter still needs `W` slots of memory though. If you are
+/// running on a low-powered target and need a leaner variant, take a look
+/// at [`SlidingWindowCounter`].
+#[derive(Debug, Clone)]
+pub struct SlidingWindowLog<T, const W: usize, C = u64>
 where
-    T: Fn() -> Duration,
+    T: Clock,
+    C: TokenCount,
 {
-    config: SlidingWindowConfig<T>,
-    /// Each slot represents a point in past time relative to current time.
-    /// When time moves forward, we effectively shift the slots to right.
-    window_buffer: [u64; W],
-    last_update_time: Duration,
+    core: SlidingWindowLogCore<W, C>,
+    time_provider: T,
 }
 
-impl<T, const W: usize> SlidingWindowLog<T, W>
+impl<T, const W: usize, C> SlidingWindowLog<T, W, C>
 where
-    T: Fn() -> Duration,
+    T: Clock,
+    C: TokenCount,
 {
     /// Initialize a new sliding window limiter utilizing the given timer
     ///
     /// # Arguments
     /// * `capacity` - how many consumes are allowed during a single window
-    /// * `time_provider_t` - closure that returns a monotonically nondecreasing
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
     ///   timestamp as [`Duration`] from some fixed epoch in the past
-
     ///
     /// # Notes
     /// * If you are developing for a `std` target, you probably wish to use [`sliding_window_log`]
     /// * Window width is defined by the generic argument `W: usize`
+    ///
+    /// # Panics
+    /// Consumes panic if `W` is zero, use [`Self::try_new_with_time_provider`]
+    /// to validate the configuration.
     pub fn new_with_time_provider(capacity: u64, time_provider: T) -> Self {
-        let time_now = time_provider();
-        let config = SlidingWindowConfig::new(capacity, time_provider);
+        let time_now = time_provider.now();
+        Self {
+            core: SlidingWindowLogCore::new(capacity, time_now),
+            time_provider,
+        }
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting invalid configurations
+    ///
+    /// # Returns
+    /// * [`ConfigError::ZeroWindow`] if `W` is zero
+    /// * [`ConfigError::ZeroCapacity`] if `capacity` is zero
+    /// * [`ConfigError::CapacityOverflow`] if a slot of type `C` can't hold `capacity` tokens
+    pub fn try_new_with_time_provider(
+        capacity: u64,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        let time_now = time_provider.now();
+        Ok(Self {
+            core: SlidingWindowLogCore::try_new(capacity, time_now)?,
+            time_provider,
+        })
+    }
+
+    /// Log usage in slots of the given width instead of one millisecond
+    ///
+    /// The window then spans `W` slots of this width. Tokens slide out of the
+    /// window a whole slot at a time, so a consume may be limited by tokens
+    /// consumed up to one slot width longer ago than the window width.
+    /// The width is rounded down to whole milliseconds, at least one.
+    ///
+    /// Any usage history is cleared.
+    pub fn with_slot_width(mut self, slot_width: Duration) -> Self {
+        self.core = self.core.with_slot_width(slot_width);
+        self
+    }
+
+    /// Assign a name to this limiter, see [`Limiter::name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.core = self.core.with_name(name);
+        self
+    }
+
+    /// Start with the given usage history instead of an empty window
+    ///
+    /// Allows a restarted service to seed the limiter from persisted usage.
+    /// `usage[0]` holds the tokens used during the current slot,
+    /// `usage[1]` the tokens used one slot before that and so on.
+    /// Entries beyond the window width are ignored.
+    pub fn with_initial_usage(mut self, usage: &[u64]) -> Self {
+        self.core = self.core.with_initial_usage(usage);
+        self
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.core.capacity()
+    }
+
+    /// Current effective window width
+    pub fn window_width(&self) -> Duration {
+        self.core.window_width()
+    }
+
+    /// Current state of the window, e.g. for dashboards and debug logs
+    pub fn state(&self) -> SlidingWindowState {
+        let now = self.time_provider.now();
+        self.core.state_at(now)
+    }
+
+    /// Change the effective window width at runtime
+    ///
+    /// The width is clamped to `1..=W` slots, `W` being the allocated
+    /// slot capacity. Usage history is kept for the full `W` slots
+    /// regardless of the effective width, so narrowing the window only stops
+    /// counting older tokens and widening it again brings them back into
+    /// account instead of starting from an empty history.
+    pub fn set_window_width(&mut self, width: Duration) {
+        self.core.set_window_width(width);
+    }
+
+    /// Get a reference to the underlying sans-IO state machine
+    pub fn core(&self) -> &SlidingWindowLogCore<W, C> {
+        &self.core
+    }
+
+    /// Unwrap the underlying sans-IO state machine, dropping the time provider
+    pub fn into_core(self) -> SlidingWindowLogCore<W, C> {
+        self.core
+    }
+
+    /// Take a plain-data snapshot of the limiter state
+    ///
+    /// The snapshot can be stashed e.g. in battery-backed RAM and later passed
+    /// to [`Self::restore`], without requiring `serde`.
+    pub fn snapshot(&self) -> SlidingWindowLogSnapshot<W, C> {
+        self.core.snapshot()
+    }
+
+    /// Resume a limiter from a snapshot taken with [`Self::snapshot`]
+    ///
+    /// The time provider must share the epoch of the one the snapshot was
+    /// taken with, e.g. a real-time clock kept running during deep sleep.
+    /// Time elapsed in between is accounted for like it had been running.
+    /// The name is not part of the snapshot, see [`Self::with_name`].
+    pub fn restore(snapshot: SlidingWindowLogSnapshot<W, C>, time_provider: T) -> Self {
         Self {
-            config,
-            window_buffer: [0; W],
-            last_update_time: time_now,
+            core: SlidingWindowLogCore::restore(snapshot),
+            time_provider,
         }
     }
 }
 
-impl<T, const W: usize> Limiter for SlidingWindowLog<T, W>
+impl<T, const W: usize, C> Limiter for SlidingWindowLog<T, W, C>
 where
-    T: Fn() -> Duration,
+    T: Clock,
+    C: TokenCount,
 {
     fn try_consume(&mut self, tokens: u64) -> LimiterResult {
-        let now = (self.config.time_provider)();
-        let delta_t = now.saturating_sub(self.last_update_time).as_millis() as u64;
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens).map(|_| ())
+    }
 
-        // delta_t is more than the window size, reset the whole limiter
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        let now = self.time_provider.now();
+        costs
+            .iter()
+            .take_while(|&&tokens| self.core.try_consume_at(now, tokens).is_ok())
+            .count()
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::SlidingWindowLog
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.core.config.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.time_provider.now();
+        self.core.self_check_at(now)
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.core.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.time_provider.now();
+        Some(self.core.idle_for_at(now))
+    }
+
+    fn reset(&mut self) {
+        let now = self.time_provider.now();
+        self.core.reset_at(now);
+    }
+}
+
+impl<T, const W: usize, C> Reset for SlidingWindowLog<T, W, C>
+where
+    T: Clock,
+    C: TokenCount,
+{
+}
+
+impl<T, const W: usize, C> Refund for SlidingWindowLog<T, W, C>
+where
+    T: Clock,
+    C: TokenCount,
+{
+    fn refund(&mut self, tokens: u64) {
+        let now = self.time_provider.now();
+        self.core.refund_at(now, tokens);
+    }
+}
+
+impl<T, const W: usize, C> RetryHint for SlidingWindowLog<T, W, C>
+where
+    T: Clock,
+    C: TokenCount,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.time_provider.now();
+        self.core.time_until_available_at(now, tokens)
+    }
+}
+
+impl<T, const W: usize, C> Inspect for SlidingWindowLog<T, W, C>
+where
+    T: Clock,
+    C: TokenCount,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.tokens_available_at(now)
+    }
+}
+
+impl<T, const W: usize, C> RemainingBudget for SlidingWindowLog<T, W, C>
+where
+    T: Clock,
+    C: TokenCount,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens)
+    }
+}
+
+/// Observable state of a sliding window limiter, e.g. a [`SlidingWindowLog`]
+/// or a [`SlidingWindowCounter`], see e.g. [`SlidingWindowLog::state`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SlidingWindowState {
+    /// Tokens that could be consumed right now
+    pub tokens: u64,
+    /// Configured capacity
+    pub capacity: u64,
+    /// Current effective window width
+    pub window_width: Duration,
+}
+
+/// Parameters of a [`SlidingWindowCounter`], e.g. deserialized from a configuration file
+///
+/// See [`SlidingWindowCounter::from_config`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct SlidingWindowCounterConfig {
+    /// How many consumes are allowed during a single window
+    pub capacity: u64,
+    /// Window width in milliseconds
+    pub window_width_ms: u64,
+}
+
+/// Plain-data snapshot of a [`SlidingWindowLog`], see [`SlidingWindowLog::snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlidingWindowLogSnapshot<const W: usize, C = u64> {
+    /// Configured capacity
+    pub capacity: u64,
+    /// Tokens used per slot, the slot of `last_update` first and older ones after it
+    pub slots: [C; W],
+    /// Effective window width in slots, see [`SlidingWindowLog::set_window_width`]
+    pub window_slots: usize,
+    /// Configured slot width, see [`SlidingWindowLog::with_slot_width`]
+    pub slot_width: Duration,
+    /// Start of the slot the latest consume went into
+    pub last_update: Duration,
+}
+
+/// Sans-IO sliding window log state machine
+///
+/// Same algorithm as [`SlidingWindowLog`], but without a time provider: the
+/// current time is passed in with every [`LimiterCore::handle`] call.
+#[derive(Debug, Clone, Copy)]
+pub struct SlidingWindowLogCore<const W: usize, C = u64>
+where
+    C: TokenCount,
+{
+    config: SlidingWindowParams,
+    /// Ring buffer of slots, each one holding the tokens used during a
+    /// point in past time. See [`Self::slot`] for the logical order.
+    window_buffer: [C; W],
+    /// Index of the slot of the current point in time
+    head: usize,
+    /// Tokens used during the effective window, i.e. the sum of its slots
+    used: u64,
+    /// Effective window width in slots, at most `W`
+    width: usize,
+    /// Slot width in milliseconds, at least one
+    slot_ms: u64,
+    last_update_time: Duration,
+    monitor: ClockMonitor,
+}
+
+impl<const W: usize, C> SlidingWindowLogCore<W, C>
+where
+    C: TokenCount,
+{
+    /// Initialize a new sliding window log state machine
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    ///
+    /// # Panics
+    /// Consumes panic if `W` is zero, use [`Self::try_new`] to validate the configuration.
+    pub fn new(capacity: u64, now: Duration) -> Self {
+        Self {
+            config: SlidingWindowParams::new(capacity),
+            window_buffer: [C::default(); W],
+            head: 0,
+            used: 0,
+            width: W,
+            slot_ms: 1,
+            last_update_time: now,
+            monitor: ClockMonitor::new(now),
+        }
+    }
+
+    /// Validating constructor, see [`SlidingWindowLog::try_new_with_time_provider`]
+    pub fn try_new(capacity: u64, now: Duration) -> Result<Self, ConfigError> {
+        if W == 0 {
+            return Err(ConfigError::ZeroWindow);
+        }
+        if capacity == 0 {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        if capacity > C::MAX.into() {
+            return Err(ConfigError::CapacityOverflow);
+        }
+        Ok(Self::new(capacity, now))
+    }
+
+    /// Return to the freshly constructed state at the given time
+    ///
+    /// The configuration, including the name, is kept.
+    pub fn reset_at(&mut self, now: Duration) {
+        self.clear();
+        self.last_update_time = now;
+        self.monitor.reset(now);
+    }
+
+    /// Take a plain-data snapshot, see [`SlidingWindowLog::snapshot`]
+    pub fn snapshot(&self) -> SlidingWindowLogSnapshot<W, C> {
+        let mut slots = [C::default(); W];
+        for (age, slot) in slots.iter_mut().enumerate() {
+            *slot = self.window_buffer[(self.head + age) % W];
+        }
+        SlidingWindowLogSnapshot {
+            capacity: self.config.capacity,
+            slots,
+            window_slots: self.width,
+            slot_width: self.slot_width(),
+            last_update: self.last_update_time,
+        }
+    }
+
+    /// Resume from a snapshot, see [`SlidingWindowLog::restore`]
+    pub fn restore(snapshot: SlidingWindowLogSnapshot<W, C>) -> Self {
+        let mut core =
+            Self::new(snapshot.capacity, snapshot.last_update).with_slot_width(snapshot.slot_width);
+        core.window_buffer = snapshot.slots;
+        core.width = snapshot.window_slots.min(W);
+        core.used = core.sum_slots(0..core.width);
+        core
+    }
+
+    /// Assign a name to this limiter, see [`SlidingWindowLog::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.config.name = Some(name);
+        self
+    }
+
+    /// Log usage in wider slots, see [`SlidingWindowLog::with_slot_width`]
+    pub fn with_slot_width(mut self, slot_width: Duration) -> Self {
+        self.slot_ms = (slot_width.as_millis().min(u64::MAX as u128) as u64).max(1);
+        self.clear();
+        self
+    }
+
+    /// Configured slot width
+    pub fn slot_width(&self) -> Duration {
+        Duration::from_millis(self.slot_ms)
+    }
+
+    /// Start with the given usage history, see [`SlidingWindowLog::with_initial_usage`]
+    pub fn with_initial_usage(mut self, usage: &[u64]) -> Self {
+        let n = usage.len().min(W);
+        self.clear();
+        for (slot, &tokens) in self.window_buffer.iter_mut().zip(&usage[..n]) {
+            *slot = C::try_from(tokens).unwrap_or(C::MAX);
+        }
+        self.used = self.sum_slots(0..self.width);
+        self
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.config.capacity
+    }
+
+    /// Current effective window width
+    pub fn window_width(&self) -> Duration {
+        Duration::from_millis((self.width as u64).saturating_mul(self.slot_ms))
+    }
+
+    /// State of the window at `now`, see [`SlidingWindowLog::state`]
+    pub fn state_at(&self, now: Duration) -> SlidingWindowState {
+        SlidingWindowState {
+            tokens: self.tokens_available_at(now),
+            capacity: self.config.capacity,
+            window_width: self.window_width(),
+        }
+    }
+
+    /// Change the effective window width, see [`SlidingWindowLog::set_window_width`]
+    pub fn set_window_width(&mut self, width: Duration) {
+        let slots = width.as_millis() / self.slot_ms as u128;
+        self.width = (slots.min(W as u128) as usize).max(W.min(1));
+        self.used = self.sum_slots(0..self.width);
+    }
+
+    /// Return tokens of an admitted consume at `now`, see [`Refund::refund`]
+    ///
+    /// Tokens are taken back from the most recent slots first, tokens that
+    /// have already slid out of the window can't be returned.
+    pub fn refund_at(&mut self, now: Duration, tokens: u64) {
+        self.advance_to(now);
+        let mut left = tokens;
+        for age in 0..self.width {
+            if left == 0 {
+                break;
+            }
+            let slot = self.slot(age);
+            let taken = slot.min(left);
+            self.window_buffer[(self.head + age) % W] = C::try_from(slot - taken).unwrap_or(C::MAX);
+            self.used -= taken;
+            left -= taken;
+        }
+    }
+
+    /// Tokens that could be consumed at `now`, see [`Inspect::tokens_available`]
+    pub fn tokens_available_at(&self, now: Duration) -> u64 {
+        let delta_t = self.elapsed_slots(now);
         if delta_t >= W as u64 {
-            self.last_update_time = now;
-            self.window_buffer.fill(0);
-            self.window_buffer[0] = tokens;
-            return Ok(());
+            return self.config.capacity;
+        }
+
+        let used = self.used_after(delta_t as usize);
+        self.config.capacity.saturating_sub(used)
+    }
+
+    /// Time until `tokens` could be consumed at `now`, see [`RetryHint::time_until_available`]
+    pub fn time_until_available_at(&self, now: Duration, tokens: u64) -> Option<Duration> {
+        if tokens > self.config.capacity {
+            return None;
+        }
+        let delta_t = self.elapsed_slots(now);
+        if delta_t >= W as u64 {
+            return Some(Duration::ZERO);
+        }
+
+        // Each slot width the oldest counted slot slides out of the window
+        let counted = self.width.saturating_sub(delta_t as usize);
+        let mut used = self.used_after(delta_t as usize);
+        for elapsed in 0..=counted {
+            if used.saturating_add(tokens) <= self.config.capacity {
+                if elapsed == 0 {
+                    return Some(Duration::ZERO);
+                }
+                let slots = delta_t + elapsed as u64;
+                let slid = Duration::from_millis(slots.saturating_mul(self.slot_ms));
+                return Some((self.last_update_time + slid).saturating_sub(now));
+            }
+            used -= self.slot(counted - 1 - elapsed);
         }
+        None
+    }
 
-        if delta_t != 0 {
+    /// Timestamp of the most recent consume attempt, see [`Limiter::last_consume_at`]
+    pub fn last_consume_at(&self) -> Option<Duration> {
+        self.monitor.last_consume_at()
+    }
+
+    /// Idle time at `now`, see [`Limiter::idle_for`]
+    pub fn idle_for_at(&self, now: Duration) -> Duration {
+        self.monitor.idle_for(now)
+    }
+
+    /// Run the self-check of [`Limiter::self_check`] at the given time
+    pub fn self_check_at(&self, now: Duration) -> Diagnostics {
+        self.monitor
+            .diagnose(now, self.config.capacity != 0 && W != 0)
+    }
+
+    /// Whole slots elapsed at `now` since the last update
+    fn elapsed_slots(&self, now: Duration) -> u64 {
+        now.saturating_sub(self.last_update_time).as_millis() as u64 / self.slot_ms
+    }
+
+    /// Tokens used during the slot `age` slots before the current one
+    fn slot(&self, age: usize) -> u64 {
+        self.window_buffer[(self.head + age) % W].into()
+    }
+
+    /// Sum of the slots with the given ages
+    fn sum_slots(&self, ages: core::ops::Range<usize>) -> u64 {
+        ages.map(|age| self.slot(age)).sum()
+    }
+
+    /// Tokens still counted in the effective window once `delta_t` more slots have passed
+    fn used_after(&self, delta_t: usize) -> u64 {
+        let counted = self.width.saturating_sub(delta_t);
+        self.used - self.sum_slots(counted..self.width)
+    }
+
+    /// Empty all slots
+    fn clear(&mut self) {
+        self.window_buffer = [C::default(); W];
+        self.head = 0;
+        self.used = 0;
+    }
+
+    /// Slide the window forward to the slot containing `now`
+    fn advance_to(&mut self, now: Duration) {
+        let delta_t = self.elapsed_slots(now);
+
+        // delta_t is more than the window size, reset the whole limiter
+        if delta_t >= W as u64 {
             self.last_update_time = now;
-            // Time has moved on, shift existing items right for delta_t slots
-            let move_range = 0..(W - delta_t as usize);
-            self.window_buffer.copy_within(move_range, delta_t as usize);
+            self.clear();
+            return;
+        }
 
-            // Zero all slots that were not updated
-            self.window_buffer[..delta_t as usize].fill(0);
+        // Advance by whole slots to keep the slot boundaries in place
+        self.last_update_time += Duration::from_millis(delta_t * self.slot_ms);
+        for _ in 0..delta_t {
+            // The oldest counted slot slides out of the effective window,
+            // and the oldest stored slot is reused for the current time
+            self.used -= self.slot(self.width - 1);
+            self.head = (self.head + W - 1) % W;
+            self.window_buffer[self.head] = C::default();
         }
+    }
+
+    /// Consume at the given time, returning the remaining budget
+    fn try_consume_at(&mut self, now: Duration, tokens: u64) -> Result<u64, CantConsume> {
+        self.monitor.observe(now);
+        self.advance_to(now);
 
         // Too many tokens used during the window?
-        let tokens_left = self.config.capacity - self.window_buffer.iter().sum::<u64>();
-        if tokens_left >= tokens {
-            // Add tokens to current timeslot
-            self.window_buffer[0] += tokens;
-            Ok(())
-        } else {
-            Err(CantConsume)
+        let tokens_left = self
+            .config
+            .capacity
+            .checked_sub(self.used)
+            .unwrap_or_else(|| {
+                self.monitor.saturate();
+                0
+            });
+        if tokens_left < tokens {
+            return Err(CantConsume);
         }
+        // Add tokens to current timeslot, if the slot type can hold them
+        let Ok(slot) = C::try_from(self.slot(0) + tokens) else {
+            self.monitor.saturate();
+            return Err(CantConsume);
+        };
+        self.window_buffer[self.head] = slot;
+        self.used += tokens;
+        Ok(tokens_left - tokens)
+    }
+}
+
+impl<const W: usize, C> LimiterCore for SlidingWindowLogCore<W, C>
+where
+    C: TokenCount,
+{
+    fn handle(&mut self, now: Duration, request: Request) -> Response {
+        match request {
+            Request::Consume(tokens) => self.try_consume_at(now, tokens).into(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl SlidingWindowCounter<StdClock> {
+    /// Build a sliding window counter limiter from a [`SlidingWindowCounterConfig`]
+    ///
+    /// # Arguments
+    /// * `config` - limiter parameters, e.g. deserialized from a configuration file
+    ///
+    /// # Returns
+    /// [`ConfigError`] for parameters rejected by [`Self::try_new_with_time_provider`]
+    pub fn from_config(config: SlidingWindowCounterConfig) -> Result<Self, ConfigError> {
+        Self::from_config_with_time_provider(config, StdClock)
     }
 }
 
@@ -156,105 +777,1718 @@ where
 ///
 /// If our window capacity was configured to be 100 or less,
 /// this consume would not be possible at this time.
-pub struct SlidingWindowCounter<T>
+///
+/// For a closer approximation, [`SlidingWindowCounter::with_buckets`] splits
+/// the window into `B` buckets. Only the oldest bucket overlapping the
+/// sliding window is then interpolated, the others are counted exactly.
+///
+/// The algorithm itself is implemented by [`SlidingWindowCounterCore`], this
+/// type only pairs it with a time provider.
+///
+/// # Generic arguments
+/// * `T` - time provider
+/// * `B` - number of buckets per window, must be nonzero
+#[derive(Debug, Clone)]
+pub struct SlidingWindowCounter<T, const B: usize = 1>
 where
-    T: Fn() -> Duration,
+    T: Clock,
 {
-    config: SlidingWindowConfig<T>,
-    tokens_prev: u64,
-    tokens_this: u64,
-    window_index: u64,
-    window_width_ms: u64,
-    start_time: Duration,
+    core: SlidingWindowCounterCore<B>,
+    time_provider: T,
 }
 
 impl<T> SlidingWindowCounter<T>
 where
-    T: Fn() -> Duration,
+    T: Clock,
 {
     /// Initialize a new sliding window limiter utilizing the given timer
     ///
     /// # Arguments
     /// * `capacity` - how many consumes are allowed during a single window
     /// * `window_width_ms` - window width in milliseconds
-    /// * `time_provider_t` - closure that returns a monotonically nondecreasing
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
     ///   timestamp as [`Duration`] from some fixed epoch in the past
     ///
     /// # Notes
     /// * If you are developing for a `std` target, you probably wish to use [`sliding_window_counter`]
     /// * Window width is defined by the generic argument `W: usize`
     pub fn new_with_time_provider(capacity: u64, window_width_ms: u64, time_provider: T) -> Self {
-        let time_now = time_provider();
-        let config = SlidingWindowConfig::new(capacity, time_provider);
+        let time_now = time_provider.now();
         Self {
-            config,
-            window_index: 0,
-            tokens_prev: 0,
-            tokens_this: 0,
-            window_width_ms,
-            start_time: time_now,
+            core: SlidingWindowCounterCore::new(capacity, window_width_ms, time_now),
+            time_provider,
         }
     }
-}
 
-impl<T> Limiter for SlidingWindowCounter<T>
-where
-    T: Fn() -> Duration,
-{
-    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
-        // Get current window index
-        let now = (self.config.time_provider)();
-        let delta_t = now.saturating_sub(self.start_time).as_millis() as f64;
-        let index_float = delta_t / self.window_width_ms as f64;
-
-        let index = index_float.trunc() as u64;
-        let overlap = index_float.fract();
-
-        if index == (self.window_index + 1) {
-            // Moved on to next window, move current tokens to previous window
-            self.tokens_prev = self.tokens_this;
-            self.tokens_this = 0;
-            self.window_index = index;
-        } else if index == (self.window_index + 2) {
-            // We skipped at least one full window, zero counters
-            self.tokens_prev = 0;
-            self.tokens_this = 0;
-            self.window_index = index;
-        }
+    /// Initialize a new sliding window limiter with the window width given as a
+    /// [`Duration`] utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width` - window width, rounded down to whole milliseconds
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use
+    /// [`sliding_window_counter_from_width`]
+    pub fn from_width_with_time_provider(
+        capacity: u64,
+        window_width: Duration,
+        time_provider: T,
+    ) -> Self {
+        Self::new_with_time_provider(capacity, window_width.as_millis() as u64, time_provider)
+    }
 
-        // Take tokens from previous window into account according to the overlap
-        let effective_tokens_previous = (self.tokens_prev as f64 * (1.0 - overlap)) as u64;
-        if (effective_tokens_previous + self.tokens_this + tokens) > self.config.capacity {
-            Err(CantConsume)
-        } else {
+    /// Like [`Self::new_with_time_provider`], but rejecting invalid configurations
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroWindow`] or [`ConfigError::ZeroCapacity`] for zero arguments
+    pub fn try_new_with_time_provider(
+        capacity: u64,
+        window_width_ms: u64,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        let time_now = time_provider.now();
+        Ok(Self {
+            core: SlidingWindowCounterCore::try_new(capacity, window_width_ms, time_now)?,
+            time_provider,
+        })
+    }
+
+    /// Initialize a new sliding window limiter from a [`SlidingWindowCounterConfig`]
+    /// utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `config` - limiter parameters, e.g. deserialized from a configuration file
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// # Returns
+    /// [`ConfigError`] for parameters rejected by [`Self::try_new_with_time_provider`]
+    ///
+    /// If you are developing for a `std` target, you probably wish to use
+    /// [`SlidingWindowCounter::from_config`]
+    pub fn from_config_with_time_provider(
+        config: SlidingWindowCounterConfig,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        Self::try_new_with_time_provider(config.capacity, config.window_width_ms, time_provider)
+    }
+
+    /// Split the window into `B` buckets for a closer approximation
+    ///
+    /// The window width should be divisible by `B`, otherwise it is
+    /// rounded down to a multiple of `B` milliseconds. Any usage history is
+    /// cleared, the configuration is kept.
+    ///
+    /// # Generic arguments
+    /// * `B` - number of buckets per window, must be nonzero
+    pub fn with_buckets<const B: usize>(self) -> SlidingWindowCounter<T, B> {
+        SlidingWindowCounter {
+            core: self.core.with_buckets(),
+            time_provider: self.time_provider,
+        }
+    }
+}
+
+impl<T, const B: usize> SlidingWindowCounter<T, B>
+where
+    T: Clock,
+{
+    /// Assign a name to this limiter, see [`Limiter::name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.core = self.core.with_name(name);
+        self
+    }
+
+    /// Start with the given usage instead of empty windows
+    ///
+    /// Allows a restarted service to seed the limiter from persisted usage.
+    ///
+    /// # Arguments
+    /// * `previous_window` - tokens used during the previous window, or bucket with `B > 1`
+    /// * `current_window` - tokens used so far during the current window, or bucket with `B > 1`
+    pub fn with_initial_usage(mut self, previous_window: u64, current_window: u64) -> Self {
+        self.core = self
+            .core
+            .with_initial_usage(previous_window, current_window);
+        self
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.core.capacity()
+    }
+
+    /// Configured window width
+    pub fn window_width(&self) -> Duration {
+        self.core.window_width()
+    }
+
+    /// Current state of the window, e.g. for dashboards and debug logs
+    pub fn state(&self) -> SlidingWindowState {
+        let now = self.time_provider.now();
+        self.core.state_at(now)
+    }
+
+    /// Time left until the current bucket ends and the budget is replenished
+    pub fn time_until_reset(&self) -> Duration {
+        let now = self.time_provider.now();
+        self.core.time_until_reset_at(now)
+    }
+
+    /// How far through the current bucket we are
+    pub fn window_elapsed(&self) -> Duration {
+        let now = self.time_provider.now();
+        self.core.window_elapsed_at(now)
+    }
+
+    /// Worst-case deviation of this limiter from an exact sliding window
+    ///
+    /// See [`SlidingWindowCounter::approximation_bound`]
+    pub fn error_bound(&self) -> ApproximationBound {
+        self.core.error_bound()
+    }
+
+    /// Worst-case deviation of a sliding window counter from an exact sliding window
+    ///
+    /// The counter assumes that tokens of the previous window were consumed
+    /// uniformly over time. The bounds are reached when that assumption is
+    /// as wrong as it can be:
+    ///
+    /// * Overshoot - all tokens of the previous window were consumed at its
+    ///   very end, and the current window is then filled during its last
+    ///   millisecond. The exact window covering both bursts holds
+    ///   `capacity + max_overshoot` tokens.
+    /// * Undershoot - all tokens of the previous window were consumed at its
+    ///   very beginning. Right after the window boundary the exact window is
+    ///   empty, but the counter still rejects consumes because it accounts for
+    ///   nearly all of the previous tokens.
+    ///
+    /// With `B > 1`, the same holds for the oldest bucket instead of the
+    /// previous window. The worst cases stay the same, as all tokens may be
+    /// consumed during a single bucket, but typical errors shrink with `B`.
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width_ms` - window width in milliseconds
+    pub const fn approximation_bound(capacity: u64, window_width_ms: u64) -> ApproximationBound {
+        SlidingWindowCounterCore::<B>::approximation_bound(capacity, window_width_ms)
+    }
+
+    /// Get a reference to the underlying sans-IO state machine
+    pub fn core(&self) -> &SlidingWindowCounterCore<B> {
+        &self.core
+    }
+
+    /// Unwrap the underlying sans-IO state machine, dropping the time provider
+    pub fn into_core(self) -> SlidingWindowCounterCore<B> {
+        self.core
+    }
+
+    /// Take a plain-data snapshot of the limiter state
+    ///
+    /// The snapshot can be stashed e.g. in battery-backed RAM and later passed
+    /// to [`Self::restore`], without requiring `serde`.
+    pub fn snapshot(&self) -> SlidingWindowCounterSnapshot<B> {
+        self.core.snapshot()
+    }
+
+    /// Resume a limiter from a snapshot taken with [`Self::snapshot`]
+    ///
+    /// The time provider must share the epoch of the one the snapshot was
+    /// taken with, e.g. a real-time clock kept running during deep sleep.
+    /// Time elapsed in between is accounted for like it had been running.
+    /// The name is not part of the snapshot, see [`Self::with_name`].
+    pub fn restore(snapshot: SlidingWindowCounterSnapshot<B>, time_provider: T) -> Self {
+        Self {
+            core: SlidingWindowCounterCore::restore(snapshot),
+            time_provider,
+        }
+    }
+}
+
+/// Worst-case deviation of a [`SlidingWindowCounter`] from an exact sliding window
+///
+/// An exact sliding window (such as [`SlidingWindowLog`]) never holds more than
+/// `capacity` tokens in any window, and admits a consume whenever that holds
+/// after the consume.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct ApproximationBound {
+    /// Maximum amount of tokens above `capacity` that can be admitted during
+    /// any window of the configured width
+    pub max_overshoot: u64,
+    /// Maximum amount of tokens an exact sliding window would still admit
+    /// when the counter rejects a consume
+    pub max_undershoot: u64,
+}
+
+impl<T, const B: usize> Limiter for SlidingWindowCounter<T, B>
+where
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens).map(|_| ())
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        let now = self.time_provider.now();
+        costs
+            .iter()
+            .take_while(|&&tokens| self.core.try_consume_at(now, tokens).is_ok())
+            .count()
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::SlidingWindowCounter
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.core.config.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.time_provider.now();
+        self.core.self_check_at(now)
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.core.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.time_provider.now();
+        Some(self.core.idle_for_at(now))
+    }
+
+    fn reset(&mut self) {
+        let now = self.time_provider.now();
+        self.core.reset_at(now);
+    }
+}
+
+impl<T, const B: usize> Reset for SlidingWindowCounter<T, B> where T: Clock {}
+
+impl<T, const B: usize> Refund for SlidingWindowCounter<T, B>
+where
+    T: Clock,
+{
+    fn refund(&mut self, tokens: u64) {
+        let now = self.time_provider.now();
+        self.core.refund_at(now, tokens);
+    }
+}
+
+impl<T, const B: usize> RetryHint for SlidingWindowCounter<T, B>
+where
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.time_provider.now();
+        self.core.time_until_available_at(now, tokens)
+    }
+}
+
+impl<T, const B: usize> Inspect for SlidingWindowCounter<T, B>
+where
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.tokens_available_at(now)
+    }
+}
+
+impl<T, const B: usize> RemainingBudget for SlidingWindowCounter<T, B>
+where
+    T: Clock,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens)
+    }
+}
+
+/// Plain-data snapshot of a [`SlidingWindowCounter`], see [`SlidingWindowCounter::snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlidingWindowCounterSnapshot<const B: usize = 1> {
+    /// Configured capacity
+    pub capacity: u64,
+    /// Configured window width
+    pub window_width: Duration,
+    /// Tokens used during the previous buckets, the most recent one first
+    pub history: [u64; B],
+    /// Tokens used during the bucket `window_index`
+    pub current_tokens: u64,
+    /// Index of the latest bucket consumed from, counted from `start_time`
+    pub window_index: u64,
+    /// Timestamp the buckets are counted from
+    pub start_time: Duration,
+    /// Latest timestamp seen by the limiter
+    pub last_update: Duration,
+}
+
+/// Sans-IO sliding window counter state machine
+///
+/// Same algorithm as [`SlidingWindowCounter`], but without a time provider:
+/// the current time is passed in with every [`LimiterCore::handle`] call.
+///
+/// # Generic arguments
+/// * `B` - number of buckets per window, must be nonzero
+#[derive(Debug, Clone, Copy)]
+pub struct SlidingWindowCounterCore<const B: usize = 1> {
+    config: SlidingWindowParams,
+    /// Tokens of the previous buckets, the most recent one first
+    history: [u64; B],
+    tokens_this: u64,
+    window_index: u64,
+    window_width_ms: u64,
+    start_time: Duration,
+    monitor: ClockMonitor,
+}
+
+impl SlidingWindowCounterCore {
+    /// Initialize a new sliding window counter state machine
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width_ms` - window width in milliseconds
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    pub fn new(capacity: u64, window_width_ms: u64, now: Duration) -> Self {
+        Self {
+            config: SlidingWindowParams::new(capacity),
+            window_index: 0,
+            history: [0],
+            tokens_this: 0,
+            window_width_ms,
+            start_time: now,
+            monitor: ClockMonitor::new(now),
+        }
+    }
+
+    /// Initialize a new sliding window counter state machine with the window
+    /// width given as a [`Duration`]
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width` - window width, rounded down to whole milliseconds
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    pub fn from_width(capacity: u64, window_width: Duration, now: Duration) -> Self {
+        Self::new(capacity, window_width.as_millis() as u64, now)
+    }
+
+    /// Validating constructor, see [`SlidingWindowCounter::try_new_with_time_provider`]
+    pub fn try_new(
+        capacity: u64,
+        window_width_ms: u64,
+        now: Duration,
+    ) -> Result<Self, ConfigError> {
+        if window_width_ms == 0 {
+            return Err(ConfigError::ZeroWindow);
+        }
+        if capacity == 0 {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        Ok(Self::new(capacity, window_width_ms, now))
+    }
+
+    /// Split the window into buckets, see [`SlidingWindowCounter::with_buckets`]
+    pub fn with_buckets<const B: usize>(self) -> SlidingWindowCounterCore<B> {
+        SlidingWindowCounterCore {
+            config: self.config,
+            history: [0; B],
+            tokens_this: 0,
+            window_index: 0,
+            window_width_ms: self.window_width_ms,
+            start_time: self.start_time,
+            monitor: self.monitor,
+        }
+    }
+}
+
+impl<const B: usize> SlidingWindowCounterCore<B> {
+    /// Return to the freshly constructed state at the given time
+    ///
+    /// The configuration, including the name, is kept.
+    pub fn reset_at(&mut self, now: Duration) {
+        self.history = [0; B];
+        self.tokens_this = 0;
+        self.window_index = 0;
+        self.start_time = now;
+        self.monitor.reset(now);
+    }
+
+    /// Take a plain-data snapshot, see [`SlidingWindowCounter::snapshot`]
+    pub fn snapshot(&self) -> SlidingWindowCounterSnapshot<B> {
+        SlidingWindowCounterSnapshot {
+            capacity: self.config.capacity,
+            window_width: Duration::from_millis(self.window_width_ms),
+            history: self.history,
+            current_tokens: self.tokens_this,
+            window_index: self.window_index,
+            start_time: self.start_time,
+            last_update: self.monitor.latest(),
+        }
+    }
+
+    /// Resume from a snapshot, see [`SlidingWindowCounter::restore`]
+    pub fn restore(snapshot: SlidingWindowCounterSnapshot<B>) -> Self {
+        Self {
+            config: SlidingWindowParams::new(snapshot.capacity),
+            history: snapshot.history,
+            tokens_this: snapshot.current_tokens,
+            window_index: snapshot.window_index,
+            window_width_ms: snapshot.window_width.as_millis() as u64,
+            start_time: snapshot.start_time,
+            monitor: ClockMonitor::new(snapshot.last_update),
+        }
+    }
+
+    /// Assign a name to this limiter, see [`SlidingWindowCounter::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.config.name = Some(name);
+        self
+    }
+
+    /// Start with the given usage, see [`SlidingWindowCounter::with_initial_usage`]
+    pub fn with_initial_usage(mut self, previous_window: u64, current_window: u64) -> Self {
+        if let Some(previous) = self.history.first_mut() {
+            *previous = previous_window;
+        }
+        self.tokens_this = current_window;
+        self
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.config.capacity
+    }
+
+    /// Configured window width
+    pub fn window_width(&self) -> Duration {
+        Duration::from_millis(self.window_width_ms)
+    }
+
+    /// State of the window at `now`, see [`SlidingWindowCounter::state`]
+    pub fn state_at(&self, now: Duration) -> SlidingWindowState {
+        SlidingWindowState {
+            tokens: self.tokens_available_at(now),
+            capacity: self.config.capacity,
+            window_width: self.window_width(),
+        }
+    }
+
+    /// Time left at `now` until the current bucket ends and the budget is replenished
+    pub fn time_until_reset_at(&self, now: Duration) -> Duration {
+        Duration::from_millis(self.bucket_width_ms()) - self.window_elapsed_at(now)
+    }
+
+    /// How far through the bucket containing `now` we are
+    pub fn window_elapsed_at(&self, now: Duration) -> Duration {
+        let width_ns = Duration::from_millis(self.bucket_width_ms())
+            .as_nanos()
+            .max(1);
+        let delta_ns = now.saturating_sub(self.start_time).as_nanos();
+        Duration::from_nanos((delta_ns % width_ns) as u64)
+    }
+
+    /// Worst-case deviation of this limiter from an exact sliding window
+    ///
+    /// See [`SlidingWindowCounter::approximation_bound`]
+    pub fn error_bound(&self) -> ApproximationBound {
+        Self::approximation_bound(self.config.capacity, self.window_width_ms)
+    }
+
+    /// Worst-case deviation of a sliding window counter from an exact sliding window,
+    /// see [`SlidingWindowCounter::approximation_bound`]
+    pub const fn approximation_bound(capacity: u64, window_width_ms: u64) -> ApproximationBound {
+        // Overlap is only resolved with millisecond precision, so during the
+        // last millisecond of a bucket `1 / bucket_width_ms` of the oldest
+        // tokens are still accounted for.
+        let bucket_width_ms = match window_width_ms.checked_div(B as u64) {
+            Some(width) => width,
+            None => window_width_ms,
+        };
+        let min_accounted = match capacity.checked_div(bucket_width_ms) {
+            Some(tokens) => tokens,
+            None => 0,
+        };
+        ApproximationBound {
+            max_overshoot: capacity - min_accounted,
+            max_undershoot: capacity,
+        }
+    }
+
+    /// Return tokens of an admitted consume at `now`, see [`Refund::refund`]
+    ///
+    /// Tokens are taken back from the most recent buckets first, tokens that
+    /// have already slid out of the window can't be returned.
+    pub fn refund_at(&mut self, now: Duration, tokens: u64) {
+        let (index, _) = self.position_at(now);
+        (self.history, self.tokens_this) = self.rolled_to(index);
+        self.window_index = self.window_index.max(index);
+
+        let from_this = self.tokens_this.min(tokens);
+        self.tokens_this -= from_this;
+        let mut left = tokens - from_this;
+        for bucket in &mut self.history {
+            let taken = (*bucket).min(left);
+            *bucket -= taken;
+            left -= taken;
+        }
+    }
+
+    /// Tokens that could be consumed at `now`, see [`Inspect::tokens_available`]
+    pub fn tokens_available_at(&self, now: Duration) -> u64 {
+        let (index, remaining_ms) = self.position_at(now);
+        let (history, tokens_this) = self.rolled_to(index);
+        self.config
+            .capacity
+            .saturating_sub(self.counted(&history, tokens_this, remaining_ms))
+    }
+
+    /// Time until `tokens` could be consumed at `now`, see [`RetryHint::time_until_available`]
+    pub fn time_until_available_at(&self, now: Duration, tokens: u64) -> Option<Duration> {
+        if tokens > self.config.capacity || self.bucket_width_ms() == 0 {
+            return None;
+        }
+        if self.tokens_available_at(now) >= tokens {
+            return Some(Duration::ZERO);
+        }
+
+        let width = self.bucket_width_ms() as u128;
+        let elapsed_ms = now.saturating_sub(self.start_time).as_millis();
+        let (mut history, mut tokens_this) = self.rolled_to((elapsed_ms / width) as u64);
+        let mut elapsed_in_bucket = elapsed_ms % width;
+
+        // Earliest point in a bucket at which `floor(oldest * (width - t) / width) <= budget`
+        let earliest = |oldest: u64, budget: u64| {
+            width.saturating_sub(((budget as u128 + 1) * width - 1) / (oldest as u128).max(1))
+        };
+        let budget = self.config.capacity - tokens;
+        let mut wait_ms = 0;
+        // All buckets are counted exactly or replaced within B + 1 buckets
+        for _ in 0..=B {
+            let oldest = history.last().copied().unwrap_or(0);
+            let exact = tokens_this.saturating_add(history[..B - 1].iter().sum::<u64>());
+            if exact <= budget {
+                // The share of the oldest bucket declines during this bucket
+                wait_ms += earliest(oldest, budget - exact).saturating_sub(elapsed_in_bucket);
+                return Some(Duration::from_millis(wait_ms as u64));
+            }
+            // Only a later bucket helps, where this bucket becomes a previous one
+            wait_ms += width - elapsed_in_bucket;
+            elapsed_in_bucket = 0;
+            history.copy_within(..B - 1, 1);
+            history[0] = tokens_this;
+            tokens_this = 0;
+        }
+        None
+    }
+
+    /// Timestamp of the most recent consume attempt, see [`Limiter::last_consume_at`]
+    pub fn last_consume_at(&self) -> Option<Duration> {
+        self.monitor.last_consume_at()
+    }
+
+    /// Idle time at `now`, see [`Limiter::idle_for`]
+    pub fn idle_for_at(&self, now: Duration) -> Duration {
+        self.monitor.idle_for(now)
+    }
+
+    /// Run the self-check of [`Limiter::self_check`] at the given time
+    pub fn self_check_at(&self, now: Duration) -> Diagnostics {
+        self.monitor.diagnose(
+            now,
+            self.config.capacity != 0 && self.bucket_width_ms() != 0 && B != 0,
+        )
+    }
+
+    /// Consume at the given time, returning the remaining budget
+    fn try_consume_at(&mut self, now: Duration, tokens: u64) -> Result<u64, CantConsume> {
+        self.monitor.observe(now);
+        // Get current bucket index
+        let (index, remaining_ms) = self.position_at(now);
+        (self.history, self.tokens_this) = self.rolled_to(index);
+        self.window_index = self.window_index.max(index);
+
+        // Take tokens from the oldest bucket into account according to the overlap
+        let Some(total) = self
+            .counted_checked(&self.history, self.tokens_this, remaining_ms)
+            .and_then(|t| t.checked_add(tokens))
+        else {
+            self.monitor.saturate();
+            return Err(CantConsume);
+        };
+        if total > self.config.capacity {
+            Err(CantConsume)
+        } else {
             self.tokens_this += tokens;
-            Ok(())
+            Ok(self.config.capacity - total)
+        }
+    }
+
+    /// Width of a single bucket in milliseconds
+    fn bucket_width_ms(&self) -> u64 {
+        self.window_width_ms.checked_div(B as u64).unwrap_or(0)
+    }
+
+    /// Bucket history and tokens of the current bucket once moved on to the bucket `index`
+    fn rolled_to(&self, index: u64) -> ([u64; B], u64) {
+        let mut history = self.history;
+        let mut tokens_this = self.tokens_this;
+        let skipped = index.saturating_sub(self.window_index);
+        if skipped > B as u64 {
+            // We skipped at least one full window, zero counters
+            return ([0; B], 0);
+        }
+        for _ in 0..skipped {
+            // Moved on to next bucket, move current tokens to the history
+            history.copy_within(..B - 1, 1);
+            history[0] = tokens_this;
+            tokens_this = 0;
+        }
+        (history, tokens_this)
+    }
+
+    /// Tokens counted in the sliding window with `remaining_ms` left in the current bucket
+    fn counted(&self, history: &[u64; B], tokens_this: u64, remaining_ms: u64) -> u64 {
+        self.counted_checked(history, tokens_this, remaining_ms)
+            .unwrap_or(u64::MAX)
+    }
+
+    /// Same as [`Self::counted`], but `None` on overflow
+    fn counted_checked(
+        &self,
+        history: &[u64; B],
+        tokens_this: u64,
+        remaining_ms: u64,
+    ) -> Option<u64> {
+        let Some((oldest, exact)) = history.split_last() else {
+            return Some(tokens_this);
+        };
+        exact
+            .iter()
+            .try_fold(tokens_this, |sum, tokens| sum.checked_add(*tokens))?
+            .checked_add(self.oldest_share(*oldest, remaining_ms))
+    }
+
+    /// Position at `now` as the bucket index and milliseconds left in it
+    fn position_at(&self, now: Duration) -> (u64, u64) {
+        let delta_ms = now.saturating_sub(self.start_time).as_millis();
+        let width = self.bucket_width_ms().max(1) as u128;
+        ((delta_ms / width) as u64, (width - delta_ms % width) as u64)
+    }
+
+    /// Share of the oldest bucket's tokens still counted with `remaining_ms`
+    /// left in the current bucket
+    ///
+    /// Integer math so that targets without an FPU don't need soft-float routines.
+    fn oldest_share(&self, tokens_oldest: u64, remaining_ms: u64) -> u64 {
+        let width = self.bucket_width_ms().max(1) as u128;
+        (tokens_oldest as u128 * remaining_ms as u128 / width) as u64
+    }
+}
+
+impl<const B: usize> LimiterCore for SlidingWindowCounterCore<B> {
+    fn handle(&mut self, now: Duration, request: Request) -> Response {
+        match request {
+            Request::Consume(tokens) => self.try_consume_at(now, tokens).into(),
+        }
+    }
+}
+
+/// Sparse sliding window log -type rate limiter
+///
+/// Like [`SlidingWindowLog`], but instead of keeping one slot per millisecond
+/// of the window, this limiter stores `(timestamp, count)` entries only for
+/// the milliseconds during which tokens were actually consumed. For bursty
+/// but mostly idle traffic this allows long windows with a tiny memory
+/// footprint, and the window width can be defined at runtime.
+///
+/// The algorithm itself is implemented by [`SparseSlidingWindowLogCore`],
+/// this type only pairs it with a time provider.
+///
+/// # Generic arguments
+/// * `N` - Maximum number of stored entries, must be nonzero
+///
+/// # Notes
+/// When all `N` entries are in use, the two oldest entries are merged into
+/// one that carries the timestamp of the newer of them. This accounting is
+/// coarse but conservative: tokens may be remembered for slightly longer
+/// than the window width, but they are never forgotten too early.
+#[derive(Debug, Clone)]
+pub struct SparseSlidingWindowLog<T, const N: usize>
+where
+    T: Clock,
+{
+    core: SparseSlidingWindowLogCore<N>,
+    time_provider: T,
+}
+
+impl<T, const N: usize> SparseSlidingWindowLog<T, N>
+where
+    T: Clock,
+{
+    /// Initialize a new sparse sliding window limiter utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width_ms` - window width in milliseconds
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// # Notes
+    /// * If you are developing for a `std` target, you probably wish to use [`sparse_sliding_window_log`]
+    /// * Maximum number of stored entries is defined by the generic argument `N: usize`
+    pub fn new_with_time_provider(capacity: u64, window_width_ms: u64, time_provider: T) -> Self {
+        let time_now = time_provider.now();
+        Self {
+            core: SparseSlidingWindowLogCore::new(capacity, window_width_ms, time_now),
+            time_provider,
+        }
+    }
+
+    /// Initialize a new sparse sliding window limiter with the window width
+    /// given as a [`Duration`] utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width` - window width, rounded down to whole milliseconds
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use
+    /// [`sparse_sliding_window_log_from_width`]
+    pub fn from_width_with_time_provider(
+        capacity: u64,
+        window_width: Duration,
+        time_provider: T,
+    ) -> Self {
+        Self::new_with_time_provider(capacity, window_width.as_millis() as u64, time_provider)
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting invalid configurations
+    ///
+    /// # Returns
+    /// * [`ConfigError::ZeroLength`] if `N` is zero
+    /// * [`ConfigError::ZeroWindow`] if `window_width_ms` is zero
+    /// * [`ConfigError::ZeroCapacity`] if `capacity` is zero
+    pub fn try_new_with_time_provider(
+        capacity: u64,
+        window_width_ms: u64,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        let time_now = time_provider.now();
+        Ok(Self {
+            core: SparseSlidingWindowLogCore::try_new(capacity, window_width_ms, time_now)?,
+            time_provider,
+        })
+    }
+
+    /// Assign a name to this limiter, see [`Limiter::name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.core = self.core.with_name(name);
+        self
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.core.capacity()
+    }
+
+    /// Configured window width
+    pub fn window_width(&self) -> Duration {
+        self.core.window_width()
+    }
+
+    /// Current state of the window, e.g. for dashboards and debug logs
+    pub fn state(&self) -> SlidingWindowState {
+        let now = self.time_provider.now();
+        self.core.state_at(now)
+    }
+
+    /// Get a reference to the underlying sans-IO state machine
+    pub fn core(&self) -> &SparseSlidingWindowLogCore<N> {
+        &self.core
+    }
+
+    /// Unwrap the underlying sans-IO state machine, dropping the time provider
+    pub fn into_core(self) -> SparseSlidingWindowLogCore<N> {
+        self.core
+    }
+
+    /// Take a plain-data snapshot of the limiter state
+    ///
+    /// The snapshot can be stashed e.g. in battery-backed RAM and later passed
+    /// to [`Self::restore`], without requiring `serde`.
+    pub fn snapshot(&self) -> SparseSlidingWindowLogSnapshot<N> {
+        self.core.snapshot()
+    }
+
+    /// Resume a limiter from a snapshot taken with [`Self::snapshot`]
+    ///
+    /// The time provider must share the epoch of the one the snapshot was
+    /// taken with, e.g. a real-time clock kept running during deep sleep.
+    /// Time elapsed in between is accounted for like it had been running.
+    /// The name is not part of the snapshot, see [`Self::with_name`].
+    pub fn restore(snapshot: SparseSlidingWindowLogSnapshot<N>, time_provider: T) -> Self {
+        Self {
+            core: SparseSlidingWindowLogCore::restore(snapshot),
+            time_provider,
+        }
+    }
+}
+
+impl<T, const N: usize> Limiter for SparseSlidingWindowLog<T, N>
+where
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens).map(|_| ())
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        let now = self.time_provider.now();
+        costs
+            .iter()
+            .take_while(|&&tokens| self.core.try_consume_at(now, tokens).is_ok())
+            .count()
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::SparseSlidingWindowLog
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.core.config.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.time_provider.now();
+        self.core.self_check_at(now)
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.core.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.time_provider.now();
+        Some(self.core.idle_for_at(now))
+    }
+
+    fn reset(&mut self) {
+        let now = self.time_provider.now();
+        self.core.reset_at(now);
+    }
+}
+
+impl<T, const N: usize> Reset for SparseSlidingWindowLog<T, N> where T: Clock {}
+
+impl<T, const N: usize> Refund for SparseSlidingWindowLog<T, N>
+where
+    T: Clock,
+{
+    fn refund(&mut self, tokens: u64) {
+        let now = self.time_provider.now();
+        self.core.refund_at(now, tokens);
+    }
+}
+
+impl<T, const N: usize> RetryHint for SparseSlidingWindowLog<T, N>
+where
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.time_provider.now();
+        self.core.time_until_available_at(now, tokens)
+    }
+}
+
+impl<T, const N: usize> Inspect for SparseSlidingWindowLog<T, N>
+where
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.tokens_available_at(now)
+    }
+}
+
+impl<T, const N: usize> RemainingBudget for SparseSlidingWindowLog<T, N>
+where
+    T: Clock,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens)
+    }
+}
+
+/// Plain-data snapshot of a [`SparseSlidingWindowLog`], see [`SparseSlidingWindowLog::snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SparseSlidingWindowLogSnapshot<const N: usize> {
+    /// Configured capacity
+    pub capacity: u64,
+    /// Configured window width
+    pub window_width: Duration,
+    /// `(ms since start_time, tokens)` entries ordered from oldest to newest,
+    /// only the first `len` ones are in use
+    pub entries: [(u64, u64); N],
+    /// Number of entries in use
+    pub len: usize,
+    /// Timestamp the entries are counted from
+    pub start_time: Duration,
+    /// Latest timestamp seen by the limiter
+    pub last_update: Duration,
+}
+
+/// Sans-IO sparse sliding window log state machine
+///
+/// Same algorithm as [`SparseSlidingWindowLog`], but without a time provider:
+/// the current time is passed in with every [`LimiterCore::handle`] call.
+#[derive(Debug, Clone, Copy)]
+pub struct SparseSlidingWindowLogCore<const N: usize> {
+    config: SlidingWindowParams,
+    window_width_ms: u64,
+    /// `(ms since start, tokens)` entries ordered from oldest to newest
+    entries: [(u64, u64); N],
+    len: usize,
+    start_time: Duration,
+    monitor: ClockMonitor,
+}
+
+impl<const N: usize> SparseSlidingWindowLogCore<N> {
+    /// Initialize a new sparse sliding window log state machine
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width_ms` - window width in milliseconds
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    pub fn new(capacity: u64, window_width_ms: u64, now: Duration) -> Self {
+        Self {
+            config: SlidingWindowParams::new(capacity),
+            window_width_ms,
+            entries: [(0, 0); N],
+            len: 0,
+            start_time: now,
+            monitor: ClockMonitor::new(now),
+        }
+    }
+
+    /// Initialize a new sparse sliding window log state machine with the window
+    /// width given as a [`Duration`]
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width` - window width, rounded down to whole milliseconds
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    pub fn from_width(capacity: u64, window_width: Duration, now: Duration) -> Self {
+        Self::new(capacity, window_width.as_millis() as u64, now)
+    }
+
+    /// Validating constructor, see [`SparseSlidingWindowLog::try_new_with_time_provider`]
+    pub fn try_new(
+        capacity: u64,
+        window_width_ms: u64,
+        now: Duration,
+    ) -> Result<Self, ConfigError> {
+        if N == 0 {
+            return Err(ConfigError::ZeroLength);
+        }
+        if window_width_ms == 0 {
+            return Err(ConfigError::ZeroWindow);
+        }
+        if capacity == 0 {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        Ok(Self::new(capacity, window_width_ms, now))
+    }
+
+    /// Return to the freshly constructed state at the given time
+    ///
+    /// The configuration, including the name, is kept.
+    pub fn reset_at(&mut self, now: Duration) {
+        self.entries = [(0, 0); N];
+        self.len = 0;
+        self.start_time = now;
+        self.monitor.reset(now);
+    }
+
+    /// Take a plain-data snapshot, see [`SparseSlidingWindowLog::snapshot`]
+    pub fn snapshot(&self) -> SparseSlidingWindowLogSnapshot<N> {
+        SparseSlidingWindowLogSnapshot {
+            capacity: self.config.capacity,
+            window_width: Duration::from_millis(self.window_width_ms),
+            entries: self.entries,
+            len: self.len,
+            start_time: self.start_time,
+            last_update: self.monitor.latest(),
+        }
+    }
+
+    /// Resume from a snapshot, see [`SparseSlidingWindowLog::restore`]
+    pub fn restore(snapshot: SparseSlidingWindowLogSnapshot<N>) -> Self {
+        Self {
+            config: SlidingWindowParams::new(snapshot.capacity),
+            window_width_ms: snapshot.window_width.as_millis() as u64,
+            entries: snapshot.entries,
+            len: snapshot.len.min(N),
+            start_time: snapshot.start_time,
+            monitor: ClockMonitor::new(snapshot.last_update),
+        }
+    }
+
+    /// Assign a name to this limiter, see [`SparseSlidingWindowLog::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.config.name = Some(name);
+        self
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.config.capacity
+    }
+
+    /// Configured window width
+    pub fn window_width(&self) -> Duration {
+        Duration::from_millis(self.window_width_ms)
+    }
+
+    /// Tokens that could be consumed at `now`, see [`Inspect::tokens_available`]
+    pub fn tokens_available_at(&self, now: Duration) -> u64 {
+        let now_ms = now.saturating_sub(self.start_time).as_millis() as u64;
+        let used = self.entries[..self.len]
+            .iter()
+            .filter(|(t, _)| now_ms.saturating_sub(*t) < self.window_width_ms)
+            .map(|(_, count)| count)
+            .sum::<u64>();
+        self.config.capacity.saturating_sub(used)
+    }
+
+    /// Time until `tokens` could be consumed at `now`, see [`RetryHint::time_until_available`]
+    pub fn time_until_available_at(&self, now: Duration, tokens: u64) -> Option<Duration> {
+        if tokens > self.config.capacity {
+            return None;
+        }
+
+        let now_ms = now.saturating_sub(self.start_time).as_millis() as u64;
+        let mut counted = self.entries[..self.len]
+            .iter()
+            .filter(|(t, _)| now_ms.saturating_sub(*t) < self.window_width_ms);
+        let mut used = counted.clone().map(|(_, count)| count).sum::<u64>();
+        if used.saturating_add(tokens) <= self.config.capacity {
+            return Some(Duration::ZERO);
+        }
+
+        // Entries slide out of the window from the oldest one
+        counted
+            .find(|(_, count)| {
+                used -= count;
+                used.saturating_add(tokens) <= self.config.capacity
+            })
+            .map(|(t, _)| Duration::from_millis(t + self.window_width_ms - now_ms))
+    }
+
+    /// State of the window at `now`, see [`SparseSlidingWindowLog::state`]
+    pub fn state_at(&self, now: Duration) -> SlidingWindowState {
+        SlidingWindowState {
+            tokens: self.tokens_available_at(now),
+            capacity: self.config.capacity,
+            window_width: self.window_width(),
+        }
+    }
+
+    /// Timestamp of the most recent consume attempt, see [`Limiter::last_consume_at`]
+    pub fn last_consume_at(&self) -> Option<Duration> {
+        self.monitor.last_consume_at()
+    }
+
+    /// Idle time at `now`, see [`Limiter::idle_for`]
+    pub fn idle_for_at(&self, now: Duration) -> Duration {
+        self.monitor.idle_for(now)
+    }
+
+    /// Run the self-check of [`Limiter::self_check`] at the given time
+    pub fn self_check_at(&self, now: Duration) -> Diagnostics {
+        self.monitor.diagnose(
+            now,
+            self.config.capacity != 0 && self.window_width_ms != 0 && N != 0,
+        )
+    }
+
+    /// Return tokens of an admitted consume at `now`, see [`Refund::refund`]
+    ///
+    /// Tokens are taken back from the most recent entries first, tokens that
+    /// have already slid out of the window can't be returned.
+    pub fn refund_at(&mut self, now: Duration, tokens: u64) {
+        let now_ms = now.saturating_sub(self.start_time).as_millis() as u64;
+        self.expire(now_ms);
+
+        let mut left = tokens;
+        while left != 0 && self.len != 0 {
+            let last = &mut self.entries[self.len - 1].1;
+            let taken = (*last).min(left);
+            *last -= taken;
+            left -= taken;
+            if *last == 0 {
+                self.len -= 1;
+            }
+        }
+    }
+
+    /// Drop entries that have slid out of the window
+    fn expire(&mut self, now_ms: u64) {
+        let expired = self.entries[..self.len]
+            .iter()
+            .take_while(|(t, _)| now_ms.saturating_sub(*t) >= self.window_width_ms)
+            .count();
+        self.entries.copy_within(expired..self.len, 0);
+        self.len -= expired;
+    }
+
+    /// Record tokens consumed at the given time
+    fn record(&mut self, now_ms: u64, tokens: u64) {
+        // Empty entries would only push out real ones
+        if tokens == 0 {
+            return;
+        }
+        if let Some(last) = self.entries[..self.len].last_mut() {
+            if last.0 == now_ms {
+                last.1 += tokens;
+                return;
+            }
+        }
+
+        if self.len == N {
+            // Out of entries, merge the two oldest ones
+            if N < 2 {
+                // Nothing to merge with, keep a single conservative entry
+                self.entries[0] = (now_ms, self.entries[0].1 + tokens);
+                return;
+            }
+            self.entries[1].1 += self.entries[0].1;
+            self.entries.copy_within(1..N, 0);
+            self.len -= 1;
+        }
+
+        self.entries[self.len] = (now_ms, tokens);
+        self.len += 1;
+    }
+
+    /// Consume at the given time, returning the remaining budget
+    fn try_consume_at(&mut self, now: Duration, tokens: u64) -> Result<u64, CantConsume> {
+        self.monitor.observe(now);
+        let now_ms = now.saturating_sub(self.start_time).as_millis() as u64;
+        self.expire(now_ms);
+
+        // Too many tokens used during the window?
+        let used = self.entries[..self.len]
+            .iter()
+            .map(|(_, count)| count)
+            .sum::<u64>();
+        let tokens_left = self.config.capacity.saturating_sub(used);
+        // Without any entries, consumes couldn't be recorded
+        if tokens_left >= tokens && N != 0 {
+            self.record(now_ms, tokens);
+            Ok(tokens_left - tokens)
+        } else {
+            Err(CantConsume)
+        }
+    }
+}
+
+impl<const N: usize> LimiterCore for SparseSlidingWindowLogCore<N> {
+    fn handle(&mut self, now: Duration, request: Request) -> Response {
+        match request {
+            Request::Consume(tokens) => self.try_consume_at(now, tokens).into(),
+        }
+    }
+}
+
+/// Exact sliding window log -type rate limiter
+///
+/// Like [`SparseSlidingWindowLog`], but entries keep the exact timestamps of
+/// the consumes instead of whole milliseconds, and entries are never merged.
+/// Tokens slide out of the window exactly one window width after they were
+/// consumed. For low-rate limits with long windows, e.g. 5 requests per 10
+/// minutes, this is both exact and tiny.
+///
+/// The algorithm itself is implemented by [`SlidingWindowLogExactCore`],
+/// this type only pairs it with a time provider.
+///
+/// # Generic arguments
+/// * `N` - Maximum number of stored entries
+///
+/// # Notes
+/// Each admitted consume takes an entry, unless it happens at the same
+/// timestamp as the previous one. While all `N` entries are in the window,
+/// consumes are rejected even if the budget would allow them, so the limiter
+/// stays exact. Choosing `N` at least the capacity rules this out.
+#[derive(Debug, Clone)]
+pub struct SlidingWindowLogExact<T, const N: usize>
+where
+    T: Clock,
+{
+    core: SlidingWindowLogExactCore<N>,
+    time_provider: T,
+}
+
+impl<T, const N: usize> SlidingWindowLogExact<T, N>
+where
+    T: Clock,
+{
+    /// Initialize a new exact sliding window limiter utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width` - window width
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// # Notes
+    /// * If you are developing for a `std` target, you probably wish to use [`sliding_window_log_exact`]
+    /// * Maximum number of stored entries is defined by the generic argument `N: usize`
+    pub fn new_with_time_provider(capacity: u64, window_width: Duration, time_provider: T) -> Self {
+        let time_now = time_provider.now();
+        Self {
+            core: SlidingWindowLogExactCore::new(capacity, window_width, time_now),
+            time_provider,
+        }
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting invalid configurations
+    ///
+    /// # Returns
+    /// * [`ConfigError::ZeroLength`] if `N` is zero
+    /// * [`ConfigError::ZeroWindow`] if `window_width` is zero
+    /// * [`ConfigError::ZeroCapacity`] if `capacity` is zero
+    pub fn try_new_with_time_provider(
+        capacity: u64,
+        window_width: Duration,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        let time_now = time_provider.now();
+        Ok(Self {
+            core: SlidingWindowLogExactCore::try_new(capacity, window_width, time_now)?,
+            time_provider,
+        })
+    }
+
+    /// Assign a name to this limiter, see [`Limiter::name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.core = self.core.with_name(name);
+        self
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.core.capacity()
+    }
+
+    /// Configured window width
+    pub fn window_width(&self) -> Duration {
+        self.core.window_width()
+    }
+
+    /// Current state of the window, e.g. for dashboards and debug logs
+    pub fn state(&self) -> SlidingWindowState {
+        let now = self.time_provider.now();
+        self.core.state_at(now)
+    }
+
+    /// Get a reference to the underlying sans-IO state machine
+    pub fn core(&self) -> &SlidingWindowLogExactCore<N> {
+        &self.core
+    }
+
+    /// Unwrap the underlying sans-IO state machine, dropping the time provider
+    pub fn into_core(self) -> SlidingWindowLogExactCore<N> {
+        self.core
+    }
+
+    /// Take a plain-data snapshot of the limiter state, see [`SlidingWindowLog::snapshot`]
+    pub fn snapshot(&self) -> SlidingWindowLogExactSnapshot<N> {
+        self.core.snapshot()
+    }
+
+    /// Resume a limiter from a snapshot taken with [`Self::snapshot`]
+    ///
+    /// The time provider must share the epoch of the one the snapshot was taken with.
+    /// The name is not part of the snapshot, see [`Self::with_name`].
+    pub fn restore(snapshot: SlidingWindowLogExactSnapshot<N>, time_provider: T) -> Self {
+        Self {
+            core: SlidingWindowLogExactCore::restore(snapshot),
+            time_provider,
+        }
+    }
+}
+
+impl<T, const N: usize> Limiter for SlidingWindowLogExact<T, N>
+where
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens).map(|_| ())
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        let now = self.time_provider.now();
+        costs
+            .iter()
+            .take_while(|&&tokens| self.core.try_consume_at(now, tokens).is_ok())
+            .count()
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::SlidingWindowLogExact
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.core.config.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.time_provider.now();
+        self.core.self_check_at(now)
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.core.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.time_provider.now();
+        Some(self.core.idle_for_at(now))
+    }
+
+    fn reset(&mut self) {
+        let now = self.time_provider.now();
+        self.core.reset_at(now);
+    }
+}
+
+impl<T, const N: usize> Reset for SlidingWindowLogExact<T, N> where T: Clock {}
+
+impl<T, const N: usize> Refund for SlidingWindowLogExact<T, N>
+where
+    T: Clock,
+{
+    fn refund(&mut self, tokens: u64) {
+        let now = self.time_provider.now();
+        self.core.refund_at(now, tokens);
+    }
+}
+
+impl<T, const N: usize> RetryHint for SlidingWindowLogExact<T, N>
+where
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.time_provider.now();
+        self.core.time_until_available_at(now, tokens)
+    }
+}
+
+impl<T, const N: usize> Inspect for SlidingWindowLogExact<T, N>
+where
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.tokens_available_at(now)
+    }
+}
+
+impl<T, const N: usize> RemainingBudget for SlidingWindowLogExact<T, N>
+where
+    T: Clock,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens)
+    }
+}
+
+/// Plain-data snapshot of a [`SlidingWindowLogExact`], see [`SlidingWindowLogExact::snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SlidingWindowLogExactSnapshot<const N: usize> {
+    /// Configured capacity
+    pub capacity: u64,
+    /// Configured window width
+    pub window_width: Duration,
+    /// `(timestamp, tokens)` entries ordered from oldest to newest, only the
+    /// first `len` ones are in use
+    pub entries: [(Duration, u64); N],
+    /// Number of entries in use
+    pub len: usize,
+    /// Latest timestamp seen by the limiter
+    pub last_update: Duration,
+}
+
+/// Sans-IO exact sliding window log state machine
+///
+/// Same algorithm as [`SlidingWindowLogExact`], but without a time provider:
+/// the current time is passed in with every [`LimiterCore::handle`] call.
+#[derive(Debug, Clone, Copy)]
+pub struct SlidingWindowLogExactCore<const N: usize> {
+    config: SlidingWindowParams,
+    window_width: Duration,
+    /// `(timestamp, tokens)` entries ordered from oldest to newest
+    entries: [(Duration, u64); N],
+    len: usize,
+    monitor: ClockMonitor,
+}
+
+impl<const N: usize> SlidingWindowLogExactCore<N> {
+    /// Initialize a new exact sliding window log state machine
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width` - window width
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    pub fn new(capacity: u64, window_width: Duration, now: Duration) -> Self {
+        Self {
+            config: SlidingWindowParams::new(capacity),
+            window_width,
+            entries: [(Duration::ZERO, 0); N],
+            len: 0,
+            monitor: ClockMonitor::new(now),
+        }
+    }
+
+    /// Validating constructor, see [`SlidingWindowLogExact::try_new_with_time_provider`]
+    pub fn try_new(
+        capacity: u64,
+        window_width: Duration,
+        now: Duration,
+    ) -> Result<Self, ConfigError> {
+        if N == 0 {
+            return Err(ConfigError::ZeroLength);
+        }
+        if window_width.is_zero() {
+            return Err(ConfigError::ZeroWindow);
+        }
+        if capacity == 0 {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        Ok(Self::new(capacity, window_width, now))
+    }
+
+    /// Return to the freshly constructed state at the given time
+    ///
+    /// The configuration, including the name, is kept.
+    pub fn reset_at(&mut self, now: Duration) {
+        self.entries = [(Duration::ZERO, 0); N];
+        self.len = 0;
+        self.monitor.reset(now);
+    }
+
+    /// Take a plain-data snapshot, see [`SlidingWindowLogExact::snapshot`]
+    pub fn snapshot(&self) -> SlidingWindowLogExactSnapshot<N> {
+        SlidingWindowLogExactSnapshot {
+            capacity: self.config.capacity,
+            window_width: self.window_width,
+            entries: self.entries,
+            len: self.len,
+            last_update: self.monitor.latest(),
+        }
+    }
+
+    /// Resume from a snapshot, see [`SlidingWindowLogExact::restore`]
+    pub fn restore(snapshot: SlidingWindowLogExactSnapshot<N>) -> Self {
+        Self {
+            config: SlidingWindowParams::new(snapshot.capacity),
+            window_width: snapshot.window_width,
+            entries: snapshot.entries,
+            len: snapshot.len.min(N),
+            monitor: ClockMonitor::new(snapshot.last_update),
+        }
+    }
+
+    /// Assign a name to this limiter, see [`SlidingWindowLogExact::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.config.name = Some(name);
+        self
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.config.capacity
+    }
+
+    /// Configured window width
+    pub fn window_width(&self) -> Duration {
+        self.window_width
+    }
+
+    /// Tokens that could be consumed at `now`, see [`Inspect::tokens_available`]
+    pub fn tokens_available_at(&self, now: Duration) -> u64 {
+        let counted = self.counted_at(now);
+        if counted.len() == N && counted.last().map(|(t, _)| *t) != Some(now) {
+            return 0;
+        }
+        let used = counted.iter().map(|(_, count)| count).sum::<u64>();
+        self.config.capacity.saturating_sub(used)
+    }
+
+    /// Time until `tokens` could be consumed at `now`, see [`RetryHint::time_until_available`]
+    pub fn time_until_available_at(&self, now: Duration, tokens: u64) -> Option<Duration> {
+        if tokens > self.config.capacity || N == 0 {
+            return None;
+        }
+        let counted = self.counted_at(now);
+        let mut used = counted.iter().map(|(_, count)| count).sum::<u64>();
+        let mut slid_out = counted.iter();
+        let mut wait = Duration::ZERO;
+
+        // Entries slide out of the window from the oldest one
+        while used.saturating_add(tokens) > self.config.capacity {
+            let (t, count) = slid_out.next()?;
+            used -= count;
+            wait = self.expiry(*t).saturating_sub(now);
+        }
+        // A full log needs an entry to slide out as well
+        if counted.len() == N && counted.last().map(|(t, _)| *t) != Some(now) {
+            wait = wait.max(self.expiry(counted[0].0).saturating_sub(now));
+        }
+        Some(wait)
+    }
+
+    /// State of the window at `now`, see [`SlidingWindowLogExact::state`]
+    pub fn state_at(&self, now: Duration) -> SlidingWindowState {
+        SlidingWindowState {
+            tokens: self.tokens_available_at(now),
+            capacity: self.config.capacity,
+            window_width: self.window_width(),
+        }
+    }
+
+    /// Timestamp of the most recent consume attempt, see [`Limiter::last_consume_at`]
+    pub fn last_consume_at(&self) -> Option<Duration> {
+        self.monitor.last_consume_at()
+    }
+
+    /// Idle time at `now`, see [`Limiter::idle_for`]
+    pub fn idle_for_at(&self, now: Duration) -> Duration {
+        self.monitor.idle_for(now)
+    }
+
+    /// Run the self-check of [`Limiter::self_check`] at the given time
+    pub fn self_check_at(&self, now: Duration) -> Diagnostics {
+        self.monitor.diagnose(
+            now,
+            self.config.capacity != 0 && !self.window_width.is_zero() && N != 0,
+        )
+    }
+
+    /// Time an entry recorded at `t` slides out of the window
+    fn expiry(&self, t: Duration) -> Duration {
+        t.saturating_add(self.window_width)
+    }
+
+    /// Entries still in the window at `now`
+    fn counted_at(&self, now: Duration) -> &[(Duration, u64)] {
+        let expired = self.entries[..self.len]
+            .iter()
+            .take_while(|(t, _)| self.expiry(*t) <= now)
+            .count();
+        &self.entries[expired..self.len]
+    }
+
+    /// Drop entries that have slid out of the window
+    fn expire(&mut self, now: Duration) {
+        let expired = self.len - self.counted_at(now).len();
+        self.entries.copy_within(expired..self.len, 0);
+        self.len -= expired;
+    }
+
+    /// Return tokens of an admitted consume at `now`, see [`Refund::refund`]
+    ///
+    /// Tokens are taken back from the most recent entries first, tokens that
+    /// have already slid out of the window can't be returned.
+    pub fn refund_at(&mut self, now: Duration, tokens: u64) {
+        self.expire(now);
+
+        let mut left = tokens;
+        while left != 0 && self.len != 0 {
+            let last = &mut self.entries[self.len - 1].1;
+            let taken = (*last).min(left);
+            *last -= taken;
+            left -= taken;
+            if *last == 0 {
+                self.len -= 1;
+            }
+        }
+    }
+
+    /// Consume at the given time, returning the remaining budget
+    fn try_consume_at(&mut self, now: Duration, tokens: u64) -> Result<u64, CantConsume> {
+        self.monitor.observe(now);
+        self.expire(now);
+
+        // Too many tokens used during the window?
+        let used = self.entries[..self.len]
+            .iter()
+            .map(|(_, count)| count)
+            .sum::<u64>();
+        let tokens_left = self.config.capacity.saturating_sub(used);
+        if tokens_left < tokens {
+            return Err(CantConsume);
         }
+
+        match self.entries[..self.len].last_mut() {
+            _ if tokens == 0 => {}
+            Some(last) if last.0 == now => last.1 += tokens,
+            _ if self.len == N => return Err(CantConsume),
+            _ => {
+                self.entries[self.len] = (now, tokens);
+                self.len += 1;
+            }
+        }
+        Ok(tokens_left - tokens)
     }
 }
 
-/// Configuration for a fixed window limiter
-#[derive(Clone, Copy)]
-struct SlidingWindowConfig<T>
-where
-    T: Fn() -> Duration,
-{
+impl<const N: usize> LimiterCore for SlidingWindowLogExactCore<N> {
+    fn handle(&mut self, now: Duration, request: Request) -> Response {
+        match request {
+            Request::Consume(tokens) => self.try_consume_at(now, tokens).into(),
+        }
+    }
+}
+
+/// Configuration for a sliding window limiter
+#[derive(Debug, Clone, Copy)]
+struct SlidingWindowParams {
     capacity: u64,
-    time_provider: T,
+    name: Option<&'static str>,
 }
 
-impl<T: Fn() -> Duration> SlidingWindowConfig<T> {
-    fn new(capacity: u64, time_provider: T) -> Self {
+impl SlidingWindowParams {
+    fn new(capacity: u64) -> Self {
         Self {
             capacity,
-            time_provider,
+            name: None,
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{mock_assets::MockClock, Limiter, SlidingWindowCounter, SlidingWindowLog};
+    use core::time::Duration;
+
+    use crate::{
+        mock_assets::MockClock, ConfigError, Inspect, Limiter, Refund, RemainingBudget, RetryHint,
+        SlidingWindowCounter, SlidingWindowLog, SlidingWindowLogCore, SlidingWindowLogExact,
+        SlidingWindowState, SparseSlidingWindowLog, SparseSlidingWindowLogCore,
+    };
 
     #[test]
     fn verify_rate_sliding() {
@@ -318,16 +2552,620 @@ mod tests {
         // T = 10ms, tokens left = 0
         assert!(w.try_consume_one().is_err());
         // Second
-        // T = 11ms, effective tokens used by previous window = 0.10.. * 1000 = 899
-        assert!(w.try_consume(101).is_ok());
-        // T = 12ms, effective tokens used by previous window = 0.199.. * 1000 = 800
-        // tokens used by this window = 101
-        // total left = 99
-        assert!(w.try_consume(99).is_ok());
-
-        // T = 12ms, effective tokens used by previous window = 0.30.. * 1000 = 700
+        // T = 11ms, effective tokens used by previous window = 0.9 * 1000 = 900
+        assert!(w.try_consume(101).is_err());
+        // T = 12ms, effective tokens used by previous window = 0.8 * 1000 = 800
+        assert!(w.try_consume(200).is_ok());
+        // T = 13ms, effective tokens used by previous window = 0.7 * 1000 = 700
         // tokens used by this window = 200
         // total left = 100
         assert!(w.try_consume(101).is_err());
+        // T = 14ms, effective tokens used by previous window = 0.6 * 1000 = 600
+        // total left = 200
+        assert!(w.try_consume(200).is_ok());
+    }
+
+    #[test]
+    fn verify_rate_sparse_sliding() {
+        let clock = MockClock::new();
+        // Each call steps the clock 1ms forward
+        let mut w =
+            SparseSlidingWindowLog::<_, 4>::new_with_time_provider(1000, 10, || clock.step(1000));
+
+        // T = 1ms, tokens left = 1000
+        assert!(w.try_consume(500).is_ok());
+        // T = 2ms, tokens left = 500
+        assert!(w.try_consume(500).is_ok());
+        // T = 3ms..10ms, tokens left = 0
+        for _ in 3..=10 {
+            assert!(w.try_consume_one().is_err());
+        }
+        // T = 11ms, tokens left = 500
+        assert!(w.try_consume_one().is_ok());
+        // T = 12ms, tokens left = 999
+        assert!(w.try_consume(999).is_ok());
+        // T = 13ms, tokens left = 0
+        assert!(w.try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_sparse_sliding_overflow() {
+        let clock = MockClock::new();
+        // Each call steps the clock 1ms forward
+        let mut w =
+            SparseSlidingWindowLog::<_, 2>::new_with_time_provider(3, 3, || clock.step(1000));
+
+        // T = 1ms, 2ms, 3ms, third entry merges the first two into T = 2ms
+        assert!(w.try_consume_one().is_ok());
+        assert!(w.try_consume_one().is_ok());
+        assert!(w.try_consume_one().is_ok());
+        // T = 4ms, exact log would have freed the token from T = 1ms,
+        // but it was merged into T = 2ms
+        assert!(w.try_consume_one().is_err());
+        // T = 5ms, merged entry has slid out
+        assert!(w.try_consume(2).is_ok());
+    }
+
+    #[test]
+    fn verify_sparse_sliding_clock_backwards() {
+        let ms = Duration::from_millis;
+        let mut core = SparseSlidingWindowLogCore::<4>::new(3, 10, ms(0));
+        assert!(core.try_consume_at(ms(20), 1).is_ok());
+        // Earlier than the recorded entry, which is still counted
+        assert!(core.try_consume_at(ms(15), 2).is_ok());
+        assert!(core.try_consume_at(ms(15), 1).is_err());
+        assert!(!core.self_check_at(ms(20)).clock_monotonic);
+
+        assert_eq!(
+            SparseSlidingWindowLog::<_, 0>::try_new_with_time_provider(3, 10, || ms(0)).err(),
+            Some(ConfigError::ZeroLength)
+        );
+        let mut empty = SparseSlidingWindowLogCore::<0>::new(3, 10, ms(0));
+        assert!(empty.try_consume_at(ms(0), 1).is_err());
+    }
+
+    #[test]
+    fn verify_sliding_counter_buckets() {
+        let ms = Duration::from_millis;
+        let clock = MockClock::new();
+        let mut one = SlidingWindowCounter::new_with_time_provider(10, 10, || clock.step(0));
+        let mut two = SlidingWindowCounter::new_with_time_provider(10, 10, || clock.step(0))
+            .with_buckets::<2>();
+
+        assert!(one.try_consume(10).is_ok());
+        assert!(two.try_consume(10).is_ok());
+        assert_eq!(two.time_until_available(1), Some(ms(11)));
+
+        // T = 5ms, the tokens are still counted exactly
+        clock.step(5_000);
+        assert_eq!(two.tokens_available(), 0);
+
+        // T = 12ms, only the oldest bucket is interpolated
+        clock.step(7_000);
+        assert_eq!(one.tokens_available(), 2);
+        assert_eq!(two.tokens_available(), 4);
+        assert_eq!(two.time_until_available(10), Some(ms(3)));
+        assert!(two.try_consume(4).is_ok());
+        assert!(two.try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_sliding_counter_long_gap() {
+        let clock = MockClock::new();
+        let mut w = SlidingWindowCounter::new_with_time_provider(10, 10, || clock.step(0));
+
+        assert!(w.try_consume(10).is_ok());
+        assert!(w.try_consume_one().is_err());
+        // Skip several windows, counters are zeroed
+        clock.step(50_000);
+        assert!(w.try_consume(10).is_ok());
+    }
+
+    #[test]
+    fn verify_sliding_counter_bound() {
+        let bound =
+            SlidingWindowCounter::<fn() -> core::time::Duration>::approximation_bound(1000, 10);
+        assert_eq!(bound.max_overshoot, 900);
+        assert_eq!(bound.max_undershoot, 1000);
+
+        // Reach the overshoot: previous window filled at its very end,
+        // current window filled at its very end
+        let clock = MockClock::new();
+        let mut w = SlidingWindowCounter::new_with_time_provider(1000, 10, || clock.step(0));
+        clock.step(9_000);
+        assert!(w.try_consume(1000).is_ok());
+        clock.step(10_000);
+        // 100 effective tokens from the previous window
+        assert!(w.try_consume(900).is_ok());
+        assert!(w.try_consume_one().is_err());
+        assert_eq!(w.error_bound(), bound);
+    }
+
+    #[test]
+    fn verify_rate_exact_sliding() {
+        let ms = Duration::from_millis;
+        let clock = MockClock::new();
+        let mut w =
+            SlidingWindowLogExact::<_, 2>::new_with_time_provider(4, ms(10), || clock.step(0));
+
+        assert!(w.try_consume(2).is_ok());
+        clock.step(2_500);
+        // T = 2.5ms
+        assert!(w.try_consume_one().is_ok());
+        assert!(w.try_consume(2).is_err());
+        assert_eq!(
+            w.time_until_available(2),
+            Some(Duration::from_micros(7_500))
+        );
+
+        // T = 9.999ms
+        clock.step(7_499);
+        assert!(w.try_consume(2).is_err());
+        clock.step(1);
+        // T = 10ms, the first entry slides out exactly
+        assert!(w.try_consume(2).is_ok());
+        assert_eq!(w.tokens_available(), 1);
+
+        // Log full until the entry of T = 2.5ms slides out
+        clock.step(1_000);
+        assert_eq!(w.tokens_available(), 0);
+        assert!(w.try_consume_one().is_err());
+        assert_eq!(
+            w.time_until_available(1),
+            Some(Duration::from_micros(1_500))
+        );
+        assert_eq!(w.time_until_available(5), None);
+    }
+
+    #[test]
+    fn verify_try_new_exact_sliding() {
+        let ms = Duration::from_millis;
+        let clock = MockClock::new();
+        assert_eq!(
+            SlidingWindowLogExact::<_, 0>::try_new_with_time_provider(4, ms(10), || clock.step(0))
+                .err(),
+            Some(ConfigError::ZeroLength)
+        );
+        assert_eq!(
+            SlidingWindowLogExact::<_, 2>::try_new_with_time_provider(4, ms(0), || clock.step(0))
+                .err(),
+            Some(ConfigError::ZeroWindow)
+        );
+        assert_eq!(
+            crate::SlidingWindowLogExactCore::<2>::try_new(0, ms(10), Duration::ZERO).err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+        let mut w =
+            SlidingWindowLogExact::<_, 2>::try_new_with_time_provider(4, ms(10), || clock.step(0))
+                .unwrap();
+        assert!(w.try_consume(4).is_ok());
+    }
+
+    #[test]
+    fn verify_config_getters() {
+        let clock = MockClock::new();
+        let w = SlidingWindowLog::<_, 10>::new_with_time_provider(1000, || clock.step(0));
+        assert_eq!(w.capacity(), 1000);
+        assert_eq!(w.window_width(), Duration::from_millis(10));
+
+        let w = SlidingWindowCounter::new_with_time_provider(100, 20, || clock.step(0));
+        assert_eq!(w.capacity(), 100);
+        assert_eq!(w.window_width(), Duration::from_millis(20));
+    }
+
+    #[test]
+    fn verify_initial_usage_sliding() {
+        let clock = MockClock::new();
+        // Each call steps the clock 1ms forward
+        let mut w = SlidingWindowLog::<_, 10>::new_with_time_provider(10, || clock.step(1000))
+            .with_initial_usage(&[0, 0, 0, 0, 0, 0, 0, 0, 5, 5]);
+
+        // T = 1ms, oldest 5 tokens slide out, tokens left = 5
+        assert!(w.try_consume(6).is_err());
+        // T = 2ms, remaining 5 tokens slide out, tokens left = 10
+        assert!(w.try_consume(10).is_ok());
+        // T = 3ms, tokens left = 0
+        assert!(w.try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_remaining_sliding() {
+        let clock = MockClock::new();
+        let mut log = SlidingWindowLog::<_, 10>::new_with_time_provider(5, || clock.step(0));
+        let mut counter = SlidingWindowCounter::new_with_time_provider(5, 10, || clock.step(0));
+        let mut sparse =
+            SparseSlidingWindowLog::<_, 4>::new_with_time_provider(5, 10, || clock.step(0));
+
+        assert_eq!(log.try_consume_remaining(2).unwrap(), 3);
+        assert_eq!(counter.try_consume_remaining(2).unwrap(), 3);
+        assert_eq!(sparse.try_consume_remaining(2).unwrap(), 3);
+        clock.step(5_000);
+        assert_eq!(log.try_consume_remaining(3).unwrap(), 0);
+        assert_eq!(counter.try_consume_remaining(3).unwrap(), 0);
+        assert_eq!(sparse.try_consume_remaining(3).unwrap(), 0);
+    }
+
+    #[test]
+    fn verify_window_width_change_sliding() {
+        let clock = MockClock::new();
+        let mut log = SlidingWindowLog::<_, 10>::new_with_time_provider(5, || clock.step(0));
+
+        assert!(log.try_consume(3).is_ok());
+        clock.step(4_000);
+        // T = 4ms, the first consume falls outside of a 3ms window
+        log.set_window_width(Duration::from_millis(3));
+        assert_eq!(log.window_width(), Duration::from_millis(3));
+        assert!(log.try_consume(5).is_ok());
+        assert!(log.try_consume(1).is_err());
+
+        // Widening brings the older history back into account
+        log.set_window_width(Duration::from_millis(100));
+        assert_eq!(log.window_width(), Duration::from_millis(10));
+        clock.step(3_000);
+        // T = 7ms, all 8 tokens are still within the window
+        assert!(log.try_consume(1).is_err());
+        log.set_window_width(Duration::ZERO);
+        assert_eq!(log.window_width(), Duration::from_millis(1));
+        assert!(log.try_consume(5).is_ok());
+    }
+
+    #[test]
+    fn verify_tokens_available_sliding() {
+        let clock = MockClock::new();
+        let mut log = SlidingWindowLog::<_, 10>::new_with_time_provider(5, || clock.step(0));
+        let mut counter = SlidingWindowCounter::new_with_time_provider(10, 10, || clock.step(0));
+        let mut sparse =
+            SparseSlidingWindowLog::<_, 4>::new_with_time_provider(5, 10, || clock.step(0));
+
+        assert!(log.try_consume(2).is_ok());
+        assert!(counter.try_consume(10).is_ok());
+        assert!(sparse.try_consume(2).is_ok());
+        clock.step(5_000);
+        assert!(log.try_consume(1).is_ok());
+        assert!(sparse.try_consume(1).is_ok());
+        assert_eq!(log.tokens_available(), 2);
+        assert_eq!(sparse.tokens_available(), 2);
+        assert_eq!(counter.tokens_available(), 0);
+
+        clock.step(9_000);
+        // T = 14ms, the first consumes have slid out of the window
+        assert_eq!(log.tokens_available(), 4);
+        assert_eq!(sparse.tokens_available(), 4);
+        // 60% of the previous window is still accounted for
+        assert_eq!(counter.tokens_available(), 4);
+        assert_eq!(log.try_consume_remaining(4).unwrap(), 0);
+    }
+
+    #[test]
+    fn verify_retry_hint_sliding() {
+        let ms = Duration::from_millis;
+        let clock = MockClock::new();
+        let mut log = SlidingWindowLog::<_, 10>::new_with_time_provider(5, || clock.step(0));
+        let mut counter = SlidingWindowCounter::new_with_time_provider(10, 10, || clock.step(0));
+        let mut sparse =
+            SparseSlidingWindowLog::<_, 4>::new_with_time_provider(5, 10, || clock.step(0));
+
+        assert!(log.try_consume(2).is_ok());
+        assert!(counter.try_consume(10).is_ok());
+        assert!(sparse.try_consume(2).is_ok());
+        clock.step(5_000);
+        // T = 5ms
+        assert!(log.try_consume(3).is_ok());
+        assert!(sparse.try_consume(3).is_ok());
+
+        // The first consume slides out of the window at T = 10ms
+        assert_eq!(log.time_until_available(1), Some(ms(5)));
+        assert_eq!(sparse.time_until_available(2), Some(ms(5)));
+        assert_eq!(log.time_until_available(4), Some(ms(10)));
+        assert_eq!(sparse.time_until_available(6), None);
+        // 90% of the first window is accounted for at T = 11ms
+        assert_eq!(counter.time_until_available(1), Some(ms(6)));
+
+        clock.step(6_000);
+        assert!(counter.try_consume(1).is_ok());
+        assert!(log.try_consume(2).is_ok());
+    }
+
+    #[test]
+    fn verify_ring_buffer_sliding() {
+        let clock = MockClock::new();
+        let mut w = SlidingWindowLog::<_, 3>::new_with_time_provider(3, || clock.step(0));
+        assert!(w.try_consume_one().is_ok());
+        clock.step(1000);
+        assert!(w.try_consume_one().is_ok());
+        clock.step(1000);
+        assert!(w.try_consume_one().is_ok());
+
+        // Wraps around the buffer several times, a token sliding out every millisecond
+        for _ in 0..10 {
+            clock.step(1000);
+            assert!(w.try_consume_one().is_ok());
+            assert!(w.try_consume_one().is_err());
+        }
+        // Two of the three tokens are still in a two millisecond window
+        w.set_window_width(Duration::from_millis(2));
+        assert_eq!(w.tokens_available(), 1);
+        w.set_window_width(Duration::from_millis(3));
+        assert_eq!(w.tokens_available(), 0);
+    }
+
+    #[test]
+    fn verify_refund_sliding() {
+        let clock = MockClock::new();
+        let mut w = SlidingWindowLog::<_, 10>::new_with_time_provider(5, || clock.step(0));
+        assert!(w.try_consume(2).is_ok());
+        clock.step(1000);
+        assert!(w.try_consume(3).is_ok());
+        // Most recent slot first, then the older one
+        w.refund(4);
+        assert_eq!(w.tokens_available(), 4);
+        assert_eq!(w.time_until_available(5), Some(Duration::from_millis(9)));
+        w.refund(10);
+        assert_eq!(w.tokens_available(), 5);
+
+        let mut c = SlidingWindowCounter::new_with_time_provider(5, 10, || clock.step(0));
+        assert!(c.try_consume(5).is_ok());
+        c.refund(2);
+        assert!(c.try_consume(2).is_ok());
+        assert!(c.try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_refund_advances_window() {
+        let clock = MockClock::new();
+        let mut w = SlidingWindowLog::<_, 10>::new_with_time_provider(5, || clock.step(0));
+        assert!(w.try_consume(3).is_ok());
+        clock.step(9000);
+        assert!(w.try_consume(2).is_ok());
+        clock.step(1000);
+        // T = 10ms, the first consume has slid out and only the second one
+        // can be returned
+        w.refund(5);
+        assert_eq!(w.tokens_available(), 5);
+        assert!(w.try_consume(5).is_ok());
+
+        let mut core = SlidingWindowLogCore::<4>::new(5, Duration::ZERO);
+        assert!(core.try_consume_at(Duration::ZERO, 5).is_ok());
+        // Refunding long after the window has slid by doesn't free extra tokens
+        core.refund_at(Duration::from_millis(100), 5);
+        assert_eq!(core.tokens_available_at(Duration::from_millis(100)), 5);
+        assert!(core.try_consume_at(Duration::from_millis(100), 6).is_err());
+    }
+
+    #[test]
+    fn verify_refund_sparse_exact() {
+        let clock = MockClock::new();
+        let mut sparse =
+            SparseSlidingWindowLog::<_, 4>::new_with_time_provider(5, 10, || clock.step(0));
+        let mut exact = SlidingWindowLogExact::<_, 4>::new_with_time_provider(
+            5,
+            Duration::from_millis(10),
+            || clock.step(0),
+        );
+        assert!(sparse.try_consume(2).is_ok());
+        assert!(exact.try_consume(2).is_ok());
+        clock.step(5000);
+        assert!(sparse.try_consume(3).is_ok());
+        assert!(exact.try_consume(3).is_ok());
+
+        // Most recent entry first, then the older one
+        sparse.give_back(4);
+        exact.give_back(4);
+        assert_eq!(sparse.tokens_available(), 4);
+        assert_eq!(exact.tokens_available(), 4);
+        clock.step(5000);
+        // T = 10ms, the remaining token of the first entry has slid out
+        assert_eq!(sparse.tokens_available(), 5);
+        assert_eq!(exact.tokens_available(), 5);
+
+        assert!(sparse.try_consume(5).is_ok());
+        assert!(exact.try_consume(5).is_ok());
+        sparse.refund(10);
+        exact.refund(10);
+        assert_eq!(sparse.tokens_available(), 5);
+        assert_eq!(exact.tokens_available(), 5);
+    }
+
+    #[test]
+    fn verify_snapshot_restore_sliding() {
+        let clock = MockClock::new();
+        let mut log =
+            SlidingWindowLog::<_, 10>::new_with_time_provider(5, || clock.step(0)).with_name("log");
+        let mut counter = SlidingWindowCounter::new_with_time_provider(5, 10, || clock.step(0));
+        assert!(log.try_consume(2).is_ok());
+        assert!(counter.try_consume(2).is_ok());
+        clock.step(3000);
+        assert!(log.try_consume(1).is_ok());
+
+        let snapshot = log.snapshot();
+        assert_eq!(snapshot.slots, [1, 0, 0, 2, 0, 0, 0, 0, 0, 0]);
+        assert_eq!(snapshot.window_slots, 10);
+        assert_eq!(snapshot.last_update, Duration::from_millis(3));
+        let mut log = SlidingWindowLog::restore(snapshot, || clock.step(0));
+        assert_eq!(log.name(), None);
+        assert_eq!(log.tokens_available(), 2);
+
+        let snapshot = counter.snapshot();
+        assert_eq!(snapshot.current_tokens, 2);
+        assert_eq!(snapshot.window_width, Duration::from_millis(10));
+        let counter = SlidingWindowCounter::restore(snapshot, || clock.step(0));
+        assert_eq!(counter.tokens_available(), 3);
+
+        clock.step(7000);
+        // T = 10ms, the first consume has slid out of the log
+        assert_eq!(log.tokens_available(), 4);
+        assert!(log.try_consume(5).is_err());
+    }
+
+    #[test]
+    fn verify_zero_token_consume_sparse() {
+        let clock = MockClock::new();
+        let mut w = SparseSlidingWindowLog::<_, 2>::new_with_time_provider(5, 10, || clock.step(0));
+        assert!(w.try_consume(2).is_ok());
+        clock.step(1000);
+        assert!(w.try_consume(0).is_ok());
+        assert_eq!(w.snapshot().len, 1);
+        clock.step(1000);
+        assert!(w.try_consume(1).is_ok());
+        // T = 2ms, both consumes still have their own entry
+        assert_eq!(w.snapshot().entries, [(0, 2), (2, 1)]);
+        clock.step(8000);
+        // T = 10ms, the first consume has slid out
+        assert_eq!(w.tokens_available(), 4);
+    }
+
+    #[test]
+    fn verify_snapshot_restore_sparse_exact() {
+        let clock = MockClock::new();
+        let mut sparse =
+            SparseSlidingWindowLog::<_, 4>::new_with_time_provider(5, 10, || clock.step(0));
+        let mut exact = SlidingWindowLogExact::<_, 4>::new_with_time_provider(
+            5,
+            Duration::from_millis(10),
+            || clock.step(0),
+        );
+        clock.step(2000);
+        assert!(sparse.try_consume(2).is_ok());
+        assert!(exact.try_consume(2).is_ok());
+
+        let snapshot = sparse.snapshot();
+        assert_eq!(snapshot.len, 1);
+        assert_eq!(snapshot.entries[0], (2, 2));
+        let mut sparse = SparseSlidingWindowLog::restore(snapshot, || clock.step(0));
+        let snapshot = exact.snapshot();
+        assert_eq!(snapshot.len, 1);
+        assert_eq!(snapshot.entries[0], (Duration::from_millis(2), 2));
+        let mut exact = SlidingWindowLogExact::restore(snapshot, || clock.step(0));
+
+        assert!(sparse.try_consume(4).is_err());
+        assert!(exact.try_consume(4).is_err());
+        clock.step(10_000);
+        // T = 12ms, the consume before the restore has slid out
+        assert!(sparse.try_consume(5).is_ok());
+        assert!(exact.try_consume(5).is_ok());
+    }
+
+    #[test]
+    fn verify_state_sliding() {
+        let clock = MockClock::new();
+        let mut w = SlidingWindowLog::<_, 10>::new_with_time_provider(5, || clock.step(0));
+        assert!(w.try_consume(2).is_ok());
+        let expected = SlidingWindowState {
+            tokens: 3,
+            capacity: 5,
+            window_width: Duration::from_millis(10),
+        };
+        assert_eq!(w.state(), expected);
+
+        let mut c = SlidingWindowCounter::new_with_time_provider(5, 10, || clock.step(0));
+        assert!(c.try_consume(2).is_ok());
+        assert_eq!(c.state(), expected);
+    }
+
+    #[test]
+    fn verify_state_sparse_exact() {
+        let clock = MockClock::new();
+        let mut sparse =
+            SparseSlidingWindowLog::<_, 4>::new_with_time_provider(5, 10, || clock.step(0));
+        let mut exact = SlidingWindowLogExact::<_, 4>::new_with_time_provider(
+            5,
+            Duration::from_millis(10),
+            || clock.step(0),
+        );
+        assert!(sparse.try_consume(2).is_ok());
+        assert!(exact.try_consume(2).is_ok());
+        let expected = SlidingWindowState {
+            tokens: 3,
+            capacity: 5,
+            window_width: Duration::from_millis(10),
+        };
+        assert_eq!(sparse.state(), expected);
+        assert_eq!(exact.state(), expected);
+
+        clock.step(10_000);
+        // T = 10ms, the consume has slid out of the window
+        assert_eq!(sparse.state().tokens, 5);
+        assert_eq!(exact.state().tokens, 5);
+    }
+
+    #[test]
+    fn verify_token_count_sliding() {
+        let clock = MockClock::new();
+        let mut w = SlidingWindowLog::<_, 4, u8>::new_with_time_provider(1000, || clock.step(0));
+        // u32 slots take half the memory
+        assert_eq!(
+            core::mem::size_of::<SlidingWindowLogCore<64>>()
+                - core::mem::size_of::<SlidingWindowLogCore<64, u32>>(),
+            64 * 4
+        );
+
+        assert_eq!(
+            SlidingWindowLog::<_, 4, u8>::try_new_with_time_provider(1000, || clock.step(0)).err(),
+            Some(crate::ConfigError::CapacityOverflow)
+        );
+        assert!(SlidingWindowLogCore::<0>::try_new(10, Duration::ZERO).is_err());
+
+        // A slot holds at most 255 tokens
+        assert!(w.try_consume(200).is_ok());
+        assert!(w.try_consume(100).is_err());
+        assert!(w.self_check().saturated);
+        clock.step(1000);
+        assert!(w.try_consume(255).is_ok());
+        assert_eq!(w.tokens_available(), 545);
+    }
+
+    #[test]
+    fn verify_slot_width_sliding() {
+        let clock = MockClock::new();
+        // Four slots of one second
+        let mut w = SlidingWindowLog::<_, 4>::new_with_time_provider(3, || clock.step(0))
+            .with_slot_width(Duration::from_secs(1));
+        assert_eq!(w.window_width(), Duration::from_secs(4));
+
+        assert!(w.try_consume(2).is_ok());
+        clock.step(1_500_000);
+        // T = 1.5s
+        assert!(w.try_consume(2).is_err());
+        assert!(w.try_consume_one().is_ok());
+        // The first slot slides out at T = 4s
+        assert_eq!(w.time_until_available(2), Some(Duration::from_millis(2500)));
+        clock.step(2_499_000);
+        assert!(w.try_consume(2).is_err());
+        clock.step(1_000);
+        assert!(w.try_consume(2).is_ok());
+    }
+
+    #[test]
+    fn verify_reset_sliding() {
+        let clock = MockClock::new();
+        let mut w = SlidingWindowLog::<_, 10>::new_with_time_provider(5, || clock.step(0));
+        let mut c = SlidingWindowCounter::new_with_time_provider(5, 10, || clock.step(0));
+        let mut s = SparseSlidingWindowLog::<_, 4>::new_with_time_provider(5, 10, || clock.step(0));
+        assert!(w.try_consume(5).is_ok());
+        assert!(c.try_consume(5).is_ok());
+        assert!(s.try_consume(5).is_ok());
+
+        // T = 15ms, still partially limited by the previous window
+        clock.step(15_000);
+        assert!(c.try_consume(5).is_err());
+        w.reset();
+        c.reset();
+        s.reset();
+        assert!(w.try_consume(5).is_ok());
+        assert!(c.try_consume(5).is_ok());
+        assert!(s.try_consume(5).is_ok());
+    }
+
+    #[test]
+    fn verify_idle_oversized_sliding() {
+        let clock = MockClock::new();
+        let mut w = SlidingWindowLog::<_, 10>::new_with_time_provider(1000, || clock.step(0));
+
+        // T = 50ms, the window is idle but still bounded by the capacity
+        clock.step(50_000);
+        assert!(!w.can_ever_consume(2000));
+        assert!(w.try_consume(2000).is_err());
+        assert_eq!(w.tokens_available(), 1000);
+        assert!(w.try_consume(1000).is_ok());
     }
 }
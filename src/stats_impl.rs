@@ -0,0 +1,681 @@
+//! Statistics collection and reporting
+
+use core::time::Duration;
+
+use crate::{
+    Clock, Diagnostics, Inspect, Limiter, LimiterInfo, LimiterKind, LimiterResult, Reset, RetryHint,
+};
+
+/// Aggregate consume statistics
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct Stats {
+    /// Number of successful consumes
+    pub accepted: u64,
+    /// Number of rejected consumes
+    pub rejected: u64,
+    /// Total amount of tokens consumed by successful consumes
+    pub tokens_granted: u64,
+}
+
+impl Stats {
+    /// Record the result of a single consume
+    pub fn record(&mut self, tokens: u64, result: &LimiterResult) {
+        if result.is_ok() {
+            self.accepted = self.accepted.saturating_add(1);
+            self.tokens_granted = self.tokens_granted.saturating_add(tokens);
+        } else {
+            self.rejected = self.rejected.saturating_add(1);
+        }
+    }
+}
+
+/// Periodic statistics reporting wrapper for any [`Limiter`]
+///
+/// Collects [`Stats`] over fixed reporting intervals. On the first consume
+/// after an interval has elapsed, the user callback is invoked with the
+/// [`LimiterInfo`] of the wrapped limiter and the statistics of the previous
+/// interval before the consume is recorded.
+/// No timer thread is involved, so this works on `no_std` targets as well.
+///
+/// The intervals follow the timestamp the wrapped limiter recorded for the
+/// consume, see [`Limiter::last_consume_at`], so the reports agree with the
+/// limiter's own view of time. The time provider of the wrapper is only used
+/// for limiters not tracking their consumes, and to start the intervals.
+///
+/// Intervals without a single consume are not reported.
+///
+/// # Generic arguments
+/// * `L` - wrapped limiter
+/// * `T` - time provider
+/// * `F` - reporting callback
+#[derive(Debug, Clone)]
+pub struct WithReporting<L, T, F>
+where
+    L: Limiter,
+    T: Clock,
+    F: FnMut(LimiterInfo, &Stats),
+{
+    inner: L,
+    stats: Stats,
+    interval: Duration,
+    interval_start: Duration,
+    time_provider: T,
+    callback: F,
+}
+
+impl<L, T, F> WithReporting<L, T, F>
+where
+    L: Limiter,
+    T: Clock,
+    F: FnMut(LimiterInfo, &Stats),
+{
+    /// Wrap a limiter with periodic statistics reporting
+    ///
+    /// # Arguments
+    /// * `inner` - limiter to wrap
+    /// * `interval` - length of a single reporting interval
+    /// * `callback` - closure invoked with the limiter info and the statistics of each elapsed interval
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    pub fn new_with_time_provider(
+        inner: L,
+        interval: Duration,
+        callback: F,
+        time_provider: T,
+    ) -> Self {
+        let time_now = time_provider.now();
+        Self {
+            inner,
+            stats: Stats::default(),
+            interval,
+            interval_start: time_now,
+            time_provider,
+            callback,
+        }
+    }
+
+    /// Statistics collected so far during the current interval
+    pub fn current_stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Get a reference to the wrapped limiter
+    pub fn inner(&self) -> &L {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped limiter
+    pub fn inner_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    /// Unwrap the wrapped limiter
+    pub fn into_inner(self) -> L {
+        self.inner
+    }
+
+    /// Report and restart the interval if it has elapsed by the latest consume
+    fn roll_interval(&mut self) {
+        let now = self
+            .inner
+            .last_consume_at()
+            .unwrap_or_else(|| self.time_provider.now());
+        let elapsed = now.saturating_sub(self.interval_start);
+        if elapsed < self.interval {
+            return;
+        }
+
+        if self.stats != Stats::default() {
+            (self.callback)(self.inner.info(), &self.stats);
+        }
+        self.stats = Stats::default();
+
+        // Keep intervals aligned to the start time, even if some were skipped
+        let interval_ns = self.interval.as_nanos().max(1);
+        let skipped = elapsed.as_nanos() / interval_ns;
+        let advance = Duration::from_nanos((skipped * interval_ns).min(u64::MAX as u128) as u64);
+        self.interval_start += advance;
+    }
+}
+
+impl<L, T, F> Limiter for WithReporting<L, T, F>
+where
+    L: Limiter,
+    T: Clock,
+    F: FnMut(LimiterInfo, &Stats),
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let result = self.inner.try_consume(tokens);
+        self.roll_interval();
+        self.stats.record(tokens, &result);
+        result
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        let admitted = self.inner.try_consume_batch(costs);
+        self.roll_interval();
+        for &tokens in &costs[..admitted] {
+            self.stats.record(tokens, &Ok(()));
+        }
+        if admitted < costs.len() {
+            self.stats.rejected = self.stats.rejected.saturating_add(1);
+        }
+        admitted
+    }
+
+    fn kind(&self) -> LimiterKind {
+        self.inner.kind()
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.inner.name()
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        self.inner.self_check()
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.inner.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        self.inner.idle_for()
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+        self.stats = Stats::default();
+        self.interval_start = self.time_provider.now();
+    }
+}
+
+impl<L, T, F> Reset for WithReporting<L, T, F>
+where
+    L: Reset,
+    T: Clock,
+    F: FnMut(LimiterInfo, &Stats),
+{
+}
+
+impl<L, T, F> RetryHint for WithReporting<L, T, F>
+where
+    L: RetryHint,
+    T: Clock,
+    F: FnMut(LimiterInfo, &Stats),
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        self.inner.time_until_available(tokens)
+    }
+}
+
+impl<L, T, F> Inspect for WithReporting<L, T, F>
+where
+    L: Inspect,
+    T: Clock,
+    F: FnMut(LimiterInfo, &Stats),
+{
+    fn tokens_available(&self) -> u64 {
+        self.inner.tokens_available()
+    }
+}
+
+/// Statistics collection wrapper for any [`Limiter`]
+///
+/// Counts [`Stats`] of every consume and the time the limiter has spent
+/// saturated, i.e. from a rejection until the next admitted consume, until
+/// [`WithStats::reset_stats`] or [`Limiter::reset`] is called. Unlike
+/// [`WithReporting`], the statistics are left for the caller to read.
+///
+/// # Generic arguments
+/// * `L` - wrapped limiter
+/// * `T` - time provider
+#[derive(Debug, Clone)]
+pub struct WithStats<L, T>
+where
+    L: Limiter,
+    T: Clock,
+{
+    inner: L,
+    stats: Stats,
+    saturated_since: Option<Duration>,
+    time_saturated: Duration,
+    time_provider: T,
+}
+
+impl<L, T> WithStats<L, T>
+where
+    L: Limiter,
+    T: Clock,
+{
+    /// Wrap a limiter with statistics collection
+    ///
+    /// # Arguments
+    /// * `inner` - limiter to wrap
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    pub fn new_with_time_provider(inner: L, time_provider: T) -> Self {
+        Self {
+            inner,
+            stats: Stats::default(),
+            saturated_since: None,
+            time_saturated: Duration::ZERO,
+            time_provider,
+        }
+    }
+
+    /// Statistics collected since the last reset
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Time spent saturated since the last reset, including an ongoing saturation
+    pub fn time_saturated(&self) -> Duration {
+        let ongoing = self.saturated_since.map_or(Duration::ZERO, |since| {
+            self.time_provider.now().saturating_sub(since)
+        });
+        self.time_saturated + ongoing
+    }
+
+    /// Zero all collected statistics
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+        self.time_saturated = Duration::ZERO;
+        self.saturated_since = self.saturated_since.map(|_| self.time_provider.now());
+    }
+
+    /// Get a reference to the wrapped limiter
+    pub fn inner(&self) -> &L {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped limiter
+    pub fn inner_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    /// Unwrap the wrapped limiter
+    pub fn into_inner(self) -> L {
+        self.inner
+    }
+
+    /// Track the saturated time after a consume
+    fn record_saturation(&mut self, admitted: bool) {
+        let now = self.time_provider.now();
+        match (admitted, self.saturated_since) {
+            (true, Some(since)) => {
+                self.time_saturated += now.saturating_sub(since);
+                self.saturated_since = None;
+            }
+            (false, None) => self.saturated_since = Some(now),
+            _ => {}
+        }
+    }
+}
+
+impl<L, T> Limiter for WithStats<L, T>
+where
+    L: Limiter,
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let result = self.inner.try_consume(tokens);
+        self.stats.record(tokens, &result);
+        self.record_saturation(result.is_ok());
+        result
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        let admitted = self.inner.try_consume_batch(costs);
+        for &tokens in &costs[..admitted] {
+            self.stats.record(tokens, &Ok(()));
+        }
+        if admitted < costs.len() {
+            self.stats.rejected = self.stats.rejected.saturating_add(1);
+        }
+        if !costs.is_empty() {
+            self.record_saturation(admitted == costs.len());
+        }
+        admitted
+    }
+
+    fn kind(&self) -> LimiterKind {
+        self.inner.kind()
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.inner.name()
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        self.inner.self_check()
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.inner.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        self.inner.idle_for()
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+        self.stats = Stats::default();
+        self.saturated_since = None;
+        self.time_saturated = Duration::ZERO;
+    }
+}
+
+impl<L, T> Reset for WithStats<L, T>
+where
+    L: Reset,
+    T: Clock,
+{
+}
+
+impl<L, T> RetryHint for WithStats<L, T>
+where
+    L: RetryHint,
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        self.inner.time_until_available(tokens)
+    }
+}
+
+impl<L, T> Inspect for WithStats<L, T>
+where
+    L: Inspect,
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        self.inner.tokens_available()
+    }
+}
+
+/// Counterfactual tuning advisor wrapper for any [`Limiter`]
+///
+/// Enforces the wrapped limiter as usual, while feeding every consume also
+/// to `N` candidate limiters that are evaluated in parallel but never
+/// enforced. Comparing the collected [`Stats`] shows what acceptance rate
+/// each candidate configuration would have produced for the observed
+/// traffic.
+///
+/// # Generic arguments
+/// * `L` - enforced limiter
+/// * `C` - candidate limiter
+/// * `N` - number of candidates
+#[derive(Debug, Clone)]
+pub struct TuningAdvisor<L, C, const N: usize>
+where
+    L: Limiter,
+    C: Limiter,
+{
+    inner: L,
+    stats: Stats,
+    candidates: [C; N],
+    candidate_stats: [Stats; N],
+}
+
+impl<L, C, const N: usize> TuningAdvisor<L, C, N>
+where
+    L: Limiter,
+    C: Limiter,
+{
+    /// Wrap a limiter with a set of candidate limiters
+    ///
+    /// # Arguments
+    /// * `inner` - limiter to enforce
+    /// * `candidates` - alternative limiters to evaluate, typically the same
+    ///   algorithm with different capacities
+    pub fn new(inner: L, candidates: [C; N]) -> Self {
+        Self {
+            inner,
+            stats: Stats::default(),
+            candidates,
+            candidate_stats: [Stats::default(); N],
+        }
+    }
+
+    /// Statistics of the enforced limiter
+    pub fn stats(&self) -> &Stats {
+        &self.stats
+    }
+
+    /// Statistics each candidate would have produced, in candidate order
+    pub fn candidate_stats(&self) -> &[Stats; N] {
+        &self.candidate_stats
+    }
+
+    /// Get a reference to the candidate limiters
+    pub fn candidates(&self) -> &[C; N] {
+        &self.candidates
+    }
+
+    /// Zero all collected statistics
+    pub fn reset_stats(&mut self) {
+        self.stats = Stats::default();
+        self.candidate_stats = [Stats::default(); N];
+    }
+
+    /// Get a reference to the wrapped limiter
+    pub fn inner(&self) -> &L {
+        &self.inner
+    }
+
+    /// Get a mutable reference to the wrapped limiter
+    pub fn inner_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+
+    /// Unwrap the wrapped limiter
+    pub fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+impl<L, C, const N: usize> Limiter for TuningAdvisor<L, C, N>
+where
+    L: Limiter,
+    C: Limiter,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        for (candidate, stats) in self
+            .candidates
+            .iter_mut()
+            .zip(self.candidate_stats.iter_mut())
+        {
+            stats.record(tokens, &candidate.try_consume(tokens));
+        }
+
+        let result = self.inner.try_consume(tokens);
+        self.stats.record(tokens, &result);
+        result
+    }
+
+    fn kind(&self) -> LimiterKind {
+        self.inner.kind()
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.inner.name()
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        self.inner.self_check()
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.inner.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        self.inner.idle_for()
+    }
+
+    fn reset(&mut self) {
+        self.inner.reset();
+        self.stats = Stats::default();
+        for (candidate, stats) in self
+            .candidates
+            .iter_mut()
+            .zip(self.candidate_stats.iter_mut())
+        {
+            candidate.reset();
+            *stats = Stats::default();
+        }
+    }
+}
+
+impl<L, C, const N: usize> Reset for TuningAdvisor<L, C, N>
+where
+    L: Reset,
+    C: Reset,
+{
+}
+
+impl<L, C, const N: usize> RetryHint for TuningAdvisor<L, C, N>
+where
+    L: RetryHint,
+    C: Limiter,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        self.inner.time_until_available(tokens)
+    }
+}
+
+impl<L, C, const N: usize> Inspect for TuningAdvisor<L, C, N>
+where
+    L: Inspect,
+    C: Limiter,
+{
+    fn tokens_available(&self) -> u64 {
+        self.inner.tokens_available()
+    }
+}
+
+#[cfg(all(test, feature = "fixed-window"))]
+mod tests {
+    use core::{cell::RefCell, time::Duration};
+
+    use crate::{mock_assets::MockClock, FixedWindow, Limiter, LimiterInfo, LimiterKind};
+
+    use super::{Stats, TuningAdvisor, WithReporting, WithStats};
+
+    #[test]
+    fn verify_stats() {
+        let clock = MockClock::new();
+        let w = FixedWindow::new_with_time_provider(3, 10, || clock.step(0));
+        let mut s = WithStats::new_with_time_provider(w, || clock.step(0));
+
+        assert!(s.try_consume(2).is_ok());
+        clock.step(2_000);
+        assert!(s.try_consume(2).is_err());
+        clock.step(3_000);
+        assert!(s.try_consume(2).is_err());
+        assert_eq!(s.time_saturated(), Duration::from_millis(3));
+        // Replenished at 10ms
+        clock.step(5_000);
+        assert!(s.try_consume_one().is_ok());
+        clock.step(5_000);
+
+        assert_eq!(s.time_saturated(), Duration::from_millis(8));
+        assert_eq!(
+            *s.stats(),
+            Stats {
+                accepted: 2,
+                rejected: 2,
+                tokens_granted: 3
+            }
+        );
+        s.reset_stats();
+        assert_eq!(*s.stats(), Stats::default());
+        assert_eq!(s.time_saturated(), Duration::ZERO);
+    }
+
+    #[test]
+    fn verify_reporting() {
+        let clock = MockClock::new();
+        let frozen_clock = MockClock::new();
+        let reports = RefCell::new([Stats::default(); 2]);
+        let report_count = RefCell::new(0);
+
+        // Each consume steps the clock 1ms forward, reporting interval is 3ms.
+        // The intervals follow the clock of the wrapped limiter, not the frozen one.
+        let w = FixedWindow::new_with_time_provider(3, 1000, || clock.step(1000)).with_name("api");
+        let mut r = WithReporting::new_with_time_provider(
+            w,
+            Duration::from_millis(3),
+            |info: LimiterInfo, s: &Stats| {
+                assert_eq!(
+                    info,
+                    LimiterInfo {
+                        kind: LimiterKind::FixedWindow,
+                        name: Some("api")
+                    }
+                );
+                reports.borrow_mut()[*report_count.borrow()] = *s;
+                *report_count.borrow_mut() += 1;
+            },
+            || frozen_clock.step(0),
+        );
+
+        // T = 1ms, 2ms
+        assert!(r.try_consume(2).is_ok());
+        assert!(r.try_consume(2).is_err());
+        assert_eq!(*report_count.borrow(), 0);
+        // T = 3ms, first interval is reported
+        assert!(r.try_consume(1).is_ok());
+        assert_eq!(*report_count.borrow(), 1);
+        // T = 4ms
+        assert!(r.try_consume(1).is_err());
+        clock.step(10_000);
+        // T = 15ms, second interval is reported, empty intervals are not
+        assert!(r.try_consume_one().is_err());
+        assert_eq!(*report_count.borrow(), 2);
+
+        let reports = reports.borrow();
+        assert_eq!(
+            reports[0],
+            Stats {
+                accepted: 1,
+                rejected: 1,
+                tokens_granted: 2
+            }
+        );
+        assert_eq!(
+            reports[1],
+            Stats {
+                accepted: 1,
+                rejected: 1,
+                tokens_granted: 1
+            }
+        );
+        assert_eq!(r.current_stats().rejected, 1);
+    }
+
+    #[test]
+    fn verify_tuning_advisor() {
+        let clock = MockClock::new();
+        let time_provider = || clock.step(0);
+        let w = FixedWindow::new_with_time_provider(5, 1000, time_provider);
+        let candidates = [
+            FixedWindow::new_with_time_provider(2, 1000, time_provider),
+            FixedWindow::new_with_time_provider(8, 1000, time_provider),
+        ];
+        let mut a = TuningAdvisor::new(w, candidates);
+
+        let admitted = (0..10).filter(|_| a.try_consume_one().is_ok()).count();
+        assert_eq!(admitted, 5);
+        assert_eq!(a.stats().accepted, 5);
+        assert_eq!(a.candidate_stats()[0].accepted, 2);
+        assert_eq!(a.candidate_stats()[1].accepted, 8);
+        assert_eq!(a.candidate_stats()[1].rejected, 2);
+
+        a.reset_stats();
+        assert_eq!(a.candidate_stats()[0], Stats::default());
+    }
+}
@@ -0,0 +1,192 @@
+//! Stream throttling with pluggable sleep functions
+
+use core::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+    time::Duration,
+};
+
+use futures_core::Stream;
+use pin_project_lite::pin_project;
+
+use crate::{RateLimited, RetryHint};
+
+/// Throttling adapter for any [`Stream`]
+///
+/// Like [`crate::AsyncLimiter::acquire`], the waiting is delegated to the
+/// async sleep function of the used runtime, so no particular executor is
+/// required.
+pub trait StreamRateLimitExt: Stream + Sized {
+    /// Yield items only as fast as a limiter permits
+    ///
+    /// Each item consumes a single token. An item pulled from the stream is
+    /// held back until its token is available. The stream ends early if the
+    /// limiter can never admit a single token, see [`RetryHint::time_until_available`].
+    ///
+    /// # Arguments
+    /// * `limiter` - limiter pacing the items
+    /// * `sleep` - async sleep function of the used runtime
+    fn rate_limit<L, S, F>(self, limiter: L, sleep: S) -> RateLimitedStream<Self, L, S, F>
+    where
+        L: RetryHint,
+        S: FnMut(Duration) -> F,
+        F: Future<Output = ()>,
+    {
+        RateLimitedStream {
+            stream: self,
+            limiter,
+            sleep,
+            sleeping: None,
+            pending: None,
+        }
+    }
+}
+
+impl<St> StreamRateLimitExt for St where St: Stream {}
+
+pin_project! {
+    /// Stream returned by [`StreamRateLimitExt::rate_limit`]
+    ///
+    /// # Generic arguments
+    /// * `St` - throttled stream
+    /// * `L` - limiter pacing the items
+    /// * `S` - async sleep function
+    pub struct RateLimitedStream<St, L, S, F>
+    where
+        St: Stream,
+    {
+        #[pin]
+        stream: St,
+        limiter: L,
+        sleep: S,
+        #[pin]
+        sleeping: Option<F>,
+        pending: Option<St::Item>,
+    }
+}
+
+impl<St, L, S, F> RateLimitedStream<St, L, S, F>
+where
+    St: Stream,
+{
+    /// Get a reference to the limiter
+    pub fn limiter(&self) -> &L {
+        &self.limiter
+    }
+
+    /// Get a mutable reference to the limiter
+    pub fn limiter_mut(&mut self) -> &mut L {
+        &mut self.limiter
+    }
+}
+
+impl<St, L, S, F> Stream for RateLimitedStream<St, L, S, F>
+where
+    St: Stream,
+    L: RetryHint,
+    S: FnMut(Duration) -> F,
+    F: Future<Output = ()>,
+{
+    type Item = St::Item;
+
+    fn poll_next(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Option<Self::Item>> {
+        let mut this = self.project();
+        loop {
+            if let Some(sleeping) = this.sleeping.as_mut().as_pin_mut() {
+                if sleeping.poll(cx).is_pending() {
+                    return Poll::Pending;
+                }
+                this.sleeping.set(None);
+            }
+
+            if this.pending.is_none() {
+                match this.stream.as_mut().poll_next(cx) {
+                    Poll::Ready(Some(item)) => *this.pending = Some(item),
+                    Poll::Ready(None) => return Poll::Ready(None),
+                    Poll::Pending => return Poll::Pending,
+                }
+            }
+
+            match this.limiter.try_consume_with_retry(1) {
+                Ok(()) => return Poll::Ready(this.pending.take()),
+                Err(RateLimited { retry_after: None }) => {
+                    *this.pending = None;
+                    return Poll::Ready(None);
+                }
+                Err(RateLimited {
+                    retry_after: Some(wait),
+                }) => this.sleeping.set(Some((this.sleep)(wait))),
+            }
+        }
+    }
+
+    fn size_hint(&self) -> (usize, Option<usize>) {
+        let pending = usize::from(self.pending.is_some());
+        let (lower, upper) = self.stream.size_hint();
+        (
+            lower.saturating_add(pending),
+            upper.and_then(|upper| upper.checked_add(pending)),
+        )
+    }
+}
+
+#[cfg(all(test, feature = "token-bucket"))]
+mod tests {
+    use core::{
+        cell::Cell,
+        future::poll_fn,
+        pin::{pin, Pin},
+        task::{Context, Poll},
+        time::Duration,
+    };
+
+    use futures_core::Stream;
+
+    use crate::{
+        mock_assets::{block_on, MockClock},
+        TokenBucket,
+    };
+
+    use super::StreamRateLimitExt;
+
+    struct Counter(u32);
+
+    impl Stream for Counter {
+        type Item = u32;
+
+        fn poll_next(mut self: Pin<&mut Self>, _cx: &mut Context<'_>) -> Poll<Option<u32>> {
+            self.0 += 1;
+            Poll::Ready((self.0 <= 5).then_some(self.0))
+        }
+    }
+
+    #[test]
+    fn verify_rate_limit_stream() {
+        let clock = MockClock::new();
+        // One token per millisecond, burst of two
+        let b = TokenBucket::new_with_time_provider(1000, 2, || clock.step(0));
+        // Sleeping only advances the clock
+        let sleeps = Cell::new(0);
+        let sleep = |d: Duration| {
+            sleeps.set(sleeps.get() + 1);
+            clock.step(d.as_micros() as u64);
+            async {}
+        };
+
+        let mut stream = pin!(Counter(0).rate_limit(b, sleep));
+        let mut next = || block_on(poll_fn(|cx| stream.as_mut().poll_next(cx)));
+
+        // Burst goes through without waiting
+        assert_eq!(next(), Some(1));
+        assert_eq!(next(), Some(2));
+        assert_eq!(sleeps.get(), 0);
+        // Then paced by the refill rate
+        assert_eq!(next(), Some(3));
+        assert_eq!(sleeps.get(), 1);
+        assert_eq!(next(), Some(4));
+        assert_eq!(next(), Some(5));
+        assert_eq!(next(), None);
+        assert_eq!(sleeps.get(), 3);
+    }
+}
@@ -0,0 +1,149 @@
+//! Clock adapter for wrapping tick counters
+
+use core::{cell::Cell, time::Duration};
+
+use crate::Clock;
+
+/// Convert a tick count of a counter running at `hz` into a [`Duration`]
+pub(crate) fn ticks_to_duration(ticks: u64, hz: u32) -> Duration {
+    let hz = u64::from(hz.max(1));
+    // (ticks % hz) * 1e9 < hz * 1e9 < 2^63, no overflow
+    let nanos = (ticks % hz) * 1_000_000_000 / hz;
+    Duration::new(ticks / hz, nanos as u32)
+}
+
+/// Raw value of a free-running tick counter that wraps around to zero
+pub trait WrappingTick: Copy {
+    /// Counter value at the wraparound
+    const ZERO: Self;
+
+    /// Ticks elapsed since an `earlier` value, assuming at most one wraparound
+    fn ticks_since(self, earlier: Self) -> u64;
+}
+
+impl WrappingTick for u16 {
+    const ZERO: Self = 0;
+
+    fn ticks_since(self, earlier: Self) -> u64 {
+        u64::from(self.wrapping_sub(earlier))
+    }
+}
+
+impl WrappingTick for u32 {
+    const ZERO: Self = 0;
+
+    fn ticks_since(self, earlier: Self) -> u64 {
+        u64::from(self.wrapping_sub(earlier))
+    }
+}
+
+impl WrappingTick for u64 {
+    const ZERO: Self = 0;
+
+    fn ticks_since(self, earlier: Self) -> u64 {
+        self.wrapping_sub(earlier)
+    }
+}
+
+/// [`Clock`] adapter for a free-running tick counter that wraps around
+///
+/// Wraps e.g. a 32-bit millisecond counter, which wraps around roughly
+/// every 49 days: `WrappingTickClock::new(|| hal::millis(), 1_000)`.
+/// The wraparounds are accumulated into a 64-bit tick count on each read,
+/// so the clock must be read at least once per wrap period to stay
+/// monotonic. Timestamps count from a counter value of zero.
+///
+/// The clock is not `Sync`, share it between limiters by reference.
+///
+/// # Generic arguments
+/// * `F` - source of the raw counter value
+/// * `W` - counter type, e.g. `u32`
+#[derive(Debug)]
+pub struct WrappingTickClock<F, W>
+where
+    F: Fn() -> W,
+    W: WrappingTick,
+{
+    source: F,
+    ticks_per_second: u32,
+    last: Cell<W>,
+    ticks: Cell<u64>,
+}
+
+impl<F, W> WrappingTickClock<F, W>
+where
+    F: Fn() -> W,
+    W: WrappingTick,
+{
+    /// Create a new clock on a tick counter
+    ///
+    /// # Arguments
+    /// * `source` - function returning the raw counter value
+    /// * `ticks_per_second` - counter frequency in Hz
+    pub const fn new(source: F, ticks_per_second: u32) -> Self {
+        Self {
+            source,
+            ticks_per_second,
+            last: Cell::new(W::ZERO),
+            ticks: Cell::new(0),
+        }
+    }
+
+    /// Accumulate a raw counter value and convert the total into a timestamp
+    pub(crate) fn now_from(&self, raw: W) -> Duration {
+        let ticks = self
+            .ticks
+            .get()
+            .saturating_add(raw.ticks_since(self.last.get()));
+        self.last.set(raw);
+        self.ticks.set(ticks);
+        ticks_to_duration(ticks, self.ticks_per_second)
+    }
+}
+
+impl<F, W> Clock for WrappingTickClock<F, W>
+where
+    F: Fn() -> W,
+    W: WrappingTick,
+{
+    fn now(&self) -> Duration {
+        self.now_from((self.source)())
+    }
+}
+
+impl<F, W> Clock for &WrappingTickClock<F, W>
+where
+    F: Fn() -> W,
+    W: WrappingTick,
+{
+    fn now(&self) -> Duration {
+        (**self).now()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::{cell::Cell, time::Duration};
+
+    use crate::Clock;
+
+    use super::WrappingTickClock;
+
+    #[test]
+    fn verify_wrapping_tick_clock() {
+        let counter = Cell::new(u32::MAX - 999);
+        let clock = WrappingTickClock::new(|| counter.get(), 1_000);
+        let epoch = clock.now();
+        assert_eq!(epoch, Duration::from_millis(u32::MAX as u64 - 999));
+
+        // Wraps around, one tick per millisecond
+        counter.set(500);
+        assert_eq!(clock.now() - epoch, Duration::from_millis(1_500));
+        assert_eq!(clock.now() - epoch, Duration::from_millis(1_500));
+
+        // Sub-second ticks and 16-bit counters
+        let clock = WrappingTickClock::new(|| 0u16, 3);
+        assert_eq!(clock.now_from(u16::MAX), Duration::new(21_845, 0));
+        assert_eq!(clock.now_from(1), Duration::new(21_845, 666_666_666));
+    }
+}
@@ -0,0 +1,522 @@
+//! Multi-tier fixed window -type limiter
+
+use core::time::Duration;
+
+#[cfg(feature = "std")]
+use crate::StdClock;
+use crate::{
+    CantConsume, Clock, ConfigError, Diagnostics, FixedWindowCore, FixedWindowSnapshot,
+    FixedWindowState, Inspect, Limiter, LimiterCore, LimiterKind, LimiterResult, RemainingBudget,
+    Request, Reset, Response, RetryHint,
+};
+
+/// [`TieredLimiter`] using the system clock, as returned by [`tiered_limiter`]
+#[cfg(feature = "std")]
+pub type StdTieredLimiter<const N: usize> = TieredLimiter<StdClock, N>;
+
+/// Build a limiter enforcing several quota tiers at once
+///
+/// # Arguments
+/// * `tiers` - `(capacity, window_width_ms)` of each tier, e.g.
+///   `[(10, 1000), (100, 60_000), (2000, 3_600_000)]` for 10/s, 100/min and 2000/h
+#[cfg(feature = "std")]
+pub fn tiered_limiter<const N: usize>(tiers: [(u64, u64); N]) -> StdTieredLimiter<N> {
+    TieredLimiter::new_with_time_provider(tiers, StdClock)
+}
+
+/// Build a limiter enforcing several quota tiers at once, with the window
+/// widths given as [`Duration`]s
+///
+/// # Arguments
+/// * `tiers` - `(capacity, window_width)` of each tier
+#[cfg(feature = "std")]
+pub fn tiered_limiter_from_widths<const N: usize>(
+    tiers: [(u64, Duration); N],
+) -> StdTieredLimiter<N> {
+    TieredLimiter::from_widths_with_time_provider(tiers, StdClock)
+}
+
+/// Multi-tier quota limiter
+///
+/// Enforces `N` fixed window quotas of different widths at the same time,
+/// e.g. 10 per second *and* 100 per minute *and* 2000 per hour. A consume is
+/// admitted only if every tier holds enough tokens, and then counts against
+/// all of them. A rejected consume leaves every tier untouched.
+///
+/// The algorithm itself is implemented by [`TieredLimiterCore`], this type
+/// only pairs it with a time provider.
+///
+/// # Generic arguments
+/// * `T` - time provider
+/// * `N` - number of tiers
+#[derive(Debug, Clone)]
+pub struct TieredLimiter<T, const N: usize>
+where
+    T: Clock,
+{
+    core: TieredLimiterCore<N>,
+    time_provider: T,
+}
+
+impl<T, const N: usize> TieredLimiter<T, N>
+where
+    T: Clock,
+{
+    /// Initialize a new multi-tier limiter utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `tiers` - `(capacity, window_width_ms)` of each tier
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`tiered_limiter`]
+    ///
+    /// # Panics
+    /// Consumes panic on a division by zero if any window width is zero,
+    /// use [`Self::try_new_with_time_provider`] to validate the configuration.
+    pub fn new_with_time_provider(tiers: [(u64, u64); N], time_provider: T) -> Self {
+        let tiers = tiers.map(|(capacity, width_ms)| (capacity, Duration::from_millis(width_ms)));
+        Self::from_widths_with_time_provider(tiers, time_provider)
+    }
+
+    /// Initialize a new multi-tier limiter with the window widths given as
+    /// [`Duration`]s utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `tiers` - `(capacity, window_width)` of each tier
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use
+    /// [`tiered_limiter_from_widths`]
+    ///
+    /// # Panics
+    /// Consumes panic on a division by zero if any window width is zero
+    pub fn from_widths_with_time_provider(tiers: [(u64, Duration); N], time_provider: T) -> Self {
+        let time_now = time_provider.now();
+        Self {
+            core: TieredLimiterCore::from_widths(tiers, time_now),
+            time_provider,
+        }
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting invalid configurations
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroLength`] if `N` is zero, [`ConfigError::ZeroWindow`] or
+    /// [`ConfigError::ZeroCapacity`] if any tier has a zero argument
+    pub fn try_new_with_time_provider(
+        tiers: [(u64, u64); N],
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        let time_now = time_provider.now();
+        Ok(Self {
+            core: TieredLimiterCore::try_new(tiers, time_now)?,
+            time_provider,
+        })
+    }
+
+    /// Assign a name to this limiter, see [`crate::TokenBucket::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.core = self.core.with_name(name);
+        self
+    }
+
+    /// Current state of each tier, in the order given to the constructor,
+    /// e.g. for dashboards and debug logs
+    pub fn state(&self) -> [FixedWindowState; N] {
+        let now = self.time_provider.now();
+        self.core.state_at(now)
+    }
+
+    /// Time left until the current window of each tier ends and its budget
+    /// is replenished, in the order given to the constructor
+    pub fn time_until_reset(&self) -> [Duration; N] {
+        let now = self.time_provider.now();
+        self.core.time_until_reset_at(now)
+    }
+
+    /// How far through the current window of each tier we are, in the order
+    /// given to the constructor
+    pub fn window_elapsed(&self) -> [Duration; N] {
+        let now = self.time_provider.now();
+        self.core.window_elapsed_at(now)
+    }
+
+    /// Get a reference to the underlying sans-IO state machine
+    pub fn core(&self) -> &TieredLimiterCore<N> {
+        &self.core
+    }
+
+    /// Unwrap the underlying sans-IO state machine, dropping the time provider
+    pub fn into_core(self) -> TieredLimiterCore<N> {
+        self.core
+    }
+
+    /// Take a plain-data snapshot of each tier, in the order given to the
+    /// constructor, see [`crate::TokenBucket::snapshot`]
+    pub fn snapshot(&self) -> [FixedWindowSnapshot; N] {
+        self.core.snapshot()
+    }
+
+    /// Resume a limiter from a snapshot taken with [`Self::snapshot`]
+    ///
+    /// The time provider must share the epoch of the one the snapshot was taken with.
+    /// The name is not part of the snapshot, see [`Self::with_name`].
+    pub fn restore(snapshot: [FixedWindowSnapshot; N], time_provider: T) -> Self {
+        Self {
+            core: TieredLimiterCore::restore(snapshot),
+            time_provider,
+        }
+    }
+}
+
+impl<T, const N: usize> Limiter for TieredLimiter<T, N>
+where
+    T: Clock,
+{
+    fn try_consume(&mut self, tokens: u64) -> LimiterResult {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens).map(|_| ())
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::Tiered
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.core.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.time_provider.now();
+        self.core.self_check_at(now)
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.core.tiers.first()?.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.time_provider.now();
+        Some(self.core.tiers.first()?.idle_for_at(now))
+    }
+
+    fn reset(&mut self) {
+        let now = self.time_provider.now();
+        self.core.reset_at(now);
+    }
+}
+
+impl<T, const N: usize> RemainingBudget for TieredLimiter<T, N>
+where
+    T: Clock,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens)
+    }
+}
+
+impl<T, const N: usize> Reset for TieredLimiter<T, N> where T: Clock {}
+
+impl<T, const N: usize> RetryHint for TieredLimiter<T, N>
+where
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.time_provider.now();
+        self.core.time_until_available_at(now, tokens)
+    }
+}
+
+impl<T, const N: usize> Inspect for TieredLimiter<T, N>
+where
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.tokens_available_at(now)
+    }
+}
+
+/// Sans-IO multi-tier limiter state machine
+///
+/// Same algorithm as [`TieredLimiter`], but without a time provider: the
+/// current time is passed in with every [`LimiterCore::handle`] call.
+#[derive(Debug, Clone, Copy)]
+pub struct TieredLimiterCore<const N: usize> {
+    tiers: [FixedWindowCore; N],
+    name: Option<&'static str>,
+}
+
+impl<const N: usize> TieredLimiterCore<N> {
+    /// Initialize a new multi-tier state machine
+    ///
+    /// # Arguments
+    /// * `tiers` - `(capacity, window_width_ms)` of each tier
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    ///
+    /// # Panics
+    /// Consumes panic on a division by zero if any window width is zero,
+    /// use [`Self::try_new`] to validate the configuration.
+    pub fn new(tiers: [(u64, u64); N], now: Duration) -> Self {
+        let tiers = tiers.map(|(capacity, width_ms)| (capacity, Duration::from_millis(width_ms)));
+        Self::from_widths(tiers, now)
+    }
+
+    /// Initialize a new multi-tier state machine with the window widths given as [`Duration`]s
+    ///
+    /// # Arguments
+    /// * `tiers` - `(capacity, window_width)` of each tier
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    ///
+    /// # Panics
+    /// Consumes panic on a division by zero if any window width is zero
+    pub fn from_widths(tiers: [(u64, Duration); N], now: Duration) -> Self {
+        Self {
+            tiers: tiers.map(|(capacity, width)| FixedWindowCore::from_width(capacity, width, now)),
+            name: None,
+        }
+    }
+
+    /// Validating constructor, see [`TieredLimiter::try_new_with_time_provider`]
+    pub fn try_new(tiers: [(u64, u64); N], now: Duration) -> Result<Self, ConfigError> {
+        if N == 0 {
+            return Err(ConfigError::ZeroLength);
+        }
+        for (capacity, width_ms) in tiers {
+            FixedWindowCore::try_new(capacity, width_ms, now)?;
+        }
+        Ok(Self::new(tiers, now))
+    }
+
+    /// Return to the freshly constructed state at the given time
+    ///
+    /// The configuration, including the name, is kept.
+    pub fn reset_at(&mut self, now: Duration) {
+        for tier in &mut self.tiers {
+            tier.reset_at(now);
+        }
+    }
+
+    /// Take a plain-data snapshot, see [`TieredLimiter::snapshot`]
+    pub fn snapshot(&self) -> [FixedWindowSnapshot; N] {
+        self.tiers.map(|tier| tier.snapshot())
+    }
+
+    /// Resume from a snapshot, see [`TieredLimiter::restore`]
+    pub fn restore(snapshot: [FixedWindowSnapshot; N]) -> Self {
+        Self {
+            tiers: snapshot.map(FixedWindowCore::restore),
+            name: None,
+        }
+    }
+
+    /// Assign a name to this limiter, see [`TieredLimiter::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.name = Some(name);
+        self
+    }
+
+    /// State of each tier, in the order given to the constructor
+    pub fn tiers(&self) -> &[FixedWindowCore; N] {
+        &self.tiers
+    }
+
+    /// Tokens that could be consumed at `now`, see [`Inspect::tokens_available`]
+    pub fn tokens_available_at(&self, now: Duration) -> u64 {
+        self.tiers
+            .iter()
+            .map(|tier| tier.tokens_available_at(now))
+            .min()
+            .unwrap_or(u64::MAX)
+    }
+
+    /// Time until `tokens` could be consumed at `now`, see [`RetryHint::time_until_available`]
+    ///
+    /// Once the longest wait is over, the tiers with a shorter wait have
+    /// been replenished as well.
+    pub fn time_until_available_at(&self, now: Duration, tokens: u64) -> Option<Duration> {
+        self.tiers.iter().try_fold(Duration::ZERO, |wait, tier| {
+            Some(wait.max(tier.time_until_available_at(now, tokens)?))
+        })
+    }
+
+    /// State of each tier at `now`, see [`TieredLimiter::state`]
+    pub fn state_at(&self, now: Duration) -> [FixedWindowState; N] {
+        core::array::from_fn(|i| self.tiers[i].state_at(now))
+    }
+
+    /// Time left at `now` until the window of each tier ends, see
+    /// [`TieredLimiter::time_until_reset`]
+    pub fn time_until_reset_at(&self, now: Duration) -> [Duration; N] {
+        core::array::from_fn(|i| self.tiers[i].time_until_reset_at(now))
+    }
+
+    /// How far through the window of each tier containing `now` we are, see
+    /// [`TieredLimiter::window_elapsed`]
+    pub fn window_elapsed_at(&self, now: Duration) -> [Duration; N] {
+        core::array::from_fn(|i| self.tiers[i].window_elapsed_at(now))
+    }
+
+    /// Run the self-check of [`Limiter::self_check`] at the given time
+    pub fn self_check_at(&self, now: Duration) -> Diagnostics {
+        self.tiers
+            .iter()
+            .fold(Diagnostics::HEALTHY, |diagnostics, tier| {
+                let tier = tier.self_check_at(now);
+                Diagnostics {
+                    clock_monotonic: diagnostics.clock_monotonic && tier.clock_monotonic,
+                    clock_progressing: diagnostics.clock_progressing && tier.clock_progressing,
+                    config_valid: diagnostics.config_valid && tier.config_valid,
+                    saturated: diagnostics.saturated || tier.saturated,
+                }
+            })
+    }
+
+    /// Consume at the given time, returning the remaining budget
+    fn try_consume_at(&mut self, now: Duration, tokens: u64) -> Result<u64, CantConsume> {
+        let admitted = self.tokens_available_at(now) >= tokens;
+        // Consuming nothing still records the attempt in every tier
+        let consumed = if admitted { tokens } else { 0 };
+        let mut remaining = u64::MAX;
+        for tier in &mut self.tiers {
+            remaining = remaining.min(tier.try_consume_at(now, consumed)?);
+        }
+        if admitted {
+            Ok(remaining)
+        } else {
+            Err(CantConsume)
+        }
+    }
+}
+
+impl<const N: usize> LimiterCore for TieredLimiterCore<N> {
+    fn handle(&mut self, now: Duration, request: Request) -> Response {
+        match request {
+            Request::Consume(tokens) => self.try_consume_at(now, tokens).into(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{
+        mock_assets::MockClock, ConfigError, Inspect, Limiter, LimiterCore, Request, Response,
+        RetryHint,
+    };
+
+    use super::{TieredLimiter, TieredLimiterCore};
+
+    #[test]
+    fn verify_state() {
+        let clock = MockClock::new();
+        let mut t = TieredLimiter::new_with_time_provider([(2, 10), (5, 100)], || clock.step(0));
+        assert!(t.try_consume(2).is_ok());
+
+        clock.step(10_000);
+        // T = 10ms, only the short tier has been replenished
+        let [short, long] = t.state();
+        assert_eq!(short.tokens, 2);
+        assert_eq!(short.window_start, Duration::from_millis(10));
+        assert_eq!(long.tokens, 3);
+        assert_eq!(long.window_start, Duration::ZERO);
+    }
+
+    #[test]
+    fn verify_reset_timing() {
+        let clock = MockClock::new();
+        let t = TieredLimiter::new_with_time_provider([(2, 10), (5, 100)], || clock.step(0));
+
+        clock.step(23_500);
+        assert_eq!(
+            t.window_elapsed(),
+            [Duration::from_micros(3_500), Duration::from_micros(23_500)]
+        );
+        assert_eq!(
+            t.time_until_reset(),
+            [Duration::from_micros(6_500), Duration::from_micros(76_500)]
+        );
+    }
+
+    #[test]
+    fn verify_snapshot_restore() {
+        let clock = MockClock::new();
+        let mut t = TieredLimiter::new_with_time_provider([(2, 10), (5, 100)], || clock.step(0));
+        assert!(t.try_consume(2).is_ok());
+        let [short, long] = t.snapshot();
+        assert_eq!(short.tokens, 0);
+        assert_eq!(long.tokens, 3);
+        assert_eq!(long.window_width, Duration::from_millis(100));
+
+        let mut t = TieredLimiter::restore([short, long], || clock.step(0));
+        clock.step(10_000);
+        assert!(t.try_consume(2).is_ok());
+        clock.step(10_000);
+        // T = 20ms, the long tier still remembers the consume before the restore
+        assert!(t.try_consume(2).is_err());
+        assert!(t.try_consume(1).is_ok());
+    }
+
+    #[test]
+    fn verify_try_new() {
+        let clock = MockClock::new();
+        assert_eq!(
+            TieredLimiter::try_new_with_time_provider([(2, 10), (3, 0)], || clock.step(0)).err(),
+            Some(ConfigError::ZeroWindow)
+        );
+        assert_eq!(
+            TieredLimiterCore::try_new([(0, 10)], Duration::ZERO).err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+        assert_eq!(
+            TieredLimiterCore::<0>::try_new([], Duration::ZERO).err(),
+            Some(ConfigError::ZeroLength)
+        );
+        let mut l =
+            TieredLimiter::try_new_with_time_provider([(2, 10), (3, 100)], || clock.step(0))
+                .unwrap();
+        assert!(l.try_consume(2).is_ok());
+    }
+
+    #[test]
+    fn verify_tiers() {
+        let clock = MockClock::new();
+        // 2 per 10ms and 3 per 100ms
+        let mut l = TieredLimiter::new_with_time_provider([(2, 10), (3, 100)], || clock.step(0));
+
+        assert!(l.try_consume(2).is_ok());
+        assert!(l.try_consume_one().is_err());
+        assert_eq!(l.time_until_available(1), Some(Duration::from_millis(10)));
+
+        // Short tier replenished, long tier has only one left
+        clock.step(10_000);
+        assert!(l.try_consume(2).is_err());
+        // Rejection didn't take from the short tier
+        assert_eq!(l.core().tiers()[0].tokens_available_at(clock.step(0)), 2);
+        assert!(l.try_consume_one().is_ok());
+        assert_eq!(l.time_until_available(1), Some(Duration::from_millis(90)));
+
+        // More than the smallest capacity is never available
+        assert_eq!(l.time_until_available(3), None);
+
+        l.reset();
+        assert_eq!(l.tokens_available(), 2);
+    }
+
+    #[test]
+    fn verify_tiers_core() {
+        let mut core = TieredLimiterCore::new([(2, 10), (3, 100)], Duration::ZERO);
+        assert_eq!(
+            core.handle(Duration::ZERO, Request::Consume(1)),
+            Response::Admitted { remaining: 1 }
+        );
+        assert_eq!(
+            core.handle(Duration::ZERO, Request::Consume(2)),
+            Response::Rejected
+        );
+    }
+}
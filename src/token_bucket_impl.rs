@@ -3,8 +3,18 @@
 use core::time::Duration;
 
 #[cfg(feature = "std")]
-use crate::macros::std_time_provider;
-use crate::{CantConsume, Limiter, LimiterResult};
+use crate::StdClock;
+use crate::{
+    diagnostics_impl::ClockMonitor, AdjustableCapacity, CantConsume, Clock, ConfigError,
+    Diagnostics, Inspect, Limiter, LimiterCore, LimiterKind, LimiterResult, Refund,
+    RemainingBudget, Request, Reset, Response, RetryHint,
+};
+
+const NANOS_PER_SEC: u128 = 1_000_000_000;
+
+/// [`TokenBucket`] using the system clock, as returned by [`token_bucket`]
+#[cfg(feature = "std")]
+pub type StdTokenBucket = TokenBucket<StdClock>;
 
 /// Build a token bucket limiter
 ///
@@ -12,8 +22,37 @@ use crate::{CantConsume, Limiter, LimiterResult};
 /// * `rate_per_sec` - how many consumes should be allowed per second on average
 /// * `capacity` - bucket capacity to dictate the burstiness of this limiter
 #[cfg(feature = "std")]
-pub fn token_bucket(rate_per_s: u64, capacity: u64) -> TokenBucket<impl Fn() -> Duration> {
-    TokenBucket::new_with_time_provider(rate_per_s, capacity, std_time_provider!())
+pub fn token_bucket(rate_per_s: u64, capacity: u64) -> StdTokenBucket {
+    TokenBucket::new_with_time_provider(rate_per_s, capacity, StdClock)
+}
+
+/// Build a token bucket limiter refilled with `tokens` every `per`
+///
+/// Expresses slow or fractional rates, e.g. `(1, Duration::from_secs(10))`
+/// for one token every ten seconds or `(5, Duration::from_secs(2))` for 2.5
+/// tokens per second.
+///
+/// # Arguments
+/// * `tokens` - how many consumes should be allowed per period on average
+/// * `per` - refill period
+/// * `capacity` - bucket capacity to dictate the burstiness of this limiter
+#[cfg(feature = "std")]
+pub fn token_bucket_from_rate(tokens: u64, per: Duration, capacity: u64) -> StdTokenBucket {
+    TokenBucket::from_rate_with_time_provider(tokens, per, capacity, StdClock)
+}
+
+#[cfg(feature = "std")]
+impl TokenBucket<StdClock> {
+    /// Build a token bucket limiter from a [`TokenBucketConfig`]
+    ///
+    /// # Arguments
+    /// * `config` - limiter parameters, e.g. deserialized from a configuration file
+    ///
+    /// # Returns
+    /// [`ConfigError`] for parameters rejected by [`Self::try_new_with_time_provider`]
+    pub fn from_config(config: TokenBucketConfig) -> Result<Self, ConfigError> {
+        Self::from_config_with_time_provider(config, StdClock)
+    }
 }
 
 /// Token bucket -type rate limiter
@@ -26,93 +65,867 @@ pub fn token_bucket(rate_per_s: u64, capacity: u64) -> TokenBucket<impl Fn() ->
 /// allowed since as long as the bucket holds tokens those can
 /// be consumed at an unlimited rate. Ultimately the bucket size
 /// is what defined the burstiness.
+///
+/// The algorithm itself is implemented by [`TokenBucketCore`], this type
+/// only pairs it with a time provider.
+#[derive(Debug, Clone)]
 pub struct TokenBucket<T>
 where
-    T: Fn() -> Duration,
+    T: Clock,
 {
-    config: TokenBucketConfig<T>,
-    tokens: u64,
-    last_update_t: Duration,
+    core: TokenBucketCore,
+    time_provider: T,
 }
 
 impl<T> TokenBucket<T>
 where
-    T: Fn() -> Duration,
+    T: Clock,
 {
     /// Initialize a new token bucket utilizing the given timer
     ///
     /// # Arguments
     /// * `rate_per_sec` - how many consumes should be allowed per second on average
     /// * `capacity` - bucket capacity to dictate the burstiness of this limiter
-    /// * `time_provider_t` - closure that returns a monotonically nondecreasing
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
     ///   timestamp as [`Duration`] from some fixed epoch in the past
     ///
     /// If you are developing for a `std` target, you probably wish to use [`token_bucket`]
     pub fn new_with_time_provider(rate_per_s: u64, capacity: u64, time_provider: T) -> Self {
-        let time_now = time_provider();
-        let config = TokenBucketConfig::new(capacity, rate_per_s, time_provider);
+        Self::from_rate_with_time_provider(
+            rate_per_s,
+            Duration::from_secs(1),
+            capacity,
+            time_provider,
+        )
+    }
+
+    /// Initialize a new token bucket refilled with `tokens` every `per` utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `tokens` - how many consumes should be allowed per period on average
+    /// * `per` - refill period
+    /// * `capacity` - bucket capacity to dictate the burstiness of this limiter
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`token_bucket_from_rate`]
+    pub fn from_rate_with_time_provider(
+        tokens: u64,
+        per: Duration,
+        capacity: u64,
+        time_provider: T,
+    ) -> Self {
+        let time_now = time_provider.now();
         Self {
-            config,
-            tokens: capacity,
-            last_update_t: time_now,
+            core: TokenBucketCore::from_rate(tokens, per, capacity, time_now),
+            time_provider,
+        }
+    }
+
+    /// Like [`Self::new_with_time_provider`], but rejecting configurations
+    /// that would never admit anything or never refill
+    ///
+    /// # Returns
+    /// [`ConfigError::ZeroRate`] or [`ConfigError::ZeroCapacity`] for zero arguments
+    pub fn try_new_with_time_provider(
+        rate_per_s: u64,
+        capacity: u64,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        let time_now = time_provider.now();
+        Ok(Self {
+            core: TokenBucketCore::try_new(rate_per_s, capacity, time_now)?,
+            time_provider,
+        })
+    }
+
+    /// Initialize a new token bucket from a [`TokenBucketConfig`] utilizing the given timer
+    ///
+    /// # Arguments
+    /// * `config` - limiter parameters, e.g. deserialized from a configuration file
+    /// * `time_provider_t` - [`Clock`] or closure returning a monotonically nondecreasing
+    ///   timestamp as [`Duration`] from some fixed epoch in the past
+    ///
+    /// # Returns
+    /// [`ConfigError`] for parameters rejected by [`Self::try_new_with_time_provider`]
+    ///
+    /// If you are developing for a `std` target, you probably wish to use [`TokenBucket::from_config`]
+    pub fn from_config_with_time_provider(
+        config: TokenBucketConfig,
+        time_provider: T,
+    ) -> Result<Self, ConfigError> {
+        Self::try_new_with_time_provider(config.rate_per_s, config.capacity, time_provider)
+    }
+
+    /// Assign a name to this limiter
+    ///
+    /// The name is reported by [`Limiter::name`] so that decisions of many
+    /// limiters feeding a single metrics pipeline can be told apart.
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.core = self.core.with_name(name);
+        self
+    }
+
+    /// Start with the given amount of tokens instead of a full bucket
+    ///
+    /// Allows a restarted service to seed the bucket from persisted state
+    /// instead of granting an immediate full burst. The amount is capped to
+    /// the bucket capacity.
+    pub fn with_initial_tokens(mut self, tokens: u64) -> Self {
+        self.core = self.core.with_initial_tokens(tokens);
+        self
+    }
+
+    /// Ramp the capacity up linearly from zero over `warm_up`
+    ///
+    /// Limits the burst a freshly started or [`Reset`] bucket can grant, so
+    /// that a fleet restarting at once doesn't defeat the limit. Refilling
+    /// runs at the configured rate throughout, only the tokens held are capped.
+    pub fn with_warm_up(mut self, warm_up: Duration) -> Self {
+        self.core = self.core.with_warm_up(warm_up);
+        self
+    }
+
+    /// Admit consumes exceeding the tokens held by borrowing up to `max_debt` tokens
+    ///
+    /// An oversized consume, e.g. an occasional large message, then succeeds
+    /// right away instead of being split. The balance goes negative, and
+    /// following consumes are rejected until refills have repaid the debt.
+    /// Only one consume can borrow at a time.
+    pub fn with_max_debt(mut self, max_debt: u64) -> Self {
+        self.core = self.core.with_max_debt(max_debt);
+        self
+    }
+
+    /// Tokens borrowed and not repaid yet, see [`Self::with_max_debt`]
+    pub fn debt(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.debt_at(now)
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.core.capacity()
+    }
+
+    /// Configured average rate in tokens per second, rounded down
+    pub fn rate_per_s(&self) -> u64 {
+        self.core.rate_per_s()
+    }
+
+    /// Configured refill rate as tokens per period
+    pub fn rate(&self) -> (u64, Duration) {
+        self.core.rate()
+    }
+
+    /// Current state of the bucket, e.g. for dashboards and debug logs
+    pub fn state(&self) -> TokenBucketState {
+        let now = self.time_provider.now();
+        self.core.state_at(now)
+    }
+
+    /// Get a reference to the underlying sans-IO state machine
+    pub fn core(&self) -> &TokenBucketCore {
+        &self.core
+    }
+
+    /// Unwrap the underlying sans-IO state machine, dropping the time provider
+    pub fn into_core(self) -> TokenBucketCore {
+        self.core
+    }
+
+    /// Take a plain-data snapshot of the limiter state
+    ///
+    /// The snapshot can be stashed e.g. in battery-backed RAM and later passed
+    /// to [`Self::restore`], without requiring `serde`.
+    pub fn snapshot(&self) -> TokenBucketSnapshot {
+        self.core.snapshot()
+    }
+
+    /// Resume a limiter from a snapshot taken with [`Self::snapshot`]
+    ///
+    /// The time provider must share the epoch of the one the snapshot was
+    /// taken with, e.g. a real-time clock kept running during deep sleep.
+    /// Time elapsed in between is accounted for like it had been running.
+    /// The name is not part of the snapshot, see [`Self::with_name`].
+    pub fn restore(snapshot: TokenBucketSnapshot, time_provider: T) -> Self {
+        Self {
+            core: TokenBucketCore::restore(snapshot),
+            time_provider,
         }
     }
 }
 
 impl<T> Limiter for TokenBucket<T>
 where
-    T: Fn() -> Duration,
+    T: Clock,
 {
     fn try_consume(&mut self, tokens: u64) -> LimiterResult {
-        // First, get elapsed time since last call
-        let now = (self.config.time_provider)();
-        let delta_t = now.saturating_sub(self.last_update_t);
-        let tokens_to_add = (delta_t.as_secs_f64() * self.config.rate_per_s) as u64;
-
-        // If the tokens to add rounds down to zero, lets not update
-        // the timestamp so we don't lose any accumulated tokens due
-        // to rounding inaccuracies.
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens).map(|_| ())
+    }
+
+    fn try_consume_batch(&mut self, costs: &[u64]) -> usize {
+        let now = self.time_provider.now();
+        costs
+            .iter()
+            .take_while(|&&tokens| self.core.try_consume_at(now, tokens).is_ok())
+            .count()
+    }
+
+    fn kind(&self) -> LimiterKind {
+        LimiterKind::TokenBucket
+    }
+
+    fn name(&self) -> Option<&'static str> {
+        self.core.config.name
+    }
+
+    fn self_check(&self) -> Diagnostics {
+        let now = self.time_provider.now();
+        self.core.self_check_at(now)
+    }
+
+    fn last_consume_at(&self) -> Option<Duration> {
+        self.core.last_consume_at()
+    }
+
+    fn idle_for(&self) -> Option<Duration> {
+        let now = self.time_provider.now();
+        Some(self.core.idle_for_at(now))
+    }
+
+    fn reset(&mut self) {
+        let now = self.time_provider.now();
+        self.core.reset_at(now);
+    }
+}
+
+impl<T> RemainingBudget for TokenBucket<T>
+where
+    T: Clock,
+{
+    fn try_consume_remaining(&mut self, tokens: u64) -> Result<u64, CantConsume> {
+        let now = self.time_provider.now();
+        self.core.try_consume_at(now, tokens)
+    }
+}
+
+impl<T> Reset for TokenBucket<T> where T: Clock {}
+
+impl<T> RetryHint for TokenBucket<T>
+where
+    T: Clock,
+{
+    fn time_until_available(&self, tokens: u64) -> Option<Duration> {
+        let now = self.time_provider.now();
+        self.core.time_until_available_at(now, tokens)
+    }
+}
+
+impl<T> Inspect for TokenBucket<T>
+where
+    T: Clock,
+{
+    fn tokens_available(&self) -> u64 {
+        let now = self.time_provider.now();
+        self.core.tokens_available_at(now)
+    }
+
+    fn would_consume(&self, tokens: u64) -> bool {
+        let now = self.time_provider.now();
+        self.core.admits_at(now, tokens)
+    }
+}
+
+impl<T> Refund for TokenBucket<T>
+where
+    T: Clock,
+{
+    fn refund(&mut self, tokens: u64) {
+        self.core.refund(tokens);
+    }
+}
+
+impl<T> AdjustableCapacity for TokenBucket<T>
+where
+    T: Clock,
+{
+    fn current_capacity(&self) -> u64 {
+        self.core.capacity()
+    }
+
+    fn set_capacity(&mut self, capacity: u64) {
+        self.core.set_capacity(capacity);
+    }
+}
+
+/// Observable state of a [`TokenBucket`] or an [`AdaptiveLimiter`](crate::AdaptiveLimiter),
+/// see e.g. [`TokenBucket::state`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TokenBucketState {
+    /// Tokens currently in the bucket
+    pub tokens: u64,
+    /// Configured capacity
+    pub capacity: u64,
+    /// Configured average rate in tokens per second
+    pub rate_per_s: u64,
+    /// Timestamp up to which refills have been accounted for
+    pub last_refill: Duration,
+    /// Tokens borrowed and not repaid yet, see [`TokenBucket::with_max_debt`]
+    pub debt: u64,
+}
+
+/// Plain-data snapshot of a [`TokenBucket`], see [`TokenBucket::snapshot`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TokenBucketSnapshot {
+    /// Configured capacity
+    pub capacity: u64,
+    /// Tokens added every `refill_period`
+    pub refill_tokens: u64,
+    /// Refill period
+    pub refill_period: Duration,
+    /// Capacity ramp-up time, see [`TokenBucket::with_warm_up`]
+    pub warm_up: Duration,
+    /// Tokens a single consume may borrow, see [`TokenBucket::with_max_debt`]
+    pub max_debt: u64,
+    /// Tokens in the bucket at `last_refill`
+    pub tokens: u64,
+    /// Tokens borrowed and not repaid yet at `last_refill`
+    pub debt: u64,
+    /// Timestamp up to which refills have been accounted for
+    pub last_refill: Duration,
+    /// Start of the warm-up, i.e. construction or the latest reset
+    pub start_time: Duration,
+}
+
+/// Parameters of a [`TokenBucket`], e.g. deserialized from a configuration file
+///
+/// See [`TokenBucket::from_config`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "defmt", derive(defmt::Format))]
+pub struct TokenBucketConfig {
+    /// How many consumes should be allowed per second on average
+    pub rate_per_s: u64,
+    /// Bucket capacity to dictate the burstiness of the limiter
+    pub capacity: u64,
+}
+
+/// Sans-IO token bucket state machine
+///
+/// Same algorithm as [`TokenBucket`], but without a time provider: the
+/// current time is passed in with every [`LimiterCore::handle`] call.
+#[derive(Debug, Clone, Copy)]
+pub struct TokenBucketCore {
+    config: TokenBucketParams,
+    tokens: u64,
+    /// Borrowed tokens, `tokens` is zero while nonzero
+    debt: u64,
+    last_update_t: Duration,
+    /// Start of the warm-up, i.e. construction or the latest reset
+    start_time: Duration,
+    monitor: ClockMonitor,
+}
+
+impl TokenBucketCore {
+    /// Initialize a new token bucket state machine
+    ///
+    /// # Arguments
+    /// * `rate_per_sec` - how many consumes should be allowed per second on average
+    /// * `capacity` - bucket capacity to dictate the burstiness of this limiter
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    pub fn new(rate_per_s: u64, capacity: u64, now: Duration) -> Self {
+        Self::from_rate(rate_per_s, Duration::from_secs(1), capacity, now)
+    }
+
+    /// Initialize a new token bucket state machine refilled with `tokens` every `per`
+    ///
+    /// # Arguments
+    /// * `tokens` - how many consumes should be allowed per period on average
+    /// * `per` - refill period
+    /// * `capacity` - bucket capacity to dictate the burstiness of this limiter
+    /// * `now` - current time as [`Duration`] from some fixed epoch in the past
+    pub fn from_rate(tokens: u64, per: Duration, capacity: u64, now: Duration) -> Self {
+        Self {
+            config: TokenBucketParams::new(capacity, tokens, per),
+            tokens: capacity,
+            debt: 0,
+            last_update_t: now,
+            start_time: now,
+            monitor: ClockMonitor::new(now),
+        }
+    }
+
+    /// Validating constructor, see [`TokenBucket::try_new_with_time_provider`]
+    pub fn try_new(rate_per_s: u64, capacity: u64, now: Duration) -> Result<Self, ConfigError> {
+        if rate_per_s == 0 {
+            return Err(ConfigError::ZeroRate);
+        }
+        if capacity == 0 {
+            return Err(ConfigError::ZeroCapacity);
+        }
+        Ok(Self::new(rate_per_s, capacity, now))
+    }
+
+    /// Return to the freshly constructed state at the given time
+    ///
+    /// The configuration, including the name, is kept.
+    pub fn reset_at(&mut self, now: Duration) {
+        self.tokens = self.config.capacity;
+        self.debt = 0;
+        self.last_update_t = now;
+        self.start_time = now;
+        self.monitor.reset(now);
+    }
+
+    /// Take a plain-data snapshot, see [`TokenBucket::snapshot`]
+    pub fn snapshot(&self) -> TokenBucketSnapshot {
+        TokenBucketSnapshot {
+            capacity: self.config.capacity,
+            refill_tokens: self.config.refill_tokens,
+            refill_period: self.config.refill_period,
+            warm_up: self.config.warm_up,
+            max_debt: self.config.max_debt,
+            tokens: self.tokens,
+            debt: self.debt,
+            last_refill: self.last_update_t,
+            start_time: self.start_time,
+        }
+    }
+
+    /// Resume from a snapshot, see [`TokenBucket::restore`]
+    pub fn restore(snapshot: TokenBucketSnapshot) -> Self {
+        let mut config = TokenBucketParams::new(
+            snapshot.capacity,
+            snapshot.refill_tokens,
+            snapshot.refill_period,
+        );
+        config.warm_up = snapshot.warm_up;
+        config.max_debt = snapshot.max_debt;
+        Self {
+            config,
+            tokens: snapshot.tokens,
+            debt: snapshot.debt,
+            last_update_t: snapshot.last_refill,
+            start_time: snapshot.start_time,
+            monitor: ClockMonitor::new(snapshot.last_refill),
+        }
+    }
+
+    /// Assign a name to this limiter, see [`TokenBucket::with_name`]
+    pub fn with_name(mut self, name: &'static str) -> Self {
+        self.config.name = Some(name);
+        self
+    }
+
+    /// Start with the given amount of tokens, see [`TokenBucket::with_initial_tokens`]
+    pub fn with_initial_tokens(mut self, tokens: u64) -> Self {
+        self.tokens = tokens.min(self.config.capacity);
+        self
+    }
+
+    /// Ramp the capacity up after construction and resets, see [`TokenBucket::with_warm_up`]
+    pub fn with_warm_up(mut self, warm_up: Duration) -> Self {
+        self.config.warm_up = warm_up;
+        self
+    }
+
+    /// Allow borrowing tokens, see [`TokenBucket::with_max_debt`]
+    pub fn with_max_debt(mut self, max_debt: u64) -> Self {
+        self.config.max_debt = max_debt;
+        self
+    }
+
+    /// Tokens borrowed and not repaid yet at `now`, see [`TokenBucket::debt`]
+    pub fn debt_at(&self, now: Duration) -> u64 {
+        self.debt.saturating_sub(self.tokens_to_add(now))
+    }
+
+    /// Whether `tokens` would be admitted at `now`, see [`Inspect::would_consume`]
+    pub fn admits_at(&self, now: Duration, tokens: u64) -> bool {
+        let available = self.tokens_available_at(now);
+        available >= tokens
+            || (self.debt_at(now) == 0 && tokens - available <= self.config.max_debt)
+    }
+
+    /// Capacity at `now`, lower than configured during the warm-up
+    pub fn capacity_at(&self, now: Duration) -> u64 {
+        let warm_up_ns = self.config.warm_up.as_nanos();
+        let elapsed_ns = now.saturating_sub(self.start_time).as_nanos();
+        if elapsed_ns >= warm_up_ns {
+            return self.config.capacity;
+        }
+        (self.config.capacity as u128 * elapsed_ns / warm_up_ns) as u64
+    }
+
+    /// Configured capacity
+    pub fn capacity(&self) -> u64 {
+        self.config.capacity
+    }
+
+    /// Configured average rate in tokens per second, rounded down
+    pub fn rate_per_s(&self) -> u64 {
+        let per_ns = self.config.refill_period.as_nanos().max(1);
+        let rate = self.config.refill_tokens as u128 * NANOS_PER_SEC / per_ns;
+        rate.min(u64::MAX as u128) as u64
+    }
+
+    /// Configured refill rate as tokens per period
+    pub fn rate(&self) -> (u64, Duration) {
+        (self.config.refill_tokens, self.config.refill_period)
+    }
+
+    /// Tokens that could be consumed at `now`, see [`Inspect::tokens_available`]
+    pub fn tokens_available_at(&self, now: Duration) -> u64 {
+        let tokens_to_add = self.tokens_to_add(now).saturating_sub(self.debt);
+        self.tokens
+            .saturating_add(tokens_to_add)
+            .min(self.capacity_at(now))
+    }
+
+    /// Change the bucket capacity, dropping tokens that no longer fit
+    pub fn set_capacity(&mut self, capacity: u64) {
+        self.config.capacity = capacity;
+        self.tokens = self.tokens.min(capacity);
+    }
+
+    /// Return tokens of an admitted consume, see [`Refund::refund`]
+    pub fn refund(&mut self, tokens: u64) {
+        // Repay the debt first
+        let repaid = tokens.min(self.debt);
+        self.debt -= repaid;
+        self.tokens = self
+            .monitor
+            .saturating_add(self.tokens, tokens - repaid)
+            .min(self.config.capacity);
+    }
+
+    /// Change the refill rate at the given time
+    ///
+    /// Tokens accumulated until `now` are refilled at the previous rate first.
+    pub fn set_rate_per_s_at(&mut self, now: Duration, rate_per_s: u64) {
+        self.tokens = self.tokens_available_at(now);
+        self.debt = self.debt_at(now);
+        self.last_update_t = self.last_update_t.max(now);
+        self.config.refill_tokens = rate_per_s;
+        self.config.refill_period = Duration::from_secs(1);
+    }
+
+    /// State of the bucket at `now`, see [`TokenBucket::state`]
+    pub fn state_at(&self, now: Duration) -> TokenBucketState {
+        TokenBucketState {
+            tokens: self.tokens_available_at(now),
+            capacity: self.config.capacity,
+            rate_per_s: self.rate_per_s(),
+            last_refill: self.last_update_t,
+            debt: self.debt_at(now),
+        }
+    }
+
+    /// Time until `tokens` could be consumed at `now`, see [`RetryHint::time_until_available`]
+    pub fn time_until_available_at(&self, now: Duration, tokens: u64) -> Option<Duration> {
+        if tokens > self.config.capacity.saturating_add(self.config.max_debt) {
+            return None;
+        }
+        if self.admits_at(now, tokens) {
+            return Some(Duration::ZERO);
+        }
+        if !self.config.refills() || self.config.capacity == 0 {
+            // A zero capacity bucket only ever admits borrowed tokens
+            return None;
+        }
+
+        // Tokens are accumulated since the last update, repaying any debt first
+        let tokens = tokens.saturating_sub(self.config.max_debt);
+        let missing = self.debt.saturating_add(tokens).saturating_sub(self.tokens) as u128;
+        let refill_ns = (missing * self.config.refill_period.as_nanos())
+            .div_ceil(self.config.refill_tokens as u128);
+        let refill_time = Duration::from_nanos(refill_ns.min(u64::MAX as u128) as u64);
+        // During the warm-up the capacity may need to grow first
+        let warm_up_ns = (tokens as u128 * self.config.warm_up.as_nanos())
+            .div_ceil(self.config.capacity as u128);
+        let warm_up_time = Duration::from_nanos(warm_up_ns.min(u64::MAX as u128) as u64);
+        let available_at = (self.last_update_t + refill_time).max(self.start_time + warm_up_time);
+        Some(available_at.saturating_sub(now))
+    }
+
+    /// Timestamp of the most recent consume attempt, see [`Limiter::last_consume_at`]
+    pub fn last_consume_at(&self) -> Option<Duration> {
+        self.monitor.last_consume_at()
+    }
+
+    /// Idle time at `now`, see [`Limiter::idle_for`]
+    pub fn idle_for_at(&self, now: Duration) -> Duration {
+        self.monitor.idle_for(now)
+    }
+
+    /// Run the self-check of [`Limiter::self_check`] at the given time
+    pub fn self_check_at(&self, now: Duration) -> Diagnostics {
+        self.monitor
+            .diagnose(now, self.config.capacity != 0 && self.config.refills())
+    }
+
+    /// Consume at the given time, returning the remaining budget
+    pub(crate) fn try_consume_at(
+        &mut self,
+        now: Duration,
+        tokens: u64,
+    ) -> Result<u64, CantConsume> {
+        self.monitor.observe(now);
+        let tokens_to_add = self.checked_tokens_to_add(now).unwrap_or_else(|| {
+            self.monitor.saturate();
+            u64::MAX
+        });
+
+        // Only advance the timestamp by the time worth of the added whole
+        // tokens, so we don't lose any accumulated fraction of a token due
+        // to rounding. With slow rates that fraction can be most of a period.
+        let capacity = self.capacity_at(now);
         if tokens_to_add != 0 {
-            self.last_update_t = now;
-            self.tokens = (self.tokens.saturating_add(tokens_to_add)).min(self.config.capacity);
+            // Refills repay the debt first
+            let repaid = tokens_to_add.min(self.debt);
+            self.debt -= repaid;
+            self.tokens = self
+                .monitor
+                .saturating_add(self.tokens, tokens_to_add - repaid)
+                .min(capacity);
+            self.last_update_t = if self.tokens == capacity {
+                now
+            } else {
+                let refill_ns = tokens_to_add as u128 * self.config.refill_period.as_nanos()
+                    / self.config.refill_tokens as u128;
+                self.last_update_t + Duration::from_nanos(refill_ns as u64)
+            };
         }
+        self.tokens = self.tokens.min(capacity);
 
         // Take away tokens, if possible
         if self.tokens >= tokens {
             self.tokens -= tokens;
-            Ok(())
+            Ok(self.tokens)
+        } else if self.debt == 0 && tokens - self.tokens <= self.config.max_debt {
+            // Borrow the rest
+            self.debt = tokens - self.tokens;
+            self.tokens = 0;
+            Ok(0)
         } else {
             Err(CantConsume)
         }
     }
+
+    /// Tokens accumulated since the last update, in integer math so that
+    /// targets without an FPU don't need soft-float routines
+    fn tokens_to_add(&self, now: Duration) -> u64 {
+        self.checked_tokens_to_add(now).unwrap_or(u64::MAX)
+    }
+
+    /// Tokens accumulated since the last update, `None` on an overflow
+    fn checked_tokens_to_add(&self, now: Duration) -> Option<u64> {
+        let delta_ns = now.saturating_sub(self.last_update_t).as_nanos();
+        let per_ns = self.config.refill_period.as_nanos().max(1);
+        let tokens = delta_ns.checked_mul(self.config.refill_tokens as u128)? / per_ns;
+        u64::try_from(tokens).ok()
+    }
 }
 
-/// Configuration for a token bucket
-#[derive(Clone, Copy)]
-struct TokenBucketConfig<T>
-where
-    T: Fn() -> Duration,
-{
+impl LimiterCore for TokenBucketCore {
+    fn handle(&mut self, now: Duration, request: Request) -> Response {
+        match request {
+            Request::Consume(tokens) => self.try_consume_at(now, tokens).into(),
+        }
+    }
+}
+
+/// Parameters of a token bucket
+#[derive(Debug, Clone, Copy)]
+struct TokenBucketParams {
     capacity: u64,
-    rate_per_s: f64,
-    time_provider: T,
+    /// Tokens added every `refill_period`
+    refill_tokens: u64,
+    refill_period: Duration,
+    /// Time to ramp the capacity up after construction and resets
+    warm_up: Duration,
+    /// Tokens a single consume may borrow
+    max_debt: u64,
+    name: Option<&'static str>,
 }
 
-impl<T: Fn() -> Duration> TokenBucketConfig<T> {
-    fn new(capacity: u64, rate_per_s: u64, time_provider: T) -> Self {
+impl TokenBucketParams {
+    fn new(capacity: u64, refill_tokens: u64, refill_period: Duration) -> Self {
         Self {
             capacity,
-            rate_per_s: rate_per_s as f64,
-            time_provider,
+            refill_tokens,
+            refill_period,
+            warm_up: Duration::ZERO,
+            max_debt: 0,
+            name: None,
         }
     }
+
+    /// Whether the bucket is refilled at all
+    fn refills(&self) -> bool {
+        self.refill_tokens != 0 && !self.refill_period.is_zero()
+    }
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::{mock_assets::MockClock, Limiter};
+    use core::{cell::Cell, time::Duration};
+
+    use crate::{
+        mock_assets::MockClock, ConfigError, Inspect, Limiter, LimiterCore, LimiterKind,
+        RateLimited, Refund, RemainingBudget, Request, Response, RetryHint,
+    };
+
+    use super::{
+        TokenBucket, TokenBucketConfig, TokenBucketCore, TokenBucketSnapshot, TokenBucketState,
+    };
+
+    #[test]
+    fn verify_try_new() {
+        let clock = MockClock::new();
+        assert_eq!(
+            TokenBucket::try_new_with_time_provider(0, 10, || clock.step(0)).err(),
+            Some(ConfigError::ZeroRate)
+        );
+        assert_eq!(
+            TokenBucketCore::try_new(10, 0, Duration::ZERO).err(),
+            Some(ConfigError::ZeroCapacity)
+        );
+        let mut b = TokenBucket::try_new_with_time_provider(10, 10, || clock.step(0)).unwrap();
+        assert!(b.try_consume(10).is_ok());
+    }
+
+    #[test]
+    fn verify_fractional_rate() {
+        let clock = MockClock::new();
+        // One token every 10 s
+        let mut b =
+            TokenBucket::from_rate_with_time_provider(1, Duration::from_secs(10), 2, || {
+                clock.step(0)
+            });
+        assert_eq!(b.rate(), (1, Duration::from_secs(10)));
+        assert_eq!(b.rate_per_s(), 0);
+        assert!(b.try_consume(2).is_ok());
+        assert_eq!(b.time_until_available(1), Some(Duration::from_secs(10)));
+
+        clock.step(15_000_000);
+        assert!(b.try_consume_one().is_ok());
+        // The half token accumulated beyond the refill is kept
+        clock.step(5_000_000);
+        assert!(b.try_consume_one().is_ok());
+        assert!(b.try_consume_one().is_err());
+
+        // 2.5 tokens per second
+        let mut b =
+            TokenBucket::from_rate_with_time_provider(5, Duration::from_secs(2), 10, || {
+                clock.step(0)
+            })
+            .with_initial_tokens(0);
+        assert_eq!(b.rate_per_s(), 2);
+        clock.step(1_000_000);
+        assert_eq!(b.tokens_available(), 2);
+        clock.step(1_000_000);
+        assert!(b.try_consume(5).is_ok());
+    }
+
+    #[test]
+    fn verify_warm_up() {
+        let clock = MockClock::new();
+        let mut b = TokenBucket::new_with_time_provider(1000, 10, || clock.step(0))
+            .with_warm_up(Duration::from_millis(10));
+        assert_eq!(b.tokens_available(), 0);
+        assert_eq!(b.time_until_available(2), Some(Duration::from_millis(2)));
+
+        // Half way through the warm-up only half of the burst is allowed
+        clock.step(5000);
+        assert!(b.try_consume(5).is_ok());
+        assert!(b.try_consume_one().is_err());
+        assert_eq!(b.time_until_available(10), Some(Duration::from_millis(10)));
+
+        clock.step(10_000);
+        assert!(b.try_consume(10).is_ok());
+
+        // A reset warms up again
+        b.reset();
+        assert_eq!(b.tokens_available(), 0);
+        clock.step(10_000);
+        assert_eq!(b.tokens_available(), 10);
+    }
+
+    #[test]
+    fn verify_debt() {
+        let clock = MockClock::new();
+        let mut b =
+            TokenBucket::new_with_time_provider(1000, 10, || clock.step(0)).with_max_debt(5);
+        assert_eq!(b.time_until_available(16), None);
+
+        // Oversized consume borrows the missing tokens
+        assert!(b.try_consume(15).is_ok());
+        assert_eq!(b.debt(), 5);
+        assert!(!b.would_consume(1));
+        assert!(b.try_consume_one().is_err());
+        assert_eq!(b.time_until_available(1), Some(Duration::from_millis(5)));
+        assert_eq!(b.time_until_available(10), Some(Duration::from_millis(10)));
 
-    use super::TokenBucket;
+        // Refills repay the debt before the bucket fills again
+        clock.step(3000);
+        assert_eq!(b.debt(), 2);
+        assert_eq!(b.tokens_available(), 0);
+        clock.step(4000);
+        assert_eq!(b.debt(), 0);
+        assert_eq!(b.tokens_available(), 2);
+        assert!(b.try_consume(7).is_ok());
+        assert_eq!(b.debt(), 5);
+
+        // Refunds repay the debt too
+        b.refund(6);
+        assert_eq!(b.debt(), 0);
+        assert_eq!(b.tokens_available(), 1);
+    }
+
+    #[test]
+    fn verify_debt_zero_capacity() {
+        let clock = MockClock::new();
+        let mut b = TokenBucket::new_with_time_provider(1000, 0, || clock.step(0)).with_max_debt(5);
+
+        assert!(b.try_consume(5).is_ok());
+        assert_eq!(b.debt(), 5);
+        // No divide by zero while in debt
+        assert_eq!(b.time_until_available(1), None);
+        assert_eq!(b.time_until_available(6), None);
+    }
+
+    #[test]
+    fn verify_from_config() {
+        let clock = MockClock::new();
+        let config = TokenBucketConfig {
+            rate_per_s: 10,
+            capacity: 3,
+        };
+        #[cfg(feature = "serde")]
+        assert_eq!(
+            serde_json::from_str::<TokenBucketConfig>(r#"{"rate_per_s": 10, "capacity": 3}"#)
+                .unwrap(),
+            config
+        );
+
+        let mut b = TokenBucket::from_config_with_time_provider(config, || clock.step(0)).unwrap();
+        assert!(b.try_consume(3).is_ok());
+        assert!(b.try_consume_one().is_err());
+
+        let config = TokenBucketConfig {
+            rate_per_s: 0,
+            ..config
+        };
+        assert_eq!(
+            TokenBucket::from_config_with_time_provider(config, || clock.step(0)).err(),
+            Some(ConfigError::ZeroRate)
+        );
+    }
 
     #[test]
     fn verify_rate() {
@@ -145,4 +958,255 @@ mod tests {
         // T = 1100us, tokens = 0
         assert!(b.try_consume(1).is_err());
     }
+
+    #[test]
+    fn verify_kind_and_name() {
+        let clock = MockClock::new();
+        let b = TokenBucket::new_with_time_provider(1000, 100, || clock.step(100));
+        assert_eq!(b.kind(), LimiterKind::TokenBucket);
+        assert_eq!(b.name(), None);
+
+        let b = b.with_name("api");
+        assert_eq!(b.name(), Some("api"));
+        assert_eq!(b.kind().as_str(), "token_bucket");
+    }
+
+    #[test]
+    fn verify_initial_tokens() {
+        let clock = MockClock::new();
+        let mut b = TokenBucket::new_with_time_provider(1000, 100, || clock.step(0))
+            .with_initial_tokens(10);
+
+        assert!(b.try_consume(11).is_err());
+        assert!(b.try_consume(10).is_ok());
+        assert!(b.try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_self_check() {
+        let now = Cell::new(Duration::from_millis(10));
+        let mut b = TokenBucket::new_with_time_provider(1000, 100, || now.get());
+
+        let d = b.self_check();
+        assert!(d.is_healthy());
+        assert!(!d.clock_progressing);
+
+        now.set(Duration::from_millis(20));
+        assert!(b.try_consume_one().is_ok());
+        assert!(b.self_check().clock_progressing);
+
+        // Clock jumps backwards
+        now.set(Duration::from_millis(15));
+        assert!(b.try_consume_one().is_ok());
+        now.set(Duration::from_millis(30));
+        assert!(!b.self_check().clock_monotonic);
+        assert!(!b.self_check().is_healthy());
+
+        let b = TokenBucket::new_with_time_provider(0, 100, || now.get());
+        assert!(!b.self_check().config_valid);
+    }
+
+    #[test]
+    fn verify_saturation() {
+        let clock = MockClock::new();
+        let mut b = TokenBucket::new_with_time_provider(u64::MAX, u64::MAX, || clock.step(0));
+        assert!(b.try_consume(u64::MAX).is_ok());
+
+        // A second worth of refills still fits the counter
+        clock.step(1_000_000);
+        assert!(b.try_consume_one().is_ok());
+        assert!(!b.self_check().saturated);
+        // Two seconds worth doesn't
+        clock.step(2_000_000);
+        assert!(b.try_consume_one().is_ok());
+        assert!(b.self_check().saturated);
+
+        // The fault survives a reset
+        b.reset();
+        assert!(b.self_check().saturated);
+
+        // Refunding into a full bucket
+        let mut b = TokenBucket::new_with_time_provider(1, u64::MAX, || clock.step(0));
+        b.refund(1);
+        assert_eq!(b.tokens_available(), u64::MAX);
+        assert!(b.self_check().saturated);
+    }
+
+    #[test]
+    fn verify_bandwidth_in_bits() {
+        let clock = MockClock::new();
+        // 10 Gb/s with a burst of ten seconds
+        let rate = 10_000_000_000;
+        let mut b = TokenBucket::new_with_time_provider(rate, 10 * rate, || clock.step(0));
+        assert!(b.try_consume(10 * rate).is_ok());
+
+        clock.step(1000);
+        assert_eq!(b.tokens_available(), rate / 1000);
+
+        // A year of idling neither overflows nor loses precision
+        clock.step(365 * 24 * 3600 * 1_000_000);
+        assert_eq!(b.tokens_available(), 10 * rate);
+        assert!(b.try_consume(10 * rate).is_ok());
+        assert!(!b.self_check().saturated);
+    }
+
+    #[test]
+    fn verify_remaining() {
+        let clock = MockClock::new();
+        let mut b = TokenBucket::new_with_time_provider(1000, 10, || clock.step(0));
+
+        assert_eq!(b.try_consume_remaining(4).unwrap(), 6);
+        assert_eq!(b.try_consume_remaining(6).unwrap(), 0);
+        assert!(b.try_consume_remaining(1).is_err());
+        clock.step(3_000);
+        // T = 3ms, 3 tokens refilled
+        assert_eq!(b.try_consume_remaining(1).unwrap(), 2);
+    }
+
+    #[test]
+    fn verify_core() {
+        let ms = Duration::from_millis;
+        let mut c = TokenBucketCore::new(1000, 10, ms(0));
+
+        assert_eq!(
+            c.handle(ms(0), Request::Consume(10)),
+            Response::Admitted { remaining: 0 }
+        );
+        assert_eq!(c.handle(ms(0), Request::Consume(1)), Response::Rejected);
+        // 5 tokens refilled
+        assert_eq!(
+            c.handle(ms(5), Request::Consume(2)),
+            Response::Admitted { remaining: 3 }
+        );
+
+        // Closure based limiter behaves the same as its core
+        let clock = MockClock::new();
+        let mut b = TokenBucket::new_with_time_provider(1000, 10, || clock.step(0));
+        assert!(b.try_consume(10).is_ok());
+        let mut c = b.into_core();
+        assert_eq!(c.handle(ms(0), Request::Consume(1)), Response::Rejected);
+    }
+
+    #[test]
+    fn verify_idle_tracking() {
+        let now = Cell::new(Duration::from_millis(10));
+        let mut b = TokenBucket::new_with_time_provider(1000, 1, || now.get());
+
+        assert_eq!(b.last_consume_at(), None);
+        now.set(Duration::from_millis(15));
+        assert_eq!(b.idle_for(), Some(Duration::from_millis(5)));
+
+        assert!(b.try_consume_one().is_ok());
+        // Rejected consumes count as activity as well
+        now.set(Duration::from_millis(15) + Duration::from_micros(100));
+        assert!(b.try_consume_one().is_err());
+        now.set(Duration::from_millis(20));
+        assert_eq!(
+            b.last_consume_at(),
+            Some(Duration::from_millis(15) + Duration::from_micros(100))
+        );
+        assert_eq!(b.idle_for(), Some(Duration::from_micros(4900)));
+    }
+
+    #[test]
+    fn verify_tokens_available() {
+        let clock = MockClock::new();
+        let mut b = TokenBucket::new_with_time_provider(1000, 10, || clock.step(0));
+
+        assert!(b.try_consume(10).is_ok());
+        clock.step(3_000);
+        // T = 3ms, 3 tokens refilled but not yet accounted for
+        assert_eq!(b.tokens_available(), 3);
+        assert_eq!(b.tokens_available(), 3);
+        // Dry runs don't consume
+        assert!(b.would_consume(3));
+        assert!(b.would_consume(3));
+        assert!(!b.would_consume(4));
+        clock.step(100_000);
+        assert_eq!(b.tokens_available(), 10);
+    }
+
+    #[test]
+    fn verify_retry_hint() {
+        let clock = MockClock::new();
+        let mut b = TokenBucket::new_with_time_provider(1000, 10, || clock.step(0));
+
+        assert_eq!(b.time_until_available(10), Some(Duration::ZERO));
+        assert!(b.try_consume_with_retry(10).is_ok());
+        assert_eq!(b.time_until_available(4), Some(Duration::from_millis(4)));
+        assert_eq!(
+            b.try_consume_with_retry(1),
+            Err(RateLimited {
+                retry_after: Some(Duration::from_millis(1))
+            })
+        );
+        // More than the bucket can ever hold
+        assert_eq!(b.time_until_available(11), None);
+        assert!(!b.can_ever_consume(11));
+        assert!(b.try_consume_with_retry(11).unwrap_err().is_permanent());
+        // Only exhausted for now
+        assert!(b.can_ever_consume(10));
+        assert!(!b.try_consume_with_retry(10).unwrap_err().is_permanent());
+        clock.step(1_000);
+        assert_eq!(b.time_until_available(4), Some(Duration::from_millis(3)));
+    }
+
+    #[test]
+    fn verify_state() {
+        let clock = MockClock::new();
+        let mut b = TokenBucket::new_with_time_provider(1000, 10, || clock.step(0));
+        assert!(b.try_consume(4).is_ok());
+        clock.step(2_000);
+        assert_eq!(
+            b.state(),
+            TokenBucketState {
+                tokens: 8,
+                capacity: 10,
+                rate_per_s: 1000,
+                last_refill: Duration::ZERO,
+                debt: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn verify_snapshot_restore() {
+        let clock = MockClock::new();
+        let mut b = TokenBucket::new_with_time_provider(1000, 10, || clock.step(0));
+        assert!(b.try_consume(10).is_ok());
+        let snapshot = b.snapshot();
+        assert_eq!(
+            snapshot,
+            TokenBucketSnapshot {
+                capacity: 10,
+                refill_tokens: 1000,
+                refill_period: Duration::from_secs(1),
+                warm_up: Duration::ZERO,
+                max_debt: 0,
+                tokens: 0,
+                debt: 0,
+                last_refill: Duration::ZERO,
+                start_time: Duration::ZERO,
+            }
+        );
+
+        // Asleep for 5ms, the new time provider shares the epoch
+        clock.step(5_000);
+        let mut b = TokenBucket::restore(snapshot, || clock.step(0));
+        assert!(b.try_consume(5).is_ok());
+        assert!(b.try_consume_one().is_err());
+    }
+
+    #[test]
+    fn verify_reset() {
+        let clock = MockClock::new();
+        let mut b = TokenBucket::new_with_time_provider(1, 10, || clock.step(0)).with_name("b");
+        assert!(b.try_consume(10).is_ok());
+        assert!(b.try_consume_one().is_err());
+
+        b.reset();
+        assert_eq!(b.name(), Some("b"));
+        assert_eq!(b.last_consume_at(), None);
+        assert!(b.try_consume(10).is_ok());
+    }
 }
@@ -0,0 +1,301 @@
+//! tower middleware
+
+use std::{
+    future::Future,
+    pin::Pin,
+    task::{Context, Poll},
+};
+
+use pin_project_lite::pin_project;
+use tower_layer::Layer;
+use tower_service::Service;
+
+use crate::{CantConsume, Limiter, SharedLimiter};
+
+/// Type-erased error returned by a [`RateLimitService`]
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+/// Rejection handler of a [`RateLimitService`] returning an error
+///
+/// Requests are rejected with a boxed [`CantConsume`], which you can map to
+/// e.g. a `429 Too Many Requests` response further up in the stack.
+#[derive(Debug, Clone, Copy, Default)]
+pub struct RejectWithError;
+
+/// Produces the result of a request rejected by a [`RateLimitService`]
+///
+/// Implemented for [`RejectWithError`] and for any `Fn(Request) -> Response`
+/// closure, which can build e.g. a `429 Too Many Requests` response directly.
+pub trait RejectionHandler<Request, Response> {
+    /// Handle a rejected request
+    fn reject(&self, request: Request) -> Result<Response, BoxError>;
+}
+
+impl<Request, Response> RejectionHandler<Request, Response> for RejectWithError {
+    fn reject(&self, _request: Request) -> Result<Response, BoxError> {
+        Err(Box::new(CantConsume))
+    }
+}
+
+impl<F, Request, Response> RejectionHandler<Request, Response> for F
+where
+    F: Fn(Request) -> Response,
+{
+    fn reject(&self, request: Request) -> Result<Response, BoxError> {
+        Ok(self(request))
+    }
+}
+
+/// [`Layer`] wrapping services into a [`RateLimitService`]
+///
+/// All services produced by the layer, and all their clones, consume from
+/// the same limiter. Each request consumes a single token.
+///
+/// # Generic arguments
+/// * `L` - shared limiter
+/// * `H` - rejection handler, see [`RejectionHandler`]
+pub struct RateLimitLayer<L, H = RejectWithError>
+where
+    L: Limiter,
+{
+    limiter: SharedLimiter<L>,
+    handler: H,
+}
+
+impl<L> RateLimitLayer<L>
+where
+    L: Limiter,
+{
+    /// Create a layer rejecting requests with a boxed [`CantConsume`] error
+    ///
+    /// # Arguments
+    /// * `limiter` - limiter shared by all requests
+    pub fn new(limiter: L) -> Self {
+        Self {
+            limiter: SharedLimiter::new(limiter),
+            handler: RejectWithError,
+        }
+    }
+}
+
+impl<L, H> RateLimitLayer<L, H>
+where
+    L: Limiter,
+{
+    /// Create a layer handling rejected requests with a custom handler
+    ///
+    /// # Arguments
+    /// * `limiter` - limiter shared by all requests
+    /// * `handler` - rejection handler, e.g. a closure returning a `429` response
+    pub fn with_rejection_handler(limiter: L, handler: H) -> Self {
+        Self {
+            limiter: SharedLimiter::new(limiter),
+            handler,
+        }
+    }
+
+    /// Get a handle to the shared limiter
+    pub fn limiter(&self) -> &SharedLimiter<L> {
+        &self.limiter
+    }
+}
+
+impl<L, H> Clone for RateLimitLayer<L, H>
+where
+    L: Limiter,
+    H: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            limiter: self.limiter.clone(),
+            handler: self.handler.clone(),
+        }
+    }
+}
+
+impl<S, L, H> Layer<S> for RateLimitLayer<L, H>
+where
+    L: Limiter,
+    H: Clone,
+{
+    type Service = RateLimitService<S, L, H>;
+
+    fn layer(&self, inner: S) -> Self::Service {
+        RateLimitService {
+            inner,
+            limiter: self.limiter.clone(),
+            handler: self.handler.clone(),
+        }
+    }
+}
+
+/// [`Service`] admitting requests to the wrapped service through a [`Limiter`]
+///
+/// Consumes a single token for every request. Requests that can't consume
+/// are not passed to the wrapped service, but to the rejection handler.
+/// Errors of the wrapped service are boxed into a [`BoxError`].
+pub struct RateLimitService<S, L, H = RejectWithError>
+where
+    L: Limiter,
+{
+    inner: S,
+    limiter: SharedLimiter<L>,
+    handler: H,
+}
+
+impl<S, L, H> RateLimitService<S, L, H>
+where
+    L: Limiter,
+{
+    /// Get a handle to the shared limiter
+    pub fn limiter(&self) -> &SharedLimiter<L> {
+        &self.limiter
+    }
+
+    /// Get a reference to the wrapped service
+    pub fn inner(&self) -> &S {
+        &self.inner
+    }
+
+    /// Unwrap the wrapped service
+    pub fn into_inner(self) -> S {
+        self.inner
+    }
+}
+
+impl<S, L, H> Clone for RateLimitService<S, L, H>
+where
+    S: Clone,
+    L: Limiter,
+    H: Clone,
+{
+    fn clone(&self) -> Self {
+        Self {
+            inner: self.inner.clone(),
+            limiter: self.limiter.clone(),
+            handler: self.handler.clone(),
+        }
+    }
+}
+
+impl<S, L, H, Request> Service<Request> for RateLimitService<S, L, H>
+where
+    S: Service<Request>,
+    S::Error: Into<BoxError>,
+    L: Limiter,
+    H: RejectionHandler<Request, S::Response>,
+{
+    type Response = S::Response;
+    type Error = BoxError;
+    type Future = ResponseFuture<S::Future, S::Response>;
+
+    fn poll_ready(&mut self, cx: &mut Context<'_>) -> Poll<Result<(), Self::Error>> {
+        self.inner.poll_ready(cx).map_err(Into::into)
+    }
+
+    fn call(&mut self, request: Request) -> Self::Future {
+        match self.limiter.try_consume_one() {
+            Ok(()) => ResponseFuture::Admitted {
+                future: self.inner.call(request),
+            },
+            Err(CantConsume) => ResponseFuture::Rejected {
+                result: Some(self.handler.reject(request)),
+            },
+        }
+    }
+}
+
+pin_project! {
+    /// Response future of a [`RateLimitService`]
+    #[project = ResponseFutureProj]
+    pub enum ResponseFuture<F, Response> {
+        /// Request was passed to the wrapped service
+        Admitted {
+            #[pin]
+            future: F,
+        },
+        /// Request was rejected
+        Rejected {
+            result: Option<Result<Response, BoxError>>,
+        },
+    }
+}
+
+impl<F, Response, E> Future for ResponseFuture<F, Response>
+where
+    F: Future<Output = Result<Response, E>>,
+    E: Into<BoxError>,
+{
+    type Output = Result<Response, BoxError>;
+
+    fn poll(self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<Self::Output> {
+        match self.project() {
+            ResponseFutureProj::Admitted { future } => future.poll(cx).map_err(Into::into),
+            ResponseFutureProj::Rejected { result } => {
+                Poll::Ready(result.take().expect("polled after completion"))
+            }
+        }
+    }
+}
+
+#[cfg(all(test, feature = "fixed-window"))]
+mod tests {
+    use std::{
+        convert::Infallible,
+        future::{ready, Ready},
+        task::{Context, Poll},
+    };
+
+    use tower_layer::Layer;
+    use tower_service::Service;
+
+    use crate::{
+        mock_assets::{block_on, MockClock},
+        CantConsume, FixedWindow, Inspect,
+    };
+
+    use super::RateLimitLayer;
+
+    #[derive(Clone)]
+    struct Echo;
+
+    impl Service<u32> for Echo {
+        type Response = u32;
+        type Error = Infallible;
+        type Future = Ready<Result<u32, Infallible>>;
+
+        fn poll_ready(&mut self, _cx: &mut Context<'_>) -> Poll<Result<(), Infallible>> {
+            Poll::Ready(Ok(()))
+        }
+
+        fn call(&mut self, request: u32) -> Self::Future {
+            ready(Ok(request))
+        }
+    }
+
+    #[test]
+    fn verify_tower_layer() {
+        let clock = MockClock::new();
+        let w = FixedWindow::new_with_time_provider(2, 1000, move || clock.step(0));
+        let layer = RateLimitLayer::new(w);
+        let mut first = layer.layer(Echo);
+        let mut second = layer.layer(Echo);
+
+        assert_eq!(block_on(first.call(1)).unwrap(), 1);
+        assert_eq!(block_on(second.call(2)).unwrap(), 2);
+        // Both services share the same budget
+        let error = block_on(first.call(3)).unwrap_err();
+        assert!(error.downcast_ref::<CantConsume>().is_some());
+    }
+
+    #[test]
+    fn verify_tower_rejection_handler() {
+        let clock = MockClock::new();
+        let w = FixedWindow::new_with_time_provider(1, 1000, move || clock.step(0));
+        let mut service = RateLimitLayer::with_rejection_handler(w, |_: u32| 429).layer(Echo);
+
+        assert_eq!(block_on(service.call(1)).unwrap(), 1);
+        assert_eq!(block_on(service.call(2)).unwrap(), 429);
+        assert_eq!(service.limiter().lock().tokens_available(), 0);
+    }
+}
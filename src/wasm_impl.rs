@@ -0,0 +1,136 @@
+//! JavaScript bindings through `wasm-bindgen`
+
+use core::time::Duration;
+
+use wasm_bindgen::prelude::wasm_bindgen;
+
+use crate::{FixedWindow, Limiter, RemainingBudget, TokenBucket};
+
+/// Browser clock, milliseconds since the Unix epoch
+fn js_time_provider() -> Duration {
+    Duration::from_secs_f64(js_sys::Date::now().max(0.0) / 1000.0)
+}
+
+/// [`TokenBucket`] exposed to JavaScript as `TokenBucket`
+#[wasm_bindgen(js_name = TokenBucket)]
+pub struct WasmTokenBucket {
+    inner: TokenBucket<fn() -> Duration>,
+}
+
+#[wasm_bindgen(js_class = TokenBucket)]
+impl WasmTokenBucket {
+    /// Create a new token bucket using the browser clock
+    ///
+    /// # Arguments
+    /// * `rate_per_s` - how many consumes should be allowed per second on average
+    /// * `capacity` - bucket capacity to dictate the burstiness of this limiter
+    #[wasm_bindgen(constructor)]
+    pub fn new(rate_per_s: u32, capacity: u32) -> Self {
+        Self {
+            inner: TokenBucket::new_with_time_provider(
+                rate_per_s.into(),
+                capacity.into(),
+                js_time_provider,
+            ),
+        }
+    }
+
+    /// Try to consume tokens, returns `true` if the tokens were consumed
+    #[wasm_bindgen(js_name = tryConsume)]
+    pub fn try_consume(&mut self, tokens: u32) -> bool {
+        self.inner.try_consume(tokens.into()).is_ok()
+    }
+
+    /// How many tokens could currently be consumed
+    pub fn remaining(&mut self) -> u32 {
+        remaining(&mut self.inner)
+    }
+}
+
+/// [`FixedWindow`] exposed to JavaScript as `FixedWindow`
+#[wasm_bindgen(js_name = FixedWindow)]
+pub struct WasmFixedWindow {
+    inner: FixedWindow<fn() -> Duration>,
+}
+
+#[wasm_bindgen(js_class = FixedWindow)]
+impl WasmFixedWindow {
+    /// Create a new fixed window limiter using the browser clock
+    ///
+    /// # Arguments
+    /// * `capacity` - how many consumes are allowed during a single window
+    /// * `window_width_ms` - window width in milliseconds
+    #[wasm_bindgen(constructor)]
+    pub fn new(capacity: u32, window_width_ms: u32) -> Self {
+        Self {
+            inner: FixedWindow::new_with_time_provider(
+                capacity.into(),
+                window_width_ms.into(),
+                js_time_provider,
+            ),
+        }
+    }
+
+    /// Try to consume tokens, returns `true` if the tokens were consumed
+    #[wasm_bindgen(js_name = tryConsume)]
+    pub fn try_consume(&mut self, tokens: u32) -> bool {
+        self.inner.try_consume(tokens.into()).is_ok()
+    }
+
+    /// How many tokens could currently be consumed
+    pub fn remaining(&mut self) -> u32 {
+        remaining(&mut self.inner)
+    }
+}
+
+/// Remaining budget, evaluated by consuming zero tokens
+fn remaining(limiter: &mut impl RemainingBudget) -> u32 {
+    let remaining = limiter.try_consume_remaining(0).unwrap_or(0);
+    remaining.min(u32::MAX as u64) as u32
+}
+
+#[cfg(test)]
+mod tests {
+    use core::time::Duration;
+
+    use crate::{FixedWindow, TokenBucket};
+
+    use super::{remaining, WasmFixedWindow, WasmTokenBucket};
+
+    // The browser clock is only available on `wasm32`, so use a frozen one
+    fn frozen() -> Duration {
+        Duration::ZERO
+    }
+
+    #[test]
+    fn verify_token_bucket() {
+        let mut b = WasmTokenBucket {
+            inner: TokenBucket::new_with_time_provider(1, 3, frozen),
+        };
+        assert_eq!(b.remaining(), 3);
+        assert!(b.try_consume(2));
+        assert_eq!(b.remaining(), 1);
+        assert!(!b.try_consume(2));
+        assert!(b.try_consume(1));
+        assert_eq!(b.remaining(), 0);
+    }
+
+    #[test]
+    fn verify_fixed_window() {
+        let mut w = WasmFixedWindow {
+            inner: FixedWindow::new_with_time_provider(2, 1000, frozen),
+        };
+        assert!(w.try_consume(1));
+        assert_eq!(w.remaining(), 1);
+        assert!(!w.try_consume(2));
+        assert!(w.try_consume(1));
+        assert_eq!(w.remaining(), 0);
+    }
+
+    #[test]
+    fn verify_remaining_saturates() {
+        // Budgets beyond the range of a JavaScript-facing u32 are clamped
+        let mut b = TokenBucket::new_with_time_provider(1, u64::MAX, frozen as fn() -> Duration);
+        assert_eq!(remaining(&mut b), u32::MAX);
+    }
+}